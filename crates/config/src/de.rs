@@ -0,0 +1,362 @@
+//! Deserializes a YAML value while keeping track of where in the document it is.
+//!
+//! Errors carry the path of the value that failed, and fields ignored by the target structs are
+//! collected instead of silently dropped, so both can be reported at their line in the file.
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_yaml::Value;
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Path of a value inside a document, e.g. `sensor.targets[0].host`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ValuePath(Vec<Segment>);
+
+impl ValuePath {
+    pub fn key(&self, key: impl Into<String>) -> ValuePath {
+        let mut segments = self.0.clone();
+        segments.push(Segment::Key(key.into()));
+        ValuePath(segments)
+    }
+
+    pub fn index(&self, index: usize) -> ValuePath {
+        let mut segments = self.0.clone();
+        segments.push(Segment::Index(index));
+        ValuePath(segments)
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.0
+    }
+}
+
+impl Display for ValuePath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (position, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if position == 0 => write!(f, "{key}")?,
+                Segment::Key(key) => write!(f, ".{key}")?,
+                Segment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A field present in the document but not known to the struct it belongs to
+#[derive(Debug, Clone)]
+pub(crate) struct UnknownField {
+    pub path: ValuePath,
+    /// Fields the struct does know
+    pub expected: &'static [&'static str],
+}
+
+/// Error of a value at a path
+#[derive(Debug)]
+pub(crate) struct PathError {
+    pub path: Option<ValuePath>,
+    pub message: String,
+}
+
+impl PathError {
+    /// Sets the path unless a nested value already did
+    fn at(mut self, path: &ValuePath) -> Self {
+        if self.path.is_none() {
+            self.path = Some(path.clone());
+        }
+        self
+    }
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for PathError {}
+
+impl de::Error for PathError {
+    fn custom<T: Display>(message: T) -> Self {
+        PathError {
+            path: None,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn yaml_error(error: serde_yaml::Error) -> PathError {
+    de::Error::custom(error)
+}
+
+/// Text of a mapping key used in paths
+pub(crate) fn key_text(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        other => serde_yaml::to_string(other)
+            .map(|text| text.trim().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Deserializes `value` found at `path`, returning the fields the target types ignored
+pub(crate) fn from_value<T: DeserializeOwned>(
+    value: Value,
+    path: &ValuePath,
+) -> Result<(T, Vec<UnknownField>), PathError> {
+    let unknown = RefCell::new(Vec::new());
+    let result = T::deserialize(Tracked {
+        value,
+        path: path.clone(),
+        unknown: &unknown,
+        siblings: &[],
+    })?;
+    Ok((result, unknown.into_inner()))
+}
+
+struct Tracked<'a> {
+    value: Value,
+    path: ValuePath,
+    unknown: &'a RefCell<Vec<UnknownField>>,
+    /// Fields of the struct this value is a field of
+    siblings: &'static [&'static str],
+}
+
+impl<'a> Tracked<'a> {
+    fn visit_mapping<'de, V: Visitor<'de>>(
+        self,
+        visitor: V,
+        fields: &'static [&'static str],
+    ) -> Result<V::Value, PathError> {
+        let path = self.path.clone();
+        match self.value {
+            Value::Mapping(mapping) => visitor.visit_map(TrackedMap {
+                entries: mapping.into_iter(),
+                value: None,
+                path: self.path,
+                unknown: self.unknown,
+                fields,
+            }),
+            Value::Tagged(tagged) => Tracked {
+                value: tagged.value,
+                ..self
+            }
+            .visit_mapping(visitor, fields),
+            _ => de::Deserializer::deserialize_any(self, visitor),
+        }
+        .map_err(|e| e.at(&path))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Tracked<'_> {
+    type Error = PathError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+        let path = self.path.clone();
+        match self.value {
+            Value::Mapping(_) => self.visit_mapping(visitor, &[]),
+            Value::Sequence(sequence) => visitor.visit_seq(TrackedSeq {
+                items: sequence.into_iter().enumerate(),
+                path: self.path,
+                unknown: self.unknown,
+            }),
+            Value::Tagged(tagged) => Tracked {
+                value: tagged.value,
+                ..self
+            }
+            .deserialize_any(visitor),
+            scalar => scalar.deserialize_any(visitor).map_err(yaml_error),
+        }
+        .map_err(|e| e.at(&path))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, PathError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+        self.visit_mapping(visitor, &[])
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, PathError> {
+        self.visit_mapping(visitor, fields)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, PathError> {
+        self.value
+            .deserialize_enum(name, variants, visitor)
+            .map_err(|e| yaml_error(e).at(&self.path))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PathError> {
+        self.unknown.borrow_mut().push(UnknownField {
+            path: self.path,
+            expected: self.siblings,
+        });
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct identifier
+    }
+}
+
+struct TrackedMap<'a> {
+    entries: serde_yaml::mapping::IntoIter,
+    /// Value of the key returned last
+    value: Option<(ValuePath, Value)>,
+    path: ValuePath,
+    unknown: &'a RefCell<Vec<UnknownField>>,
+    fields: &'static [&'static str],
+}
+
+impl<'de> de::MapAccess<'de> for TrackedMap<'_> {
+    type Error = PathError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, PathError> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        let path = self.path.key(key_text(&key));
+        self.value = Some((path.clone(), value));
+        seed.deserialize(Tracked {
+            value: key,
+            path,
+            unknown: self.unknown,
+            siblings: &[],
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, PathError> {
+        let (path, value) = self
+            .value
+            .take()
+            .ok_or_else(|| <PathError as de::Error>::custom("value requested before key"))?;
+        // errors of the target itself, e.g. a secret that could not be resolved, have no path
+        seed.deserialize(Tracked {
+            value,
+            path: path.clone(),
+            unknown: self.unknown,
+            siblings: self.fields,
+        })
+        .map_err(|e| e.at(&path))
+    }
+}
+
+struct TrackedSeq<'a> {
+    items: std::iter::Enumerate<std::vec::IntoIter<Value>>,
+    path: ValuePath,
+    unknown: &'a RefCell<Vec<UnknownField>>,
+}
+
+impl<'de> de::SeqAccess<'de> for TrackedSeq<'_> {
+    type Error = PathError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, PathError> {
+        let Some((index, value)) = self.items.next() else {
+            return Ok(None);
+        };
+        let path = self.path.index(index);
+        seed.deserialize(Tracked {
+            value,
+            path: path.clone(),
+            unknown: self.unknown,
+            siblings: &[],
+        })
+        .map(Some)
+        .map_err(|e| e.at(&path))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize)]
+    struct Target {
+        host: String,
+        #[serde(default)]
+        port: Option<u16>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        targets: Vec<Target>,
+        #[serde(default)]
+        labels: HashMap<String, String>,
+    }
+
+    fn parse(text: &str) -> Result<(Config, Vec<UnknownField>), PathError> {
+        from_value(
+            serde_yaml::from_str(text).unwrap(),
+            &ValuePath::default().key("sensor"),
+        )
+    }
+
+    #[test]
+    fn test_unknown_fields_are_collected() {
+        let (config, unknown) =
+            parse("targets:\n  - host: a\n    prot: 80\nlabels:\n  team: ops\ninterval: 5m\n")
+                .unwrap();
+        assert_eq!(config.targets[0].host, "a");
+        assert_eq!(config.targets[0].port, None);
+        assert_eq!(config.labels["team"], "ops");
+
+        let paths: Vec<_> = unknown.iter().map(|field| field.path.to_string()).collect();
+        assert_eq!(paths, ["sensor.targets[0].prot", "sensor.interval"]);
+        assert_eq!(unknown[0].expected, ["host", "port"]);
+    }
+
+    #[test]
+    fn test_errors_carry_the_innermost_path() {
+        let error = parse("targets:\n  - host: a\n  - host: b\n    port: http\n").unwrap_err();
+        assert_eq!(error.path.unwrap().to_string(), "sensor.targets[1].port");
+        assert!(error.message.contains("invalid type"), "{}", error.message);
+
+        let error = parse("targets:\n  - port: 80\n").unwrap_err();
+        assert_eq!(error.path.unwrap().to_string(), "sensor.targets[0]");
+        assert!(error.message.contains("missing field `host`"));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::de::{self, PathError, UnknownField, ValuePath};
+use crate::error::Location;
+use loid_neurons::prelude::{ActivationConfig, ExecutionConfig};
+pub use loid_resources::prelude::{ResourceConfig, ResourceSpec};
+use loid_sensors::prelude::{
+    AmqpSensorConfig, BufferConfig, CommandSensorConfig, EventReceiver, EventSink, FlapDetector,
+    FlappingConfig, Mapping, MappingConfig, MetricDetector, MetricsConfig, NatsSensorConfig,
+    OtlpSensorConfig, PatternMatcher, PatternsConfig, ProbeSensorConfig, RedisStreamSensorConfig,
+    SensorError, SmtpSensorConfig, SqlSensorConfig, StatusHandle, WatchSensorConfig,
+    WindowAggregator, WindowsConfig,
+};
+use serde::Deserialize;
+use serde_yaml::Value;
+
+/// Version of the document format understood by the loader
+pub const VERSION: u32 = 1;
+
+/// Fields every document starts with
+#[derive(Debug, Clone, Deserialize)]
+pub struct Header {
+    /// Version of the document format, currently always 1
+    pub version: u32,
+    /// Human readable name, neurons call it `name`
+    #[serde(alias = "name")]
+    pub title: String,
+    /// Identifies the sensor, resource or neuron, unique across all documents
+    pub key: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A loaded document together with where it was defined
+#[derive(Debug, Clone)]
+pub struct Document<T> {
+    pub header: Header,
+    /// Start of the document
+    pub location: Location,
+    pub spec: T,
+}
+
+impl<T> Document<T> {
+    pub fn key(&self) -> &str {
+        &self.header.key
+    }
+}
+
+/// Sections of a sensor document
+#[derive(Debug, Clone)]
+pub struct SensorSpec {
+    pub sensor: SensorConfig,
+    pub mapping: Option<MappingConfig>,
+    pub metrics: Option<MetricsConfig>,
+    pub windows: Option<WindowsConfig>,
+    pub patterns: Option<PatternsConfig>,
+    pub flapping: Option<FlappingConfig>,
+    pub buffer: Option<BufferConfig>,
+}
+
+impl SensorSpec {
+    /// Creates the sink of the sensor, passing its events through the mapping and the stages
+    /// into the buffer the receiver reads. Has to be called within a Tokio runtime, which ticks
+    /// the stages.
+    pub fn sink(&self, status: StatusHandle) -> Result<(EventSink, EventReceiver), SensorError> {
+        let buffer = self.buffer.clone().unwrap_or_default();
+        let (mut sink, receiver) = EventSink::buffered(&buffer, status)?;
+        if let Some(mapping) = &self.mapping {
+            sink = sink.with_mapping(Mapping::new(mapping)?);
+        }
+        if let Some(metrics) = &self.metrics {
+            sink = sink.with_stage(MetricDetector::new(metrics)?);
+        }
+        if let Some(windows) = &self.windows {
+            sink = sink.with_stage(WindowAggregator::new(windows)?);
+        }
+        if let Some(patterns) = &self.patterns {
+            sink = sink.with_stage(PatternMatcher::new(patterns)?);
+        }
+        if let Some(flapping) = &self.flapping {
+            sink = sink.with_stage(FlapDetector::new(flapping)?);
+        }
+        Ok((sink, receiver))
+    }
+}
+
+/// Sections of a neuron document
+#[derive(Debug, Clone)]
+pub struct NeuronSpec {
+    pub activation: ActivationConfig,
+    pub execution: ExecutionConfig,
+}
+
+/// The `sensor` section, selected by its `type`
+#[derive(Debug, Clone)]
+pub enum SensorConfig {
+    Amqp(AmqpSensorConfig),
+    Command(CommandSensorConfig),
+    Nats(NatsSensorConfig),
+    Otlp(OtlpSensorConfig),
+    Probe(ProbeSensorConfig),
+    RedisStream(RedisStreamSensorConfig),
+    Smtp(SmtpSensorConfig),
+    Sql(SqlSensorConfig),
+    Watch(WatchSensorConfig),
+}
+
+impl SensorConfig {
+    /// Values of `type` known to the loader
+    pub const TYPES: &'static [&'static str] = &[
+        "amqp",
+        "command",
+        "nats",
+        "otlp",
+        "probe",
+        "redis_stream",
+        "smtp",
+        "sql",
+        "watch",
+    ];
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            SensorConfig::Amqp(_) => "amqp",
+            SensorConfig::Command(_) => "command",
+            SensorConfig::Nats(_) => "nats",
+            SensorConfig::Otlp(_) => "otlp",
+            SensorConfig::Probe(_) => "probe",
+            SensorConfig::RedisStream(_) => "redis_stream",
+            SensorConfig::Smtp(_) => "smtp",
+            SensorConfig::Sql(_) => "sql",
+            SensorConfig::Watch(_) => "watch",
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), SensorError> {
+        match self {
+            SensorConfig::Amqp(config) => config.validate(),
+            SensorConfig::Command(config) => config.validate(),
+            SensorConfig::Nats(config) => config.validate(),
+            SensorConfig::Otlp(config) => config.validate(),
+            SensorConfig::Probe(config) => config.validate(),
+            SensorConfig::RedisStream(config) => config.validate(),
+            SensorConfig::Smtp(config) => config.validate(),
+            SensorConfig::Sql(config) => config.validate(),
+            SensorConfig::Watch(config) => config.validate(),
+        }
+    }
+
+    /// Keys of the resources the sensor uses, each with the field naming it
+    pub fn resources(&self) -> Vec<(&'static str, &str)> {
+        match self {
+            SensorConfig::Sql(config) => vec![("resource", config.resource.as_str())],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Deserializes the section without its `type`, `None` for an unknown type
+    pub(crate) fn from_type(
+        kind: &str,
+        value: Value,
+        path: &ValuePath,
+    ) -> Option<Result<(Self, Vec<UnknownField>), PathError>> {
+        fn typed<T: serde::de::DeserializeOwned>(
+            value: Value,
+            path: &ValuePath,
+            variant: fn(T) -> SensorConfig,
+        ) -> Result<(SensorConfig, Vec<UnknownField>), PathError> {
+            de::from_value(value, path).map(|(config, unknown)| (variant(config), unknown))
+        }
+
+        Some(match kind {
+            "amqp" => typed(value, path, SensorConfig::Amqp),
+            "command" => typed(value, path, SensorConfig::Command),
+            "nats" => typed(value, path, SensorConfig::Nats),
+            "otlp" => typed(value, path, SensorConfig::Otlp),
+            "probe" => typed(value, path, SensorConfig::Probe),
+            "redis_stream" => typed(value, path, SensorConfig::RedisStream),
+            "smtp" => typed(value, path, SensorConfig::Smtp),
+            "sql" => typed(value, path, SensorConfig::Sql),
+            "watch" => typed(value, path, SensorConfig::Watch),
+            _ => return None,
+        })
+    }
+}
+
+/// Deserializes a `resource` section without its `type`, `None` for an unknown type
+pub(crate) fn resource_from_type(
+    kind: &str,
+    value: Value,
+    path: &ValuePath,
+) -> Option<Result<(ResourceConfig, Vec<UnknownField>), PathError>> {
+    fn typed<T: serde::de::DeserializeOwned>(
+        value: Value,
+        path: &ValuePath,
+        variant: fn(T) -> ResourceConfig,
+    ) -> Result<(ResourceConfig, Vec<UnknownField>), PathError> {
+        de::from_value(value, path).map(|(config, unknown)| (variant(config), unknown))
+    }
+
+    Some(match kind {
+        "http" => typed(value, path, ResourceConfig::Http),
+        "mysql" => typed(value, path, ResourceConfig::Mysql),
+        "postgres" => typed(value, path, ResourceConfig::Postgres),
+        "sqlite" => typed(value, path, ResourceConfig::Sqlite),
+        _ => return None,
+    })
+}
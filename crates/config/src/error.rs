@@ -0,0 +1,75 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+/// Position in a configuration file, line and column start at 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The configuration cannot be used
+    Error,
+    /// The configuration is used, but likely not as intended
+    Warning,
+}
+
+/// A problem found in a configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub location: Location,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {severity}: {}", self.location, self.message)
+    }
+}
+
+/// Errors that can occur while loading the configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// A file or directory could not be read
+    Io(PathBuf, std::io::Error),
+    /// The configuration has errors, all diagnostics including warnings are reported
+    Invalid(Vec<Diagnostic>),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, error) => write!(f, "cannot read {}: {error}", path.display()),
+            ConfigError::Invalid(diagnostics) => {
+                write!(f, "invalid configuration")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n{diagnostic}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(_, error) => Some(error),
+            ConfigError::Invalid(_) => None,
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Loads the YAML documents describing sensors, resources and neurons.
+//!
+//! Every document shares the same header and carries one kind of section:
+//!
+//! ```yaml
+//! version: 1
+//! title: "Orders Database"
+//! key: "orders-db"
+//! description: ""
+//!
+//! resource:
+//!   type: postgres
+//!   connection: "postgres://loid@localhost/orders"
+//! ```
+//!
+//! A file may hold several documents separated by `---`. Keys are unique across all documents
+//! and resources referenced by sensors have to exist. Problems are reported with the file, line
+//! and column they were found at.
+
+mod de;
+pub mod document;
+mod error;
+pub mod loader;
+mod locate;
+pub mod prelude;
+pub mod secrets;
+
+pub use crate::error::{ConfigError, Diagnostic, Location, Severity};
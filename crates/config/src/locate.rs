@@ -0,0 +1,178 @@
+//! Finds the line of a value in the text of a document.
+//!
+//! `serde_yaml` only reports positions for syntax errors, so the position of a path is looked up
+//! in the block structure of the text. Flow collections are not followed, the position of the
+//! closest enclosing block value is returned instead.
+
+use crate::de::{Segment, ValuePath};
+
+/// A line with content, the column is where the content starts after any `- ` indicators
+struct Line<'a> {
+    number: usize,
+    indent: usize,
+    column: usize,
+    content: &'a str,
+}
+
+fn lines(text: &str) -> Vec<Line<'_>> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(number, line)| {
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                return None;
+            }
+            let indent = line.len() - trimmed.len();
+            let mut content = trimmed;
+            let mut column = indent;
+            while let Some(rest) = content.strip_prefix('-') {
+                if !(rest.is_empty() || rest.starts_with(' ')) {
+                    break;
+                }
+                let after = rest.trim_start();
+                column += content.len() - after.len();
+                content = after;
+            }
+            Some(Line {
+                number,
+                indent,
+                column,
+                content,
+            })
+        })
+        .collect()
+}
+
+fn is_key(content: &str, key: &str) -> bool {
+    [key.to_string(), format!("\"{key}\""), format!("'{key}'")]
+        .iter()
+        .any(|quoted| {
+            content
+                .strip_prefix(quoted.as_str())
+                .and_then(|rest| rest.strip_prefix(':'))
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '#']))
+        })
+}
+
+/// Zero based line and column of the value at `path`, or of its closest located parent
+pub(crate) fn locate(text: &str, path: &ValuePath) -> Option<(usize, usize)> {
+    let lines = lines(text);
+    // the node found last: index into `lines`, column of its marker and whether it is an item
+    let mut node: Option<(usize, usize, bool)> = None;
+    let mut found = None;
+
+    for segment in path.segments() {
+        // children follow the line of a key, an item may start its first child on its own line
+        let (first, marker, item) = match node {
+            None => (0, None, false),
+            Some((index, column, true)) => (index, Some(column), true),
+            Some((index, column, false)) => (index + 1, Some(column), false),
+        };
+        let mut child_column = None;
+        let mut next = None;
+        let mut count = 0;
+
+        for (index, line) in lines.iter().enumerate().skip(first) {
+            let own_line = item && index == first;
+            if let Some(marker) = marker.filter(|_| !own_line) {
+                // a sequence may start at the indentation of the key holding it
+                let sequence = !item && line.indent == marker && line.column > line.indent;
+                if line.indent < marker || (line.indent == marker && !sequence) {
+                    break;
+                }
+            }
+            match segment {
+                Segment::Key(key) => {
+                    // items of a nested sequence do not start keys of this mapping
+                    if (!own_line && line.column != line.indent) || line.content.is_empty() {
+                        continue;
+                    }
+                    let column = *child_column.get_or_insert(line.column);
+                    if line.column == column && is_key(line.content, key) {
+                        next = Some((index, column, false));
+                        break;
+                    }
+                }
+                Segment::Index(wanted) => {
+                    if own_line || line.column == line.indent {
+                        continue;
+                    }
+                    let column = *child_column.get_or_insert(line.indent);
+                    if line.indent != column {
+                        continue;
+                    }
+                    if count == *wanted {
+                        next = Some((index, line.indent, true));
+                        break;
+                    }
+                    count += 1;
+                }
+            }
+        }
+
+        let Some(next) = next else {
+            break;
+        };
+        let line = &lines[next.0];
+        found = Some((line.number, next.1));
+        node = Some(next);
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "\
+version: 1
+key: probes
+# comment
+sensor:
+  type: probe
+  targets:
+    - name: web
+      check:
+        type: http
+    - name: db
+      labels: { team: ops }
+  port: 80
+mapping:
+  rules:
+    -
+      set:
+        impact: HIGH
+";
+
+    fn at(path: &ValuePath) -> Option<(usize, usize)> {
+        locate(DOCUMENT, path)
+    }
+
+    #[test]
+    fn test_locate_keys_and_items() {
+        let root = ValuePath::default();
+        assert_eq!(at(&root.key("key")), Some((1, 0)));
+        assert_eq!(at(&root.key("sensor").key("type")), Some((4, 2)));
+        assert_eq!(at(&root.key("sensor").key("port")), Some((11, 2)));
+        let targets = root.key("sensor").key("targets");
+        assert_eq!(at(&targets.index(1)), Some((9, 4)));
+        assert_eq!(at(&targets.index(1).key("name")), Some((9, 6)));
+        assert_eq!(at(&targets.index(0).key("check").key("type")), Some((8, 8)));
+        let rules = root.key("mapping").key("rules");
+        assert_eq!(at(&rules.index(0).key("set").key("impact")), Some((16, 8)));
+    }
+
+    #[test]
+    fn test_locate_falls_back_to_the_closest_parent() {
+        let targets = ValuePath::default().key("sensor").key("targets");
+        // inside a flow mapping
+        assert_eq!(
+            at(&targets.index(1).key("labels").key("team")),
+            Some((10, 6))
+        );
+        // does not exist
+        assert_eq!(at(&targets.index(5)), Some((5, 2)));
+        // a key of the root with the name of a nested key
+        assert_eq!(at(&ValuePath::default().key("name")), None);
+    }
+}
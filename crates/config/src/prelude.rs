@@ -0,0 +1,8 @@
+pub use crate::document::{
+    Document, Header, NeuronSpec, ResourceConfig, ResourceSpec, SensorConfig, SensorSpec, VERSION,
+};
+pub use crate::loader::{Configuration, Loader, load_dir};
+pub use crate::secrets::{
+    EnvProvider, FileProvider, SecretError, SecretProvider, SecretRef, Secrets, SopsProvider,
+};
+pub use crate::{ConfigError, Diagnostic, Location, Severity};
@@ -0,0 +1,455 @@
+//! Secret references in documents, resolved while loading.
+//!
+//! ```yaml
+//! resource:
+//!   type: postgres
+//!   connection: "postgres://loid:${secret:db/prod#password}@db.internal/loid"
+//! ```
+//!
+//! References are only resolved in [`Secret`] fields, the loader rejects them anywhere else.
+//! A reference names a secret by a path and an optional key after `#`. The providers are asked
+//! in order and the first one knowing the secret resolves it, see [`Secrets`]. `$${` is kept as
+//! a literal `${`.
+
+use loid_events::prelude::Secret;
+use serde_yaml::Value;
+use std::fmt::{Debug, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+const START: &str = "${secret:";
+
+/// A parsed `${secret:path#key}` reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretRef {
+    /// Slash separated path, e.g. `db/prod`
+    pub path: String,
+    /// Entry of the secret, e.g. `password`
+    pub key: Option<String>,
+}
+
+impl SecretRef {
+    /// Parses the part between `${secret:` and `}`
+    pub fn parse(reference: &str) -> Result<Self, SecretError> {
+        let (path, key) = match reference.split_once('#') {
+            Some((path, key)) => (path, Some(key.to_string())),
+            None => (reference, None),
+        };
+        let valid = |part: &str| {
+            !part.is_empty()
+                && part != "."
+                && part != ".."
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+        if !path.split('/').all(valid) || key.as_deref().is_some_and(|key| !valid(key)) {
+            return Err(SecretError::Syntax(format!(
+                "invalid secret reference `{reference}`, expected `path/to/secret#key` with \
+                letters, digits, `-`, `_` and `.`"
+            )));
+        }
+        Ok(Self {
+            path: path.to_string(),
+            key,
+        })
+    }
+
+    fn segments(&self) -> impl Iterator<Item = &str> {
+        self.path.split('/').chain(self.key.as_deref())
+    }
+}
+
+impl Display for SecretRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{}#{key}", self.path),
+            None => write!(f, "{}", self.path),
+        }
+    }
+}
+
+/// Errors while resolving secret references
+#[derive(Debug)]
+pub enum SecretError {
+    /// The reference is malformed
+    Syntax(String),
+    /// No provider knows the secret
+    NotFound(SecretRef),
+    /// A provider failed, e.g. the encrypted file could not be decrypted
+    Provider {
+        provider: &'static str,
+        message: String,
+    },
+}
+
+impl Display for SecretError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretError::Syntax(message) => write!(f, "{message}"),
+            SecretError::NotFound(reference) => write!(f, "unknown secret `{reference}`"),
+            SecretError::Provider { provider, message } => {
+                write!(f, "secret provider {provider} failed: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SecretError {}
+
+/// A source of secrets
+pub trait SecretProvider: Debug + Send + Sync {
+    /// The secret or `None` if the provider does not know it
+    fn resolve(&self, reference: &SecretRef) -> Result<Option<Secret>, SecretError>;
+}
+
+/// Environment variables, `db/prod#password` is read from `DB_PROD_PASSWORD`.
+///
+/// Letters are upper cased and other characters replaced by `_`, the prefix is put in front.
+#[derive(Debug, Clone, Default)]
+pub struct EnvProvider {
+    prefix: String,
+}
+
+impl EnvProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only variables starting with the prefix are used, e.g. `LOID_` reads
+    /// `LOID_DB_PROD_PASSWORD`
+    pub fn with_prefix(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+
+    pub fn variable(&self, reference: &SecretRef) -> String {
+        let name: Vec<_> = reference.segments().collect();
+        let name: String = name
+            .join("_")
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("{}{name}", self.prefix)
+    }
+}
+
+impl SecretProvider for EnvProvider {
+    fn resolve(&self, reference: &SecretRef) -> Result<Option<Secret>, SecretError> {
+        Ok(std::env::var(self.variable(reference))
+            .ok()
+            .map(Secret::from))
+    }
+}
+
+/// Files of a directory, as Docker and Kubernetes mount secrets. `db/prod#password` is read
+/// from `<directory>/db/prod/password`, `db/prod` from `<directory>/db/prod`.
+///
+/// A trailing line break is removed.
+#[derive(Debug, Clone)]
+pub struct FileProvider {
+    directory: PathBuf,
+}
+
+impl FileProvider {
+    /// Directory Docker mounts secrets to
+    pub const DOCKER: &'static str = "/run/secrets";
+
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+impl Default for FileProvider {
+    fn default() -> Self {
+        Self::new(Self::DOCKER)
+    }
+}
+
+impl SecretProvider for FileProvider {
+    fn resolve(&self, reference: &SecretRef) -> Result<Option<Secret>, SecretError> {
+        let path = reference
+            .segments()
+            .fold(self.directory.clone(), |path, segment| path.join(segment));
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let text = text.strip_suffix('\n').unwrap_or(&text);
+                let text = text.strip_suffix('\r').unwrap_or(text);
+                Ok(Some(Secret::new(text)))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(SecretError::Provider {
+                provider: "file",
+                message: format!("{}: {e}", path.display()),
+            }),
+        }
+    }
+}
+
+/// A YAML or JSON file encrypted with [sops](https://github.com/getsops/sops), e.g. with age
+/// keys. `db/prod#password` is the value at `db.prod.password` of the decrypted file.
+///
+/// The file is decrypted once by the `sops` command on first use, which finds the keys as
+/// usual, e.g. through `SOPS_AGE_KEY_FILE`.
+pub struct SopsProvider {
+    file: PathBuf,
+    command: PathBuf,
+    decrypted: OnceLock<Result<Value, String>>,
+}
+
+impl SopsProvider {
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        Self {
+            file: file.into(),
+            command: PathBuf::from("sops"),
+            decrypted: OnceLock::new(),
+        }
+    }
+
+    /// Uses another `sops` executable than the one on the `PATH`
+    pub fn with_command(mut self, command: impl Into<PathBuf>) -> Self {
+        self.command = command.into();
+        self
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    fn decrypt(&self) -> Result<Value, String> {
+        let output = Command::new(&self.command)
+            .arg("--decrypt")
+            .arg(&self.file)
+            .output()
+            .map_err(|e| format!("could not run {}: {e}", self.command.display()))?;
+        if !output.status.success() {
+            // sops reports what went wrong without printing the secrets
+            return Err(format!(
+                "could not decrypt {}: {}",
+                self.file.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        serde_yaml::from_slice(&output.stdout)
+            .map_err(|e| format!("{} is not YAML or JSON: {e}", self.file.display()))
+    }
+}
+
+impl Debug for SopsProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SopsProvider")
+            .field("file", &self.file)
+            .field("command", &self.command)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SecretProvider for SopsProvider {
+    fn resolve(&self, reference: &SecretRef) -> Result<Option<Secret>, SecretError> {
+        let decrypted = self
+            .decrypted
+            .get_or_init(|| self.decrypt())
+            .as_ref()
+            .map_err(|message| SecretError::Provider {
+                provider: "sops",
+                message: message.clone(),
+            })?;
+        Ok(lookup(decrypted, reference))
+    }
+}
+
+/// The scalar at the path of the reference
+fn lookup(value: &Value, reference: &SecretRef) -> Option<Secret> {
+    let value = reference
+        .segments()
+        .try_fold(value, |value, segment| value.get(segment))?;
+    match value {
+        Value::String(text) => Some(Secret::new(text.as_str())),
+        Value::Number(number) => Some(Secret::new(number.to_string())),
+        Value::Bool(flag) => Some(Secret::new(flag.to_string())),
+        _ => None,
+    }
+}
+
+/// Whether a text has a reference, `$${secret:` is none
+pub fn has_reference(text: &str) -> bool {
+    text.match_indices(START)
+        .any(|(start, _)| !text[..start].ends_with('$'))
+}
+
+/// The providers references are resolved through, in order.
+///
+/// The default asks the environment first and then the files in `/run/secrets`.
+#[derive(Debug)]
+pub struct Secrets {
+    providers: Vec<Box<dyn SecretProvider>>,
+}
+
+impl Default for Secrets {
+    fn default() -> Self {
+        Self::empty()
+            .with(EnvProvider::new())
+            .with(FileProvider::default())
+    }
+}
+
+impl Secrets {
+    /// Without providers, every reference is an error
+    pub fn empty() -> Self {
+        Self {
+            providers: Vec::new(),
+        }
+    }
+
+    /// Adds a provider asked after the ones already added
+    pub fn with(mut self, provider: impl SecretProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    pub fn resolve(&self, reference: &SecretRef) -> Result<Secret, SecretError> {
+        for provider in &self.providers {
+            if let Some(secret) = provider.resolve(reference)? {
+                return Ok(secret);
+            }
+        }
+        Err(SecretError::NotFound(reference.clone()))
+    }
+
+    /// Replaces the references in a text, `None` if it has none
+    pub fn substitute(&self, text: &str) -> Result<Option<String>, SecretError> {
+        if !text.contains("${") {
+            return Ok(None);
+        }
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                result.push_str(&rest[..start - 1]);
+                result.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            result.push_str(&rest[..start]);
+            let Some(reference) = rest[start..].strip_prefix(START) else {
+                result.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            };
+            let Some(end) = reference.find('}') else {
+                return Err(SecretError::Syntax(
+                    "secret reference is missing its closing `}`".to_string(),
+                ));
+            };
+            let secret = self.resolve(&SecretRef::parse(&reference[..end])?)?;
+            result.push_str(secret.expose());
+            rest = &reference[end + 1..];
+        }
+        result.push_str(rest);
+        Ok(Some(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Fixed(Value);
+
+    impl SecretProvider for Fixed {
+        fn resolve(&self, reference: &SecretRef) -> Result<Option<Secret>, SecretError> {
+            Ok(lookup(&self.0, reference))
+        }
+    }
+
+    fn fixed() -> Fixed {
+        Fixed(serde_yaml::from_str("db: {prod: {password: hunter2, port: 5432}}").unwrap())
+    }
+
+    #[test]
+    fn test_substitute() {
+        let secrets = Secrets::empty().with(fixed());
+        assert_eq!(
+            secrets
+                .substitute("postgres://loid:${secret:db/prod#password}@db:${secret:db/prod#port}")
+                .unwrap()
+                .unwrap(),
+            "postgres://loid:hunter2@db:5432"
+        );
+        assert_eq!(secrets.substitute("plain").unwrap(), None);
+        assert_eq!(
+            secrets
+                .substitute("$${secret:db/prod#password} ${HOME}")
+                .unwrap()
+                .unwrap(),
+            "${secret:db/prod#password} ${HOME}"
+        );
+
+        for (text, message) in [
+            ("${secret:db/prod#user}", "unknown secret `db/prod#user`"),
+            ("${secret:db/../etc#passwd}", "invalid secret reference"),
+            ("${secret:db/prod", "closing"),
+        ] {
+            let error = secrets.substitute(text).unwrap_err().to_string();
+            assert!(error.contains(message), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_providers() {
+        let reference = SecretRef::parse("db/prod#password").unwrap();
+        let directory = std::env::temp_dir().join(format!("loid-secrets-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("db/prod")).unwrap();
+        std::fs::write(directory.join("db/prod/password"), "from-file\n").unwrap();
+
+        let env = EnvProvider::with_prefix("LOID_TEST_");
+        assert_eq!(env.variable(&reference), "LOID_TEST_DB_PROD_PASSWORD");
+        assert_eq!(env.resolve(&reference).unwrap(), None);
+
+        let files = FileProvider::new(&directory);
+        let secrets = Secrets::empty().with(env).with(files);
+        assert_eq!(secrets.resolve(&reference).unwrap().expose(), "from-file");
+        let missing = SecretRef::parse("db/staging#password").unwrap();
+        assert!(matches!(
+            secrets.resolve(&missing),
+            Err(SecretError::NotFound(_))
+        ));
+
+        // an executable printing a decrypted file stands in for sops
+        let sops = directory.join("sops");
+        std::fs::write(
+            &sops,
+            "#!/bin/sh\necho 'db: {prod: {password: from-sops}}'\n",
+        )
+        .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&sops, std::fs::Permissions::from_mode(0o755)).unwrap();
+            let provider = SopsProvider::new("secrets.enc.yaml").with_command(&sops);
+            let secret = provider.resolve(&reference).unwrap().unwrap();
+            assert_eq!(secret.expose(), "from-sops");
+            assert!(!format!("{provider:?}").contains("from-sops"));
+        }
+        let broken = SopsProvider::new("secrets.enc.yaml").with_command(directory.join("none"));
+        assert!(matches!(
+            broken.resolve(&reference),
+            Err(SecretError::Provider {
+                provider: "sops",
+                ..
+            })
+        ));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -1,2 +1,3 @@
 mod models;
 pub mod prelude;
+mod secret;
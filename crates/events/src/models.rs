@@ -301,6 +301,7 @@ pub struct EventBuilder {
     impact: Impact,
     urgency: Urgency,
     received_at: Option<DateTime<Utc>>,
+    resolved_at: Option<DateTime<Utc>>,
 }
 
 impl EventBuilder {
@@ -313,6 +314,7 @@ impl EventBuilder {
             impact: Impact::default(),
             urgency: Urgency::default(),
             received_at: None,
+            resolved_at: None,
         }
     }
 
@@ -341,6 +343,11 @@ impl EventBuilder {
         self
     }
 
+    pub fn with_resolved_at(&mut self, resolved_at: DateTime<Utc>) -> &mut Self {
+        self.resolved_at = Some(resolved_at);
+        self
+    }
+
     pub fn with_field(&mut self, key: &str, value: Value) -> &mut Self {
         self.fields.insert(key.to_string(), value);
         self
@@ -388,7 +395,7 @@ impl EventBuilder {
             priority: self.priority,
             impact: self.impact,
             urgency: self.urgency,
-            resolved_at: None,
+            resolved_at: self.resolved_at,
         }
     }
 }
@@ -759,6 +766,14 @@ mod tests {
         assert_eq!(event.priority, Priority::MEDIUM);
     }
 
+    #[test]
+    fn test_event_builder_with_resolved_at() {
+        let resolved_at = Utc::now();
+        let event = EventBuilder::new().with_resolved_at(resolved_at).build();
+
+        assert_eq!(event.resolved_at, Some(resolved_at));
+    }
+
     #[test]
     fn test_event_builder_with_field() {
         let value = Value::String("test_value".to_string());
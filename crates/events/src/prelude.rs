@@ -1 +1,2 @@
 pub use crate::models::{Event, EventBuilder, Impact, Priority, Source, Urgency, Value};
+pub use crate::secret::Secret;
@@ -0,0 +1,131 @@
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::cell::RefCell;
+use std::fmt::{Debug, Display, Formatter};
+
+type Resolver = Box<dyn FnMut(&str) -> Result<String, String>>;
+
+thread_local! {
+    static RESOLVER: RefCell<Option<Resolver>> = const { RefCell::new(None) };
+}
+
+/// A credential, e.g. a password or a connection URL containing one.
+///
+/// `Debug` and `Display` print `***` so secrets do not end up in logs. There is no conversion to
+/// [`Value`](crate::prelude::Value) or `Serialize` implementation, a secret can not become an
+/// event field by accident.
+///
+/// A secret deserializes from a string, see [`Secret::resolving`] for references to secrets
+/// kept elsewhere.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// Runs `f` with every secret deserialized on this thread passed through `resolve` first,
+    /// e.g. to replace references by the secrets they name. Only secrets are resolved, other
+    /// strings are deserialized as they are.
+    pub fn resolving<T>(
+        resolve: impl FnMut(&str) -> Result<String, String> + 'static,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        struct Restore(Option<Resolver>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                RESOLVER.set(self.0.take());
+            }
+        }
+
+        let _restore = Restore(RESOLVER.replace(Some(Box::new(resolve))));
+        f()
+    }
+
+    /// The secret itself, to hand it to the client that needs it
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.trim().is_empty()
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        RESOLVER
+            .with_borrow_mut(|resolver| match resolver {
+                Some(resolve) => resolve(&text),
+                None => Ok(text),
+            })
+            .map(Self)
+            .map_err(D::Error::custom)
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret(***)")
+    }
+}
+
+impl Display for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "***")
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_is_redacted() {
+        let secret: Secret = serde_json::from_str(r#""postgres://loid:hunter2@db/loid""#).unwrap();
+        assert_eq!(secret.expose(), "postgres://loid:hunter2@db/loid");
+        assert_eq!(format!("{secret:?}"), "Secret(***)");
+        assert_eq!(format!("{secret}"), "***");
+        assert!(!format!("{:?}", Some(&secret)).contains("hunter2"));
+    }
+
+    #[test]
+    fn test_resolving_secrets() {
+        #[derive(Debug, Deserialize)]
+        struct Config {
+            name: String,
+            password: Secret,
+        }
+
+        let text = r#"{"name": "${password}", "password": "${password}"}"#;
+        let config: Config = Secret::resolving(
+            |text| Ok(text.replace("${password}", "hunter2")),
+            || serde_json::from_str(text).unwrap(),
+        );
+        assert_eq!(config.name, "${password}");
+        assert_eq!(config.password.expose(), "hunter2");
+
+        let error = Secret::resolving(
+            |_| Err("unknown secret".to_string()),
+            || serde_json::from_str::<Config>(text).unwrap_err(),
+        );
+        assert!(error.to_string().contains("unknown secret"));
+        let config: Config = serde_json::from_str(text).unwrap();
+        assert_eq!(config.password.expose(), "${password}");
+    }
+}
@@ -0,0 +1,26 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// The `activation` section deciding when a neuron is considered
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ActivationConfig {
+    /// Goals the neuron can work towards
+    #[serde(default)]
+    pub goals: Vec<String>,
+    /// Required context fields, a field without value only has to exist
+    #[serde(default)]
+    pub conditions: HashMap<String, Option<serde_json::Value>>,
+}
+
+/// The `execution` section describing what a neuron does
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExecutionConfig {
+    /// Executor running the neuron, e.g. `bash`
+    pub uses: String,
+    /// Maximum runtime in seconds
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// Settings specific to the executor
+    #[serde(flatten)]
+    pub options: HashMap<String, serde_json::Value>,
+}
@@ -1 +1,2 @@
+pub use crate::config::{ActivationConfig, ExecutionConfig};
 pub use crate::models::{Neuron};
@@ -0,0 +1,339 @@
+//! Access policies limiting what sensors and neurons may do with a resource.
+//!
+//! A policy grants a resource to some sensors and neurons only, makes it read-only and allows
+//! only some statement types and tables. The resource enforces the policy itself, a violation
+//! fails with [`ResourceError::Access`] and is logged to the `loid::audit` target along with
+//! the span of the caller.
+//!
+//! Read-only database resources run every statement in a read-only transaction, so the
+//! database rejects writes the statement checks could not see, e.g. in a function.
+
+use crate::ResourceError;
+use crate::database::Dialect;
+use crate::database::statement::analyze;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// The `access` section of a resource document, everything is allowed without
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct AccessPolicy {
+    /// Only reads: `SELECT` statements in read-only transactions or `GET`, `HEAD` and
+    /// `OPTIONS` requests
+    #[serde(default)]
+    pub read_only: bool,
+    /// Statement types that may run, all if not set
+    #[serde(default)]
+    pub statements: Option<Vec<StatementKind>>,
+    /// Tables statements may use, all if not set. A table in another schema has to be listed
+    /// with it, e.g. `audit.events`
+    #[serde(default)]
+    pub tables: Option<Vec<String>>,
+    /// Keys of the sensors and neurons that may use the resource, all if not set
+    #[serde(default)]
+    pub grants: Option<Vec<String>>,
+}
+
+impl AccessPolicy {
+    /// Checks the policy for contradictions
+    pub fn validate(&self) -> Result<(), ResourceError> {
+        if self.read_only
+            && let Some(kind) = self
+                .statements
+                .iter()
+                .flatten()
+                .find(|kind| **kind != StatementKind::Select)
+        {
+            return Err(ResourceError::Config(format!(
+                "access.statements: {kind} statements can not run on a read_only resource"
+            )));
+        }
+        if self
+            .tables
+            .iter()
+            .flatten()
+            .any(|table| table.trim().is_empty())
+        {
+            return Err(ResourceError::Config(
+                "access.tables must not contain empty names".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether the policy restricts statements, which is only possible for databases
+    pub(crate) fn restricts_statements(&self) -> bool {
+        self.statements.is_some() || self.tables.is_some()
+    }
+}
+
+/// Type of a SQL statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementKind {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    Merge,
+    Create,
+    Alter,
+    Drop,
+    Truncate,
+}
+
+impl StatementKind {
+    /// The type of a statement starting with the keyword, lowercase
+    pub(crate) fn from_keyword(keyword: &str) -> Option<Self> {
+        Some(match keyword {
+            "select" | "values" => StatementKind::Select,
+            "insert" => StatementKind::Insert,
+            "update" => StatementKind::Update,
+            "delete" => StatementKind::Delete,
+            "merge" => StatementKind::Merge,
+            "create" => StatementKind::Create,
+            "alter" => StatementKind::Alter,
+            "drop" => StatementKind::Drop,
+            "truncate" => StatementKind::Truncate,
+            _ => return None,
+        })
+    }
+}
+
+impl Display for StatementKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let keyword = match self {
+            StatementKind::Select => "SELECT",
+            StatementKind::Insert => "INSERT",
+            StatementKind::Update => "UPDATE",
+            StatementKind::Delete => "DELETE",
+            StatementKind::Merge => "MERGE",
+            StatementKind::Create => "CREATE",
+            StatementKind::Alter => "ALTER",
+            StatementKind::Drop => "DROP",
+            StatementKind::Truncate => "TRUNCATE",
+        };
+        write!(f, "{keyword}")
+    }
+}
+
+/// Why the policy of a resource denied access
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessError {
+    /// The sensor or neuron is not in the grants of the resource
+    NotGranted { user: String },
+    /// The resource is read-only, the operation writes, e.g. `UPDATE` or `POST`
+    ReadOnly { operation: String },
+    /// The statement type is not in the allowed statements
+    Statement(StatementKind),
+    /// The statement uses a table not in the allowed tables
+    Table(String),
+    /// The statement could not be checked, e.g. because it holds several statements
+    Unchecked(String),
+}
+
+impl Display for AccessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessError::NotGranted { user } => write!(f, "`{user}` is not granted the resource"),
+            AccessError::ReadOnly { operation } => {
+                write!(f, "the resource is read-only, {operation} is not allowed")
+            }
+            AccessError::Statement(kind) => write!(f, "{kind} statements are not allowed"),
+            AccessError::Table(table) => write!(f, "table `{table}` is not allowed"),
+            AccessError::Unchecked(reason) => {
+                write!(f, "the statement can not be checked: {reason}")
+            }
+        }
+    }
+}
+
+/// The policy of one resource, enforced by the resource
+#[derive(Debug, Clone, Default)]
+pub struct AccessGuard {
+    resource: String,
+    policy: AccessPolicy,
+}
+
+impl AccessGuard {
+    pub fn new(resource: impl Into<String>, policy: AccessPolicy) -> Self {
+        Self {
+            resource: resource.into(),
+            policy,
+        }
+    }
+
+    pub fn policy(&self) -> &AccessPolicy {
+        &self.policy
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.policy.read_only
+    }
+
+    /// Checks that the sensor or neuron with the key may use the resource
+    pub fn check_user(&self, user: &str) -> Result<(), ResourceError> {
+        match &self.policy.grants {
+            Some(grants) if !grants.iter().any(|grant| grant == user) => Err(self.deny(
+                AccessError::NotGranted {
+                    user: user.to_string(),
+                },
+                None,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a SQL statement against read-only mode and the allowed statements and tables
+    pub fn check_statement(&self, statement: &str, dialect: Dialect) -> Result<(), ResourceError> {
+        if !self.policy.read_only && !self.policy.restricts_statements() {
+            return Ok(());
+        }
+        let analysis = match analyze(statement, dialect) {
+            Ok(analysis) => analysis,
+            Err(reason) => return Err(self.deny(AccessError::Unchecked(reason), Some(statement))),
+        };
+        for kind in &analysis.kinds {
+            if self.policy.read_only && *kind != StatementKind::Select {
+                let operation = kind.to_string();
+                return Err(self.deny(AccessError::ReadOnly { operation }, Some(statement)));
+            }
+            if let Some(allowed) = &self.policy.statements
+                && !allowed.contains(kind)
+            {
+                return Err(self.deny(AccessError::Statement(*kind), Some(statement)));
+            }
+        }
+        // a statement naming no table is not known to stay within the allowed ones
+        if self.policy.tables.is_some() && analysis.tables.is_empty() {
+            let reason = "no table found".to_string();
+            return Err(self.deny(AccessError::Unchecked(reason), Some(statement)));
+        }
+        if let Some(allowed) = &self.policy.tables
+            && let Some(table) = analysis
+                .tables
+                .iter()
+                .find(|table| !allowed.iter().any(|name| name.eq_ignore_ascii_case(table)))
+        {
+            return Err(self.deny(AccessError::Table(table.clone()), Some(statement)));
+        }
+        Ok(())
+    }
+
+    /// Checks the method of an HTTP request against read-only mode
+    pub(crate) fn check_method(&self, method: &Method) -> Result<(), ResourceError> {
+        if self.policy.read_only && ![Method::GET, Method::HEAD, Method::OPTIONS].contains(method) {
+            let operation = method.to_string();
+            return Err(self.deny(AccessError::ReadOnly { operation }, None));
+        }
+        Ok(())
+    }
+
+    fn deny(&self, error: AccessError, statement: Option<&str>) -> ResourceError {
+        tracing::warn!(
+            target: "loid::audit",
+            resource = %self.resource,
+            violation = %error,
+            statement,
+            "resource access denied"
+        );
+        ResourceError::Access {
+            resource: self.resource.clone(),
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(json: serde_json::Value) -> AccessGuard {
+        AccessGuard::new("orders-db", serde_json::from_value(json).unwrap())
+    }
+
+    fn denied(result: Result<(), ResourceError>) -> AccessError {
+        match result {
+            Err(ResourceError::Access { resource, error }) => {
+                assert_eq!(resource, "orders-db");
+                error
+            }
+            other => panic!("expected access to be denied, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let open = AccessGuard::default();
+        assert!(open.check_user("anyone").is_ok());
+        assert!(
+            open.check_statement("DROP TABLE alerts; --", Dialect::Postgres)
+                .is_ok()
+        );
+
+        let restricted = guard(serde_json::json!({
+            "statements": ["select", "update"],
+            "tables": ["alerts", "audit.events"],
+            "grants": ["stuck-orders"],
+        }));
+        assert!(restricted.check_user("stuck-orders").is_ok());
+        assert_eq!(
+            denied(restricted.check_user("close-alert")),
+            AccessError::NotGranted {
+                user: "close-alert".to_string()
+            }
+        );
+        let check = |statement| restricted.check_statement(statement, Dialect::Postgres);
+        assert!(
+            check("UPDATE Alerts SET status = $1 WHERE id IN (SELECT id FROM audit.events)")
+                .is_ok()
+        );
+        assert_eq!(
+            denied(check("DELETE FROM alerts")),
+            AccessError::Statement(StatementKind::Delete)
+        );
+        assert_eq!(
+            denied(check("SELECT * FROM alerts, users")),
+            AccessError::Table("users".to_string())
+        );
+        for statement in [
+            "SELECT 1; SELECT 2",
+            "SELECT 1",
+            "SELECT * FROM (alerts JOIN users ON true)",
+            "SELECT * FROM alerts STRAIGHT_JOIN users",
+            "SELECT * FROM dblink('host=db', 'SELECT * FROM users') AS t(a text)",
+        ] {
+            assert!(
+                matches!(
+                    denied(check(statement)),
+                    AccessError::Unchecked(_) | AccessError::Table(_)
+                ),
+                "{statement}"
+            );
+        }
+
+        let read_only = guard(serde_json::json!({"read_only": true}));
+        assert!(
+            read_only
+                .check_statement("SELECT * FROM users", Dialect::Mysql)
+                .is_ok()
+        );
+        assert_eq!(
+            denied(read_only.check_statement(
+                "WITH gone AS (DELETE FROM alerts RETURNING id) SELECT * FROM gone",
+                Dialect::Postgres
+            )),
+            AccessError::ReadOnly {
+                operation: "DELETE".to_string()
+            }
+        );
+        assert!(read_only.check_method(&Method::GET).is_ok());
+        assert!(read_only.check_method(&Method::POST).is_err());
+
+        let contradiction: AccessPolicy = serde_json::from_value(
+            serde_json::json!({"read_only": true, "statements": ["insert"]}),
+        )
+        .unwrap();
+        assert!(contradiction.validate().is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! Circuit breaker protecting a resource that went down from the callers hammering it.
+//!
+//! The circuit is closed while the resource works. After `failures` consecutive calls failed
+//! because the resource could not be reached it opens, calls fail right away with
+//! [`ResourceError::Unavailable`]. Once `open_for` passed it is half-open and lets a single
+//! trial call through, which closes the circuit on success and opens it again on failure.
+//!
+//! Errors of the call itself, e.g. a statement with a syntax error or a 404 response, do not
+//! count as failures.
+
+use crate::ResourceError;
+use futures_util::stream::BoxStream;
+use futures_util::{StreamExt, future};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// The `circuit_breaker` section of a resource document
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures that open the circuit
+    #[serde(default = "default_failures")]
+    pub failures: u32,
+    /// Time the circuit stays open before a trial call is let through
+    #[serde(with = "humantime_serde", default = "default_open_for")]
+    pub open_for: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failures: default_failures(),
+            open_for: default_open_for(),
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    /// Checks the configuration for values the breaker cannot work with
+    pub fn validate(&self) -> Result<(), ResourceError> {
+        if self.failures == 0 {
+            return Err(ResourceError::Config(
+                "circuit_breaker.failures must be greater than 0".to_string(),
+            ));
+        }
+        if self.open_for.is_zero() {
+            return Err(ResourceError::Config(
+                "circuit_breaker.open_for must be greater than 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn default_failures() -> u32 {
+    5
+}
+
+fn default_open_for() -> Duration {
+    Duration::from_secs(30)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// Calls fail right away
+    Open,
+    /// A single trial call decides whether the circuit closes or opens again
+    HalfOpen,
+}
+
+/// Breaker of one resource, clones share the state
+#[derive(Clone)]
+pub struct CircuitBreaker(Arc<Inner>);
+
+struct Inner {
+    breaker: Mutex<Breaker>,
+    state: watch::Sender<CircuitState>,
+}
+
+struct Breaker {
+    config: CircuitBreakerConfig,
+    failures: u32,
+    opened_at: Instant,
+    /// A trial call of the half-open circuit is running
+    trial: bool,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(CircuitBreakerConfig::default())
+    }
+}
+
+impl std::fmt::Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("state", &self.state())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self(Arc::new(Inner {
+            breaker: Mutex::new(Breaker {
+                config,
+                failures: 0,
+                opened_at: Instant::now(),
+                trial: false,
+            }),
+            state: watch::Sender::new(CircuitState::Closed),
+        }))
+    }
+
+    pub fn state(&self) -> CircuitState {
+        *self.0.state.borrow()
+    }
+
+    /// Receives every change of the state
+    pub fn subscribe(&self) -> watch::Receiver<CircuitState> {
+        self.0.state.subscribe()
+    }
+
+    /// Replaces the configuration and closes the circuit, subscribers stay subscribed
+    pub fn reset(&self, config: CircuitBreakerConfig) {
+        let mut breaker = self.0.breaker.lock().unwrap();
+        *breaker = Breaker {
+            config,
+            failures: 0,
+            opened_at: Instant::now(),
+            trial: false,
+        };
+        self.set(CircuitState::Closed);
+    }
+
+    /// Permission for one call, the call reports its outcome through the permit
+    pub fn permit(&self) -> Result<Permit, ResourceError> {
+        let mut breaker = self.0.breaker.lock().unwrap();
+        match self.state() {
+            CircuitState::Closed => {}
+            CircuitState::Open => {
+                let elapsed = breaker.opened_at.elapsed();
+                if elapsed < breaker.config.open_for {
+                    return Err(ResourceError::Unavailable {
+                        retry_in: breaker.config.open_for - elapsed,
+                    });
+                }
+                breaker.trial = true;
+                self.set(CircuitState::HalfOpen);
+            }
+            CircuitState::HalfOpen if breaker.trial => {
+                return Err(ResourceError::Unavailable {
+                    retry_in: Duration::ZERO,
+                });
+            }
+            CircuitState::HalfOpen => breaker.trial = true,
+        }
+        Ok(Permit {
+            breaker: Some(self.clone()),
+        })
+    }
+
+    /// Runs the call if the circuit lets it through and records its outcome
+    pub async fn call<T>(
+        &self,
+        call: impl Future<Output = Result<T, ResourceError>>,
+    ) -> Result<T, ResourceError> {
+        let permit = self.permit()?;
+        let result = call.await;
+        permit.record(&result);
+        result
+    }
+
+    /// Like [`CircuitBreaker::call`] for a stream, its first item decides the outcome
+    pub fn stream<'a, T: Send + 'a>(
+        &self,
+        stream: impl FnOnce() -> BoxStream<'a, Result<T, ResourceError>>,
+    ) -> BoxStream<'a, Result<T, ResourceError>> {
+        match self.permit() {
+            Err(error) => futures_util::stream::once(future::ready(Err(error))).boxed(),
+            Ok(permit) => {
+                let mut permit = Some(permit);
+                stream()
+                    .inspect(move |item| {
+                        if let Some(permit) = permit.take() {
+                            permit.record(item);
+                        }
+                    })
+                    .boxed()
+            }
+        }
+    }
+
+    fn finish(&self, failed: bool) {
+        let mut breaker = self.0.breaker.lock().unwrap();
+        let trial = std::mem::take(&mut breaker.trial);
+        match (self.state(), failed) {
+            (_, false) => {
+                breaker.failures = 0;
+                self.set(CircuitState::Closed);
+            }
+            (CircuitState::Closed, true) => {
+                breaker.failures += 1;
+                if breaker.failures >= breaker.config.failures {
+                    breaker.opened_at = Instant::now();
+                    self.set(CircuitState::Open);
+                }
+            }
+            (CircuitState::HalfOpen, true) if trial => {
+                breaker.opened_at = Instant::now();
+                self.set(CircuitState::Open);
+            }
+            // a call started before the circuit opened
+            (_, true) => {}
+        }
+    }
+
+    /// Gives up the trial of a call that ended without an outcome, e.g. because it was dropped
+    fn release(&self) {
+        self.0.breaker.lock().unwrap().trial = false;
+    }
+
+    fn set(&self, state: CircuitState) {
+        let changed = self.0.state.send_if_modified(|current| {
+            let changed = *current != state;
+            *current = state;
+            changed
+        });
+        if changed {
+            tracing::info!(state = ?state, "resource circuit changed");
+        }
+    }
+}
+
+/// Permission for one call granted by [`CircuitBreaker::permit`]
+pub struct Permit {
+    breaker: Option<CircuitBreaker>,
+}
+
+impl Permit {
+    /// Records the outcome, only errors reaching the resource failed count as failures
+    pub fn record<T>(self, result: &Result<T, ResourceError>) {
+        self.finish(result.as_ref().is_err_and(ResourceError::is_unavailable));
+    }
+
+    pub fn success(self) {
+        self.finish(false);
+    }
+
+    pub fn failure(self) {
+        self.finish(true);
+    }
+
+    fn finish(mut self, failed: bool) {
+        if let Some(breaker) = self.breaker.take() {
+            breaker.finish(failed);
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(breaker) = self.breaker.take() {
+            breaker.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unreachable() -> ResourceError {
+        ResourceError::Database(sqlx::Error::PoolTimedOut)
+    }
+
+    #[tokio::test]
+    async fn test_opens_and_recovers() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            failures: 2,
+            open_for: Duration::from_millis(50),
+        });
+        let mut states = breaker.subscribe();
+
+        // errors of the statement itself leave the circuit closed
+        for _ in 0..3 {
+            let _ = breaker
+                .call(async { Err::<(), _>(ResourceError::Config("syntax".to_string())) })
+                .await;
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        let _ = breaker.call(async { Err::<(), _>(unreachable()) }).await;
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        let _ = breaker.call(async { Err::<(), _>(unreachable()) }).await;
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(states.has_changed().unwrap());
+        assert_eq!(*states.borrow_and_update(), CircuitState::Open);
+
+        // open: fails without calling
+        let result = breaker.call(async { panic!("must not be called") }).await;
+        assert!(matches!(
+            result,
+            Err::<(), _>(ResourceError::Unavailable { .. })
+        ));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        // half-open: a single trial, others fail fast
+        let trial = breaker.permit().unwrap();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.permit().is_err());
+        trial.failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        // a dropped trial lets the next call try
+        drop(breaker.permit().unwrap());
+        breaker.call(async { Ok(()) }).await.unwrap();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_stream_first_item_decides() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            failures: 1,
+            open_for: Duration::from_secs(60),
+        });
+        let items: Vec<Result<u32, _>> = breaker
+            .stream(|| futures_util::stream::iter([Err(unreachable()), Ok(1)]).boxed())
+            .collect()
+            .await;
+        assert_eq!(items.len(), 2);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let items: Vec<Result<u32, _>> = breaker
+            .stream(|| futures_util::stream::iter([Ok(1)]).boxed())
+            .collect()
+            .await;
+        assert!(matches!(
+            items.as_slice(),
+            [Err(ResourceError::Unavailable { .. })]
+        ));
+    }
+}
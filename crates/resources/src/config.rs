@@ -0,0 +1,80 @@
+use crate::ResourceError;
+use crate::access::AccessPolicy;
+use crate::circuit::CircuitBreakerConfig;
+use crate::database::{
+    DatabaseResourceConfig, MySQLResource, PostgresResource, SqliteResourceConfig,
+};
+use crate::http::HttpResourceConfig;
+
+/// The `resource` section, selected by its `type`
+#[derive(Debug, Clone)]
+pub enum ResourceConfig {
+    Http(HttpResourceConfig),
+    Mysql(DatabaseResourceConfig),
+    Postgres(DatabaseResourceConfig),
+    Sqlite(SqliteResourceConfig),
+}
+
+impl ResourceConfig {
+    /// Values of `type` known to the loader
+    pub const TYPES: &'static [&'static str] = &["http", "mysql", "postgres", "sqlite"];
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ResourceConfig::Http(_) => "http",
+            ResourceConfig::Mysql(_) => "mysql",
+            ResourceConfig::Postgres(_) => "postgres",
+            ResourceConfig::Sqlite(_) => "sqlite",
+        }
+    }
+
+    /// Checks the section for values the resource cannot work with
+    pub fn validate(&self) -> Result<(), ResourceError> {
+        match self {
+            ResourceConfig::Http(config) => config.validate(),
+            ResourceConfig::Mysql(config) => config
+                .validate()
+                .and_then(|()| config.check_scheme(MySQLResource::SCHEMES)),
+            ResourceConfig::Postgres(config) => config
+                .validate()
+                .and_then(|()| config.check_scheme(PostgresResource::SCHEMES)),
+            ResourceConfig::Sqlite(config) => config.validate(),
+        }
+    }
+}
+
+/// Sections of a resource document
+#[derive(Debug, Clone)]
+pub struct ResourceSpec {
+    pub resource: ResourceConfig,
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    pub access: Option<AccessPolicy>,
+}
+
+impl ResourceSpec {
+    pub fn validate(&self) -> Result<(), ResourceError> {
+        self.resource.validate()?;
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            circuit_breaker.validate()?;
+        }
+        if let Some(access) = &self.access {
+            access.validate()?;
+            if matches!(self.resource, ResourceConfig::Http(_)) && access.restricts_statements() {
+                return Err(ResourceError::Config(
+                    "access.statements and access.tables only apply to databases".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<ResourceConfig> for ResourceSpec {
+    fn from(resource: ResourceConfig) -> Self {
+        Self {
+            resource,
+            circuit_breaker: None,
+            access: None,
+        }
+    }
+}
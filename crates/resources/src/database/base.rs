@@ -1,4 +1,60 @@
+use crate::ResourceError;
+use futures_util::stream::BoxStream;
+use loid_events::prelude::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// A result row, column name to value
+pub type Row = HashMap<String, Value>;
+
+/// A pooled SQL database.
+///
+/// Parameters are bound in order to the placeholders of the statement, `$1` for Postgres and
+/// `?` for MySQL and SQLite. Lists and maps are bound as JSON.
+///
+/// The `_named` variants take `:name` placeholders and the values by name, see
+/// [`NamedStatement`](crate::database::NamedStatement). Postgres checks the values against the
+/// parameter types it infers for the statement and binds them as those types, e.g. a string as
+/// `UUID`. MySQL and SQLite convert the values themselves.
 pub trait SQLResource {
-  async fn execute_query(&self);
-  async fn ping(&self);
-}
\ No newline at end of file
+    /// Runs the statement and returns all rows
+    fn query(
+        &self,
+        statement: &str,
+        params: &[Value],
+    ) -> impl Future<Output = Result<Vec<Row>, ResourceError>> + Send;
+
+    /// Runs the statement and returns the number of rows it affected
+    fn execute(
+        &self,
+        statement: &str,
+        params: &[Value],
+    ) -> impl Future<Output = Result<u64, ResourceError>> + Send;
+
+    /// Runs the statement with named parameters and returns all rows
+    fn query_named(
+        &self,
+        statement: &str,
+        params: &HashMap<String, Value>,
+    ) -> impl Future<Output = Result<Vec<Row>, ResourceError>> + Send;
+
+    /// Runs the statement with named parameters and returns the number of rows it affected
+    fn execute_named(
+        &self,
+        statement: &str,
+        params: &HashMap<String, Value>,
+    ) -> impl Future<Output = Result<u64, ResourceError>> + Send;
+
+    /// Runs the statement and returns the rows as they arrive, for results too large to hold
+    fn stream<'a>(
+        &'a self,
+        statement: &'a str,
+        params: &'a [Value],
+    ) -> BoxStream<'a, Result<Row, ResourceError>>;
+
+    /// Checks a pooled connection and returns how long the round trip took
+    fn ping(&self) -> impl Future<Output = Result<Duration, ResourceError>> + Send;
+
+    /// Stops handing out connections and waits for the checked out ones to be returned
+    fn close(&self) -> impl Future<Output = ()> + Send;
+}
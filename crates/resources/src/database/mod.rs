@@ -1,7 +1,15 @@
+pub(crate) mod base;
+pub(crate) mod config;
+pub(crate) mod mysql;
+pub(crate) mod named;
 pub(crate) mod postgres;
 pub(crate) mod sqlite;
-pub(crate) mod mysql;
-pub(crate) mod base;
+pub(crate) mod statement;
+pub(crate) mod value;
 
+pub use base::{Row, SQLResource};
+pub use config::{DatabaseResourceConfig, JournalMode, SqliteResourceConfig, TlsConfig, TlsMode};
+pub use mysql::MySQLResource;
+pub use named::{Dialect, NamedStatement};
 pub use postgres::PostgresResource;
-pub use sqlite::SqliteResource;
\ No newline at end of file
+pub use sqlite::SqliteResource;
@@ -1,18 +1,268 @@
-use sqlx::{MySql, Pool};
-use sqlx::mysql::MySqlPoolOptions;
-use crate::database::base::SQLResource;
+use crate::ResourceError;
+use crate::access::AccessGuard;
+use crate::circuit::CircuitBreaker;
+use crate::database::base::{Row, SQLResource};
+use crate::database::config::{DatabaseResourceConfig, TlsMode, invalid_connection};
+use crate::database::named::{Dialect, NamedStatement};
+use crate::database::value::{decimal, hex, json, text};
+use futures_util::stream::BoxStream;
+use futures_util::{StreamExt, TryStreamExt, future, stream};
+use loid_events::prelude::Value;
+use sqlx::mysql::{MySqlArguments, MySqlConnectOptions, MySqlConnection, MySqlRow, MySqlSslMode};
+use sqlx::query::Query;
+use sqlx::types::Json;
+use sqlx::types::chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use sqlx::{Column, Connection, Executor, MySql, Pool, Row as _, TypeInfo, ValueRef};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 pub struct MySQLResource {
-    pool: Pool<MySql>
+    pool: Pool<MySql>,
+    breaker: CircuitBreaker,
+    access: AccessGuard,
+}
+
+impl MySQLResource {
+    /// Schemes of the connection URLs
+    pub const SCHEMES: &[&str] = &["mysql", "mariadb"];
+
+    /// Creates the pool, connecting right away unless `lazy` is set
+    pub async fn connect(config: &DatabaseResourceConfig) -> Result<Self, ResourceError> {
+        config.validate()?;
+        config.check_scheme(Self::SCHEMES)?;
+        let mut options = MySqlConnectOptions::from_str(config.connection.expose())
+            .map_err(invalid_connection)?;
+        if let Some(tls) = &config.tls {
+            options = options.ssl_mode(match tls.mode {
+                TlsMode::Disable => MySqlSslMode::Disabled,
+                TlsMode::Prefer => MySqlSslMode::Preferred,
+                TlsMode::Require => MySqlSslMode::Required,
+                TlsMode::VerifyCa => MySqlSslMode::VerifyCa,
+                TlsMode::VerifyFull => MySqlSslMode::VerifyIdentity,
+            });
+            if let Some(ca_file) = &tls.ca_file {
+                options = options.ssl_ca(ca_file);
+            }
+        }
+        let mut pool = config.pool();
+        if let Some(timeout) = config.statement_timeout {
+            // MySQL only limits the execution time of reads
+            let statement = format!("SET SESSION max_execution_time = {}", timeout.as_millis());
+            pool = pool.after_connect(move |connection: &mut MySqlConnection, _| {
+                let statement = statement.clone();
+                Box::pin(async move {
+                    connection.execute(statement.as_str()).await?;
+                    Ok(())
+                })
+            });
+        }
+        let pool = if config.lazy {
+            pool.connect_lazy_with(options)
+        } else {
+            pool.connect_with(options).await?
+        };
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Uses an already configured pool
+    pub fn from_pool(pool: Pool<MySql>) -> Self {
+        Self {
+            pool,
+            breaker: CircuitBreaker::default(),
+            access: AccessGuard::default(),
+        }
+    }
+
+    /// Guards the calls with the breaker instead of one with the default configuration
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    /// Enforces the access policy on every statement, read-only resources run them in
+    /// `READ ONLY` transactions
+    pub fn with_access(mut self, access: AccessGuard) -> Self {
+        self.access = access;
+        self
+    }
+
+    pub fn access(&self) -> &AccessGuard {
+        &self.access
+    }
+
+    async fn rows(
+        &self,
+        query: Query<'_, MySql, MySqlArguments>,
+    ) -> Result<Vec<MySqlRow>, ResourceError> {
+        if !self.access.is_read_only() {
+            return Ok(query.fetch_all(&self.pool).await?);
+        }
+        let mut transaction = self.pool.begin_with("START TRANSACTION READ ONLY").await?;
+        let rows = query.fetch_all(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(rows)
+    }
+
+    async fn affected(
+        &self,
+        query: Query<'_, MySql, MySqlArguments>,
+    ) -> Result<u64, ResourceError> {
+        if !self.access.is_read_only() {
+            return Ok(query.execute(&self.pool).await?.rows_affected());
+        }
+        let mut transaction = self.pool.begin_with("START TRANSACTION READ ONLY").await?;
+        let result = query.execute(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(result.rows_affected())
+    }
+}
+
+fn bind<'q>(
+    query: Query<'q, MySql, MySqlArguments>,
+    value: &Value,
+) -> Query<'q, MySql, MySqlArguments> {
+    match value {
+        // NULL is sent without a value, the type of the option is not checked against the column
+        Value::None => query.bind(None::<String>),
+        Value::String(text) => query.bind(text.clone()),
+        Value::Int(number) => query.bind(*number),
+        Value::Float(number) => query.bind(*number),
+        Value::Bool(flag) => query.bind(*flag),
+        Value::List(_) | Value::Map(_) => query.bind(Json(value.clone())),
+    }
+}
+
+fn statement<'q>(statement: &'q str, params: &[Value]) -> Query<'q, MySql, MySqlArguments> {
+    params.iter().fold(sqlx::query(statement), bind)
+}
+
+/// The values of the named parameters in placeholder order, the driver converts them
+fn positional(
+    statement: &NamedStatement,
+    params: &HashMap<String, Value>,
+) -> Result<Vec<Value>, ResourceError> {
+    let values = statement.values(params)?;
+    Ok(values.into_iter().map(|(_, value)| value.clone()).collect())
+}
+
+fn column(row: &MySqlRow, index: usize) -> Result<Value, ResourceError> {
+    if row.try_get_raw(index)?.is_null() {
+        return Ok(Value::None);
+    }
+    let value = match row.columns()[index].type_info().name() {
+        "BOOLEAN" => Value::Bool(row.try_get(index)?),
+        "TINYINT" => Value::Int(row.try_get::<i8, _>(index)?.into()),
+        "SMALLINT" => Value::Int(row.try_get::<i16, _>(index)?.into()),
+        "INT" | "MEDIUMINT" => Value::Int(row.try_get::<i32, _>(index)?.into()),
+        "BIGINT" => Value::Int(row.try_get(index)?),
+        "TINYINT UNSIGNED" => Value::from(row.try_get::<u8, _>(index)?),
+        "SMALLINT UNSIGNED" | "YEAR" => Value::from(row.try_get::<u16, _>(index)?),
+        "INT UNSIGNED" | "MEDIUMINT UNSIGNED" => Value::from(row.try_get::<u32, _>(index)?),
+        "BIGINT UNSIGNED" | "BIT" => Value::from(row.try_get::<u64, _>(index)?),
+        "FLOAT" => Value::Float(row.try_get::<f32, _>(index)?.into()),
+        "DOUBLE" => Value::Float(row.try_get(index)?),
+        "DECIMAL" => decimal(row.try_get(index)?),
+        "JSON" => json(row.try_get(index)?),
+        "TIMESTAMP" => Value::String(row.try_get::<DateTime<Utc>, _>(index)?.to_rfc3339()),
+        "DATETIME" => Value::String(row.try_get::<NaiveDateTime, _>(index)?.to_string()),
+        "DATE" => Value::String(row.try_get::<NaiveDate, _>(index)?.to_string()),
+        "TIME" => Value::String(row.try_get::<NaiveTime, _>(index)?.to_string()),
+        "BINARY" | "VARBINARY" | "TINYBLOB" | "BLOB" | "MEDIUMBLOB" | "LONGBLOB" => {
+            hex(row.try_get_unchecked::<&[u8], _>(index)?)
+        }
+        _ => text(row, index)?,
+    };
+    Ok(value)
+}
+
+fn decode(row: &MySqlRow) -> Result<Row, ResourceError> {
+    row.columns()
+        .iter()
+        .map(|column| {
+            Ok((
+                column.name().to_string(),
+                self::column(row, column.ordinal())?,
+            ))
+        })
+        .collect()
 }
 
 impl SQLResource for MySQLResource {
-    async fn execute_query(&self) {
-        todo!()
+    async fn query(&self, statement: &str, params: &[Value]) -> Result<Vec<Row>, ResourceError> {
+        self.access.check_statement(statement, Dialect::Mysql)?;
+        self.breaker
+            .call(async {
+                let rows = self.rows(self::statement(statement, params)).await?;
+                rows.iter().map(decode).collect()
+            })
+            .await
+    }
+
+    async fn execute(&self, statement: &str, params: &[Value]) -> Result<u64, ResourceError> {
+        self.access.check_statement(statement, Dialect::Mysql)?;
+        self.breaker
+            .call(self.affected(self::statement(statement, params)))
+            .await
+    }
+
+    async fn query_named(
+        &self,
+        statement: &str,
+        params: &HashMap<String, Value>,
+    ) -> Result<Vec<Row>, ResourceError> {
+        let statement = NamedStatement::parse(statement, Dialect::Mysql)?;
+        self.query(statement.sql(), &positional(&statement, params)?)
+            .await
+    }
+
+    async fn execute_named(
+        &self,
+        statement: &str,
+        params: &HashMap<String, Value>,
+    ) -> Result<u64, ResourceError> {
+        let statement = NamedStatement::parse(statement, Dialect::Mysql)?;
+        self.execute(statement.sql(), &positional(&statement, params)?)
+            .await
     }
 
-    async fn ping(&self) {
-        let pool = MySqlPoolOptions::new().max_connections(1).connect("").await.unwrap();
-        todo!()
+    fn stream<'a>(
+        &'a self,
+        statement: &'a str,
+        params: &'a [Value],
+    ) -> BoxStream<'a, Result<Row, ResourceError>> {
+        if let Err(error) = self.access.check_statement(statement, Dialect::Mysql) {
+            return stream::once(future::ready(Err(error))).boxed();
+        }
+        self.breaker.stream(|| {
+            let query = self::statement(statement, params);
+            if self.access.is_read_only() {
+                // the read-only connection state can not outlive the call, so the rows are
+                // fetched at once
+                return stream::once(self.rows(query))
+                    .map_ok(|rows| stream::iter(rows.into_iter().map(|row| decode(&row))))
+                    .try_flatten()
+                    .boxed();
+            }
+            query.fetch(&self.pool).map(|row| decode(&row?)).boxed()
+        })
     }
-}
\ No newline at end of file
+
+    async fn ping(&self) -> Result<Duration, ResourceError> {
+        self.breaker
+            .call(async {
+                let start = Instant::now();
+                self.pool.acquire().await?.ping().await?;
+                Ok(start.elapsed())
+            })
+            .await
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+}
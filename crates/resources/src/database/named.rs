@@ -0,0 +1,294 @@
+//! Statements with named parameters, e.g. `UPDATE alerts SET status = :status WHERE id = :id`.
+//!
+//! The names are replaced by the placeholders of the driver and the values bound as parameters,
+//! they never become part of the statement text. Colons inside string literals, quoted
+//! identifiers and comments are left alone, as are casts like `::text`.
+
+use crate::ResourceError;
+use loid_events::prelude::Value;
+use std::collections::HashMap;
+
+/// SQL dialect deciding the placeholders and how literals are quoted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// `$1`, `$2`, …, a name used twice is bound once. Dollar-quoted strings and backslash
+    /// escapes in `E'…'` strings are recognized
+    Postgres,
+    /// `?` for every use of a name, backslash escapes in all strings, double quoted ones too
+    Mysql,
+    /// `?` for every use of a name
+    Sqlite,
+}
+
+/// A statement with `:name` parameters, rewritten for a dialect
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedStatement {
+    sql: String,
+    names: Vec<String>,
+}
+
+impl NamedStatement {
+    /// Replaces the names by placeholders, statements also using the placeholders of the
+    /// dialect themselves are rejected
+    pub fn parse(statement: &str, dialect: Dialect) -> Result<Self, ResourceError> {
+        let bytes = statement.as_bytes();
+        let mut sql = String::with_capacity(statement.len());
+        let mut names: Vec<String> = Vec::new();
+        // start of the text not yet copied to `sql`
+        let mut copied = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let next = bytes.get(i + 1).copied();
+            match bytes[i] {
+                quote @ (b'\'' | b'"' | b'`') => {
+                    i = quoted(bytes, i, quote, escapes(bytes, i, dialect))
+                }
+                b'-' if next == Some(b'-') => {
+                    i = find(bytes, i + 2, b"\n").map_or(bytes.len(), |end| end + 1)
+                }
+                b'/' if next == Some(b'*') => {
+                    i = find(bytes, i + 2, b"*/").map_or(bytes.len(), |end| end + 2)
+                }
+                b'$' if dialect == Dialect::Postgres => {
+                    if next.is_some_and(|byte| byte.is_ascii_digit()) {
+                        return Err(mixed("$1"));
+                    }
+                    i = dollar_quoted(bytes, i).unwrap_or(i + 1);
+                }
+                b'?' if dialect != Dialect::Postgres => return Err(mixed("?")),
+                // a cast, `::text`
+                b':' if next == Some(b':') => i += 2,
+                b':' if next.is_some_and(is_name_start) && (i == 0 || !is_name(bytes[i - 1])) => {
+                    let end = (i + 1..bytes.len())
+                        .find(|&end| !is_name(bytes[end]))
+                        .unwrap_or(bytes.len());
+                    let name = &statement[i + 1..end];
+                    sql.push_str(&statement[copied..i]);
+                    match dialect {
+                        Dialect::Postgres => {
+                            let position = match names.iter().position(|known| known == name) {
+                                Some(position) => position,
+                                None => {
+                                    names.push(name.to_string());
+                                    names.len() - 1
+                                }
+                            };
+                            sql.push_str(&format!("${}", position + 1));
+                        }
+                        Dialect::Mysql | Dialect::Sqlite => {
+                            names.push(name.to_string());
+                            sql.push('?');
+                        }
+                    }
+                    copied = end;
+                    i = end;
+                }
+                _ => i += 1,
+            }
+        }
+        sql.push_str(&statement[copied..]);
+        Ok(Self { sql, names })
+    }
+
+    /// The statement with the placeholders of the dialect
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Name bound to each placeholder, in order
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Value of each placeholder with its name, fails listing every parameter without a value.
+    /// Values not used by the statement are ignored
+    pub fn values<'a>(
+        &'a self,
+        params: &'a HashMap<String, Value>,
+    ) -> Result<Vec<(&'a str, &'a Value)>, ResourceError> {
+        let mut missing: Vec<String> = Vec::new();
+        let mut values = Vec::with_capacity(self.names.len());
+        for name in &self.names {
+            match params.get(name) {
+                Some(value) => values.push((name.as_str(), value)),
+                None if !missing.contains(name) => missing.push(name.clone()),
+                None => {}
+            }
+        }
+        if missing.is_empty() {
+            Ok(values)
+        } else {
+            Err(ResourceError::MissingParameters(missing))
+        }
+    }
+}
+
+/// Name of the kind of a value, for errors
+pub(crate) fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::None => "null",
+        Value::String(_) => "a string",
+        Value::Int(_) => "an integer",
+        Value::Float(_) => "a float",
+        Value::Bool(_) => "a boolean",
+        Value::List(_) => "a list",
+        Value::Map(_) => "a map",
+    }
+}
+
+fn mixed(placeholder: &str) -> ResourceError {
+    ResourceError::Statement(format!(
+        "named parameters can not be mixed with `{placeholder}` placeholders"
+    ))
+}
+
+fn is_name_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'_'
+}
+
+pub(crate) fn is_name(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+pub(crate) fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| from + position)
+}
+
+/// Whether backslashes escape in the literal or quoted identifier starting at `start`
+pub(crate) fn escapes(bytes: &[u8], start: usize, dialect: Dialect) -> bool {
+    match (bytes[start], dialect) {
+        (b'`', _) => false,
+        // MySQL quotes strings with double quotes too, unless `ANSI_QUOTES` is set
+        (b'"', dialect) => dialect == Dialect::Mysql,
+        (_, Dialect::Postgres) => {
+            start > 0
+                && matches!(bytes[start - 1], b'E' | b'e')
+                && (start == 1 || !is_name(bytes[start - 2]))
+        }
+        (_, Dialect::Mysql) => true,
+        (_, Dialect::Sqlite) => false,
+    }
+}
+
+/// End of the literal or identifier starting at `start`, a doubled quote is part of it
+pub(crate) fn quoted(bytes: &[u8], start: usize, quote: u8, escapes: bool) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if escapes => i += 2,
+            byte if byte == quote => {
+                if bytes.get(i + 1) == Some(&quote) {
+                    i += 2;
+                } else {
+                    return i + 1;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// End of the dollar-quoted string starting at `start`, e.g. `$body$ … $body$`
+pub(crate) fn dollar_quoted(bytes: &[u8], start: usize) -> Option<usize> {
+    let tag_end = (start + 1..bytes.len()).find(|&i| !is_name(bytes[i]))?;
+    if bytes[tag_end] != b'$' || bytes.get(start + 1).is_some_and(u8::is_ascii_digit) {
+        return None;
+    }
+    let tag = &bytes[start..=tag_end];
+    let end = find(bytes, tag_end + 1, tag).map_or(bytes.len(), |end| end + tag.len());
+    Some(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(statement: &str, dialect: Dialect) -> (String, Vec<String>) {
+        let statement = NamedStatement::parse(statement, dialect).unwrap();
+        (statement.sql().to_string(), statement.names().to_vec())
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let statement = "UPDATE alerts SET status = :status WHERE id = :id AND status <> :status";
+        assert_eq!(
+            parse(statement, Dialect::Postgres),
+            (
+                "UPDATE alerts SET status = $1 WHERE id = $2 AND status <> $1".to_string(),
+                vec!["status".to_string(), "id".to_string()]
+            )
+        );
+        assert_eq!(
+            parse(statement, Dialect::Mysql),
+            (
+                "UPDATE alerts SET status = ? WHERE id = ? AND status <> ?".to_string(),
+                vec!["status".to_string(), "id".to_string(), "status".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_ignores_literals_comments_and_casts() {
+        let statement = "SELECT ':a', \"b:c\", `d:e`, x::text, arr[1:n], $f$ :g $f$ \
+                         -- :h\n /* :i */ FROM t WHERE y = :y";
+        let (sql, names) = parse(statement, Dialect::Postgres);
+        assert_eq!(names, ["y"]);
+        assert!(
+            sql.ends_with("WHERE y = $1") && sql.contains("$f$ :g $f$"),
+            "{sql}"
+        );
+
+        // backslash escapes only where the dialect has them
+        let (_, names) = parse(r"SELECT 'it\'s :a' , :b", Dialect::Mysql);
+        assert_eq!(names, ["b"]);
+        let (_, names) = parse(r"SELECT E'it\'s :a', :b", Dialect::Postgres);
+        assert_eq!(names, ["b"]);
+        let (_, names) = parse(r"SELECT 'C:\', :b", Dialect::Sqlite);
+        assert_eq!(names, ["b"]);
+        let (_, names) = parse(r#"SELECT "say \"hi :a\"", :b"#, Dialect::Mysql);
+        assert_eq!(names, ["b"]);
+        let (_, names) = parse(r#"SELECT "C:\", :b"#, Dialect::Postgres);
+        assert_eq!(names, ["b"]);
+    }
+
+    #[test]
+    fn test_values() {
+        let statement = NamedStatement::parse(
+            "SELECT * FROM t WHERE a = :a AND b = :b AND c = :c OR b = :b",
+            Dialect::Sqlite,
+        )
+        .unwrap();
+        let params = HashMap::from([
+            ("a".to_string(), Value::Int(1)),
+            ("unused".to_string(), Value::Int(2)),
+        ]);
+        match statement.values(&params) {
+            Err(ResourceError::MissingParameters(missing)) => assert_eq!(missing, ["b", "c"]),
+            other => panic!("expected missing parameters, got {other:?}"),
+        }
+
+        let params = HashMap::from([
+            ("a".to_string(), Value::Int(1)),
+            ("b".to_string(), Value::from("x")),
+            ("c".to_string(), Value::None),
+        ]);
+        let values = statement.values(&params).unwrap();
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[3], ("b", &Value::from("x")));
+
+        for (statement, dialect) in [
+            ("SELECT :a, $1", Dialect::Postgres),
+            ("SELECT :a, ?", Dialect::Mysql),
+        ] {
+            assert!(matches!(
+                NamedStatement::parse(statement, dialect),
+                Err(ResourceError::Statement(_))
+            ));
+        }
+    }
+}
@@ -1,16 +1,446 @@
-use sqlx::{Executor, Pool, Postgres};
-use crate::database::base::SQLResource;
+use crate::ResourceError;
+use crate::access::AccessGuard;
+use crate::circuit::CircuitBreaker;
+use crate::database::base::{Row, SQLResource};
+use crate::database::config::{DatabaseResourceConfig, TlsMode, invalid_connection};
+use crate::database::named::{Dialect, NamedStatement, kind};
+use crate::database::value::{decimal, hex, json, text};
+use futures_util::stream::BoxStream;
+use futures_util::{StreamExt, TryStreamExt, future, stream};
+use loid_events::prelude::Value;
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::types::Oid;
+use sqlx::postgres::{
+    PgArgumentBuffer, PgArguments, PgConnectOptions, PgRow, PgSslMode, PgTypeInfo,
+};
+use sqlx::query::Query;
+use sqlx::types::chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use sqlx::types::{Decimal, Json, Uuid};
+use sqlx::{
+    Column, Connection, Either, Encode, Executor, Pool, Postgres, Row as _, Type, TypeInfo,
+    ValueRef,
+};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 pub struct PostgresResource {
     pool: Pool<Postgres>,
+    breaker: CircuitBreaker,
+    access: AccessGuard,
+}
+
+impl PostgresResource {
+    /// Schemes of the connection URLs
+    pub const SCHEMES: &[&str] = &["postgres", "postgresql"];
+
+    /// Creates the pool, connecting right away unless `lazy` is set
+    pub async fn connect(config: &DatabaseResourceConfig) -> Result<Self, ResourceError> {
+        config.validate()?;
+        config.check_scheme(Self::SCHEMES)?;
+        let mut options =
+            PgConnectOptions::from_str(config.connection.expose()).map_err(invalid_connection)?;
+        if let Some(tls) = &config.tls {
+            options = options.ssl_mode(match tls.mode {
+                TlsMode::Disable => PgSslMode::Disable,
+                TlsMode::Prefer => PgSslMode::Prefer,
+                TlsMode::Require => PgSslMode::Require,
+                TlsMode::VerifyCa => PgSslMode::VerifyCa,
+                TlsMode::VerifyFull => PgSslMode::VerifyFull,
+            });
+            if let Some(ca_file) = &tls.ca_file {
+                options = options.ssl_root_cert(ca_file);
+            }
+        }
+        if let Some(timeout) = config.statement_timeout {
+            options = options.options([("statement_timeout", timeout.as_millis())]);
+        }
+        let pool = config.pool();
+        let pool = if config.lazy {
+            pool.connect_lazy_with(options)
+        } else {
+            pool.connect_with(options).await?
+        };
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Uses an already configured pool
+    pub fn from_pool(pool: Pool<Postgres>) -> Self {
+        Self {
+            pool,
+            breaker: CircuitBreaker::default(),
+            access: AccessGuard::default(),
+        }
+    }
+
+    /// Guards the calls with the breaker instead of one with the default configuration
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    /// Enforces the access policy on every statement, read-only resources run them in
+    /// `READ ONLY` transactions
+    pub fn with_access(mut self, access: AccessGuard) -> Self {
+        self.access = access;
+        self
+    }
+
+    pub fn access(&self) -> &AccessGuard {
+        &self.access
+    }
+
+    async fn rows(
+        &self,
+        query: Query<'_, Postgres, PgArguments>,
+    ) -> Result<Vec<PgRow>, ResourceError> {
+        if !self.access.is_read_only() {
+            return Ok(query.fetch_all(&self.pool).await?);
+        }
+        let mut transaction = self.pool.begin_with("BEGIN READ ONLY").await?;
+        let rows = query.fetch_all(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(rows)
+    }
+
+    async fn affected(
+        &self,
+        query: Query<'_, Postgres, PgArguments>,
+    ) -> Result<u64, ResourceError> {
+        if !self.access.is_read_only() {
+            return Ok(query.execute(&self.pool).await?.rows_affected());
+        }
+        let mut transaction = self.pool.begin_with("BEGIN READ ONLY").await?;
+        let result = query.execute(&mut *transaction).await?;
+        transaction.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Binds the named parameters as the types Postgres infers for them, which takes a round
+    /// trip to prepare the statement
+    async fn named<'q>(
+        &self,
+        statement: &'q NamedStatement,
+        params: &HashMap<String, Value>,
+    ) -> Result<Query<'q, Postgres, PgArguments>, ResourceError> {
+        let values = statement.values(params)?;
+        let describe = (&self.pool).describe(statement.sql()).await?;
+        let types = match describe.parameters() {
+            Some(Either::Left(types)) => types,
+            _ => &[],
+        };
+        values.into_iter().enumerate().try_fold(
+            sqlx::query(statement.sql()),
+            |query, (i, (name, value))| match types.get(i) {
+                Some(type_info) => bind_typed(query, name, value, type_info),
+                None => Ok(bind(query, value)),
+            },
+        )
+    }
+}
+
+fn bind<'q>(
+    query: Query<'q, Postgres, PgArguments>,
+    value: &Value,
+) -> Query<'q, Postgres, PgArguments> {
+    match value {
+        Value::None => query.bind(Null),
+        Value::String(text) => query.bind(text.clone()),
+        Value::Int(number) => query.bind(*number),
+        Value::Float(number) => query.bind(*number),
+        Value::Bool(flag) => query.bind(*flag),
+        Value::List(_) | Value::Map(_) => query.bind(Json(value.clone())),
+    }
+}
+
+/// NULL without a type, the database infers the type of its placeholder like for a literal NULL
+struct Null;
+
+impl Type<Postgres> for Null {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_oid(Oid(0))
+    }
+}
+
+impl Encode<'_, Postgres> for Null {
+    fn encode_by_ref(&self, _: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        Ok(IsNull::Yes)
+    }
+}
+
+/// Named parameter checked against the type of its placeholder
+struct Param<'a> {
+    name: &'a str,
+    value: &'a Value,
+    expected: &'a str,
+}
+
+impl Param<'_> {
+    /// The value converted for the placeholder, null for any type
+    fn get<T>(
+        &self,
+        convert: impl FnOnce(&Value) -> Option<T>,
+    ) -> Result<Option<T>, ResourceError> {
+        if *self.value == Value::None {
+            return Ok(None);
+        }
+        convert(self.value)
+            .map(Some)
+            .ok_or_else(|| ResourceError::ParameterType {
+                name: self.name.to_string(),
+                expected: self.expected.to_string(),
+                actual: kind(self.value),
+            })
+    }
+}
+
+fn int(value: &Value) -> Option<i64> {
+    match value {
+        Value::Int(number) => Some(*number),
+        _ => None,
+    }
+}
+
+fn float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(number) => Some(*number as f64),
+        Value::Float(number) => Some(*number),
+        _ => None,
+    }
+}
+
+fn string(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(text) => Some(text),
+        _ => None,
+    }
+}
+
+/// Binds the value as the type of its placeholder, values for types without a conversion are
+/// bound as they are and left to the database
+fn bind_typed<'q>(
+    query: Query<'q, Postgres, PgArguments>,
+    name: &str,
+    value: &Value,
+    type_info: &PgTypeInfo,
+) -> Result<Query<'q, Postgres, PgArguments>, ResourceError> {
+    let param = Param {
+        name,
+        value,
+        expected: type_info.name(),
+    };
+    Ok(match param.expected {
+        "BOOL" => query.bind(param.get(|value| match value {
+            Value::Bool(flag) => Some(*flag),
+            _ => None,
+        })?),
+        "INT2" => query.bind(param.get(|value| i16::try_from(int(value)?).ok())?),
+        "INT4" => query.bind(param.get(|value| i32::try_from(int(value)?).ok())?),
+        "INT8" => query.bind(param.get(int)?),
+        "FLOAT4" => query.bind(param.get(|value| float(value).map(|number| number as f32))?),
+        "FLOAT8" => query.bind(param.get(float)?),
+        "NUMERIC" => query.bind(param.get(|value| match value {
+            Value::Int(number) => Some(Decimal::from(*number)),
+            Value::Float(number) => Decimal::try_from(*number).ok(),
+            Value::String(text) => text.parse().ok(),
+            _ => None,
+        })?),
+        "TEXT" | "VARCHAR" | "BPCHAR" | "NAME" => {
+            query.bind(param.get(|value| string(value).map(str::to_string))?)
+        }
+        "UUID" => query.bind(param.get(|value| string(value)?.parse::<Uuid>().ok())?),
+        "TIMESTAMPTZ" => query.bind(param.get(|value| {
+            DateTime::parse_from_rfc3339(string(value)?)
+                .ok()
+                .map(|time| time.with_timezone(&Utc))
+        })?),
+        "TIMESTAMP" => query.bind(param.get(|value| {
+            let text = string(value)?;
+            NaiveDateTime::from_str(text)
+                .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f"))
+                .ok()
+        })?),
+        "DATE" => query.bind(param.get(|value| NaiveDate::from_str(string(value)?).ok())?),
+        "JSON" | "JSONB" => query.bind(param.get(|value| Some(Json(value.clone())))?),
+        _ => bind(query, value),
+    })
+}
+
+fn statement<'q>(statement: &'q str, params: &[Value]) -> Query<'q, Postgres, PgArguments> {
+    params.iter().fold(sqlx::query(statement), bind)
+}
+
+fn list<T>(values: Vec<T>) -> Value
+where
+    Value: From<T>,
+{
+    Value::List(values.into_iter().map(Value::from).collect())
+}
+
+fn column(row: &PgRow, index: usize) -> Result<Value, ResourceError> {
+    if row.try_get_raw(index)?.is_null() {
+        return Ok(Value::None);
+    }
+    let value = match row.columns()[index].type_info().name() {
+        "BOOL" => Value::Bool(row.try_get(index)?),
+        "INT2" => Value::Int(row.try_get::<i16, _>(index)?.into()),
+        "INT4" => Value::Int(row.try_get::<i32, _>(index)?.into()),
+        "INT8" => Value::Int(row.try_get(index)?),
+        "FLOAT4" => Value::Float(row.try_get::<f32, _>(index)?.into()),
+        "FLOAT8" => Value::Float(row.try_get(index)?),
+        "NUMERIC" => decimal(row.try_get(index)?),
+        "JSON" | "JSONB" => json(row.try_get(index)?),
+        "UUID" => Value::String(row.try_get::<sqlx::types::Uuid, _>(index)?.to_string()),
+        "TIMESTAMPTZ" => Value::String(row.try_get::<DateTime<Utc>, _>(index)?.to_rfc3339()),
+        "TIMESTAMP" => Value::String(row.try_get::<NaiveDateTime, _>(index)?.to_string()),
+        "DATE" => Value::String(row.try_get::<NaiveDate, _>(index)?.to_string()),
+        "TIME" => Value::String(row.try_get::<NaiveTime, _>(index)?.to_string()),
+        "BYTEA" => hex(row.try_get_unchecked::<&[u8], _>(index)?),
+        "BOOL[]" => list(row.try_get::<Vec<bool>, _>(index)?),
+        "INT2[]" => list(row.try_get::<Vec<i16>, _>(index)?),
+        "INT4[]" => list(row.try_get::<Vec<i32>, _>(index)?),
+        "INT8[]" => list(row.try_get::<Vec<i64>, _>(index)?),
+        "FLOAT8[]" => list(row.try_get::<Vec<f64>, _>(index)?),
+        "TEXT[]" | "VARCHAR[]" | "NAME[]" => list(row.try_get::<Vec<String>, _>(index)?),
+        _ => text(row, index)?,
+    };
+    Ok(value)
+}
+
+fn decode(row: &PgRow) -> Result<Row, ResourceError> {
+    row.columns()
+        .iter()
+        .map(|column| {
+            Ok((
+                column.name().to_string(),
+                self::column(row, column.ordinal())?,
+            ))
+        })
+        .collect()
 }
 
 impl SQLResource for PostgresResource {
-    async fn execute_query(&self) {
-        todo!()
+    async fn query(&self, statement: &str, params: &[Value]) -> Result<Vec<Row>, ResourceError> {
+        self.access.check_statement(statement, Dialect::Postgres)?;
+        self.breaker
+            .call(async {
+                let rows = self.rows(self::statement(statement, params)).await?;
+                rows.iter().map(decode).collect()
+            })
+            .await
     }
 
-    async fn ping(&self) {
-        let ping_res = sqlx::query("SELECT 1").fetch_one(&self.pool).await;
+    async fn execute(&self, statement: &str, params: &[Value]) -> Result<u64, ResourceError> {
+        self.access.check_statement(statement, Dialect::Postgres)?;
+        self.breaker
+            .call(self.affected(self::statement(statement, params)))
+            .await
     }
-}
\ No newline at end of file
+
+    async fn query_named(
+        &self,
+        statement: &str,
+        params: &HashMap<String, Value>,
+    ) -> Result<Vec<Row>, ResourceError> {
+        let statement = NamedStatement::parse(statement, Dialect::Postgres)?;
+        self.access
+            .check_statement(statement.sql(), Dialect::Postgres)?;
+        self.breaker
+            .call(async {
+                let rows = self.rows(self.named(&statement, params).await?).await?;
+                rows.iter().map(decode).collect()
+            })
+            .await
+    }
+
+    async fn execute_named(
+        &self,
+        statement: &str,
+        params: &HashMap<String, Value>,
+    ) -> Result<u64, ResourceError> {
+        let statement = NamedStatement::parse(statement, Dialect::Postgres)?;
+        self.access
+            .check_statement(statement.sql(), Dialect::Postgres)?;
+        self.breaker
+            .call(async { self.affected(self.named(&statement, params).await?).await })
+            .await
+    }
+
+    fn stream<'a>(
+        &'a self,
+        statement: &'a str,
+        params: &'a [Value],
+    ) -> BoxStream<'a, Result<Row, ResourceError>> {
+        if let Err(error) = self.access.check_statement(statement, Dialect::Postgres) {
+            return stream::once(future::ready(Err(error))).boxed();
+        }
+        self.breaker.stream(|| {
+            let query = self::statement(statement, params);
+            if self.access.is_read_only() {
+                // the transaction can not outlive the call, so the rows are fetched at once
+                return stream::once(self.rows(query))
+                    .map_ok(|rows| stream::iter(rows.into_iter().map(|row| decode(&row))))
+                    .try_flatten()
+                    .boxed();
+            }
+            query.fetch(&self.pool).map(|row| decode(&row?)).boxed()
+        })
+    }
+
+    async fn ping(&self) -> Result<Duration, ResourceError> {
+        self.breaker
+            .call(async {
+                let start = Instant::now();
+                self.pool.acquire().await?.ping().await?;
+                Ok(start.elapsed())
+            })
+            .await
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: serde_json::Value) -> DatabaseResourceConfig {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_connect_lazy_and_eager() {
+        let unreachable = serde_json::json!({
+            "connection": "postgres://loid@127.0.0.1:1/loid",
+            "acquire_timeout": "1s",
+            "lazy": true,
+        });
+        let resource = PostgresResource::connect(&config(unreachable.clone()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            resource.ping().await,
+            Err(ResourceError::Database(_))
+        ));
+
+        let mut eager = unreachable;
+        eager["lazy"] = false.into();
+        assert!(matches!(
+            PostgresResource::connect(&config(eager)).await,
+            Err(ResourceError::Database(_))
+        ));
+
+        let invalid = serde_json::json!({"connection": "mysql://loid@localhost/loid"});
+        match PostgresResource::connect(&config(invalid)).await {
+            Err(ResourceError::Config(error)) => assert!(error.contains("postgres://"), "{error}"),
+            other => panic!("expected a configuration error, got {:?}", other.err()),
+        }
+    }
+}
@@ -1 +1,527 @@
-pub struct SqliteResource {}
+use crate::ResourceError;
+use crate::access::AccessGuard;
+use crate::circuit::CircuitBreaker;
+use crate::database::base::{Row, SQLResource};
+use crate::database::config::{JournalMode, SqliteResourceConfig};
+use crate::database::named::{Dialect, NamedStatement};
+use crate::database::value::{hex, text};
+use futures_util::stream::BoxStream;
+use futures_util::{StreamExt, TryStreamExt, future, stream};
+use loid_events::prelude::Value;
+use sqlx::pool::PoolConnection;
+use sqlx::query::Query;
+use sqlx::sqlite::{
+    SqliteArguments, SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqlitePoolOptions,
+    SqliteRow,
+};
+use sqlx::types::Json;
+use sqlx::{Column, Connection, Pool, Row as _, Sqlite, TypeInfo, ValueRef};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+pub struct SqliteResource {
+    pool: Pool<Sqlite>,
+    breaker: CircuitBreaker,
+    access: AccessGuard,
+}
+
+impl SqliteResource {
+    /// Opens the database file or creates the in-memory database
+    pub async fn connect(config: &SqliteResourceConfig) -> Result<Self, ResourceError> {
+        config.validate()?;
+        let mut options = SqliteConnectOptions::from_str(&format!("sqlite:{}", config.path))?
+            .busy_timeout(config.busy_timeout);
+        let mut pool = SqlitePoolOptions::new().max_connections(config.max_connections);
+        if config.in_memory() {
+            // the database lives as long as one of its connections, so the pool keeps one open
+            pool = pool
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None);
+        } else {
+            options = options
+                .create_if_missing(config.create)
+                .journal_mode(journal_mode(config.journal_mode));
+        }
+        let pool = pool.connect_with(options).await?;
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Uses an already configured pool
+    pub fn from_pool(pool: Pool<Sqlite>) -> Self {
+        Self {
+            pool,
+            breaker: CircuitBreaker::default(),
+            access: AccessGuard::default(),
+        }
+    }
+
+    /// Guards the calls with the breaker instead of one with the default configuration
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    /// Enforces the access policy on every statement, read-only resources run them on
+    /// connections with `PRAGMA query_only` set
+    pub fn with_access(mut self, access: AccessGuard) -> Self {
+        self.access = access;
+        self
+    }
+
+    pub fn access(&self) -> &AccessGuard {
+        &self.access
+    }
+
+    async fn rows<'q>(
+        &self,
+        query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    ) -> Result<Vec<SqliteRow>, ResourceError> {
+        if !self.access.is_read_only() {
+            return Ok(query.fetch_all(&self.pool).await?);
+        }
+        let mut connection = ReadOnly::acquire(&self.pool).await?;
+        let rows = query.fetch_all(connection.connection()).await;
+        connection.release().await?;
+        Ok(rows?)
+    }
+
+    async fn affected<'q>(
+        &self,
+        query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    ) -> Result<u64, ResourceError> {
+        if !self.access.is_read_only() {
+            return Ok(query.execute(&self.pool).await?.rows_affected());
+        }
+        let mut connection = ReadOnly::acquire(&self.pool).await?;
+        let result = query.execute(connection.connection()).await;
+        connection.release().await?;
+        Ok(result?.rows_affected())
+    }
+}
+
+/// A pooled connection with `PRAGMA query_only` set, which has to be reset before the
+/// connection goes back to the pool.
+///
+/// Dropped without [`ReadOnly::release`], e.g. when the call was cancelled, the reset runs in a
+/// task. A connection that can not be reset is closed instead of handed to the next caller.
+struct ReadOnly(Option<PoolConnection<Sqlite>>);
+
+impl ReadOnly {
+    async fn acquire(pool: &Pool<Sqlite>) -> Result<Self, ResourceError> {
+        let mut guard = Self(Some(pool.acquire().await?));
+        sqlx::query("PRAGMA query_only = ON")
+            .execute(guard.connection())
+            .await?;
+        Ok(guard)
+    }
+
+    fn connection(&mut self) -> &mut SqliteConnection {
+        self.0
+            .as_mut()
+            .expect("the connection is only taken on release")
+    }
+
+    /// Resets the connection and returns it to the pool
+    async fn release(mut self) -> Result<(), ResourceError> {
+        match self.0.take() {
+            Some(mut connection) => reset(&mut connection).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ReadOnly {
+    fn drop(&mut self) {
+        let Some(mut connection) = self.0.take() else {
+            return;
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move { reset(&mut connection).await });
+            }
+            Err(_) => connection.close_on_drop(),
+        }
+    }
+}
+
+async fn reset(connection: &mut PoolConnection<Sqlite>) -> Result<(), ResourceError> {
+    let result = sqlx::query("PRAGMA query_only = OFF")
+        .execute(&mut **connection)
+        .await;
+    if result.is_err() {
+        connection.close_on_drop();
+    }
+    result?;
+    Ok(())
+}
+
+fn journal_mode(mode: JournalMode) -> SqliteJournalMode {
+    match mode {
+        JournalMode::Delete => SqliteJournalMode::Delete,
+        JournalMode::Truncate => SqliteJournalMode::Truncate,
+        JournalMode::Persist => SqliteJournalMode::Persist,
+        JournalMode::Memory => SqliteJournalMode::Memory,
+        JournalMode::Wal => SqliteJournalMode::Wal,
+        JournalMode::Off => SqliteJournalMode::Off,
+    }
+}
+
+fn bind<'q>(
+    query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    value: &Value,
+) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    match value {
+        // NULL is sent without a value, the type of the option is not checked against the column
+        Value::None => query.bind(None::<String>),
+        Value::String(text) => query.bind(text.clone()),
+        Value::Int(number) => query.bind(*number),
+        Value::Float(number) => query.bind(*number),
+        Value::Bool(flag) => query.bind(*flag),
+        Value::List(_) | Value::Map(_) => query.bind(Json(value.clone())),
+    }
+}
+
+fn statement<'q>(statement: &'q str, params: &[Value]) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    params.iter().fold(sqlx::query(statement), bind)
+}
+
+/// The values of the named parameters in placeholder order, the driver converts them
+fn positional(
+    statement: &NamedStatement,
+    params: &HashMap<String, Value>,
+) -> Result<Vec<Value>, ResourceError> {
+    let values = statement.values(params)?;
+    Ok(values.into_iter().map(|(_, value)| value.clone()).collect())
+}
+
+fn column(row: &SqliteRow, index: usize) -> Result<Value, ResourceError> {
+    let raw = row.try_get_raw(index)?;
+    if raw.is_null() {
+        return Ok(Value::None);
+    }
+    // SQLite stores every value with its own type, the declared type of a table column tells
+    // what it means, e.g. an integer declared as boolean
+    let stored = raw.type_info().into_owned();
+    let declared = row.columns()[index].type_info();
+    let type_info = if declared.is_null() {
+        &stored
+    } else {
+        declared
+    };
+    let value = match type_info.name() {
+        "BOOLEAN" => Value::Bool(row.try_get(index)?),
+        "INTEGER" => Value::Int(row.try_get(index)?),
+        "REAL" => Value::Float(row.try_get(index)?),
+        "NUMERIC" => match row.try_get::<i64, _>(index) {
+            Ok(number) => Value::Int(number),
+            Err(_) => Value::Float(row.try_get(index)?),
+        },
+        "BLOB" => hex(row.try_get_unchecked::<&[u8], _>(index)?),
+        _ => text(row, index)?,
+    };
+    Ok(value)
+}
+
+fn decode(row: &SqliteRow) -> Result<Row, ResourceError> {
+    row.columns()
+        .iter()
+        .map(|column| {
+            Ok((
+                column.name().to_string(),
+                self::column(row, column.ordinal())?,
+            ))
+        })
+        .collect()
+}
+
+impl SQLResource for SqliteResource {
+    async fn query(&self, statement: &str, params: &[Value]) -> Result<Vec<Row>, ResourceError> {
+        self.access.check_statement(statement, Dialect::Sqlite)?;
+        self.breaker
+            .call(async {
+                let rows = self.rows(self::statement(statement, params)).await?;
+                rows.iter().map(decode).collect()
+            })
+            .await
+    }
+
+    async fn execute(&self, statement: &str, params: &[Value]) -> Result<u64, ResourceError> {
+        self.access.check_statement(statement, Dialect::Sqlite)?;
+        self.breaker
+            .call(self.affected(self::statement(statement, params)))
+            .await
+    }
+
+    async fn query_named(
+        &self,
+        statement: &str,
+        params: &HashMap<String, Value>,
+    ) -> Result<Vec<Row>, ResourceError> {
+        let statement = NamedStatement::parse(statement, Dialect::Sqlite)?;
+        self.query(statement.sql(), &positional(&statement, params)?)
+            .await
+    }
+
+    async fn execute_named(
+        &self,
+        statement: &str,
+        params: &HashMap<String, Value>,
+    ) -> Result<u64, ResourceError> {
+        let statement = NamedStatement::parse(statement, Dialect::Sqlite)?;
+        self.execute(statement.sql(), &positional(&statement, params)?)
+            .await
+    }
+
+    fn stream<'a>(
+        &'a self,
+        statement: &'a str,
+        params: &'a [Value],
+    ) -> BoxStream<'a, Result<Row, ResourceError>> {
+        if let Err(error) = self.access.check_statement(statement, Dialect::Sqlite) {
+            return stream::once(future::ready(Err(error))).boxed();
+        }
+        self.breaker.stream(|| {
+            let query = self::statement(statement, params);
+            if self.access.is_read_only() {
+                // the read-only connection state can not outlive the call, so the rows are
+                // fetched at once
+                return stream::once(self.rows(query))
+                    .map_ok(|rows| stream::iter(rows.into_iter().map(|row| decode(&row))))
+                    .try_flatten()
+                    .boxed();
+            }
+            query.fetch(&self.pool).map(|row| decode(&row?)).boxed()
+        })
+    }
+
+    async fn ping(&self) -> Result<Duration, ResourceError> {
+        self.breaker
+            .call(async {
+                let start = Instant::now();
+                self.pool.acquire().await?.ping().await?;
+                Ok(start.elapsed())
+            })
+            .await
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(path: &str) -> SqliteResourceConfig {
+        serde_json::from_value(serde_json::json!({ "path": path })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_queries() {
+        let resource = SqliteResource::connect(&config(":memory:")).await.unwrap();
+        resource.ping().await.unwrap();
+        resource
+            .execute(
+                "CREATE TABLE alerts (id INTEGER PRIMARY KEY, name TEXT, score REAL, \
+                 open BOOLEAN, labels TEXT, data BLOB)",
+                &[],
+            )
+            .await
+            .unwrap();
+        let inserted = resource
+            .execute(
+                "INSERT INTO alerts (name, score, open, labels, data) \
+                 VALUES (?, ?, ?, ?, x'0aff'), ('cpu', NULL, false, NULL, NULL)",
+                &[
+                    Value::from("disk"),
+                    Value::Float(0.5),
+                    Value::Bool(true),
+                    Value::Map(HashMap::from([("team".to_string(), Value::from("ops"))])),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(inserted, 2);
+
+        // every pooled connection sees the same in-memory database
+        let disk = [Value::from("disk")];
+        let (first, second) = tokio::join!(
+            resource.query("SELECT * FROM alerts WHERE name = ?", &disk),
+            resource.query("SELECT count(*) AS count FROM alerts", &[]),
+        );
+        let row = &first.unwrap()[0];
+        assert_eq!(row["id"], Value::Int(1));
+        assert_eq!(row["name"], Value::from("disk"));
+        assert_eq!(row["score"], Value::Float(0.5));
+        assert_eq!(row["open"], Value::Bool(true));
+        assert_eq!(row["labels"], Value::from(r#"{"team":"ops"}"#));
+        assert_eq!(row["data"], Value::from("0aff"));
+        assert_eq!(second.unwrap()[0]["count"], Value::Int(2));
+
+        let names: Vec<_> = resource
+            .stream("SELECT name, score FROM alerts ORDER BY id", &[])
+            .map_ok(|row| (row["name"].clone(), row["score"].clone()))
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(
+            names,
+            [
+                (Value::from("disk"), Value::Float(0.5)),
+                (Value::from("cpu"), Value::None)
+            ]
+        );
+
+        let error = resource.query("SELECT * FROM missing", &[]).await;
+        assert!(matches!(error, Err(ResourceError::Database(_))));
+    }
+
+    #[tokio::test]
+    async fn test_named_parameters() {
+        let resource = SqliteResource::connect(&config(":memory:")).await.unwrap();
+        resource
+            .execute(
+                "CREATE TABLE alerts (id INTEGER PRIMARY KEY, status TEXT)",
+                &[],
+            )
+            .await
+            .unwrap();
+        resource
+            .execute("INSERT INTO alerts (status) VALUES ('open'), ('open')", &[])
+            .await
+            .unwrap();
+
+        // the value is bound, not spliced into the statement
+        let status = "closed'; DROP TABLE alerts; --";
+        let params = HashMap::from([
+            ("new_alert_status".to_string(), Value::from(status)),
+            ("alert_id".to_string(), Value::Int(1)),
+            ("unused".to_string(), Value::Bool(true)),
+        ]);
+        let updated = resource
+            .execute_named(
+                "UPDATE alerts SET status = :new_alert_status WHERE id = :alert_id",
+                &params,
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated, 1);
+        let rows = resource
+            .query_named("SELECT status FROM alerts WHERE id = :alert_id", &params)
+            .await
+            .unwrap();
+        assert_eq!(rows[0]["status"], Value::from(status));
+
+        match resource
+            .query_named("SELECT * FROM alerts WHERE id = :id", &params)
+            .await
+        {
+            Err(error @ ResourceError::MissingParameters(_)) => {
+                assert_eq!(error.to_string(), "missing values for the parameters :id")
+            }
+            other => panic!("expected a missing parameter, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_only_access() {
+        let writer = SqliteResource::connect(&config(":memory:")).await.unwrap();
+        writer
+            .execute("CREATE TABLE alerts (id INTEGER PRIMARY KEY)", &[])
+            .await
+            .unwrap();
+        let policy = serde_json::from_value(serde_json::json!({"read_only": true})).unwrap();
+        let reader = SqliteResource::from_pool(writer.pool.clone())
+            .with_access(AccessGuard::new("local", policy));
+
+        assert!(reader.query("SELECT * FROM alerts", &[]).await.is_ok());
+        assert!(matches!(
+            reader
+                .execute("INSERT INTO alerts DEFAULT VALUES", &[])
+                .await,
+            Err(ResourceError::Access { .. })
+        ));
+        let rows: Vec<_> = reader
+            .stream("DELETE FROM alerts RETURNING id", &[])
+            .collect()
+            .await;
+        assert!(matches!(rows[..], [Err(ResourceError::Access { .. })]));
+
+        // past the statement checks the connection still refuses to write, and is writable
+        // again for other resources sharing the pool
+        let unchecked = reader
+            .affected(statement("INSERT INTO alerts DEFAULT VALUES", &[]))
+            .await;
+        assert!(matches!(unchecked, Err(ResourceError::Database(_))));
+        assert_eq!(
+            writer
+                .execute("INSERT INTO alerts DEFAULT VALUES", &[])
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_only_connection_is_reset_when_dropped() {
+        let mut config = config(":memory:");
+        config.max_connections = 1;
+        let resource = SqliteResource::connect(&config).await.unwrap();
+        resource
+            .execute("CREATE TABLE alerts (id INTEGER PRIMARY KEY)", &[])
+            .await
+            .unwrap();
+
+        // dropped without being released, like by a cancelled call, the only connection of the
+        // pool comes back writable
+        let mut connection = ReadOnly::acquire(&resource.pool).await.unwrap();
+        assert!(
+            sqlx::query("INSERT INTO alerts DEFAULT VALUES")
+                .execute(connection.connection())
+                .await
+                .is_err()
+        );
+        drop(connection);
+        assert_eq!(
+            resource
+                .execute("INSERT INTO alerts DEFAULT VALUES", &[])
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_in_wal_mode() {
+        let directory = std::env::temp_dir().join(format!("loid-sqlite-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("loid.db");
+        let resource = SqliteResource::connect(&config(path.to_str().unwrap()))
+            .await
+            .unwrap();
+        let rows = resource.query("PRAGMA journal_mode", &[]).await.unwrap();
+        assert_eq!(rows[0]["journal_mode"], Value::from("wal"));
+        resource
+            .execute("CREATE TABLE notes (text TEXT)", &[])
+            .await
+            .unwrap();
+        assert!(path.exists());
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let mut missing = config(directory.join("missing.db").to_str().unwrap());
+        missing.create = false;
+        assert!(SqliteResource::connect(&missing).await.is_err());
+        assert!(matches!(
+            SqliteResource::connect(&config(" ")).await,
+            Err(ResourceError::Config(_))
+        ));
+    }
+}
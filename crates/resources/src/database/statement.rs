@@ -0,0 +1,496 @@
+//! What a statement does and which tables it uses, for the access policies of a resource.
+//!
+//! This is not a full SQL parser. A statement it can not make sense of is reported as such, a
+//! policy rejects it instead of guessing.
+
+use crate::access::StatementKind;
+use crate::database::named::{Dialect, dollar_quoted, escapes, find, is_name, quoted};
+
+/// Types and tables of a statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Analysis {
+    /// Type of the statement first, followed by the types of nested statements writing, e.g. a
+    /// `DELETE` in a `WITH` clause. Subqueries only reading are covered by the tables
+    pub kinds: Vec<StatementKind>,
+    /// Tables read or written, sorted and without duplicates. Names in quotes keep their case,
+    /// others are lowercase
+    pub tables: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Keyword or unquoted name, lowercase
+    Word(String),
+    /// Name in quotes, without them
+    Quoted(String),
+    Symbol(u8),
+}
+
+/// Words that may come between a keyword and the table it introduces
+const SKIPPED: &[&str] = &["only", "lateral", "if", "not", "exists", "table"];
+
+/// Words that can not be a table, e.g. `set` following `UPDATE` in an upsert
+const RESERVED: &[&str] = &["select", "set", "values", "default", "where", "with"];
+
+/// Words joining the next table to the ones before, `NATURAL` and `CROSS` joins end in `JOIN`
+const JOINS: &[&str] = &["join", "straight_join"];
+
+/// Words `STRAIGHT_JOIN` follows as a modifier of `SELECT` instead of joining tables
+const SELECT_MODIFIERS: &[&str] = &["select", "all", "distinct", "distinctrow", "high_priority"];
+
+/// Words ending the table list of a `FROM` clause
+const FROM_END: &[&str] = &[
+    "where",
+    "group",
+    "having",
+    "window",
+    "order",
+    "limit",
+    "offset",
+    "fetch",
+    "for",
+    "union",
+    "intersect",
+    "except",
+    "returning",
+    "into",
+    "select",
+];
+
+/// Analyzes a single statement, fails with the reason if it can not be analyzed
+pub(crate) fn analyze(statement: &str, dialect: Dialect) -> Result<Analysis, String> {
+    let tokens = tokens(statement, dialect);
+    // a trailing semicolon is fine, anything after it is another statement
+    let end = tokens
+        .iter()
+        .position(|token| *token == Token::Symbol(b';'))
+        .unwrap_or(tokens.len());
+    if tokens[end..]
+        .iter()
+        .any(|token| *token != Token::Symbol(b';'))
+    {
+        return Err("multiple statements".to_string());
+    }
+    let tokens = &tokens[..end];
+
+    let with = matches!(
+        tokens.iter().find(|token| **token != Token::Symbol(b'(')),
+        Some(Token::Word(word)) if word == "with"
+    );
+    let main = if with {
+        // the statement following the common table expressions
+        let mut depth = 0;
+        tokens.iter().find_map(|token| match token {
+            Token::Symbol(b'(') => {
+                depth += 1;
+                None
+            }
+            Token::Symbol(b')') => {
+                depth -= 1;
+                None
+            }
+            Token::Word(word) if depth == 0 => StatementKind::from_keyword(word),
+            _ => None,
+        })
+    } else {
+        match tokens.iter().find(|token| **token != Token::Symbol(b'(')) {
+            Some(Token::Word(word)) => StatementKind::from_keyword(word),
+            _ => None,
+        }
+    };
+    let Some(main) = main else {
+        return Err("unknown statement type".to_string());
+    };
+    let mut kinds = vec![main];
+    for pair in tokens.windows(2) {
+        if let [Token::Symbol(b'('), Token::Word(word)] = pair
+            && let Some(kind) = StatementKind::from_keyword(word)
+            && kind != StatementKind::Select
+            && !kinds.contains(&kind)
+        {
+            kinds.push(kind);
+        }
+    }
+
+    let ctes = if with { ctes(tokens) } else { Vec::new() };
+    let index = main == StatementKind::Create && tokens.contains(&word("index"));
+    let mut tables = Tables {
+        tokens,
+        ctes,
+        names: Vec::new(),
+        found: 0,
+        error: None,
+    };
+    // whether each open parenthesis holds a statement, as opposed to e.g. the arguments of
+    // `extract(year FROM created)`
+    let mut statements = vec![true];
+    for (i, token) in tokens.iter().enumerate() {
+        let keyword = match token {
+            Token::Symbol(b'(') => {
+                statements.push(is_statement(tokens.get(i + 1)));
+                continue;
+            }
+            Token::Symbol(b')') if statements.len() > 1 => {
+                statements.pop();
+                continue;
+            }
+            Token::Word(keyword) if statements.last() == Some(&true) => keyword,
+            _ => continue,
+        };
+        let previous = i.checked_sub(1).map(|i| &tokens[i]);
+        match keyword.as_str() {
+            // the columns of a join, `JOIN b USING (id)`
+            "using"
+                if tokens.get(i + 1) == Some(&Token::Symbol(b'('))
+                    && !is_statement(tokens.get(i + 2)) => {}
+            "from" | "using" => {
+                tables.from(i + 1);
+            }
+            // not `ON CONFLICT DO UPDATE`, `ON DUPLICATE KEY UPDATE` or `FOR UPDATE`
+            "update" if !matches!(previous, Some(Token::Word(word)) if ["do", "key", "for"].contains(&word.as_str())) => {
+                tables.list(i + 1)
+            }
+            "table" | "truncate" => tables.list(i + 1),
+            "straight_join" if matches!(previous, Some(Token::Word(word)) if SELECT_MODIFIERS.contains(&word.as_str())) =>
+                {}
+            "join" | "straight_join" | "into" | "references" => {
+                tables.item(i + 1, JOINS.contains(&keyword.as_str()));
+            }
+            "on" if index => {
+                tables.item(i + 1, false);
+            }
+            _ => {}
+        }
+    }
+    if let Some(error) = tables.error {
+        return Err(error);
+    }
+    let mut tables = tables.names;
+    tables.sort();
+    tables.dedup();
+    Ok(Analysis { kinds, tables })
+}
+
+/// Collects the tables of a statement
+struct Tables<'a> {
+    tokens: &'a [Token],
+    /// Names of common table expressions, which are not tables
+    ctes: Vec<String>,
+    names: Vec<String>,
+    /// Tables, common table expressions and subqueries found, to tell whether a parenthesis
+    /// held any
+    found: usize,
+    /// Why the tables are incomplete, e.g. a table function that may read anything
+    error: Option<String>,
+}
+
+impl Tables<'_> {
+    /// The items of a `FROM` clause, including the ones following joins, e.g. `a JOIN b ON …, c`
+    fn from(&mut self, start: usize) {
+        let mut i = self.item(start, true);
+        let mut depth = 0;
+        while let Some(token) = self.tokens.get(i) {
+            match token {
+                Token::Symbol(b'(') => depth += 1,
+                Token::Symbol(b')') if depth == 0 => break,
+                Token::Symbol(b')') => depth -= 1,
+                Token::Symbol(b',') if depth == 0 => {
+                    i = self.item(i + 1, true);
+                    continue;
+                }
+                Token::Word(word) if depth == 0 && JOINS.contains(&word.as_str()) => {
+                    i = self.item(i + 1, true);
+                    continue;
+                }
+                Token::Word(word) if depth == 0 && FROM_END.contains(&word.as_str()) => break,
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    /// Tables separated by commas, each with an optional alias, e.g. `UPDATE a x, b y`
+    fn list(&mut self, start: usize) {
+        let mut i = start;
+        loop {
+            i = self.item(i, false);
+            if self.tokens.get(i) == Some(&word("as")) {
+                i += 1;
+            }
+            if matches!(self.tokens.get(i), Some(Token::Word(_) | Token::Quoted(_)))
+                && self.tokens.get(i + 1) == Some(&Token::Symbol(b','))
+            {
+                i += 1;
+            }
+            if self.tokens.get(i) != Some(&Token::Symbol(b',')) {
+                break;
+            }
+            i += 1;
+        }
+    }
+
+    /// A table, subquery, parenthesized join or table function, returns the index after it.
+    /// Table functions are only expected where `functions` is set, e.g. not after `INTO`
+    fn item(&mut self, start: usize, functions: bool) -> usize {
+        let mut i = start;
+        while matches!(self.tokens.get(i), Some(Token::Word(word)) if SKIPPED.contains(&word.as_str()))
+        {
+            i += 1;
+        }
+        if self.tokens.get(i) == Some(&Token::Symbol(b'(')) {
+            let end = closing(self.tokens, i);
+            if is_statement(self.tokens.get(i + 1)) {
+                // a subquery, its tables are found on their own
+                self.found += 1;
+                return end;
+            }
+            // a join in parentheses, e.g. `(a JOIN b ON …)`
+            let found = self.found;
+            self.from(i + 1);
+            if self.found == found {
+                self.error
+                    .get_or_insert_with(|| "parentheses without a table".to_string());
+            }
+            return end;
+        }
+        if matches!(self.tokens.get(i), Some(Token::Word(word)) if RESERVED.contains(&word.as_str()))
+        {
+            return i;
+        }
+        let Some((table, next)) = name(self.tokens, i) else {
+            return i;
+        };
+        if functions && self.tokens.get(next) == Some(&Token::Symbol(b'(')) {
+            // a function can read anything, e.g. `dblink` another database
+            self.error
+                .get_or_insert_with(|| format!("table function `{table}`"));
+            return closing(self.tokens, next);
+        }
+        self.found += 1;
+        if !self.ctes.contains(&table) && !self.names.contains(&table) {
+            self.names.push(table);
+        }
+        next
+    }
+}
+
+fn word(word: &str) -> Token {
+    Token::Word(word.to_string())
+}
+
+/// Whether a parenthesis starting with the token holds a statement
+fn is_statement(token: Option<&Token>) -> bool {
+    matches!(
+        token,
+        Some(Token::Word(word)) if word == "with" || StatementKind::from_keyword(word).is_some()
+    )
+}
+
+fn tokens(statement: &str, dialect: Dialect) -> Vec<Token> {
+    let bytes = statement.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let next = bytes.get(i + 1).copied();
+        match bytes[i] {
+            // literals are skipped, they can not name a table
+            b'\'' => i = quoted(bytes, i, b'\'', escapes(bytes, i, dialect)),
+            quote @ (b'"' | b'`') => {
+                let end = quoted(bytes, i, quote, escapes(bytes, i, dialect));
+                let inner = &statement[i + 1..end.max(i + 2) - 1];
+                let doubled = (quote as char).to_string().repeat(2);
+                tokens.push(Token::Quoted(inner.replace(&doubled, &doubled[1..])));
+                i = end;
+            }
+            b'-' if next == Some(b'-') => {
+                i = find(bytes, i + 2, b"\n").map_or(bytes.len(), |end| end + 1)
+            }
+            b'/' if next == Some(b'*') => {
+                i = find(bytes, i + 2, b"*/").map_or(bytes.len(), |end| end + 2)
+            }
+            b'$' if dialect == Dialect::Postgres && dollar_quoted(bytes, i).is_some() => {
+                i = dollar_quoted(bytes, i).unwrap_or(bytes.len());
+            }
+            byte if is_name(byte) || !byte.is_ascii() => {
+                let end = (i..bytes.len())
+                    .find(|&end| !is_name(bytes[end]) && bytes[end].is_ascii())
+                    .unwrap_or(bytes.len());
+                tokens.push(Token::Word(statement[i..end].to_lowercase()));
+                i = end;
+            }
+            byte if byte.is_ascii_whitespace() => i += 1,
+            byte => {
+                tokens.push(Token::Symbol(byte));
+                i += 1;
+            }
+        }
+    }
+    tokens
+}
+
+/// A name, possibly qualified, e.g. `public.alerts`, and the index after it
+fn name(tokens: &[Token], start: usize) -> Option<(String, usize)> {
+    let part = |token: Option<&Token>| match token {
+        Some(Token::Word(word)) if word.chars().next().is_some_and(|c| !c.is_ascii_digit()) => {
+            Some(word.clone())
+        }
+        Some(Token::Quoted(name)) => Some(name.clone()),
+        _ => None,
+    };
+    let mut name = part(tokens.get(start))?;
+    let mut i = start + 1;
+    while tokens.get(i) == Some(&Token::Symbol(b'.'))
+        && let Some(next) = part(tokens.get(i + 1))
+    {
+        name.push('.');
+        name.push_str(&next);
+        i += 2;
+    }
+    Some((name, i))
+}
+
+/// Index after the parenthesis closing the one at `open`
+fn closing(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::Symbol(b'(') => depth += 1,
+            Token::Symbol(b')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+/// Names of the common table expressions, `name AS (…)` or `name (columns) AS (…)`
+fn ctes(tokens: &[Token]) -> Vec<String> {
+    let mut names = Vec::new();
+    for i in 0..tokens.len() {
+        let Some((name, next)) = self::name(tokens, i) else {
+            continue;
+        };
+        let next = match tokens.get(next) {
+            Some(Token::Symbol(b'(')) => closing(tokens, next),
+            _ => next,
+        };
+        if tokens.get(next) == Some(&word("as"))
+            && tokens.get(next + 1) == Some(&Token::Symbol(b'('))
+        {
+            names.push(name);
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StatementKind::*;
+
+    fn analysis(statement: &str) -> (Vec<StatementKind>, Vec<String>) {
+        let analysis = analyze(statement, Dialect::Postgres).unwrap();
+        (analysis.kinds, analysis.tables)
+    }
+
+    #[test]
+    fn test_kinds_and_tables() {
+        for (statement, kinds, tables) in [
+            (
+                "SELECT a.id FROM alerts a JOIN public.hosts AS h ON h.id = a.host, \"Teams\" t \
+                 WHERE a.id IN (SELECT alert FROM notes) ORDER BY a.id, h.name",
+                vec![Select],
+                vec!["Teams", "alerts", "notes", "public.hosts"],
+            ),
+            (
+                "SELECT extract(year FROM created) FROM alerts;",
+                vec![Select],
+                vec!["alerts"],
+            ),
+            (
+                "INSERT INTO alerts (name) (SELECT name FROM staged)",
+                vec![Insert],
+                vec!["alerts", "staged"],
+            ),
+            (
+                "UPDATE alerts SET status = $1 WHERE note = 'FROM secrets' -- FROM keys",
+                vec![Update],
+                vec!["alerts"],
+            ),
+            (
+                "WITH gone AS (DELETE FROM alerts RETURNING id) SELECT count(*) FROM gone",
+                vec![Select, Delete],
+                vec!["alerts"],
+            ),
+            (
+                "DROP TABLE IF EXISTS alerts, notes",
+                vec![Drop],
+                vec!["alerts", "notes"],
+            ),
+            (
+                "CREATE INDEX alerts_name ON alerts (name)",
+                vec![Create],
+                vec!["alerts"],
+            ),
+            ("TRUNCATE alerts", vec![Truncate], vec!["alerts"]),
+            (
+                "INSERT INTO alerts (id) VALUES (1) ON CONFLICT (id) DO UPDATE SET id = 2",
+                vec![Insert],
+                vec!["alerts"],
+            ),
+            (
+                "SELECT * FROM alerts a JOIN hosts h USING (id), teams WHERE x IN (1, 2)",
+                vec![Select],
+                vec!["alerts", "hosts", "teams"],
+            ),
+            (
+                "SELECT * FROM (alerts JOIN secrets ON true)",
+                vec![Select],
+                vec!["alerts", "secrets"],
+            ),
+            (
+                "SELECT * FROM alerts a LEFT JOIN ((hosts NATURAL JOIN teams) CROSS JOIN \
+                 (SELECT * FROM notes) n) ON true",
+                vec![Select],
+                vec!["alerts", "hosts", "notes", "teams"],
+            ),
+        ] {
+            assert_eq!(
+                analysis(statement),
+                (kinds, tables.into_iter().map(String::from).collect()),
+                "{statement}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_what_it_can_not_analyze() {
+        for statement in [
+            "SELECT 1; DROP TABLE alerts",
+            "GRANT SELECT ON alerts TO public",
+            "SET default_transaction_read_only = off",
+            "",
+            "SELECT * FROM dblink('host=db', 'SELECT * FROM secrets') AS t(a text)",
+            "SELECT pg_read_file('/etc/passwd') FROM alerts JOIN pg_read_file('x') f ON true",
+            "SELECT * FROM generate_series(1, 3) g, alerts",
+            "SELECT * FROM (1) x",
+        ] {
+            assert!(
+                analyze(statement, Dialect::Postgres).is_err(),
+                "{statement}"
+            );
+        }
+        let analysis = analyze("SELECT `from` FROM `my``table`", Dialect::Mysql).unwrap();
+        assert_eq!(analysis.tables, ["my`table"]);
+        let analysis = analyze(
+            "SELECT STRAIGHT_JOIN a.id FROM alerts a STRAIGHT_JOIN secrets s ON s.id = a.id",
+            Dialect::Mysql,
+        )
+        .unwrap();
+        assert_eq!(analysis.tables, ["alerts", "secrets"]);
+    }
+}
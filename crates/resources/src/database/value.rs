@@ -0,0 +1,79 @@
+use crate::ResourceError;
+use loid_events::prelude::Value;
+use sqlx::types::{Decimal, JsonValue};
+use sqlx::{Column, Row, TypeInfo};
+use std::fmt::Write;
+
+/// Value of a JSON column, numbers too large for an integer become floats
+pub(crate) fn json(value: JsonValue) -> Value {
+    serde_json::from_value(value).unwrap_or_default()
+}
+
+/// Value of a decimal column, a float if it holds the decimal exactly and its text otherwise
+pub(crate) fn decimal(value: Decimal) -> Value {
+    let text = value.to_string();
+    match text.parse::<f64>() {
+        Ok(number) if Decimal::try_from(number) == Ok(value) => Value::Float(number),
+        _ => Value::String(text),
+    }
+}
+
+/// Binary columns become lowercase hex text
+pub(crate) fn hex(bytes: &[u8]) -> Value {
+    let mut text = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(text, "{byte:02x}");
+    }
+    Value::String(text)
+}
+
+/// Text of a column of a type without a dedicated conversion
+pub(crate) fn text<R>(row: &R, index: usize) -> Result<Value, ResourceError>
+where
+    R: Row,
+    usize: sqlx::ColumnIndex<R>,
+    for<'r> String: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database>,
+{
+    row.try_get::<String, _>(index)
+        .map(Value::String)
+        .map_err(|_| {
+            let column = &row.columns()[index];
+            ResourceError::Decode {
+                column: column.name().to_string(),
+                type_name: column.type_info().name().to_string(),
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_conversions() {
+        let value = json(serde_json::json!({"id": 7, "tags": ["a", null], "ok": true}));
+        assert_eq!(
+            value,
+            Value::Map(HashMap::from([
+                ("id".to_string(), Value::Int(7)),
+                (
+                    "tags".to_string(),
+                    Value::List(vec![Value::from("a"), Value::None])
+                ),
+                ("ok".to_string(), Value::Bool(true)),
+            ]))
+        );
+        assert_eq!(decimal(Decimal::new(12345, 2)), Value::Float(123.45));
+        assert_eq!(
+            decimal(Decimal::from_str("12345678901234567890.12").unwrap()),
+            Value::from("12345678901234567890.12")
+        );
+        assert_eq!(
+            decimal(Decimal::from_str("0.1000000000000000000000000001").unwrap()),
+            Value::from("0.1000000000000000000000000001")
+        );
+        assert_eq!(hex(&[0, 15, 255]), Value::from("000fff"));
+    }
+}
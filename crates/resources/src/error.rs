@@ -0,0 +1,150 @@
+use crate::access::AccessError;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// Errors that can occur while configuring or using a resource.
+#[derive(Debug)]
+pub enum ResourceError {
+    /// The resource configuration is invalid
+    Config(String),
+    /// The database could not be reached or rejected the statement
+    Database(sqlx::Error),
+    /// A column holds a type that can not be converted to a value
+    Decode { column: String, type_name: String },
+    /// The HTTP request could not be sent or its response not be read
+    Http(reqwest::Error),
+    /// The HTTP server answered with an error status
+    Status { status: u16, body: String },
+    /// The credentials of the resource were not accepted
+    Auth(String),
+    /// A body could not be converted from or to JSON
+    Json(serde_json::Error),
+    /// No resource is registered under the key
+    Unknown(String),
+    /// The resource registered under the key is of another type than requested
+    WrongType {
+        key: String,
+        expected: &'static str,
+        actual: &'static str,
+    },
+    /// The registry was closed
+    Closed,
+    /// The circuit breaker of the resource is open
+    Unavailable { retry_in: Duration },
+    /// The statement can not be run as written
+    Statement(String),
+    /// Named parameters of the statement without a value
+    MissingParameters(Vec<String>),
+    /// A parameter value does not fit the type the database expects
+    ParameterType {
+        name: String,
+        expected: String,
+        actual: &'static str,
+    },
+    /// The access policy of the resource denied the operation
+    Access {
+        resource: String,
+        error: AccessError,
+    },
+}
+
+impl Display for ResourceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceError::Config(message) => {
+                write!(f, "invalid resource configuration: {message}")
+            }
+            ResourceError::Database(error) => write!(f, "database error: {error}"),
+            ResourceError::Decode { column, type_name } => {
+                write!(f, "column '{column}' has the unsupported type {type_name}")
+            }
+            ResourceError::Http(error) => write!(f, "HTTP error: {error}"),
+            ResourceError::Status { status, body } => {
+                write!(f, "server responded with status {status}: {body}")
+            }
+            ResourceError::Auth(message) => write!(f, "authentication failed: {message}"),
+            ResourceError::Json(error) => write!(f, "invalid JSON: {error}"),
+            ResourceError::Unknown(key) => write!(f, "unknown resource '{key}'"),
+            ResourceError::WrongType {
+                key,
+                expected,
+                actual,
+            } => write!(f, "resource '{key}' is a {actual} resource, not {expected}"),
+            ResourceError::Closed => write!(f, "the resource registry is closed"),
+            ResourceError::Unavailable { retry_in } => write!(
+                f,
+                "resource is unavailable, next try in {}s",
+                retry_in.as_secs_f64().ceil()
+            ),
+            ResourceError::Statement(message) => write!(f, "invalid statement: {message}"),
+            ResourceError::MissingParameters(names) => {
+                write!(f, "missing values for the parameters ")?;
+                for (i, name) in names.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { ", " };
+                    write!(f, "{separator}:{name}")?;
+                }
+                Ok(())
+            }
+            ResourceError::ParameterType {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "parameter :{name} is {actual}, the statement expects {expected}"
+            ),
+            ResourceError::Access { resource, error } => {
+                write!(f, "access to resource '{resource}' denied: {error}")
+            }
+        }
+    }
+}
+
+impl ResourceError {
+    /// Whether the resource could not be reached or did not work, as opposed to rejecting what
+    /// was asked of it, e.g. a statement with a syntax error
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            ResourceError::Database(error) => matches!(
+                error,
+                sqlx::Error::Io(_)
+                    | sqlx::Error::Tls(_)
+                    | sqlx::Error::Protocol(_)
+                    | sqlx::Error::PoolTimedOut
+                    | sqlx::Error::WorkerCrashed
+            ),
+            ResourceError::Http(error) => error.is_connect() || error.is_timeout(),
+            ResourceError::Status { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+impl std::error::Error for ResourceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ResourceError::Database(error) => Some(error),
+            ResourceError::Http(error) => Some(error),
+            ResourceError::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for ResourceError {
+    fn from(error: sqlx::Error) -> Self {
+        ResourceError::Database(error)
+    }
+}
+
+impl From<reqwest::Error> for ResourceError {
+    fn from(error: reqwest::Error) -> Self {
+        ResourceError::Http(error)
+    }
+}
+
+impl From<serde_json::Error> for ResourceError {
+    fn from(error: serde_json::Error) -> Self {
+        ResourceError::Json(error)
+    }
+}
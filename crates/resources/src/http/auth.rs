@@ -0,0 +1,112 @@
+use crate::ResourceError;
+use crate::http::config::HttpAuth;
+use loid_events::prelude::Secret;
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Tokens are renewed this long before they expire, so requests in flight do not carry an
+/// expired token
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// Adds the configured credentials to requests, fetching OAuth2 tokens when needed
+pub(crate) struct Authenticator {
+    auth: HttpAuth,
+    token: Mutex<Option<Token>>,
+}
+
+struct Token {
+    access_token: String,
+    expires: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+impl Authenticator {
+    pub(crate) fn new(auth: HttpAuth) -> Self {
+        Self {
+            auth,
+            token: Mutex::new(None),
+        }
+    }
+
+    pub(crate) async fn apply(
+        &self,
+        client: &Client,
+        request: RequestBuilder,
+    ) -> Result<RequestBuilder, ResourceError> {
+        Ok(match &self.auth {
+            HttpAuth::Basic { username, password } => {
+                request.basic_auth(username, password.as_ref().map(Secret::expose))
+            }
+            HttpAuth::Bearer { token } => request.bearer_auth(token.expose()),
+            HttpAuth::OAuth2 { .. } => request.bearer_auth(self.token(client).await?),
+        })
+    }
+
+    /// Drops the cached token after the server rejected it, the next request fetches a new one
+    pub(crate) async fn invalidate(&self) -> bool {
+        matches!(self.auth, HttpAuth::OAuth2 { .. }) && self.token.lock().await.take().is_some()
+    }
+
+    async fn token(&self, client: &Client) -> Result<String, ResourceError> {
+        // holding the lock while fetching makes concurrent requests wait for one token
+        let mut token = self.token.lock().await;
+        if let Some(current) = token.as_ref()
+            && current
+                .expires
+                .is_none_or(|expires| Instant::now() + EXPIRY_MARGIN < expires)
+        {
+            return Ok(current.access_token.clone());
+        }
+        let fetched = self.fetch(client).await?;
+        let access_token = fetched.access_token.clone();
+        *token = Some(fetched);
+        Ok(access_token)
+    }
+
+    async fn fetch(&self, client: &Client) -> Result<Token, ResourceError> {
+        let HttpAuth::OAuth2 {
+            token_url,
+            client_id,
+            client_secret,
+            scopes,
+        } = &self.auth
+        else {
+            unreachable!("only OAuth2 fetches tokens");
+        };
+        let mut form = vec![("grant_type", "client_credentials".to_string())];
+        if !scopes.is_empty() {
+            form.push(("scope", scopes.join(" ")));
+        }
+        let response = client
+            .post(token_url)
+            .basic_auth(client_id, Some(client_secret.expose()))
+            .form(&form)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ResourceError::Auth(format!(
+                "token endpoint responded with {status}"
+            )));
+        }
+        let response: TokenResponse = response
+            .json()
+            .await
+            .map_err(|error| ResourceError::Auth(format!("invalid token response: {error}")))?;
+        Ok(Token {
+            access_token: response.access_token,
+            expires: response
+                .expires_in
+                .map(|seconds| Instant::now() + Duration::from_secs(seconds)),
+        })
+    }
+}
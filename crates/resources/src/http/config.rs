@@ -0,0 +1,242 @@
+use crate::ResourceError;
+use loid_events::prelude::Secret;
+use reqwest::Url;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Configuration of the HTTP API resource
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpResourceConfig {
+    /// URL the paths of the requests are relative to, e.g. `https://api.internal/v1`
+    pub base_url: String,
+    /// Headers sent with every request
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub auth: Option<HttpAuth>,
+    /// Time a request may take including reading the response, retries get their own
+    #[serde(with = "humantime_serde", default = "default_timeout")]
+    pub timeout: Duration,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    /// Requests above the rate wait for their turn, unlimited without
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Path requested by the health checks, any response below 500 counts as healthy.
+    /// The base URL is requested if not set
+    #[serde(default)]
+    pub health_path: Option<String>,
+}
+
+/// Credentials added to every request
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HttpAuth {
+    Basic {
+        username: String,
+        #[serde(default)]
+        password: Option<Secret>,
+    },
+    Bearer {
+        token: Secret,
+    },
+    /// OAuth2 client credentials grant, the token is fetched on first use and renewed before
+    /// it expires
+    #[serde(rename = "oauth2")]
+    OAuth2 {
+        token_url: String,
+        client_id: String,
+        client_secret: Secret,
+        #[serde(default)]
+        scopes: Vec<String>,
+    },
+}
+
+/// Retries of failed requests with idempotent methods, `GET`, `HEAD`, `OPTIONS`, `PUT`,
+/// `DELETE` and `TRACE`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetryConfig {
+    /// Number of retries after the first attempt, 0 disables retries
+    #[serde(default = "default_attempts")]
+    pub attempts: u32,
+    /// Wait before the first retry, doubled for every further retry
+    #[serde(with = "humantime_serde", default = "default_backoff")]
+    pub backoff: Duration,
+    /// Upper bound of the wait, also for a `Retry-After` sent by the server
+    #[serde(with = "humantime_serde", default = "default_max_backoff")]
+    pub max_backoff: Duration,
+    /// Response statuses that are retried, connection errors and timeouts always are
+    #[serde(default = "default_statuses")]
+    pub statuses: Vec<u16>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: default_attempts(),
+            backoff: default_backoff(),
+            max_backoff: default_max_backoff(),
+            statuses: default_statuses(),
+        }
+    }
+}
+
+/// Token bucket limiting the request rate
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitConfig {
+    /// Requests allowed per `per`
+    pub requests: u32,
+    #[serde(with = "humantime_serde", default = "default_per")]
+    pub per: Duration,
+    /// Requests that may be sent at once after a quiet period, `requests` if not set
+    #[serde(default)]
+    pub burst: Option<u32>,
+}
+
+impl HttpResourceConfig {
+    /// Checks the configuration for values the resource cannot work with
+    pub fn validate(&self) -> Result<(), ResourceError> {
+        self.url()?;
+        for (name, value) in &self.headers {
+            if HeaderName::from_bytes(name.as_bytes()).is_err() {
+                return Err(ResourceError::Config(format!(
+                    "headers: invalid header name `{name}`"
+                )));
+            }
+            if HeaderValue::from_str(value).is_err() {
+                return Err(ResourceError::Config(format!(
+                    "headers: invalid value of `{name}`"
+                )));
+            }
+        }
+        if let Some(HttpAuth::OAuth2 { token_url, .. }) = &self.auth {
+            parse_url("auth.token_url", token_url)?;
+        }
+        if self.timeout.is_zero() {
+            return Err(ResourceError::Config(
+                "timeout must be greater than 0".to_string(),
+            ));
+        }
+        if self.retry.backoff > self.retry.max_backoff {
+            return Err(ResourceError::Config(
+                "retry.backoff must not exceed retry.max_backoff".to_string(),
+            ));
+        }
+        if let Some(limit) = &self.rate_limit {
+            if limit.requests == 0 || limit.burst == Some(0) {
+                return Err(ResourceError::Config(
+                    "rate_limit.requests and rate_limit.burst must be greater than 0".to_string(),
+                ));
+            }
+            if limit.per.is_zero() {
+                return Err(ResourceError::Config(
+                    "rate_limit.per must be greater than 0".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The base URL, parsed
+    pub(crate) fn url(&self) -> Result<Url, ResourceError> {
+        parse_url("base_url", &self.base_url)
+    }
+}
+
+fn parse_url(field: &str, url: &str) -> Result<Url, ResourceError> {
+    match Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(url),
+        Ok(_) => Err(ResourceError::Config(format!(
+            "{field} must start with http:// or https://"
+        ))),
+        Err(error) => Err(ResourceError::Config(format!("{field}: {error}"))),
+    }
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_attempts() -> u32 {
+    3
+}
+
+fn default_backoff() -> Duration {
+    Duration::from_millis(200)
+}
+
+fn default_max_backoff() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_statuses() -> Vec<u16> {
+    vec![429, 502, 503, 504]
+}
+
+fn default_per() -> Duration {
+    Duration::from_secs(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: serde_json::Value) -> HttpResourceConfig {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_http_config() {
+        let valid = config(serde_json::json!({
+            "base_url": "https://api.internal/v1",
+            "headers": {"x-team": "ops"},
+            "auth": {
+                "type": "oauth2",
+                "token_url": "https://login.internal/token",
+                "client_id": "loid",
+                "client_secret": "secret",
+            },
+            "rate_limit": {"requests": 10},
+        }));
+        assert!(valid.validate().is_ok());
+        assert_eq!(valid.retry.attempts, 3);
+        assert_eq!(valid.rate_limit.unwrap().per, Duration::from_secs(1));
+
+        for (json, message) in [
+            (serde_json::json!({"base_url": "api.internal"}), "base_url"),
+            (
+                serde_json::json!({"base_url": "ftp://api.internal"}),
+                "http://",
+            ),
+            (
+                serde_json::json!({"base_url": "http://api", "headers": {"x team": "ops"}}),
+                "header name",
+            ),
+            (
+                serde_json::json!({"base_url": "http://api", "timeout": "0s"}),
+                "timeout",
+            ),
+            (
+                serde_json::json!({"base_url": "http://api", "retry": {"backoff": "1m"}}),
+                "max_backoff",
+            ),
+            (
+                serde_json::json!({"base_url": "http://api", "rate_limit": {"requests": 0}}),
+                "rate_limit",
+            ),
+            (
+                serde_json::json!({"base_url": "http://api", "auth": {
+                    "type": "oauth2", "token_url": "/token", "client_id": "a", "client_secret": "b"
+                }}),
+                "token_url",
+            ),
+        ] {
+            match config(json).validate() {
+                Err(ResourceError::Config(error)) => assert!(error.contains(message), "{error}"),
+                other => panic!("expected an error about {message}, got {other:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::http::config::RateLimitConfig;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Token bucket, every request takes a token and tokens refill at the configured rate
+pub(crate) struct RateLimiter {
+    capacity: f64,
+    /// Tokens added per second
+    rate: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: &RateLimitConfig) -> Self {
+        let capacity = f64::from(config.burst.unwrap_or(config.requests));
+        Self {
+            capacity,
+            rate: f64::from(config.requests) / config.per.as_secs_f64(),
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a token is available and takes it
+    pub(crate) async fn acquire(&self) {
+        loop {
+            match self.try_acquire(Instant::now()) {
+                Ok(()) => return,
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    /// Takes a token or returns how long it takes until one is available
+    fn try_acquire(&self, now: Instant) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            requests: 10,
+            per: Duration::from_secs(1),
+            burst: Some(2),
+        });
+        let start = Instant::now();
+        assert!(limiter.try_acquire(start).is_ok());
+        assert!(limiter.try_acquire(start).is_ok());
+        let wait = limiter.try_acquire(start).unwrap_err();
+        assert_eq!(wait.as_millis(), 100);
+
+        assert!(limiter.try_acquire(start + wait).is_ok());
+        // a quiet period refills the bucket up to the burst only
+        let later = start + Duration::from_secs(10);
+        assert!(limiter.try_acquire(later).is_ok());
+        assert!(limiter.try_acquire(later).is_ok());
+        assert!(limiter.try_acquire(later).is_err());
+    }
+}
@@ -0,0 +1,23 @@
+//! Resource calling an HTTP API.
+//!
+//! ```yaml
+//! resource:
+//!     type: http
+//!     base_url: "https://api.internal/v1"
+//!     auth:
+//!         type: oauth2
+//!         token_url: "https://login.internal/oauth/token"
+//!         client_id: "loid"
+//!         client_secret: "..."
+//!     rate_limit:
+//!         requests: 20
+//!         per: 1s
+//! ```
+
+pub(crate) mod auth;
+pub(crate) mod config;
+pub(crate) mod limiter;
+pub(crate) mod resource;
+
+pub use config::{HttpAuth, HttpResourceConfig, RateLimitConfig, RetryConfig};
+pub use resource::{HttpRequest, HttpResource, HttpResponse};
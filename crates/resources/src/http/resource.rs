@@ -0,0 +1,467 @@
+use crate::ResourceError;
+use crate::access::AccessGuard;
+use crate::circuit::CircuitBreaker;
+use crate::http::auth::Authenticator;
+use crate::http::config::{HttpResourceConfig, RetryConfig};
+use crate::http::limiter::RateLimiter;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use reqwest::{Client, Method, StatusCode, Url};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::time::{Duration, Instant};
+
+/// An HTTP API, requests are sent relative to its base URL with its headers and credentials
+pub struct HttpResource {
+    client: Client,
+    base_url: Url,
+    auth: Option<Authenticator>,
+    retry: RetryConfig,
+    limiter: Option<RateLimiter>,
+    health_path: Option<String>,
+    breaker: CircuitBreaker,
+    access: AccessGuard,
+}
+
+/// A request to an HTTP resource, kept so it can be sent again on a retry
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    /// Path relative to the base URL
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+/// A response with its body read
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn new(method: Method, path: impl Into<String>) -> Self {
+        Self {
+            method,
+            path: path.into(),
+            query: Vec::new(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn get(path: impl Into<String>) -> Self {
+        Self::new(Method::GET, path)
+    }
+
+    pub fn post(path: impl Into<String>) -> Self {
+        Self::new(Method::POST, path)
+    }
+
+    pub fn query(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sends the value as JSON body
+    pub fn json(self, value: &impl Serialize) -> Result<Self, ResourceError> {
+        let body = serde_json::to_vec(value)?;
+        Ok(self.header("content-type", "application/json").body(body))
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Methods that have the same effect when sent twice, only these are retried
+    pub fn is_idempotent(&self) -> bool {
+        [
+            Method::GET,
+            Method::HEAD,
+            Method::OPTIONS,
+            Method::PUT,
+            Method::DELETE,
+            Method::TRACE,
+        ]
+        .contains(&self.method)
+    }
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        self.status.is_success()
+    }
+
+    /// The response or an error for statuses from 400 on
+    pub fn error_for_status(self) -> Result<Self, ResourceError> {
+        if self.status.is_client_error() || self.status.is_server_error() {
+            return Err(ResourceError::Status {
+                status: self.status.as_u16(),
+                body: String::from_utf8_lossy(&self.body).into_owned(),
+            });
+        }
+        Ok(self)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, ResourceError> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+impl HttpResource {
+    pub fn new(config: &HttpResourceConfig) -> Result<Self, ResourceError> {
+        config.validate()?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            // both were checked by validate
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes()).expect("valid header name"),
+                HeaderValue::from_str(value).expect("valid header value"),
+            );
+        }
+        let client = Client::builder()
+            .default_headers(headers)
+            .timeout(config.timeout)
+            .build()?;
+        Ok(Self {
+            client,
+            base_url: config.url()?,
+            auth: config.auth.clone().map(Authenticator::new),
+            retry: config.retry.clone(),
+            limiter: config.rate_limit.as_ref().map(RateLimiter::new),
+            health_path: config.health_path.clone(),
+            breaker: CircuitBreaker::default(),
+            access: AccessGuard::default(),
+        })
+    }
+
+    /// Guards the requests with the breaker instead of one with the default configuration
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    /// Enforces the access policy on every request, read-only resources only send `GET`,
+    /// `HEAD` and `OPTIONS` requests
+    pub fn with_access(mut self, access: AccessGuard) -> Self {
+        self.access = access;
+        self
+    }
+
+    pub fn access(&self) -> &AccessGuard {
+        &self.access
+    }
+
+    /// URL of a path relative to the base URL
+    pub fn url(&self, path: &str) -> Result<Url, ResourceError> {
+        let base = self.base_url.as_str().trim_end_matches('/');
+        let path = path.trim_start_matches('/');
+        let url = if path.is_empty() {
+            base.to_string()
+        } else {
+            format!("{base}/{path}")
+        };
+        Url::parse(&url).map_err(|error| ResourceError::Config(format!("invalid path: {error}")))
+    }
+
+    /// Sends the request, retrying idempotent requests that failed on the way or with a
+    /// retryable status. The response of the last attempt is returned whatever its status
+    pub async fn send(&self, request: &HttpRequest) -> Result<HttpResponse, ResourceError> {
+        self.access.check_method(&request.method)?;
+        let url = self.url(&request.path)?;
+        let retries = if request.is_idempotent() {
+            self.retry.attempts
+        } else {
+            0
+        };
+        let mut renewed_token = false;
+        let mut attempt = 0;
+        loop {
+            let result = self.attempt(&url, request).await;
+            if let Ok(response) = &result
+                && response.status == StatusCode::UNAUTHORIZED
+                && !renewed_token
+                && let Some(auth) = &self.auth
+                && auth.invalidate().await
+            {
+                // the token was revoked before it expired, one more attempt with a new token
+                renewed_token = true;
+                continue;
+            }
+            let wait = match &result {
+                Ok(response) if self.retry.statuses.contains(&response.status.as_u16()) => {
+                    retry_after(&response.headers)
+                }
+                Err(ResourceError::Http(error)) if error.is_connect() || error.is_timeout() => None,
+                _ => return result,
+            };
+            if attempt >= retries {
+                return result;
+            }
+            let wait = wait
+                .unwrap_or_else(|| self.retry.backoff.saturating_mul(1 << attempt.min(16)))
+                .min(self.retry.max_backoff);
+            tracing::debug!(url = %url, attempt, wait = ?wait, "retrying request");
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+
+    /// Sends a GET request and decodes the JSON response, statuses from 400 on are errors
+    pub async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, ResourceError> {
+        self.send(&HttpRequest::get(path))
+            .await?
+            .error_for_status()?
+            .json()
+    }
+
+    /// Requests the health path and returns how long the round trip took, any response below
+    /// 500 counts as reachable
+    pub async fn ping(&self) -> Result<Duration, ResourceError> {
+        let start = Instant::now();
+        let request = HttpRequest::get(self.health_path.as_deref().unwrap_or_default());
+        let response = self.attempt(&self.url(&request.path)?, &request).await?;
+        if response.status.is_server_error() {
+            return Err(ResourceError::Status {
+                status: response.status.as_u16(),
+                body: response.text(),
+            });
+        }
+        Ok(start.elapsed())
+    }
+
+    /// Sends the request once if the circuit lets it through, server errors count as failures
+    async fn attempt(
+        &self,
+        url: &Url,
+        request: &HttpRequest,
+    ) -> Result<HttpResponse, ResourceError> {
+        let permit = self.breaker.permit()?;
+        let result = self.request(url, request).await;
+        match &result {
+            Ok(response) if response.status.is_server_error() => permit.failure(),
+            _ => permit.record(&result),
+        }
+        result
+    }
+
+    async fn request(
+        &self,
+        url: &Url,
+        request: &HttpRequest,
+    ) -> Result<HttpResponse, ResourceError> {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire().await;
+        }
+        let mut builder = self.client.request(request.method.clone(), url.clone());
+        if !request.query.is_empty() {
+            builder = builder.query(&request.query);
+        }
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = &request.body {
+            builder = builder.body(body.clone());
+        }
+        if let Some(auth) = &self.auth {
+            builder = auth.apply(&self.client, builder).await?;
+        }
+        let response = builder.send().await?;
+        Ok(HttpResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.bytes().await?.to_vec(),
+        })
+    }
+}
+
+/// Wait requested by the server in seconds, dates are not supported
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Answers the connections with the canned responses in order, the last one repeats, and
+    /// records the requests
+    async fn http_server(responses: &[&'static str]) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let responses = responses.to_vec();
+        tokio::spawn(async move {
+            let mut index = 0;
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let request = read_request(&mut stream).await;
+                recorded.lock().unwrap().push(request);
+                let (status, body) = responses[index.min(responses.len() - 1)]
+                    .split_once(' ')
+                    .unwrap();
+                index += 1;
+                let response = format!(
+                    "HTTP/1.1 {status} X\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (format!("http://{address}"), requests)
+    }
+
+    /// Reads the head and, as far as its content length tells, the body of a request
+    async fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let text = String::from_utf8_lossy(&request).into_owned();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map_or(0, |length| length.parse().unwrap());
+                if body.len() >= length {
+                    return text;
+                }
+            }
+            match stream.read(&mut buffer).await {
+                Ok(0) | Err(_) => return text,
+                Ok(read) => request.extend_from_slice(&buffer[..read]),
+            }
+        }
+    }
+
+    fn config(json: serde_json::Value) -> HttpResourceConfig {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retries_idempotent_requests() {
+        let (url, requests) = http_server(&["503 busy", "200 {\"open\":2}"]).await;
+        let resource = HttpResource::new(&config(serde_json::json!({
+            "base_url": format!("{url}/api/"),
+            "headers": {"x-team": "ops"},
+            "auth": {"type": "bearer", "token": "abc"},
+            "retry": {"backoff": "10ms"},
+        })))
+        .unwrap();
+
+        let count: serde_json::Value = resource.get_json("/alerts?state=open").await.unwrap();
+        assert_eq!(count, serde_json::json!({"open": 2}));
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].starts_with("GET /api/alerts?state=open HTTP/1.1"));
+        assert!(requests[1].contains("x-team: ops"));
+        assert!(requests[1].contains("authorization: Bearer abc"));
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_post() {
+        let (url, requests) = http_server(&["503 busy"]).await;
+        let resource = HttpResource::new(&config(serde_json::json!({
+            "base_url": url,
+            "retry": {"backoff": "10ms"},
+        })))
+        .unwrap();
+
+        let request = HttpRequest::post("alerts")
+            .json(&serde_json::json!({"name": "disk"}))
+            .unwrap();
+        let response = resource.send(&request).await.unwrap();
+        assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert!(matches!(
+            response.error_for_status(),
+            Err(ResourceError::Status { status: 503, .. })
+        ));
+
+        // GET gives up after the configured retries and returns the last response
+        let response = resource.send(&HttpRequest::get("alerts")).await.unwrap();
+        assert_eq!(response.text(), "busy");
+        assert_eq!(requests.lock().unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_oauth2_client_credentials() {
+        let (url, requests) = http_server(&[
+            r#"200 {"access_token":"first","expires_in":3600}"#,
+            "200 []",
+            "200 []",
+            "401 expired",
+            r#"200 {"access_token":"second","expires_in":3600}"#,
+            "200 []",
+        ])
+        .await;
+        let resource = HttpResource::new(&config(serde_json::json!({
+            "base_url": url,
+            "auth": {
+                "type": "oauth2",
+                "token_url": format!("{url}/token"),
+                "client_id": "loid",
+                "client_secret": "secret",
+                "scopes": ["alerts.read"],
+            },
+        })))
+        .unwrap();
+
+        for _ in 0..3 {
+            let alerts: Vec<String> = resource.get_json("alerts").await.unwrap();
+            assert!(alerts.is_empty());
+        }
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 6);
+        assert!(requests[0].starts_with("POST /token"));
+        // loid:secret
+        assert!(requests[0].contains("authorization: Basic bG9pZDpzZWNyZXQ="));
+        assert!(requests[0].ends_with("grant_type=client_credentials&scope=alerts.read"));
+        assert!(requests[1].contains("authorization: Bearer first"));
+        assert!(requests[2].contains("authorization: Bearer first"));
+        // the rejected token is replaced once
+        assert!(requests[4].starts_with("POST /token"));
+        assert!(requests[5].contains("authorization: Bearer second"));
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let (url, requests) = http_server(&["404 missing", "500 down"]).await;
+        let resource = HttpResource::new(&config(serde_json::json!({
+            "base_url": url,
+            "health_path": "/health",
+        })))
+        .unwrap();
+        assert!(resource.ping().await.is_ok());
+        assert!(matches!(
+            resource.ping().await,
+            Err(ResourceError::Status { status: 500, .. })
+        ));
+        assert!(requests.lock().unwrap()[0].starts_with("GET /health"));
+    }
+}
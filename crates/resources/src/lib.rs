@@ -1,2 +1,10 @@
+pub mod access;
+pub mod circuit;
+pub mod config;
 pub mod database;
+mod error;
+pub mod http;
 pub mod prelude;
+pub mod registry;
+
+pub use crate::error::ResourceError;
@@ -1 +1,15 @@
-pub use crate::database::{PostgresResource, SqliteResource};
+pub use crate::ResourceError;
+pub use crate::access::{AccessError, AccessGuard, AccessPolicy, StatementKind};
+pub use crate::circuit::{CircuitBreaker, CircuitBreakerConfig, CircuitState, Permit};
+pub use crate::config::{ResourceConfig, ResourceSpec};
+pub use crate::database::{
+    DatabaseResourceConfig, Dialect, JournalMode, MySQLResource, NamedStatement, PostgresResource,
+    Row, SQLResource, SqliteResource, SqliteResourceConfig, TlsConfig, TlsMode,
+};
+pub use crate::http::{
+    HttpAuth, HttpRequest, HttpResource, HttpResourceConfig, HttpResponse, RateLimitConfig,
+    RetryConfig,
+};
+pub use crate::registry::{
+    Resource, ResourceHealth, ResourceRegistry, ResourceScope, TypedResource,
+};
@@ -0,0 +1,664 @@
+//! Live resources shared by sensors and neurons, looked up by the key of their document.
+//!
+//! A resource connects when it is first requested, so resources no sensor or neuron uses never
+//! open a connection.
+
+use crate::ResourceError;
+use crate::access::{AccessGuard, AccessPolicy};
+use crate::circuit::{CircuitBreaker, CircuitState};
+use crate::config::{ResourceConfig, ResourceSpec};
+use crate::database::{MySQLResource, PostgresResource, SQLResource, SqliteResource};
+use crate::http::HttpResource;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{OnceCell, watch};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+/// A connected resource, cheap to clone
+#[derive(Clone)]
+pub enum Resource {
+    Http(Arc<HttpResource>),
+    Mysql(Arc<MySQLResource>),
+    Postgres(Arc<PostgresResource>),
+    Sqlite(Arc<SqliteResource>),
+}
+
+impl Resource {
+    /// Connects the resource described by the configuration, its calls guarded by the breaker
+    /// and the access policy
+    pub async fn connect(
+        config: &ResourceConfig,
+        breaker: CircuitBreaker,
+        access: AccessGuard,
+    ) -> Result<Self, ResourceError> {
+        Ok(match config {
+            ResourceConfig::Http(config) => Resource::Http(Arc::new(
+                HttpResource::new(config)?
+                    .with_circuit_breaker(breaker)
+                    .with_access(access),
+            )),
+            ResourceConfig::Mysql(config) => Resource::Mysql(Arc::new(
+                MySQLResource::connect(config)
+                    .await?
+                    .with_circuit_breaker(breaker)
+                    .with_access(access),
+            )),
+            ResourceConfig::Postgres(config) => Resource::Postgres(Arc::new(
+                PostgresResource::connect(config)
+                    .await?
+                    .with_circuit_breaker(breaker)
+                    .with_access(access),
+            )),
+            ResourceConfig::Sqlite(config) => Resource::Sqlite(Arc::new(
+                SqliteResource::connect(config)
+                    .await?
+                    .with_circuit_breaker(breaker)
+                    .with_access(access),
+            )),
+        })
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Resource::Http(_) => "http",
+            Resource::Mysql(_) => "mysql",
+            Resource::Postgres(_) => "postgres",
+            Resource::Sqlite(_) => "sqlite",
+        }
+    }
+
+    /// Checks the resource and returns how long the round trip took
+    pub async fn ping(&self) -> Result<Duration, ResourceError> {
+        match self {
+            Resource::Http(resource) => resource.ping().await,
+            Resource::Mysql(resource) => resource.ping().await,
+            Resource::Postgres(resource) => resource.ping().await,
+            Resource::Sqlite(resource) => resource.ping().await,
+        }
+    }
+
+    /// Waits for running statements to finish and closes the connections
+    pub async fn close(&self) {
+        match self {
+            // idle connections close when the last handle is dropped
+            Resource::Http(_) => {}
+            Resource::Mysql(resource) => resource.close().await,
+            Resource::Postgres(resource) => resource.close().await,
+            Resource::Sqlite(resource) => resource.close().await,
+        }
+    }
+}
+
+/// A resource type that can be requested from the registry
+pub trait TypedResource: Sized {
+    /// Value of `type` in the resource document
+    const TYPE: &'static str;
+
+    /// The handle if the resource is of this type
+    fn from_resource(resource: &Resource) -> Option<Arc<Self>>;
+}
+
+impl TypedResource for HttpResource {
+    const TYPE: &'static str = "http";
+
+    fn from_resource(resource: &Resource) -> Option<Arc<Self>> {
+        match resource {
+            Resource::Http(resource) => Some(resource.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl TypedResource for MySQLResource {
+    const TYPE: &'static str = "mysql";
+
+    fn from_resource(resource: &Resource) -> Option<Arc<Self>> {
+        match resource {
+            Resource::Mysql(resource) => Some(resource.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl TypedResource for PostgresResource {
+    const TYPE: &'static str = "postgres";
+
+    fn from_resource(resource: &Resource) -> Option<Arc<Self>> {
+        match resource {
+            Resource::Postgres(resource) => Some(resource.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl TypedResource for SqliteResource {
+    const TYPE: &'static str = "sqlite";
+
+    fn from_resource(resource: &Resource) -> Option<Arc<Self>> {
+        match resource {
+            Resource::Sqlite(resource) => Some(resource.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of the last health check of a resource
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceHealth {
+    pub healthy: bool,
+    /// Round trip of the ping, `None` if it failed
+    pub latency: Option<Duration>,
+    /// Why the resource could not be connected or pinged
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl ResourceHealth {
+    fn from_result(result: Result<Duration, &ResourceError>) -> Self {
+        Self {
+            healthy: result.is_ok(),
+            latency: result.ok(),
+            error: result.err().map(ToString::to_string),
+            checked_at: Utc::now(),
+        }
+    }
+}
+
+struct Entry {
+    config: ResourceConfig,
+    breaker: CircuitBreaker,
+    access: AccessGuard,
+    resource: OnceCell<Resource>,
+    health: Mutex<Option<ResourceHealth>>,
+}
+
+impl Entry {
+    fn new(config: ResourceConfig, breaker: CircuitBreaker, access: AccessGuard) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            breaker,
+            access,
+            resource: OnceCell::new(),
+            health: Mutex::new(None),
+        })
+    }
+
+    async fn resource(&self, key: &str) -> Result<Resource, ResourceError> {
+        let result = self
+            .resource
+            .get_or_try_init(|| {
+                // failing to connect counts, an open circuit does not try again until its time
+                self.breaker.call(Resource::connect(
+                    &self.config,
+                    self.breaker.clone(),
+                    self.access.clone(),
+                ))
+            })
+            .await;
+        match result {
+            Ok(resource) => Ok(resource.clone()),
+            Err(error) => {
+                tracing::warn!(resource = key, error = %error, "failed to connect resource");
+                self.set_health(Err(&error));
+                Err(error)
+            }
+        }
+    }
+
+    fn set_health(&self, result: Result<Duration, &ResourceError>) {
+        *self.health.lock().unwrap() = Some(ResourceHealth::from_result(result));
+    }
+}
+
+/// Resources by key, connected on first use and shared by every sensor and neuron using them
+#[derive(Clone, Default)]
+pub struct ResourceRegistry {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: RwLock<HashMap<String, Arc<Entry>>>,
+    closed: AtomicBool,
+}
+
+impl ResourceRegistry {
+    /// Registers the resources without connecting them, fails on the first invalid configuration
+    pub fn new(
+        specs: impl IntoIterator<Item = (String, impl Into<ResourceSpec>)>,
+    ) -> Result<Self, ResourceError> {
+        let mut entries = HashMap::new();
+        for (key, spec) in specs {
+            let spec = spec.into();
+            spec.validate()?;
+            let breaker = CircuitBreaker::new(spec.circuit_breaker.unwrap_or_default());
+            let access = AccessGuard::new(key.clone(), spec.access.unwrap_or_default());
+            entries.insert(key, Entry::new(spec.resource, breaker, access));
+        }
+        Ok(Self {
+            inner: Arc::new(Inner {
+                entries: RwLock::new(entries),
+                closed: AtomicBool::new(false),
+            }),
+        })
+    }
+
+    /// Keys of all registered resources, sorted
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<_> = self.entries().keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Configuration the resource was registered with
+    pub fn config(&self, key: &str) -> Option<ResourceConfig> {
+        self.entries().get(key).map(|entry| entry.config.clone())
+    }
+
+    /// The resource under the key, connected on the first call
+    pub async fn resource(&self, key: &str) -> Result<Resource, ResourceError> {
+        self.entry(key)?.resource(key).await
+    }
+
+    /// The resource under the key as the requested type
+    pub async fn get<T: TypedResource>(&self, key: &str) -> Result<Arc<T>, ResourceError> {
+        typed(key, self.resource(key).await?)
+    }
+
+    /// Access policy of the resource
+    pub fn access(&self, key: &str) -> Option<AccessPolicy> {
+        Some(self.entries().get(key)?.access.policy().clone())
+    }
+
+    /// The resources the sensor or neuron with the key is granted
+    pub fn scope(&self, user: impl Into<String>) -> ResourceScope {
+        ResourceScope {
+            registry: self.clone(),
+            user: user.into(),
+        }
+    }
+
+    /// State of the circuit breaker of the resource
+    pub fn circuit_state(&self, key: &str) -> Option<CircuitState> {
+        Some(self.entries().get(key)?.breaker.state())
+    }
+
+    /// Receives every change of the circuit state of the resource, also across reloads
+    pub fn subscribe(&self, key: &str) -> Option<watch::Receiver<CircuitState>> {
+        Some(self.entries().get(key)?.breaker.subscribe())
+    }
+
+    /// Whether calls to the resource are expected to work, false while its circuit is not
+    /// closed or the key is unknown
+    pub fn is_available(&self, key: &str) -> bool {
+        self.circuit_state(key) == Some(CircuitState::Closed)
+    }
+
+    /// Keys of the resources whose circuit is not closed, sorted
+    pub fn unavailable(&self) -> Vec<String> {
+        let mut keys: Vec<_> = self
+            .entries()
+            .iter()
+            .filter(|(_, entry)| entry.breaker.state() != CircuitState::Closed)
+            .map(|(key, _)| key.clone())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Result of the last health check, `None` before the resource was checked
+    pub fn health(&self, key: &str) -> Option<ResourceHealth> {
+        let entry = self.entries().get(key)?.clone();
+        entry.health.lock().unwrap().clone()
+    }
+
+    /// Pings every connected resource and records the results, resources nobody requested yet
+    /// are left unconnected. A ping is the trial call of a half-open circuit, resources that
+    /// failed to connect try again once their circuit allows it
+    pub async fn check_health(&self) {
+        let entries: Vec<_> = self
+            .entries()
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.clone()))
+            .collect();
+        for (key, entry) in entries {
+            let resource = match entry.resource.get() {
+                Some(resource) => resource.clone(),
+                None if entry.breaker.state() != CircuitState::Closed => {
+                    match entry.resource(&key).await {
+                        Ok(resource) => resource,
+                        Err(_) => continue,
+                    }
+                }
+                None => continue,
+            };
+            let result = resource.ping().await;
+            if let Err(error) = &result {
+                tracing::warn!(resource = key, error = %error, "resource health check failed");
+            }
+            entry.set_health(result.as_ref().copied());
+        }
+    }
+
+    /// Checks the health of the resources every interval until the registry is closed
+    pub fn spawn_health_checks(&self, interval: Duration) -> JoinHandle<()> {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // the first tick completes immediately, resources are not connected yet
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                if registry.is_closed() {
+                    break;
+                }
+                registry.check_health().await;
+            }
+        })
+    }
+
+    /// Replaces the definition of a resource, adding it if the key is new.
+    ///
+    /// Later requests get a resource connected with the new configuration. The old pool stops
+    /// handing out connections and closes once running statements returned theirs.
+    pub async fn reload(
+        &self,
+        key: &str,
+        spec: impl Into<ResourceSpec>,
+    ) -> Result<(), ResourceError> {
+        let spec = spec.into();
+        spec.validate()?;
+        if self.is_closed() {
+            return Err(ResourceError::Closed);
+        }
+        let breaker_config = spec.circuit_breaker.unwrap_or_default();
+        let previous = {
+            let mut entries = self.inner.entries.write().unwrap();
+            // the breaker is kept so subscribers follow the new definition, it starts closed
+            let breaker = match entries.get(key) {
+                Some(entry) => {
+                    entry.breaker.reset(breaker_config);
+                    entry.breaker.clone()
+                }
+                None => CircuitBreaker::new(breaker_config),
+            };
+            let access = AccessGuard::new(key, spec.access.unwrap_or_default());
+            entries.insert(key.to_string(), Entry::new(spec.resource, breaker, access))
+        };
+        tracing::info!(resource = key, "resource reloaded");
+        if let Some(resource) = previous.as_ref().and_then(|entry| entry.resource.get()) {
+            resource.close().await;
+        }
+        Ok(())
+    }
+
+    /// Removes the resource and closes its pool once running statements finished
+    pub async fn remove(&self, key: &str) -> bool {
+        let previous = self.inner.entries.write().unwrap().remove(key);
+        let Some(previous) = previous else {
+            return false;
+        };
+        if let Some(resource) = previous.resource.get() {
+            resource.close().await;
+        }
+        true
+    }
+
+    /// Closes all resources once running statements finished, later requests fail with
+    /// [`ResourceError::Closed`]
+    pub async fn close(&self) {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        let entries: Vec<_> = self.inner.entries.write().unwrap().drain().collect();
+        for (_, entry) in entries {
+            if let Some(resource) = entry.resource.get() {
+                resource.close().await;
+            }
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.closed.load(Ordering::SeqCst)
+    }
+
+    fn entries(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Arc<Entry>>> {
+        self.inner.entries.read().unwrap()
+    }
+
+    fn entry(&self, key: &str) -> Result<Arc<Entry>, ResourceError> {
+        if self.is_closed() {
+            return Err(ResourceError::Closed);
+        }
+        self.entries()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| ResourceError::Unknown(key.to_string()))
+    }
+}
+
+fn typed<T: TypedResource>(key: &str, resource: Resource) -> Result<Arc<T>, ResourceError> {
+    T::from_resource(&resource).ok_or_else(|| ResourceError::WrongType {
+        key: key.to_string(),
+        expected: T::TYPE,
+        actual: resource.type_name(),
+    })
+}
+
+/// The registry as seen by one sensor or neuron, resources not granted to it are denied
+#[derive(Clone)]
+pub struct ResourceScope {
+    registry: ResourceRegistry,
+    user: String,
+}
+
+impl ResourceScope {
+    /// Key of the sensor or neuron
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    /// The resource under the key if it is granted, connected on the first call
+    pub async fn resource(&self, key: &str) -> Result<Resource, ResourceError> {
+        let entry = self.registry.entry(key)?;
+        entry.access.check_user(&self.user)?;
+        entry.resource(key).await
+    }
+
+    /// The resource under the key as the requested type if it is granted
+    pub async fn get<T: TypedResource>(&self, key: &str) -> Result<Arc<T>, ResourceError> {
+        typed(key, self.resource(key).await?)
+    }
+
+    /// Receives every change of the circuit state of the resource, see
+    /// [`ResourceRegistry::subscribe`]
+    pub fn subscribe(&self, key: &str) -> Option<watch::Receiver<CircuitState>> {
+        self.registry.subscribe(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::CircuitBreakerConfig;
+    use crate::database::SqliteResourceConfig;
+    use crate::http::HttpResourceConfig;
+
+    fn sqlite(path: &str) -> ResourceConfig {
+        ResourceConfig::Sqlite(serde_json::from_value(serde_json::json!({ "path": path })).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_lazy_shared_handles() {
+        let registry = ResourceRegistry::new([("local".to_string(), sqlite(":memory:"))]).unwrap();
+        assert_eq!(registry.keys(), ["local"]);
+        assert!(registry.entry("local").unwrap().resource.get().is_none());
+
+        let first = registry.get::<SqliteResource>("local").await.unwrap();
+        first
+            .execute("CREATE TABLE notes (text TEXT)", &[])
+            .await
+            .unwrap();
+        // the second handle shares the pool and with it the in-memory database
+        let second = registry.get::<SqliteResource>("local").await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(second.query("SELECT * FROM notes", &[]).await.is_ok());
+
+        assert!(matches!(
+            registry.get::<PostgresResource>("local").await,
+            Err(ResourceError::WrongType {
+                expected: "postgres",
+                actual: "sqlite",
+                ..
+            })
+        ));
+        assert!(matches!(
+            registry.resource("missing").await,
+            Err(ResourceError::Unknown(key)) if key == "missing"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_health_checks() {
+        let directory = std::env::temp_dir().join(format!("loid-registry-{}", std::process::id()));
+        let missing: SqliteResourceConfig = serde_json::from_value(serde_json::json!({
+            "path": directory.join("missing.db"),
+            "create": false,
+        }))
+        .unwrap();
+        let registry = ResourceRegistry::new([
+            ("local".to_string(), sqlite(":memory:")),
+            ("missing".to_string(), ResourceConfig::Sqlite(missing)),
+            ("unused".to_string(), sqlite(":memory:")),
+        ])
+        .unwrap();
+        registry.resource("local").await.unwrap();
+        assert!(registry.resource("missing").await.is_err());
+
+        registry.check_health().await;
+        let local = registry.health("local").unwrap();
+        assert!(local.healthy);
+        assert!(local.latency.is_some());
+        let missing = registry.health("missing").unwrap();
+        assert!(!missing.healthy);
+        assert!(missing.error.is_some());
+        assert_eq!(registry.health("unused"), None);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        // a port nobody listens on
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let http: HttpResourceConfig = serde_json::from_value(serde_json::json!({
+            "base_url": format!("http://127.0.0.1:{port}"),
+        }))
+        .unwrap();
+        let spec = ResourceSpec {
+            resource: ResourceConfig::Http(http),
+            circuit_breaker: Some(CircuitBreakerConfig {
+                failures: 1,
+                open_for: Duration::from_secs(3600),
+            }),
+            access: None,
+        };
+        let registry = ResourceRegistry::new([
+            ("api".to_string(), spec.clone()),
+            ("local".to_string(), sqlite(":memory:").into()),
+        ])
+        .unwrap();
+        let mut states = registry.subscribe("api").unwrap();
+        assert!(registry.is_available("api"));
+
+        let api = registry.get::<HttpResource>("api").await.unwrap();
+        registry.check_health().await;
+        assert_eq!(registry.circuit_state("api"), Some(CircuitState::Open));
+        assert_eq!(*states.borrow_and_update(), CircuitState::Open);
+        assert_eq!(registry.unavailable(), ["api"]);
+        assert!(!registry.is_available("api"));
+        assert!(registry.is_available("local"));
+        assert!(matches!(
+            api.get_json::<serde_json::Value>("/").await,
+            Err(ResourceError::Unavailable { .. })
+        ));
+
+        // a reload closes the circuit, subscribers keep receiving
+        registry.reload("api", spec).await.unwrap();
+        assert!(states.has_changed().unwrap());
+        assert_eq!(*states.borrow_and_update(), CircuitState::Closed);
+        assert!(registry.unavailable().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scope_checks_grants() {
+        let spec = ResourceSpec {
+            access: serde_json::from_value(serde_json::json!({
+                "grants": ["stuck-orders"],
+                "statements": ["select"],
+            }))
+            .unwrap(),
+            ..sqlite(":memory:").into()
+        };
+        let registry = ResourceRegistry::new([
+            ("orders-db".to_string(), spec),
+            ("local".to_string(), sqlite(":memory:").into()),
+        ])
+        .unwrap();
+        assert_eq!(
+            registry.access("orders-db").unwrap().grants.unwrap(),
+            ["stuck-orders"]
+        );
+
+        let granted = registry.scope("stuck-orders");
+        let db = granted.get::<SqliteResource>("orders-db").await.unwrap();
+        assert!(db.query("SELECT 1", &[]).await.is_ok());
+        assert!(matches!(
+            db.execute("CREATE TABLE notes (text TEXT)", &[]).await,
+            Err(ResourceError::Access { .. })
+        ));
+
+        let other = registry.scope("close-alert");
+        match other.resource("orders-db").await {
+            Err(error @ ResourceError::Access { .. }) => assert_eq!(
+                error.to_string(),
+                "access to resource 'orders-db' denied: `close-alert` is not granted the resource"
+            ),
+            other => panic!("expected access to be denied, got {:?}", other.err()),
+        }
+        // resources without grants are open to everyone
+        assert!(other.get::<SqliteResource>("local").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_reload_and_close() {
+        let registry = ResourceRegistry::new([("local".to_string(), sqlite(":memory:"))]).unwrap();
+        let old = registry.get::<SqliteResource>("local").await.unwrap();
+        old.execute("CREATE TABLE notes (text TEXT)", &[])
+            .await
+            .unwrap();
+
+        registry.reload("local", sqlite(":memory:")).await.unwrap();
+        // the old pool is closed, the new one starts with an empty database
+        assert!(old.query("SELECT 1", &[]).await.is_err());
+        let new = registry.get::<SqliteResource>("local").await.unwrap();
+        assert!(new.query("SELECT * FROM notes", &[]).await.is_err());
+        assert!(matches!(
+            registry.reload("local", sqlite(" ")).await,
+            Err(ResourceError::Config(_))
+        ));
+
+        registry.close().await;
+        assert!(registry.is_closed());
+        assert!(new.query("SELECT 1", &[]).await.is_err());
+        assert!(matches!(
+            registry.resource("local").await,
+            Err(ResourceError::Closed)
+        ));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::SensorError;
+use crate::adapter::{correlation_id, severity, string_map};
+use chrono::{DateTime, Utc};
+use loid_events::prelude::{Event, EventBuilder, Impact, Source, Urgency};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Webhook payload of Alertmanager, Grafana unified alerting sends a superset of it
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Notification {
+    #[serde(default)]
+    pub receiver: String,
+    #[serde(default)]
+    pub external_url: Option<String>,
+    #[serde(default)]
+    pub group_key: Option<String>,
+    /// Sent by Grafana only
+    #[serde(default)]
+    pub title: Option<String>,
+    pub alerts: Vec<Alert>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Alert {
+    /// `firing` or `resolved`
+    pub status: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+    #[serde(default)]
+    pub starts_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub ends_at: Option<DateTime<Utc>>,
+    #[serde(default, rename = "generatorURL")]
+    pub generator_url: Option<String>,
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    /// Sent by Grafana only
+    #[serde(default)]
+    pub value_string: Option<String>,
+    /// Sent by Grafana only
+    #[serde(default, rename = "dashboardURL")]
+    pub dashboard_url: Option<String>,
+    /// Sent by Grafana only
+    #[serde(default, rename = "panelURL")]
+    pub panel_url: Option<String>,
+}
+
+impl Alert {
+    pub fn is_resolved(&self) -> bool {
+        self.status.eq_ignore_ascii_case("resolved")
+    }
+
+    /// The fingerprint of the alert, the label set sorted by name if missing, e.g.
+    /// `{alertname="DiskFull",instance="db-01"}`
+    pub fn identity(&self) -> String {
+        match &self.fingerprint {
+            Some(fingerprint) if !fingerprint.is_empty() => fingerprint.clone(),
+            _ => {
+                let labels: BTreeMap<_, _> = self.labels.iter().collect();
+                let pairs: Vec<String> = labels
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let value = value.replace('\\', r"\\").replace('"', r#"\""#);
+                        format!(r#"{name}="{value}""#)
+                    })
+                    .collect();
+                format!("{{{}}}", pairs.join(","))
+            }
+        }
+    }
+
+    /// The identity and start of the alert, a refire after a resolution starts a new incident
+    pub fn incident(&self) -> String {
+        match self.starts_at {
+            Some(starts_at) => format!("{}@{}", self.identity(), starts_at.to_rfc3339()),
+            None => self.identity(),
+        }
+    }
+
+    /// Builds the event of a single alert, the caller adds tool specific fields
+    pub fn event_builder(&self, system: &str, notification: &Notification) -> EventBuilder {
+        let identity = self.identity();
+        let (impact, urgency) = if self.is_resolved() {
+            (Impact::NEGLIGIBLE, Urgency::LOW)
+        } else {
+            self.labels
+                .get("severity")
+                .map(|name| severity(name))
+                .unwrap_or((Impact::MODERATE, Urgency::MEDIUM))
+        };
+
+        let mut builder = EventBuilder::new();
+        builder
+            .with_source(Source {
+                system: system.to_string(),
+                source_id: Some(identity.clone()),
+            })
+            .with_correlation_id(correlation_id(system, &self.incident()))
+            .with_impact(impact)
+            .with_urgency(urgency)
+            .with_text_field("status", &self.status)
+            .with_text_field("receiver", &notification.receiver)
+            .with_field("labels", string_map(&self.labels))
+            .with_field("annotations", string_map(&self.annotations));
+        if let Some(alertname) = self.labels.get("alertname") {
+            builder.with_text_field("alertname", alertname);
+        }
+        for annotation in ["summary", "description"] {
+            if let Some(value) = self.annotations.get(annotation) {
+                builder.with_text_field(annotation, value);
+            }
+        }
+        if let Some(starts_at) = self.starts_at {
+            builder.with_text_field("starts_at", &starts_at.to_rfc3339());
+        }
+        if let Some(url) = &self.generator_url {
+            builder.with_text_field("generator_url", url);
+        }
+        if let Some(url) = &notification.external_url {
+            builder.with_text_field("external_url", url);
+        }
+        let optional = [
+            ("group_key", &notification.group_key),
+            ("title", &notification.title),
+            ("value_string", &self.value_string),
+            ("dashboard_url", &self.dashboard_url),
+            ("panel_url", &self.panel_url),
+        ];
+        for (field, value) in optional {
+            if let Some(value) = value {
+                builder.with_text_field(field, value);
+            }
+        }
+        if self.is_resolved() {
+            // firing alerts carry the zero time as end
+            let ends_at = self
+                .ends_at
+                .filter(|ends_at| ends_at.timestamp() > 0)
+                .unwrap_or_else(Utc::now);
+            builder
+                .with_resolved_at(ends_at)
+                .with_text_field("ends_at", &ends_at.to_rfc3339());
+        }
+        builder
+    }
+}
+
+pub(crate) fn normalize(notification: Notification) -> Result<Vec<Event>, SensorError> {
+    Ok(notification
+        .alerts
+        .iter()
+        .map(|alert| alert.event_builder("alertmanager", &notification).build())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::adapter::PayloadAdapter;
+    use loid_events::prelude::{Impact, Urgency, Value};
+    use std::collections::HashMap;
+
+    const PAYLOAD: &str = r#"{
+        "version": "4",
+        "groupKey": "{}:{alertname=\"DiskFull\"}",
+        "status": "firing",
+        "receiver": "loid",
+        "externalURL": "http://alertmanager:9093",
+        "alerts": [
+            {
+                "status": "firing",
+                "labels": {"alertname": "DiskFull", "instance": "db-01", "severity": "critical"},
+                "annotations": {"summary": "Disk full on db-01"},
+                "startsAt": "2026-10-19T08:00:00Z",
+                "endsAt": "0001-01-01T00:00:00Z",
+                "generatorURL": "http://prometheus:9090/graph",
+                "fingerprint": "4f1e2c"
+            },
+            {
+                "status": "resolved",
+                "labels": {"alertname": "HighLatency", "severity": "warning"},
+                "annotations": {},
+                "startsAt": "2026-10-19T07:00:00Z",
+                "endsAt": "2026-10-19T07:30:00Z",
+                "fingerprint": "9a8b7c"
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_normalize_alertmanager() {
+        let events = PayloadAdapter::Alertmanager
+            .normalize(PAYLOAD.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert_eq!(events.len(), 2);
+
+        let firing = &events[0];
+        assert_eq!(firing.source.system, "alertmanager");
+        assert_eq!(firing.source.source_id.as_deref(), Some("4f1e2c"));
+        assert_eq!(firing.impact, Impact::SEVERE);
+        assert_eq!(firing.urgency, Urgency::CRITICAL);
+        assert_eq!(
+            firing.fields.get("alertname"),
+            Some(&Value::from("DiskFull"))
+        );
+        assert_eq!(
+            firing.fields.get("summary"),
+            Some(&Value::from("Disk full on db-01"))
+        );
+        assert!(firing.resolved_at.is_none());
+
+        let resolved = &events[1];
+        assert_eq!(resolved.impact, Impact::NEGLIGIBLE);
+        assert_eq!(
+            resolved.resolved_at.map(|at| at.to_rfc3339()).as_deref(),
+            Some("2026-10-19T07:30:00+00:00")
+        );
+    }
+
+    #[test]
+    fn test_resolution_shares_correlation_id() {
+        let firing = PAYLOAD.replace(r#""status": "resolved""#, r#""status": "firing""#);
+        let resolved = PAYLOAD.replace(r#""status": "firing""#, r#""status": "resolved""#);
+        let firing = PayloadAdapter::Alertmanager
+            .normalize(firing.as_bytes(), &HashMap::new())
+            .unwrap();
+        let resolved = PayloadAdapter::Alertmanager
+            .normalize(resolved.as_bytes(), &HashMap::new())
+            .unwrap();
+
+        assert!(firing[0].resolved_at.is_none());
+        assert!(resolved[0].resolved_at.is_some());
+        assert_eq!(firing[0].correlation_id, resolved[0].correlation_id);
+        assert_ne!(firing[0].correlation_id, firing[1].correlation_id);
+
+        let refire = PAYLOAD.replace("2026-10-19T08:00:00Z", "2026-10-19T09:00:00Z");
+        let refire = PayloadAdapter::Alertmanager
+            .normalize(refire.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert_eq!(refire[0].source.source_id, firing[0].source.source_id);
+        assert_ne!(refire[0].correlation_id, firing[0].correlation_id);
+    }
+
+    #[test]
+    fn test_identity_without_fingerprint() {
+        let payload = PAYLOAD
+            .replace(r#""fingerprint": "4f1e2c""#, r#""fingerprint": """#)
+            .replace(r#""instance": "db-01""#, r#""instance": "db-\"01\"""#);
+        let events = PayloadAdapter::Alertmanager
+            .normalize(payload.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert_eq!(
+            events[0].source.source_id.as_deref(),
+            Some(r#"{alertname="DiskFull",instance="db-\"01\"",severity="critical"}"#)
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use crate::SensorError;
+use crate::adapter::{correlation_id, parameter, severity};
+use chrono::Utc;
+use loid_events::prelude::{Event, EventBuilder, Impact, Source, Urgency, Value};
+use std::collections::HashMap;
+
+/// Payload of the webhook integration.
+///
+/// The payload is a template in Datadog, the adapter expects the variables under their lowercase
+/// names, e.g. `{"alert_id": "$ALERT_ID", "alert_transition": "$ALERT_TRANSITION", ...}`.
+pub(crate) type Payload = HashMap<String, serde_json::Value>;
+
+/// Field names of the event and the variables they are read from
+const FIELDS: [(&str, &[&str]); 11] = [
+    ("title", &["title", "event_title"]),
+    ("body", &["body", "event_msg"]),
+    ("alert_id", &["alert_id"]),
+    ("alert_type", &["alert_type"]),
+    ("alert_transition", &["alert_transition"]),
+    ("alert_priority", &["alert_priority"]),
+    ("alert_status", &["alert_status"]),
+    ("alert_scope", &["alert_scope"]),
+    ("event_type", &["event_type"]),
+    ("link", &["link"]),
+    ("date", &["date"]),
+];
+
+pub(crate) fn normalize(payload: Payload) -> Result<Vec<Event>, SensorError> {
+    let alert_id = parameter(&payload, &["alert_id"]);
+    let identity = parameter(&payload, &["aggreg_key"])
+        .or_else(|| {
+            let scope = parameter(&payload, &["alert_scope"]).unwrap_or_default();
+            alert_id
+                .as_ref()
+                .map(|alert_id| format!("{alert_id}:{scope}"))
+        })
+        .or_else(|| parameter(&payload, &["id"]))
+        .ok_or_else(|| {
+            SensorError::Payload("alert_id, aggreg_key or id is required".to_string())
+        })?;
+
+    let transition = parameter(&payload, &["alert_transition"]).unwrap_or_default();
+    let alert_type = parameter(&payload, &["alert_type"]).unwrap_or_default();
+    let resolved = transition.eq_ignore_ascii_case("recovered") || alert_type == "success";
+    let (impact, urgency) = if resolved {
+        (Impact::NEGLIGIBLE, Urgency::LOW)
+    } else if let Some(priority) = parameter(&payload, &["alert_priority"]) {
+        severity(&priority)
+    } else {
+        severity(&alert_type)
+    };
+    let host = parameter(&payload, &["hostname", "host"]);
+
+    let mut builder = EventBuilder::new();
+    builder
+        .with_source(Source {
+            system: "datadog".to_string(),
+            source_id: host.clone().or(alert_id),
+        })
+        .with_correlation_id(correlation_id("datadog", &identity))
+        .with_impact(impact)
+        .with_urgency(urgency)
+        .with_text_field("status", if resolved { "resolved" } else { "triggered" });
+    if let Some(host) = &host {
+        builder.with_text_field("host", host);
+    }
+    for (field, names) in FIELDS {
+        if let Some(value) = parameter(&payload, names) {
+            builder.with_text_field(field, &value);
+        }
+    }
+    if let Some(tags) = parameter(&payload, &["tags"]) {
+        builder.with_list_field(
+            "tags",
+            tags.split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(Value::from)
+                .collect(),
+        );
+    }
+    if resolved {
+        builder.with_resolved_at(Utc::now());
+    }
+    Ok(vec![builder.build()])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::adapter::PayloadAdapter;
+    use loid_events::prelude::{Impact, Urgency, Value};
+    use std::collections::HashMap;
+
+    const TRIGGERED: &str = r#"{
+        "id": "7412093418032",
+        "title": "[Triggered on {host:web-01}] CPU high",
+        "body": "CPU above 90% for 5 minutes",
+        "alert_id": "1234",
+        "alert_type": "error",
+        "alert_transition": "Triggered",
+        "alert_scope": "host:web-01",
+        "hostname": "web-01",
+        "tags": "env:prod, team:platform",
+        "link": "https://app.datadoghq.com/event/event?id=7412093418032"
+    }"#;
+
+    #[test]
+    fn test_normalize_triggered_and_recovered() {
+        let events = PayloadAdapter::Datadog
+            .normalize(TRIGGERED.as_bytes(), &HashMap::new())
+            .unwrap();
+        let event = &events[0];
+        assert_eq!(event.source.system, "datadog");
+        assert_eq!(event.source.source_id.as_deref(), Some("web-01"));
+        assert_eq!(event.impact, Impact::SIGNIFICANT);
+        assert_eq!(event.urgency, Urgency::HIGH);
+        assert_eq!(
+            event.fields.get("tags"),
+            Some(&Value::List(vec![
+                Value::from("env:prod"),
+                Value::from("team:platform")
+            ]))
+        );
+        assert!(event.resolved_at.is_none());
+
+        let recovered = TRIGGERED
+            .replace(r#""Triggered""#, r#""Recovered""#)
+            .replace(r#""error""#, r#""success""#)
+            .replace("7412093418032", "7412093419999");
+        let resolved = PayloadAdapter::Datadog
+            .normalize(recovered.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert!(resolved[0].resolved_at.is_some());
+        assert_eq!(resolved[0].correlation_id, event.correlation_id);
+    }
+
+    #[test]
+    fn test_normalize_priority_takes_precedence() {
+        let payload = TRIGGERED.replace(
+            r#""alert_type": "error""#,
+            r#""alert_type": "warning", "alert_priority": "P1""#,
+        );
+        let events = PayloadAdapter::Datadog
+            .normalize(payload.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert_eq!(events[0].impact, Impact::SEVERE);
+    }
+}
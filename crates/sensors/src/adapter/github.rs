@@ -0,0 +1,293 @@
+use crate::SensorError;
+use crate::adapter::{correlation_id, severity};
+use chrono::Utc;
+use loid_events::prelude::{Event, EventBuilder, Impact, Source, Urgency};
+use serde_json::Value as Json;
+
+/// What an event of GitHub means for the alerting
+enum Outcome {
+    Problem(Impact, Urgency),
+    Resolved,
+    /// Progress or results without meaning for alerting
+    Ignored,
+}
+
+/// Returns the string at the JSON pointer, empty if missing
+fn text<'a>(payload: &'a Json, pointer: &str) -> &'a str {
+    payload
+        .pointer(pointer)
+        .and_then(Json::as_str)
+        .unwrap_or_default()
+}
+
+/// Derives the event type from the payload if the `X-GitHub-Event` header is not available
+fn infer_event_type(payload: &Json) -> Option<&'static str> {
+    let has = |key: &str| payload.get(key).is_some();
+    if has("workflow_run") {
+        Some("workflow_run")
+    } else if has("check_run") {
+        Some("check_run")
+    } else if has("deployment_status") {
+        Some("deployment_status")
+    } else if payload.pointer("/alert/security_advisory").is_some() {
+        Some("dependabot_alert")
+    } else if payload.pointer("/alert/rule").is_some() {
+        Some("code_scanning_alert")
+    } else if payload.pointer("/alert/secret_type").is_some() {
+        Some("secret_scanning_alert")
+    } else if has("zen") {
+        Some("ping")
+    } else {
+        None
+    }
+}
+
+/// Outcome of a finished workflow run or check run
+fn conclusion(conclusion: &str) -> Outcome {
+    match conclusion {
+        "success" => Outcome::Resolved,
+        "failure" | "timed_out" | "startup_failure" => {
+            Outcome::Problem(Impact::MODERATE, Urgency::MEDIUM)
+        }
+        _ => Outcome::Ignored,
+    }
+}
+
+pub(crate) fn normalize(
+    payload: Json,
+    event_type: Option<&str>,
+) -> Result<Vec<Event>, SensorError> {
+    let event_type = event_type
+        .or_else(|| infer_event_type(&payload))
+        .ok_or_else(|| SensorError::Payload("unknown GitHub event".to_string()))?;
+    let repository = text(&payload, "/repository/full_name");
+    let action = text(&payload, "/action");
+
+    let mut fields: Vec<(&str, &str)> = Vec::new();
+    let (outcome, identity) = match event_type {
+        "workflow_run" => {
+            fields.extend([
+                ("workflow", text(&payload, "/workflow_run/name")),
+                ("branch", text(&payload, "/workflow_run/head_branch")),
+                ("conclusion", text(&payload, "/workflow_run/conclusion")),
+                ("head_sha", text(&payload, "/workflow_run/head_sha")),
+                ("actor", text(&payload, "/workflow_run/actor/login")),
+                ("url", text(&payload, "/workflow_run/html_url")),
+            ]);
+            let outcome = match action {
+                "completed" => conclusion(text(&payload, "/workflow_run/conclusion")),
+                _ => Outcome::Ignored,
+            };
+            let workflow_id = payload
+                .pointer("/workflow_run/workflow_id")
+                .map(Json::to_string)
+                .unwrap_or_default();
+            let branch = text(&payload, "/workflow_run/head_branch");
+            (outcome, format!("workflow:{workflow_id}:{branch}"))
+        }
+        "check_run" => {
+            fields.extend([
+                ("check", text(&payload, "/check_run/name")),
+                (
+                    "branch",
+                    text(&payload, "/check_run/check_suite/head_branch"),
+                ),
+                ("conclusion", text(&payload, "/check_run/conclusion")),
+                ("head_sha", text(&payload, "/check_run/head_sha")),
+                ("url", text(&payload, "/check_run/html_url")),
+            ]);
+            let outcome = match action {
+                "completed" => conclusion(text(&payload, "/check_run/conclusion")),
+                _ => Outcome::Ignored,
+            };
+            let check = text(&payload, "/check_run/name");
+            let branch = text(&payload, "/check_run/check_suite/head_branch");
+            (outcome, format!("check:{check}:{branch}"))
+        }
+        "deployment_status" => {
+            let environment = text(&payload, "/deployment/environment");
+            let state = text(&payload, "/deployment_status/state");
+            fields.extend([
+                ("environment", environment),
+                ("state", state),
+                (
+                    "description",
+                    text(&payload, "/deployment_status/description"),
+                ),
+                ("url", text(&payload, "/deployment_status/target_url")),
+            ]);
+            let outcome = match state {
+                "success" => Outcome::Resolved,
+                "failure" | "error" => Outcome::Problem(Impact::SIGNIFICANT, Urgency::HIGH),
+                _ => Outcome::Ignored,
+            };
+            (outcome, format!("deployment:{environment}"))
+        }
+        "dependabot_alert" | "code_scanning_alert" | "secret_scanning_alert" => {
+            let level = match event_type {
+                "dependabot_alert" => text(&payload, "/alert/security_advisory/severity"),
+                "code_scanning_alert" => {
+                    match text(&payload, "/alert/rule/security_severity_level") {
+                        "" => text(&payload, "/alert/rule/severity"),
+                        level => level,
+                    }
+                }
+                // leaked credentials are always urgent
+                _ => "high",
+            };
+            fields.extend([
+                ("severity", level),
+                ("state", text(&payload, "/alert/state")),
+                ("package", text(&payload, "/alert/dependency/package/name")),
+                (
+                    "summary",
+                    text(&payload, "/alert/security_advisory/summary"),
+                ),
+                ("rule", text(&payload, "/alert/rule/id")),
+                ("secret_type", text(&payload, "/alert/secret_type")),
+                ("url", text(&payload, "/alert/html_url")),
+            ]);
+            let outcome = match action {
+                "created" | "reopened" | "auto_reopened" | "appeared_in_branch" => {
+                    let (impact, urgency) = severity(level);
+                    Outcome::Problem(impact, urgency)
+                }
+                "fixed" | "dismissed" | "auto_dismissed" | "closed_by_user" | "resolved" => {
+                    Outcome::Resolved
+                }
+                _ => Outcome::Ignored,
+            };
+            let number = payload
+                .pointer("/alert/number")
+                .map(Json::to_string)
+                .unwrap_or_default();
+            (outcome, format!("{event_type}:{number}"))
+        }
+        _ => (Outcome::Ignored, String::new()),
+    };
+
+    let (impact, urgency) = match outcome {
+        Outcome::Problem(impact, urgency) => (impact, urgency),
+        Outcome::Resolved => (Impact::NEGLIGIBLE, Urgency::LOW),
+        Outcome::Ignored => {
+            tracing::debug!(event_type, action, "GitHub event ignored");
+            return Ok(vec![]);
+        }
+    };
+
+    let mut builder = EventBuilder::new();
+    builder
+        .with_source(Source {
+            system: "github".to_string(),
+            source_id: Some(repository.to_string()),
+        })
+        .with_correlation_id(correlation_id(
+            "github",
+            &format!("{repository}:{identity}"),
+        ))
+        .with_impact(impact)
+        .with_urgency(urgency)
+        .with_text_field("event_type", event_type)
+        .with_text_field("repository", repository);
+    if !action.is_empty() {
+        builder.with_text_field("action", action);
+    }
+    for (field, value) in fields {
+        if !value.is_empty() {
+            builder.with_text_field(field, value);
+        }
+    }
+    if matches!(outcome, Outcome::Resolved) {
+        builder.with_resolved_at(Utc::now());
+    }
+    Ok(vec![builder.build()])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::adapter::PayloadAdapter;
+    use loid_events::prelude::{Impact, Value};
+    use std::collections::HashMap;
+
+    fn workflow_run(conclusion: &str) -> String {
+        format!(
+            r#"{{
+                "action": "completed",
+                "workflow_run": {{
+                    "name": "CI",
+                    "workflow_id": 42,
+                    "head_branch": "main",
+                    "head_sha": "abc123",
+                    "conclusion": "{conclusion}",
+                    "html_url": "https://github.com/loid-labs/loid/actions/runs/1",
+                    "actor": {{"login": "octocat"}}
+                }},
+                "repository": {{"full_name": "loid-labs/loid"}}
+            }}"#
+        )
+    }
+
+    #[test]
+    fn test_normalize_workflow_run_failure_and_fix() {
+        let headers = HashMap::from([("x-github-event".to_string(), "workflow_run".to_string())]);
+        let failed = PayloadAdapter::Github
+            .normalize(workflow_run("failure").as_bytes(), &headers)
+            .unwrap();
+        let event = &failed[0];
+        assert_eq!(event.source.system, "github");
+        assert_eq!(event.source.source_id.as_deref(), Some("loid-labs/loid"));
+        assert_eq!(event.impact, Impact::MODERATE);
+        assert_eq!(event.fields.get("workflow"), Some(&Value::from("CI")));
+        assert!(event.resolved_at.is_none());
+
+        // without header the event type is inferred from the payload
+        let fixed = PayloadAdapter::Github
+            .normalize(workflow_run("success").as_bytes(), &HashMap::new())
+            .unwrap();
+        assert!(fixed[0].resolved_at.is_some());
+        assert_eq!(fixed[0].correlation_id, event.correlation_id);
+
+        let cancelled = PayloadAdapter::Github
+            .normalize(workflow_run("cancelled").as_bytes(), &headers)
+            .unwrap();
+        assert!(cancelled.is_empty());
+    }
+
+    #[test]
+    fn test_normalize_dependabot_alert() {
+        let payload = r#"{
+            "action": "created",
+            "alert": {
+                "number": 3,
+                "state": "open",
+                "dependency": {"package": {"name": "openssl"}},
+                "security_advisory": {"severity": "critical", "summary": "RCE in openssl"},
+                "html_url": "https://github.com/loid-labs/loid/security/dependabot/3"
+            },
+            "repository": {"full_name": "loid-labs/loid"}
+        }"#;
+        let events = PayloadAdapter::Github
+            .normalize(payload.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert_eq!(events[0].impact, Impact::SEVERE);
+        assert_eq!(
+            events[0].fields.get("package"),
+            Some(&Value::from("openssl"))
+        );
+
+        let fixed = payload.replace(r#""action": "created""#, r#""action": "fixed""#);
+        let resolved = PayloadAdapter::Github
+            .normalize(fixed.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert!(resolved[0].resolved_at.is_some());
+        assert_eq!(resolved[0].correlation_id, events[0].correlation_id);
+    }
+
+    #[test]
+    fn test_normalize_ping_is_ignored() {
+        let events = PayloadAdapter::Github
+            .normalize(br#"{"zen": "Keep it logically awesome."}"#, &HashMap::new())
+            .unwrap();
+        assert!(events.is_empty());
+    }
+}
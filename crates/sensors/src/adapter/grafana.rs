@@ -0,0 +1,183 @@
+use crate::SensorError;
+use crate::adapter::alertmanager::Notification;
+use crate::adapter::{correlation_id, severity, string_map};
+use chrono::Utc;
+use loid_events::prelude::{Event, EventBuilder, Impact, Source, Urgency, Value};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Payload {
+    /// Grafana 8+ alerting, a superset of the Alertmanager webhook
+    Unified(Notification),
+    Legacy(LegacyAlert),
+}
+
+/// Webhook payload of the legacy dashboard alerting
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LegacyAlert {
+    #[serde(default)]
+    pub title: String,
+    pub rule_id: i64,
+    #[serde(default)]
+    pub rule_name: String,
+    #[serde(default)]
+    pub rule_url: Option<String>,
+    /// `alerting`, `ok`, `no_data`, `pending` or `paused`
+    pub state: String,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub eval_matches: Vec<EvalMatch>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct EvalMatch {
+    pub metric: String,
+    pub value: Option<f64>,
+}
+
+pub(crate) fn normalize(payload: Payload) -> Result<Vec<Event>, SensorError> {
+    match payload {
+        Payload::Unified(notification) => Ok(notification
+            .alerts
+            .iter()
+            .map(|alert| alert.event_builder("grafana", &notification).build())
+            .collect()),
+        Payload::Legacy(alert) => Ok(legacy_event(&alert).into_iter().collect()),
+    }
+}
+
+fn legacy_event(alert: &LegacyAlert) -> Option<Event> {
+    let (impact, urgency) = match alert.state.as_str() {
+        "alerting" => alert
+            .tags
+            .get("severity")
+            .map(|name| severity(name))
+            .unwrap_or((Impact::MODERATE, Urgency::MEDIUM)),
+        "no_data" => (Impact::MINOR, Urgency::LOW),
+        "ok" => (Impact::NEGLIGIBLE, Urgency::LOW),
+        // pending and paused rules did not change their state yet
+        _ => return None,
+    };
+
+    let rule_id = alert.rule_id.to_string();
+    let mut builder = EventBuilder::new();
+    builder
+        .with_source(Source {
+            system: "grafana".to_string(),
+            source_id: Some(rule_id.clone()),
+        })
+        .with_correlation_id(correlation_id("grafana", &format!("rule:{rule_id}")))
+        .with_impact(impact)
+        .with_urgency(urgency)
+        .with_text_field("status", &alert.state)
+        .with_text_field("title", &alert.title)
+        .with_text_field("rule_name", &alert.rule_name)
+        .with_int_field("rule_id", alert.rule_id)
+        .with_field("labels", string_map(&alert.tags))
+        .with_list_field(
+            "eval_matches",
+            alert
+                .eval_matches
+                .iter()
+                .map(|eval_match| {
+                    Value::Map(HashMap::from([
+                        (
+                            "metric".to_string(),
+                            Value::from(eval_match.metric.as_str()),
+                        ),
+                        (
+                            "value".to_string(),
+                            eval_match.value.map(Value::Float).unwrap_or_default(),
+                        ),
+                    ]))
+                })
+                .collect(),
+        );
+    if let Some(message) = &alert.message {
+        builder.with_text_field("message", message);
+    }
+    if let Some(url) = &alert.rule_url {
+        builder.with_text_field("rule_url", url);
+    }
+    if alert.state == "ok" {
+        builder.with_resolved_at(Utc::now());
+    }
+    Some(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::adapter::PayloadAdapter;
+    use loid_events::prelude::{Impact, Value};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_normalize_unified_alerting() {
+        let payload = r#"{
+            "receiver": "loid",
+            "status": "firing",
+            "orgId": 1,
+            "title": "[FIRING:1] HighErrorRate",
+            "alerts": [{
+                "status": "firing",
+                "labels": {"alertname": "HighErrorRate", "severity": "high"},
+                "annotations": {"summary": "5xx above 5%"},
+                "startsAt": "2026-10-19T08:00:00Z",
+                "endsAt": "0001-01-01T00:00:00Z",
+                "fingerprint": "c0ffee",
+                "valueString": "[ var='A' value=7.3 ]",
+                "dashboardURL": "http://grafana/d/abc"
+            }]
+        }"#;
+        let events = PayloadAdapter::Grafana
+            .normalize(payload.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].source.system, "grafana");
+        assert_eq!(events[0].impact, Impact::SIGNIFICANT);
+        assert_eq!(
+            events[0].fields.get("value_string"),
+            Some(&Value::from("[ var='A' value=7.3 ]"))
+        );
+    }
+
+    #[test]
+    fn test_normalize_legacy_alerting() {
+        let alerting = r#"{
+            "title": "[Alerting] CPU high",
+            "ruleId": 7,
+            "ruleName": "CPU high",
+            "state": "alerting",
+            "message": "CPU above 90%",
+            "evalMatches": [{"metric": "web-01", "value": 97.5, "tags": null}],
+            "tags": {"severity": "critical"}
+        }"#;
+        let events = PayloadAdapter::Grafana
+            .normalize(alerting.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert_eq!(events[0].impact, Impact::SEVERE);
+        assert_eq!(events[0].source.source_id.as_deref(), Some("7"));
+        assert!(events[0].resolved_at.is_none());
+
+        let ok = alerting.replace(r#""state": "alerting""#, r#""state": "ok""#);
+        let resolved = PayloadAdapter::Grafana
+            .normalize(ok.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert!(resolved[0].resolved_at.is_some());
+        assert_eq!(resolved[0].correlation_id, events[0].correlation_id);
+
+        let pending = alerting.replace(r#""state": "alerting""#, r#""state": "pending""#);
+        assert!(
+            PayloadAdapter::Grafana
+                .normalize(pending.as_bytes(), &HashMap::new())
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
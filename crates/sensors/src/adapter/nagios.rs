@@ -0,0 +1,166 @@
+use crate::SensorError;
+use crate::adapter::{correlation_id, parameter};
+use crate::command::CheckState;
+use chrono::Utc;
+use loid_events::prelude::{Event, EventBuilder, Source};
+use std::collections::HashMap;
+
+/// Notification macros sent by a notification command, e.g. `{"HOSTNAME": "$HOSTNAME$", ...}`.
+///
+/// Macro names are matched case-insensitively.
+pub(crate) type Macros = HashMap<String, serde_json::Value>;
+
+/// Field names of the event and the macros they are read from, service macros take precedence
+const FIELDS: [(&str, &[&str]); 9] = [
+    ("host_address", &["HOSTADDRESS"]),
+    ("output", &["SERVICEOUTPUT", "HOSTOUTPUT"]),
+    ("long_output", &["LONGSERVICEOUTPUT", "LONGHOSTOUTPUT"]),
+    ("state_type", &["SERVICESTATETYPE", "HOSTSTATETYPE"]),
+    ("attempt", &["SERVICEATTEMPT", "HOSTATTEMPT"]),
+    ("author", &["NOTIFICATIONAUTHOR"]),
+    ("comment", &["NOTIFICATIONCOMMENT"]),
+    (
+        "notification_number",
+        &["SERVICENOTIFICATIONNUMBER", "HOSTNOTIFICATIONNUMBER"],
+    ),
+    ("datetime", &["LONGDATETIME"]),
+];
+
+fn state(name: &str) -> CheckState {
+    match name.to_ascii_uppercase().as_str() {
+        "OK" | "UP" => CheckState::Ok,
+        "WARNING" => CheckState::Warning,
+        "CRITICAL" | "DOWN" => CheckState::Critical,
+        _ => CheckState::Unknown,
+    }
+}
+
+pub(crate) fn normalize(macros: Macros) -> Result<Vec<Event>, SensorError> {
+    let macros: Macros = macros
+        .into_iter()
+        .map(|(name, value)| (name.to_ascii_uppercase(), value))
+        .collect();
+
+    let host = parameter(&macros, &["HOSTNAME"])
+        .ok_or_else(|| SensorError::Payload("HOSTNAME is missing".to_string()))?;
+    let service = parameter(&macros, &["SERVICEDESC"]);
+    let state_name = match service {
+        Some(_) => parameter(&macros, &["SERVICESTATE"]),
+        None => parameter(&macros, &["HOSTSTATE"]),
+    }
+    .unwrap_or_else(|| "UNKNOWN".to_string());
+    let state = state(&state_name);
+    let notification_type = parameter(&macros, &["NOTIFICATIONTYPE"]);
+    let resolved = match notification_type.as_deref() {
+        Some(notification_type) => notification_type.eq_ignore_ascii_case("RECOVERY"),
+        None => state.is_ok(),
+    };
+
+    // recoveries carry the id of the resolved problem in the LAST*PROBLEMID macro
+    let problem_id = match (&service, resolved) {
+        (Some(_), true) => parameter(&macros, &["LASTSERVICEPROBLEMID"]),
+        (Some(_), false) => parameter(&macros, &["SERVICEPROBLEMID"]),
+        (None, true) => parameter(&macros, &["LASTHOSTPROBLEMID"]),
+        (None, false) => parameter(&macros, &["HOSTPROBLEMID"]),
+    }
+    .filter(|id| id != "0");
+    let identity = match (&problem_id, &service) {
+        (Some(id), _) => format!("problem:{id}"),
+        (None, Some(service)) => format!("{host}/{service}"),
+        (None, None) => host.clone(),
+    };
+
+    let mut builder = EventBuilder::new();
+    builder
+        .with_source(Source {
+            system: "nagios".to_string(),
+            source_id: Some(host.clone()),
+        })
+        .with_correlation_id(correlation_id("nagios", &identity))
+        .with_impact(state.impact())
+        .with_urgency(state.urgency())
+        .with_text_field("host", &host)
+        .with_text_field("state", &state_name.to_ascii_uppercase());
+    if let Some(service) = &service {
+        builder.with_text_field("service", service);
+    }
+    if let Some(notification_type) = &notification_type {
+        builder.with_text_field("notification_type", notification_type);
+    }
+    if let Some(problem_id) = &problem_id {
+        builder.with_text_field("problem_id", problem_id);
+    }
+    for (field, names) in FIELDS {
+        if let Some(value) = parameter(&macros, names) {
+            builder.with_text_field(field, &value);
+        }
+    }
+    if resolved {
+        builder.with_resolved_at(Utc::now());
+    }
+    Ok(vec![builder.build()])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::adapter::PayloadAdapter;
+    use loid_events::prelude::{Impact, Urgency, Value};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_normalize_service_problem_and_recovery() {
+        let problem = r#"{
+            "NOTIFICATIONTYPE": "PROBLEM",
+            "HOSTNAME": "web-01",
+            "HOSTADDRESS": "10.0.0.7",
+            "SERVICEDESC": "HTTP",
+            "SERVICESTATE": "CRITICAL",
+            "SERVICESTATETYPE": "HARD",
+            "SERVICEOUTPUT": "connection refused",
+            "SERVICEPROBLEMID": "815",
+            "LASTSERVICEPROBLEMID": "0"
+        }"#;
+        let events = PayloadAdapter::Nagios
+            .normalize(problem.as_bytes(), &HashMap::new())
+            .unwrap();
+        let event = &events[0];
+        assert_eq!(event.source.system, "nagios");
+        assert_eq!(event.source.source_id.as_deref(), Some("web-01"));
+        assert_eq!(event.impact, Impact::SIGNIFICANT);
+        assert_eq!(event.urgency, Urgency::HIGH);
+        assert_eq!(event.fields.get("service"), Some(&Value::from("HTTP")));
+        assert_eq!(
+            event.fields.get("output"),
+            Some(&Value::from("connection refused"))
+        );
+        assert!(event.resolved_at.is_none());
+
+        let recovery = r#"{
+            "notificationtype": "RECOVERY",
+            "hostname": "web-01",
+            "servicedesc": "HTTP",
+            "servicestate": "OK",
+            "serviceproblemid": "0",
+            "lastserviceproblemid": 815
+        }"#;
+        let resolved = PayloadAdapter::Nagios
+            .normalize(recovery.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert!(resolved[0].resolved_at.is_some());
+        assert_eq!(resolved[0].impact, Impact::NEGLIGIBLE);
+        assert_eq!(resolved[0].correlation_id, event.correlation_id);
+    }
+
+    #[test]
+    fn test_normalize_host_down() {
+        let events = PayloadAdapter::Nagios
+            .normalize(
+                br#"{"NOTIFICATIONTYPE": "PROBLEM", "HOSTNAME": "db-01", "HOSTSTATE": "DOWN"}"#,
+                &HashMap::new(),
+            )
+            .unwrap();
+        assert_eq!(events[0].impact, Impact::SIGNIFICANT);
+        assert_eq!(events[0].fields.get("state"), Some(&Value::from("DOWN")));
+        assert!(!events[0].fields.contains_key("service"));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::SensorError;
+use crate::adapter::{correlation_id, parameter, severity};
+use chrono::Utc;
+use loid_events::prelude::{Event, EventBuilder, Impact, Source, Urgency};
+use std::collections::HashMap;
+
+/// Parameters of the webhook media type.
+///
+/// The parameter names are chosen freely in Zabbix, the adapter expects the names of the
+/// bundled webhooks (`event_id`, `event_value`, `event_severity`, ...) and accepts a few aliases.
+pub(crate) type Parameters = HashMap<String, serde_json::Value>;
+
+const SEVERITIES: [&str; 6] = [
+    "Not classified",
+    "Information",
+    "Warning",
+    "Average",
+    "High",
+    "Disaster",
+];
+
+/// Field names of the event and the parameter names they are read from
+const FIELDS: [(&str, &[&str]); 10] = [
+    ("event_name", &["event_name", "name", "subject"]),
+    ("host", &["host_name", "host", "hostname"]),
+    ("host_ip", &["host_ip"]),
+    ("trigger_id", &["trigger_id"]),
+    ("trigger_description", &["trigger_description"]),
+    ("operational_data", &["event_opdata"]),
+    ("tags", &["event_tags"]),
+    ("event_date", &["event_date"]),
+    ("event_time", &["event_time"]),
+    ("zabbix_url", &["zabbix_url"]),
+];
+
+pub(crate) fn normalize(parameters: Parameters) -> Result<Vec<Event>, SensorError> {
+    let event_id = parameter(&parameters, &["event_id", "eventid"])
+        .ok_or_else(|| SensorError::Payload("event_id is missing".to_string()))?;
+    // {EVENT.VALUE} is 1 for problems and 0 for recoveries
+    let resolved = matches!(
+        parameter(&parameters, &["event_value", "value"]).as_deref(),
+        Some("0") | Some("RESOLVED") | Some("OK")
+    );
+    let severity_name = parameter(&parameters, &["event_severity", "severity"]).map(|name| {
+        // {EVENT.NSEVERITY} is the index of the severity
+        name.parse::<usize>()
+            .ok()
+            .and_then(|index| SEVERITIES.get(index))
+            .map(|name| name.to_string())
+            .unwrap_or(name)
+    });
+    let (impact, urgency) = match (&severity_name, resolved) {
+        (_, true) => (Impact::NEGLIGIBLE, Urgency::LOW),
+        (Some(name), false) => severity(name),
+        (None, false) => (Impact::MODERATE, Urgency::MEDIUM),
+    };
+
+    let mut builder = EventBuilder::new();
+    builder
+        .with_source(Source {
+            system: "zabbix".to_string(),
+            source_id: parameter(&parameters, &["host_name", "host", "hostname"]),
+        })
+        .with_correlation_id(correlation_id("zabbix", &event_id))
+        .with_impact(impact)
+        .with_urgency(urgency)
+        .with_text_field("event_id", &event_id)
+        .with_text_field("status", if resolved { "resolved" } else { "problem" });
+    if let Some(severity_name) = &severity_name {
+        builder.with_text_field("severity", severity_name);
+    }
+    for (field, names) in FIELDS {
+        if let Some(value) = parameter(&parameters, names) {
+            builder.with_text_field(field, &value);
+        }
+    }
+    if resolved {
+        builder.with_resolved_at(Utc::now());
+    }
+    Ok(vec![builder.build()])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SensorError;
+    use crate::adapter::PayloadAdapter;
+    use loid_events::prelude::{Impact, Urgency, Value};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_normalize_problem_and_recovery() {
+        let problem = r#"{
+            "event_id": "4711",
+            "event_value": "1",
+            "event_severity": "High",
+            "event_name": "Zabbix agent is not available",
+            "host_name": "db-01",
+            "host_ip": "10.0.0.5",
+            "event_tags": "scope:availability"
+        }"#;
+        let events = PayloadAdapter::Zabbix
+            .normalize(problem.as_bytes(), &HashMap::new())
+            .unwrap();
+        let event = &events[0];
+        assert_eq!(event.source.system, "zabbix");
+        assert_eq!(event.source.source_id.as_deref(), Some("db-01"));
+        assert_eq!(event.impact, Impact::SIGNIFICANT);
+        assert_eq!(event.urgency, Urgency::HIGH);
+        assert_eq!(event.fields.get("host_ip"), Some(&Value::from("10.0.0.5")));
+        assert!(event.resolved_at.is_none());
+
+        let recovery = problem.replace(r#""event_value": "1""#, r#""event_value": 0"#);
+        let resolved = PayloadAdapter::Zabbix
+            .normalize(recovery.as_bytes(), &HashMap::new())
+            .unwrap();
+        assert!(resolved[0].resolved_at.is_some());
+        assert_eq!(resolved[0].impact, Impact::NEGLIGIBLE);
+        assert_eq!(resolved[0].correlation_id, event.correlation_id);
+    }
+
+    #[test]
+    fn test_normalize_numeric_severity() {
+        let events = PayloadAdapter::Zabbix
+            .normalize(
+                br#"{"eventid": 1, "value": "1", "severity": "5"}"#,
+                &HashMap::new(),
+            )
+            .unwrap();
+        assert_eq!(events[0].impact, Impact::SEVERE);
+        assert_eq!(
+            events[0].fields.get("severity"),
+            Some(&Value::from("Disaster"))
+        );
+    }
+
+    #[test]
+    fn test_normalize_requires_event_id() {
+        assert!(matches!(
+            PayloadAdapter::Zabbix.normalize(br#"{"event_value": "1"}"#, &HashMap::new()),
+            Err(SensorError::Payload(_))
+        ));
+    }
+}
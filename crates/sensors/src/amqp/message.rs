@@ -0,0 +1,254 @@
+use crate::SensorError;
+use crate::adapter::PayloadAdapter;
+use chrono::DateTime;
+use lapin::BasicProperties;
+use lapin::types::{AMQPValue, FieldTable};
+use loid_events::prelude::{Event, EventBuilder, Source, Value};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// A delivered message, independent of the channel it was delivered on
+pub(crate) struct Message<'a> {
+    pub exchange: &'a str,
+    pub routing_key: &'a str,
+    pub properties: &'a BasicProperties,
+    pub body: &'a [u8],
+}
+
+fn value(value: &AMQPValue) -> Value {
+    match value {
+        AMQPValue::Boolean(value) => Value::Bool(*value),
+        AMQPValue::ShortShortInt(value) => Value::Int(*value as i64),
+        AMQPValue::ShortShortUInt(value) => Value::Int(*value as i64),
+        AMQPValue::ShortInt(value) => Value::Int(*value as i64),
+        AMQPValue::ShortUInt(value) => Value::Int(*value as i64),
+        AMQPValue::LongInt(value) => Value::Int(*value as i64),
+        AMQPValue::LongUInt(value) => Value::Int(*value as i64),
+        AMQPValue::LongLongInt(value) => Value::Int(*value),
+        AMQPValue::Float(value) => Value::Float(*value as f64),
+        AMQPValue::Double(value) => Value::Float(*value),
+        AMQPValue::DecimalValue(decimal) => {
+            Value::Float(decimal.value as f64 / 10f64.powi(decimal.scale as i32))
+        }
+        AMQPValue::ShortString(value) => Value::String(value.as_str().to_string()),
+        AMQPValue::LongString(value) => Value::String(value.to_string()),
+        AMQPValue::FieldArray(values) => {
+            Value::List(values.as_slice().iter().map(self::value).collect())
+        }
+        AMQPValue::Timestamp(seconds) => DateTime::from_timestamp(*seconds as i64, 0)
+            .map(|time| Value::String(time.to_rfc3339()))
+            .unwrap_or(Value::Int(*seconds as i64)),
+        AMQPValue::FieldTable(table) => Value::Map(headers(table)),
+        AMQPValue::ByteArray(bytes) => {
+            Value::String(String::from_utf8_lossy(bytes.as_slice()).into_owned())
+        }
+        AMQPValue::Void => Value::None,
+    }
+}
+
+/// Headers of a message as event values
+pub(crate) fn headers(table: &FieldTable) -> HashMap<String, Value> {
+    table
+        .inner()
+        .iter()
+        .map(|(name, header)| (name.as_str().to_string(), value(header)))
+        .collect()
+}
+
+impl Message<'_> {
+    fn headers(&self) -> HashMap<String, Value> {
+        self.properties
+            .headers()
+            .as_ref()
+            .map(headers)
+            .unwrap_or_default()
+    }
+
+    /// Turns the message into events, with an adapter or from a JSON object
+    pub fn normalize(&self, adapter: Option<PayloadAdapter>) -> Result<Vec<Event>, SensorError> {
+        let headers = self.headers();
+        let mut events = match adapter {
+            Some(adapter) => {
+                // adapters look up header names in lowercase, like HTTP headers
+                let text = headers
+                    .iter()
+                    .filter_map(|(name, value)| {
+                        let text = match value {
+                            Value::String(text) => text.clone(),
+                            Value::Int(value) => value.to_string(),
+                            Value::Float(value) => value.to_string(),
+                            Value::Bool(value) => value.to_string(),
+                            _ => return None,
+                        };
+                        Some((name.to_ascii_lowercase(), text))
+                    })
+                    .collect();
+                adapter.normalize(self.body, &text)?
+            }
+            None => vec![self.event()?],
+        };
+
+        for event in &mut events {
+            event
+                .fields
+                .insert("exchange".to_string(), Value::from(self.exchange));
+            event
+                .fields
+                .insert("routing_key".to_string(), Value::from(self.routing_key));
+            if !headers.is_empty() {
+                event
+                    .fields
+                    .insert("headers".to_string(), Value::Map(headers.clone()));
+            }
+            if let Some(message_id) = self.properties.message_id() {
+                event
+                    .fields
+                    .insert("message_id".to_string(), Value::from(message_id.as_str()));
+            }
+        }
+        Ok(events)
+    }
+
+    /// Event of a message without adapter, the body has to be a JSON object
+    fn event(&self) -> Result<Event, SensorError> {
+        let fields: HashMap<String, Value> =
+            serde_json::from_slice(self.body).map_err(|e| SensorError::Payload(e.to_string()))?;
+
+        let system = self
+            .properties
+            .app_id()
+            .as_ref()
+            .map_or_else(|| "amqp".to_string(), |app_id| app_id.to_string());
+        let source_id = Some(self.routing_key)
+            .filter(|routing_key| !routing_key.is_empty())
+            .map(str::to_string);
+        let mut builder = EventBuilder::new();
+        builder.with_source(Source { system, source_id });
+        if let Some(correlation_id) = self
+            .properties
+            .correlation_id()
+            .as_ref()
+            .and_then(|id| Uuid::parse_str(id.as_str()).ok())
+        {
+            builder.with_correlation_id(correlation_id);
+        }
+        for (key, value) in fields {
+            builder.with_field(&key, value);
+        }
+        if let Some(time) = self
+            .properties
+            .timestamp()
+            .and_then(|seconds| DateTime::from_timestamp(seconds as i64, 0))
+        {
+            builder.with_text_field("timestamp", &time.to_rfc3339());
+        }
+        Ok(builder.build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lapin::types::{DecimalValue, FieldArray, ShortString};
+    use loid_events::prelude::Impact;
+
+    fn message<'a>(properties: &'a BasicProperties, body: &'a [u8]) -> Message<'a> {
+        Message {
+            exchange: "alerts",
+            routing_key: "alerts.db.critical",
+            properties,
+            body,
+        }
+    }
+
+    #[test]
+    fn test_headers_become_values() {
+        let mut table = FieldTable::default();
+        table.insert("retries".into(), AMQPValue::LongInt(3));
+        table.insert("host".into(), AMQPValue::LongString("db-1".into()));
+        table.insert(
+            "load".into(),
+            AMQPValue::DecimalValue(DecimalValue {
+                scale: 2,
+                value: 125,
+            }),
+        );
+        table.insert(
+            "tags".into(),
+            AMQPValue::FieldArray(FieldArray::from(vec![AMQPValue::ShortString(
+                ShortString::from("prod"),
+            )])),
+        );
+        let headers = headers(&table);
+        assert_eq!(headers["retries"], Value::Int(3));
+        assert_eq!(headers["host"], Value::from("db-1"));
+        assert_eq!(headers["load"], Value::Float(1.25));
+        assert_eq!(headers["tags"], Value::List(vec![Value::from("prod")]));
+    }
+
+    #[test]
+    fn test_normalize_json_object() {
+        let mut table = FieldTable::default();
+        table.insert("team".into(), AMQPValue::LongString("dba".into()));
+        let correlation_id = Uuid::new_v4();
+        let properties = BasicProperties::default()
+            .with_app_id("billing".into())
+            .with_message_id("m-1".into())
+            .with_correlation_id(correlation_id.to_string().as_str().into())
+            .with_headers(table);
+
+        let events = message(&properties, br#"{"message": "replication lag", "lag": 42}"#)
+            .normalize(None)
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.source.system, "billing");
+        assert_eq!(
+            event.source.source_id.as_deref(),
+            Some("alerts.db.critical")
+        );
+        assert_eq!(event.correlation_id, Some(correlation_id));
+        assert_eq!(event.fields["lag"], Value::Int(42));
+        assert_eq!(event.fields["exchange"], Value::from("alerts"));
+        assert_eq!(
+            event.fields["routing_key"],
+            Value::from("alerts.db.critical")
+        );
+        assert_eq!(event.fields["message_id"], Value::from("m-1"));
+        let Value::Map(headers) = &event.fields["headers"] else {
+            panic!("headers are not a map: {:?}", event.fields["headers"]);
+        };
+        assert_eq!(headers["team"], Value::from("dba"));
+
+        let properties = BasicProperties::default();
+        assert!(matches!(
+            message(&properties, b"[1, 2]").normalize(None),
+            Err(SensorError::Payload(_))
+        ));
+    }
+
+    #[test]
+    fn test_normalize_with_adapter() {
+        let mut table = FieldTable::default();
+        table.insert(
+            "X-GitHub-Event".into(),
+            AMQPValue::LongString("deployment_status".into()),
+        );
+        let properties = BasicProperties::default().with_headers(table);
+        let body = br#"{
+            "deployment_status": {"state": "failure"},
+            "deployment": {"environment": "production"},
+            "repository": {"full_name": "loid-labs/loid"}
+        }"#;
+        let events = message(&properties, body)
+            .normalize(Some(PayloadAdapter::Github))
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].source.system, "github");
+        assert_eq!(events[0].impact, Impact::SIGNIFICANT);
+        assert_eq!(
+            events[0].fields["routing_key"],
+            Value::from("alerts.db.critical")
+        );
+    }
+}
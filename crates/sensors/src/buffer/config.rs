@@ -0,0 +1,88 @@
+use crate::SensorError;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Configuration of the buffer between a sensor and the engine
+#[derive(Debug, Clone, Deserialize)]
+pub struct BufferConfig {
+    /// Events held in memory
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+    /// What happens to new events while the buffer is full
+    #[serde(default)]
+    pub overflow: OverflowPolicy,
+    /// Segment log events are spilled to, required by [`OverflowPolicy::Spill`]
+    #[serde(default)]
+    pub spill: Option<SpillConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// The sensor waits until there is room again
+    #[default]
+    Block,
+    /// The oldest buffered event is discarded to make room
+    DropOldest,
+    /// The new event is discarded
+    DropNewest,
+    /// Events are written to disk and read back in order once there is room again
+    Spill,
+}
+
+/// Location and limits of the on-disk segment log
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpillConfig {
+    /// Directory of the segment files, must not be shared between sensors
+    pub directory: PathBuf,
+    /// A new segment is started once the current one reaches this many bytes
+    #[serde(default = "default_segment_size")]
+    pub segment_size: u64,
+    /// Events are dropped once all segments together reach this many bytes
+    #[serde(default = "default_max_size")]
+    pub max_size: u64,
+}
+
+fn default_capacity() -> usize {
+    1024
+}
+
+fn default_segment_size() -> u64 {
+    16 * 1024 * 1024
+}
+
+fn default_max_size() -> u64 {
+    1024 * 1024 * 1024
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_capacity(),
+            overflow: OverflowPolicy::default(),
+            spill: None,
+        }
+    }
+}
+
+impl BufferConfig {
+    /// Checks the configuration for values the buffer cannot work with
+    pub fn validate(&self) -> Result<(), SensorError> {
+        if self.capacity == 0 {
+            return Err(SensorError::Config(
+                "buffer capacity must be greater than 0".to_string(),
+            ));
+        }
+        match (&self.overflow, &self.spill) {
+            (OverflowPolicy::Spill, None) => Err(SensorError::Config(
+                "overflow policy spill requires a spill directory".to_string(),
+            )),
+            (_, Some(spill)) if spill.segment_size == 0 || spill.max_size < spill.segment_size => {
+                Err(SensorError::Config(
+                    "spill segment_size must be greater than 0 and not exceed max_size".to_string(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{EventSink, SendOutcome};
+use loid_events::prelude::Event;
+use std::time::Duration;
+
+/// What became of the events of a message consumed from a broker
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Handoff {
+    /// All events were handed off to the sink
+    Delivered,
+    /// Some events were handed off and the buffer dropped the given number of the others, a
+    /// redelivery would send the handed off ones a second time
+    Lost(usize),
+    /// The buffer dropped the first event, nothing of the message was handed off
+    Dropped,
+    /// The sink is closed
+    Closed,
+}
+
+/// Hands the events of one message off to the sink, stops at the first event if the buffer
+/// drops it so the message can be redelivered as a whole
+pub(crate) async fn hand_off(sink: &EventSink, events: Vec<Event>) -> Handoff {
+    let mut handed_off = false;
+    let mut lost = 0;
+    for event in events {
+        match sink.send(event).await {
+            Ok(SendOutcome::Dropped) if !handed_off => return Handoff::Dropped,
+            Ok(SendOutcome::Dropped) => lost += 1,
+            Ok(_) => handed_off = true,
+            Err(_) => return Handoff::Closed,
+        }
+    }
+    if lost > 0 {
+        Handoff::Lost(lost)
+    } else {
+        Handoff::Delivered
+    }
+}
+
+/// Delay before a message the buffer dropped is handed back to the broker, doubling while the
+/// buffer stays full so the message is not redelivered in a tight loop
+#[derive(Debug, Default)]
+pub(crate) struct Redelivery {
+    attempts: u32,
+}
+
+impl Redelivery {
+    const INITIAL: Duration = Duration::from_millis(100);
+    const MAX: Duration = Duration::from_secs(5);
+
+    /// The delay before the next message is handed back
+    pub fn delay(&mut self) -> Duration {
+        let delay = Self::INITIAL.saturating_mul(1 << self.attempts.min(16));
+        self.attempts += 1;
+        delay.min(Self::MAX)
+    }
+
+    /// The buffer took a message again
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StatusHandle;
+    use crate::buffer::{BufferConfig, OverflowPolicy};
+    use loid_events::prelude::{EventBuilder, Value};
+
+    fn event(message: &str) -> Event {
+        EventBuilder::new()
+            .with_text_field("message", message)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_hand_off_only_drops_untouched_messages() {
+        let config = BufferConfig {
+            capacity: 2,
+            overflow: OverflowPolicy::DropNewest,
+            spill: None,
+        };
+        let (sink, mut receiver) = EventSink::buffered(&config, StatusHandle::default()).unwrap();
+
+        let handoff = hand_off(&sink, vec![event("a"), event("b"), event("c"), event("d")]).await;
+        assert_eq!(handoff, Handoff::Lost(2));
+        assert_eq!(hand_off(&sink, vec![event("e")]).await, Handoff::Dropped);
+
+        receiver.recv().await.unwrap();
+        receiver.recv().await.unwrap();
+        assert_eq!(hand_off(&sink, vec![event("e")]).await, Handoff::Delivered);
+        let received = receiver.recv().await.unwrap();
+        assert_eq!(received.fields.get("message"), Some(&Value::from("e")));
+
+        drop(receiver);
+        assert_eq!(hand_off(&sink, vec![event("f")]).await, Handoff::Closed);
+    }
+
+    #[test]
+    fn test_redelivery_backs_off() {
+        let mut redelivery = Redelivery::default();
+        assert_eq!(redelivery.delay(), Duration::from_millis(100));
+        assert_eq!(redelivery.delay(), Duration::from_millis(200));
+        assert_eq!(redelivery.delay(), Duration::from_millis(400));
+        for _ in 0..40 {
+            redelivery.delay();
+        }
+        assert_eq!(redelivery.delay(), Duration::from_secs(5));
+        redelivery.reset();
+        assert_eq!(redelivery.delay(), Duration::from_millis(100));
+    }
+}
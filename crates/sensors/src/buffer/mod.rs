@@ -0,0 +1,24 @@
+//! Bounded buffer between a sensor and the engine.
+//!
+//! Every sensor gets its own buffer, configured next to the `sensor` section:
+//!
+//! ```yaml
+//! buffer:
+//!     capacity: 10000
+//!     overflow: spill
+//!     spill:
+//!         directory: /var/lib/loid/spill/syslog
+//!         segment_size: 16777216
+//!         max_size: 1073741824
+//! ```
+//!
+//! While the buffer is full the sensor is reported as
+//! [`Throttled`](crate::HealthState::Throttled) until it drained to half its capacity.
+
+pub(crate) mod config;
+pub(crate) mod handoff;
+pub(crate) mod spill;
+
+pub use config::{BufferConfig, OverflowPolicy, SpillConfig};
+pub(crate) use handoff::{Handoff, Redelivery, hand_off};
+pub(crate) use spill::SegmentLog;
@@ -0,0 +1,299 @@
+use crate::buffer::SpillConfig;
+use loid_events::prelude::Event;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+
+const EXTENSION: &str = "segment";
+
+#[derive(Debug)]
+struct Segment {
+    sequence: u64,
+    path: PathBuf,
+    /// Bytes written to the file
+    size: u64,
+    /// Events not read yet
+    events: usize,
+}
+
+/// Append-only log of events spread over numbered segment files.
+///
+/// Every record is the length of the JSON encoded event as little endian `u32` followed by the
+/// event. Fully read segments are deleted, segments left over by a previous run are read first.
+#[derive(Debug)]
+pub(crate) struct SegmentLog {
+    config: SpillConfig,
+    segments: VecDeque<Segment>,
+    /// Appends to the last segment
+    writer: Option<BufWriter<File>>,
+    /// Reads from the first segment
+    reader: Option<BufReader<File>>,
+    events: usize,
+    size: u64,
+}
+
+/// Reads the next record, `None` at the end of the file or on an incomplete record
+fn read_record(reader: &mut impl Read) -> std::io::Result<Option<Vec<u8>>> {
+    let mut length = [0u8; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut record = vec![0u8; u32::from_le_bytes(length) as usize];
+    match reader.read_exact(&mut record) {
+        Ok(()) => Ok(Some(record)),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl SegmentLog {
+    /// Opens the log in the configured directory and recovers segments of a previous run
+    pub fn open(config: SpillConfig) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&config.directory)?;
+        let mut segments = Vec::new();
+        for entry in std::fs::read_dir(&config.directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            let Some(sequence) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse().ok())
+            else {
+                continue;
+            };
+            segments.push(Self::recover(sequence, path)?);
+        }
+        segments.sort_by_key(|segment| segment.sequence);
+        segments
+            .retain(|segment| segment.events > 0 || std::fs::remove_file(&segment.path).is_err());
+
+        Ok(Self {
+            events: segments.iter().map(|segment| segment.events).sum(),
+            size: segments.iter().map(|segment| segment.size).sum(),
+            segments: segments.into(),
+            config,
+            writer: None,
+            reader: None,
+        })
+    }
+
+    /// Counts the complete records of a segment and cuts off an incomplete last record
+    fn recover(sequence: u64, path: PathBuf) -> std::io::Result<Segment> {
+        let mut reader = BufReader::new(File::open(&path)?);
+        let mut events = 0;
+        let mut size = 0;
+        while let Some(record) = read_record(&mut reader)? {
+            events += 1;
+            size += 4 + record.len() as u64;
+        }
+        let file = OpenOptions::new().write(true).open(&path)?;
+        if file.metadata()?.len() != size {
+            file.set_len(size)?;
+        }
+        Ok(Segment {
+            sequence,
+            path,
+            size,
+            events,
+        })
+    }
+
+    /// Number of events not read yet
+    pub fn len(&self) -> usize {
+        self.events
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events == 0
+    }
+
+    /// Appends an event, `false` if the log reached its maximum size
+    pub fn push(&mut self, event: &Event) -> std::io::Result<bool> {
+        let record = serde_json::to_vec(event).map_err(std::io::Error::other)?;
+        let length = 4 + record.len() as u64;
+        if self.size + length > self.config.max_size {
+            return Ok(false);
+        }
+
+        let rotate = match self.segments.back() {
+            Some(segment) => segment.size > 0 && segment.size + length > self.config.segment_size,
+            None => true,
+        };
+        if rotate || self.writer.is_none() {
+            self.writer = None;
+            if rotate {
+                let sequence = self.segments.back().map_or(0, |s| s.sequence + 1);
+                let path = self
+                    .config
+                    .directory
+                    .join(format!("{sequence:020}.{EXTENSION}"));
+                self.segments.push_back(Segment {
+                    sequence,
+                    path,
+                    size: 0,
+                    events: 0,
+                });
+            }
+            let segment = self.segments.back().expect("segment was just added");
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&segment.path)?;
+            self.writer = Some(BufWriter::new(file));
+        }
+
+        let writer = self.writer.as_mut().expect("writer was just opened");
+        writer.write_all(&(record.len() as u32).to_le_bytes())?;
+        writer.write_all(&record)?;
+        // the reader has to see the record as soon as it is counted
+        writer.flush()?;
+
+        let segment = self.segments.back_mut().expect("segment was just added");
+        segment.size += length;
+        segment.events += 1;
+        self.size += length;
+        self.events += 1;
+        Ok(true)
+    }
+
+    /// Removes the oldest event, records that cannot be decoded are skipped
+    pub fn pop(&mut self) -> std::io::Result<Option<Event>> {
+        while let Some(segment) = self.segments.front_mut() {
+            if segment.events == 0 {
+                self.remove_front()?;
+                continue;
+            }
+            if self.reader.is_none() {
+                self.reader = Some(BufReader::new(File::open(&segment.path)?));
+            }
+            let reader = self.reader.as_mut().expect("reader was just opened");
+            let record = read_record(reader)?;
+            segment.events -= 1;
+            self.events -= 1;
+            if segment.events == 0 {
+                self.remove_front()?;
+            }
+            match record.map(|record| serde_json::from_slice(&record)) {
+                Some(Ok(event)) => return Ok(Some(event)),
+                Some(Err(e)) => tracing::warn!(error = %e, "skipping undecodable spilled event"),
+                None => tracing::warn!("spilled segment ended unexpectedly"),
+            }
+        }
+        Ok(None)
+    }
+
+    fn remove_front(&mut self) -> std::io::Result<()> {
+        let Some(segment) = self.segments.pop_front() else {
+            return Ok(());
+        };
+        self.reader = None;
+        if self.segments.is_empty() {
+            self.writer = None;
+        }
+        self.size -= segment.size;
+        std::fs::remove_file(&segment.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use loid_events::prelude::{EventBuilder, Value};
+    use uuid::Uuid;
+
+    fn config(segment_size: u64, max_size: u64) -> SpillConfig {
+        SpillConfig {
+            directory: std::env::temp_dir().join(format!("loid-spill-{}", Uuid::new_v4())),
+            segment_size,
+            max_size,
+        }
+    }
+
+    fn event(number: i64) -> Event {
+        EventBuilder::new().with_int_field("number", number).build()
+    }
+
+    fn segment_files(config: &SpillConfig) -> usize {
+        std::fs::read_dir(&config.directory).unwrap().count()
+    }
+
+    #[test]
+    fn test_push_and_pop_in_order_across_segments() {
+        let config = config(512, 1024 * 1024);
+        let mut log = SegmentLog::open(config.clone()).unwrap();
+        for number in 0..10 {
+            assert!(log.push(&event(number)).unwrap());
+        }
+        assert_eq!(log.len(), 10);
+        assert!(segment_files(&config) > 1);
+
+        for number in 0..5 {
+            let event = log.pop().unwrap().unwrap();
+            assert_eq!(event.fields.get("number"), Some(&Value::Int(number)));
+        }
+        log.push(&event(10)).unwrap();
+        for number in 5..=10 {
+            let event = log.pop().unwrap().unwrap();
+            assert_eq!(event.fields.get("number"), Some(&Value::Int(number)));
+        }
+        assert!(log.pop().unwrap().is_none());
+        assert!(log.is_empty());
+        assert_eq!(segment_files(&config), 0);
+        std::fs::remove_dir_all(&config.directory).unwrap();
+    }
+
+    #[test]
+    fn test_push_stops_at_max_size() {
+        let config = config(512, 1024);
+        let mut log = SegmentLog::open(config.clone()).unwrap();
+        let mut pushed = 0;
+        while log.push(&event(pushed)).unwrap() {
+            pushed += 1;
+        }
+        assert!(pushed > 0);
+        assert_eq!(log.len(), pushed as usize);
+        std::fs::remove_dir_all(&config.directory).unwrap();
+    }
+
+    #[test]
+    fn test_open_recovers_previous_segments() {
+        let config = config(1024 * 1024, 1024 * 1024);
+        let mut log = SegmentLog::open(config.clone()).unwrap();
+        log.push(&event(1)).unwrap();
+        log.push(&event(2)).unwrap();
+        drop(log);
+
+        // a record cut off by a crash
+        let path = std::fs::read_dir(&config.directory)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&100u32.to_le_bytes()).unwrap();
+        file.write_all(b"{\"id\"").unwrap();
+        drop(file);
+
+        let mut log = SegmentLog::open(config.clone()).unwrap();
+        assert_eq!(log.len(), 2);
+        log.push(&event(3)).unwrap();
+        let numbers: Vec<_> = std::iter::from_fn(|| log.pop().unwrap())
+            .map(|event| event.fields.get("number").cloned())
+            .collect();
+        assert_eq!(
+            numbers,
+            vec![
+                Some(Value::Int(1)),
+                Some(Value::Int(2)),
+                Some(Value::Int(3))
+            ]
+        );
+        std::fs::remove_dir_all(&config.directory).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+use crate::SensorError;
+use crate::command::CheckState;
+use loid_events::prelude::Secret;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Configuration of a [`CommandSensor`](crate::command::CommandSensor)
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandSensorConfig {
+    /// Program and arguments to execute, no shell is involved
+    pub command: Vec<String>,
+    /// Additional environment variables passed to the command, e.g. credentials
+    #[serde(default)]
+    pub env: HashMap<String, Secret>,
+    /// Time between two checks while the state is hard
+    #[serde(with = "humantime_serde", default = "default_interval")]
+    pub interval: Duration,
+    /// Time between two checks while a problem is not confirmed yet, defaults to `interval`
+    #[serde(with = "humantime_serde", default)]
+    pub retry_interval: Option<Duration>,
+    /// Maximum runtime of a single check before it is killed
+    #[serde(with = "humantime_serde", default = "default_timeout")]
+    pub timeout: Duration,
+    /// State reported for a check that exceeded its timeout
+    #[serde(default = "default_timeout_state")]
+    pub timeout_state: CheckState,
+    /// Number of consecutive non-OK results required before a problem becomes hard
+    #[serde(default = "default_max_check_attempts")]
+    pub max_check_attempts: u32,
+    /// Emit events for soft state changes as well, not only for hard ones
+    #[serde(default)]
+    pub emit_soft_states: bool,
+}
+
+fn default_interval() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_timeout_state() -> CheckState {
+    CheckState::Unknown
+}
+
+fn default_max_check_attempts() -> u32 {
+    3
+}
+
+impl CommandSensorConfig {
+    /// Interval used while a problem is in a soft state
+    pub fn retry_interval(&self) -> Duration {
+        self.retry_interval.unwrap_or(self.interval)
+    }
+
+    /// Checks the configuration for values the sensor cannot work with
+    pub fn validate(&self) -> Result<(), SensorError> {
+        if self.command.is_empty() || self.command[0].is_empty() {
+            return Err(SensorError::Config("command must not be empty".to_string()));
+        }
+        if self.max_check_attempts == 0 {
+            return Err(SensorError::Config(
+                "max_check_attempts must be at least 1".to_string(),
+            ));
+        }
+        if self.interval.is_zero() || self.retry_interval().is_zero() {
+            return Err(SensorError::Config(
+                "interval and retry_interval must be greater than zero".to_string(),
+            ));
+        }
+        if self.timeout.is_zero() {
+            return Err(SensorError::Config(
+                "timeout must be greater than zero".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,21 @@
+//! Command-execution check sensor compatible with Nagios/Icinga plugins.
+//!
+//! ```yaml
+//! sensor:
+//!     type: command
+//!     command: [ "/usr/lib/nagios/plugins/check_disk", "-w", "20%", "-c", "10%", "-p", "/" ]
+//!     interval: 5m
+//!     retry_interval: 1m
+//!     timeout: 30s
+//!     max_check_attempts: 3
+//! ```
+
+pub(crate) mod config;
+pub(crate) mod output;
+pub(crate) mod sensor;
+pub(crate) mod state;
+
+pub use config::CommandSensorConfig;
+pub use output::{PerfData, PluginOutput};
+pub use sensor::CommandSensor;
+pub use state::{CheckState, StateTracker, StateTransition, StateType};
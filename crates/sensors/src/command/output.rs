@@ -0,0 +1,300 @@
+use loid_events::prelude::Value;
+use std::collections::HashMap;
+
+/// Output of a plugin split into its parts.
+///
+/// Plugins print `TEXT | PERFDATA` on the first line, optionally followed by long text lines. The
+/// first `|` found in the long text starts additional perfdata which spans all remaining lines.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PluginOutput {
+    /// Text of the first line
+    pub text: String,
+    /// All further text lines
+    pub long_text: String,
+    /// Parsed performance data of all lines
+    pub perfdata: Vec<PerfData>,
+}
+
+impl PluginOutput {
+    /// Parses the stdout of a plugin
+    pub fn parse(stdout: &str) -> Self {
+        let mut lines = stdout.lines();
+        let mut perf = Vec::new();
+
+        let first = lines.next().unwrap_or_default();
+        let text = match first.split_once('|') {
+            Some((text, data)) => {
+                perf.push(data);
+                text
+            }
+            None => first,
+        };
+
+        let mut long_text = Vec::new();
+        let mut in_perfdata = false;
+        for line in lines {
+            if in_perfdata {
+                perf.push(line);
+            } else if let Some((text, data)) = line.split_once('|') {
+                long_text.push(text);
+                perf.push(data);
+                in_perfdata = true;
+            } else {
+                long_text.push(line);
+            }
+        }
+
+        Self {
+            text: text.trim().to_string(),
+            long_text: long_text.join("\n").trim().to_string(),
+            perfdata: perf.into_iter().flat_map(PerfData::parse_all).collect(),
+        }
+    }
+
+    /// Returns the perfdata as a map of labels to their values
+    pub fn perfdata_value(&self) -> Value {
+        Value::Map(
+            self.perfdata
+                .iter()
+                .map(|data| (data.label.clone(), data.to_value()))
+                .collect(),
+        )
+    }
+}
+
+/// A single perfdata entry in the form `'label'=value[UOM];[warn];[crit];[min];[max]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerfData {
+    pub label: String,
+    /// `None` if the plugin reported the value as undetermined (`U`)
+    pub value: Option<f64>,
+    pub uom: Option<String>,
+    pub warn: Option<String>,
+    pub crit: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl PerfData {
+    /// Parses all space separated entries of a perfdata string, skipping malformed ones
+    pub fn parse_all(data: &str) -> Vec<PerfData> {
+        split_entries(data)
+            .iter()
+            .filter_map(|entry| PerfData::parse(entry))
+            .collect()
+    }
+
+    /// Parses a single perfdata entry
+    pub fn parse(entry: &str) -> Option<PerfData> {
+        let (label, rest) = split_label(entry)?;
+        let mut parts = rest.split(';');
+
+        let raw_value = parts.next()?.trim();
+        let (value, uom) = if raw_value == "U" {
+            (None, None)
+        } else {
+            let (value, uom) = split_number(raw_value)?;
+            (Some(value), (!uom.is_empty()).then(|| uom.to_string()))
+        };
+
+        let mut next = || {
+            parts
+                .next()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        let warn = next();
+        let crit = next();
+        let min = next().and_then(|s| s.parse().ok());
+        let max = next().and_then(|s| s.parse().ok());
+
+        Some(PerfData {
+            label,
+            value,
+            uom,
+            warn,
+            crit,
+            min,
+            max,
+        })
+    }
+
+    /// Converts the entry into a map value, leaving out everything that is not set
+    pub fn to_value(&self) -> Value {
+        let mut map = HashMap::new();
+        if let Some(value) = self.value {
+            map.insert("value".to_string(), Value::Float(value));
+        }
+        if let Some(uom) = &self.uom {
+            map.insert("uom".to_string(), Value::String(uom.clone()));
+        }
+        if let Some(warn) = &self.warn {
+            map.insert("warn".to_string(), Value::String(warn.clone()));
+        }
+        if let Some(crit) = &self.crit {
+            map.insert("crit".to_string(), Value::String(crit.clone()));
+        }
+        if let Some(min) = self.min {
+            map.insert("min".to_string(), Value::Float(min));
+        }
+        if let Some(max) = self.max {
+            map.insert("max".to_string(), Value::Float(max));
+        }
+        Value::Map(map)
+    }
+}
+
+/// Splits perfdata at whitespace that is not part of a quoted label
+fn split_entries(data: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in data.chars() {
+        match c {
+            '\'' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    entries.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        entries.push(current);
+    }
+    entries
+}
+
+/// Splits a value like `12.5ms` into its longest numeric prefix and the unit of measurement
+fn split_number(raw: &str) -> Option<(f64, &str)> {
+    let end = raw
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')))
+        .unwrap_or(raw.len());
+    // an exponent marker might belong to the unit (e.g. `EB`), so back off until it parses
+    (1..=end)
+        .rev()
+        .find_map(|i| raw[..i].parse::<f64>().ok().map(|value| (value, &raw[i..])))
+}
+
+/// Splits an entry into its label and the remaining `value;warn;...` part.
+///
+/// Quoted labels may contain `=` and use `''` to encode a literal quote.
+fn split_label(entry: &str) -> Option<(String, &str)> {
+    if let Some(quoted) = entry.strip_prefix('\'') {
+        let mut label = String::new();
+        let mut chars = quoted.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c != '\'' {
+                label.push(c);
+            } else if matches!(chars.peek(), Some((_, '\''))) {
+                label.push('\'');
+                chars.next();
+            } else {
+                let rest = quoted[i + 1..].strip_prefix('=')?;
+                return (!label.is_empty()).then_some((label, rest));
+            }
+        }
+        None
+    } else {
+        let (label, rest) = entry.split_once('=')?;
+        (!label.is_empty()).then(|| (label.to_string(), rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_text_only() {
+        let output = PluginOutput::parse("DISK OK - free space: / 3326 MB (56%);\n");
+        assert_eq!(output.text, "DISK OK - free space: / 3326 MB (56%);");
+        assert!(output.long_text.is_empty());
+        assert!(output.perfdata.is_empty());
+    }
+
+    #[test]
+    fn test_parse_text_and_perfdata() {
+        let output = PluginOutput::parse(
+            "PING OK - Packet loss = 0%, RTA = 0.80 ms | rta=0.800000ms;100.000000;500.000000;0.000000 pl=0%;20;60;0",
+        );
+        assert_eq!(output.text, "PING OK - Packet loss = 0%, RTA = 0.80 ms");
+        assert_eq!(output.perfdata.len(), 2);
+
+        let rta = &output.perfdata[0];
+        assert_eq!(rta.label, "rta");
+        assert_eq!(rta.value, Some(0.8));
+        assert_eq!(rta.uom.as_deref(), Some("ms"));
+        assert_eq!(rta.warn.as_deref(), Some("100.000000"));
+        assert_eq!(rta.crit.as_deref(), Some("500.000000"));
+        assert_eq!(rta.min, Some(0.0));
+        assert_eq!(rta.max, None);
+
+        let pl = &output.perfdata[1];
+        assert_eq!(pl.label, "pl");
+        assert_eq!(pl.value, Some(0.0));
+        assert_eq!(pl.uom.as_deref(), Some("%"));
+    }
+
+    #[test]
+    fn test_parse_long_text_with_perfdata() {
+        let stdout = "DISK OK | /=2643MB;5948;5958;0;5968\n\
+                      / 15272 MB (77%);\n\
+                      /boot 68 MB (69%); | /boot=68MB;88;93;0;98\n\
+                      /home=69357MB;253404;253409;0;253414";
+        let output = PluginOutput::parse(stdout);
+
+        assert_eq!(output.text, "DISK OK");
+        assert_eq!(output.long_text, "/ 15272 MB (77%);\n/boot 68 MB (69%);");
+        let labels: Vec<_> = output.perfdata.iter().map(|p| p.label.as_str()).collect();
+        assert_eq!(labels, vec!["/", "/boot", "/home"]);
+        assert_eq!(output.perfdata[2].max, Some(253414.0));
+    }
+
+    #[test]
+    fn test_parse_quoted_labels() {
+        let data = PerfData::parse_all("'used space'=10GB;;;0;20 'it''s=odd'=1c");
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0].label, "used space");
+        assert_eq!(data[0].value, Some(10.0));
+        assert_eq!(data[0].uom.as_deref(), Some("GB"));
+        assert_eq!(data[0].warn, None);
+        assert_eq!(data[0].max, Some(20.0));
+        assert_eq!(data[1].label, "it's=odd");
+        assert_eq!(data[1].uom.as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn test_parse_undetermined_and_malformed() {
+        let data = PerfData::parse_all("a=U b=notanumber =5 c=-1.5e2s d=2EB");
+        assert_eq!(data.len(), 3);
+        assert_eq!(data[0].label, "a");
+        assert_eq!(data[0].value, None);
+        assert_eq!(data[1].label, "c");
+        assert_eq!(data[1].value, Some(-150.0));
+        assert_eq!(data[1].uom.as_deref(), Some("s"));
+        assert_eq!(data[2].value, Some(2.0));
+        assert_eq!(data[2].uom.as_deref(), Some("EB"));
+    }
+
+    #[test]
+    fn test_perfdata_value() {
+        let output = PluginOutput::parse("OK | load=1.5;5;10");
+        let Value::Map(map) = output.perfdata_value() else {
+            panic!("Expected map value");
+        };
+        let Some(Value::Map(load)) = map.get("load") else {
+            panic!("Expected load entry");
+        };
+        assert_eq!(load.get("value"), Some(&Value::Float(1.5)));
+        assert_eq!(load.get("warn"), Some(&Value::String("5".to_string())));
+        assert_eq!(load.get("crit"), Some(&Value::String("10".to_string())));
+        assert!(!load.contains_key("min"));
+    }
+}
@@ -0,0 +1,347 @@
+use crate::command::{
+    CheckState, CommandSensorConfig, PluginOutput, StateTracker, StateTransition, StateType,
+};
+use crate::{EventSink, HealthState, LifecycleState, Sensor, SensorError, StatusHandle};
+use chrono::Utc;
+use loid_events::prelude::{Event, EventBuilder, Source, Value};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use uuid::Uuid;
+
+/// Result of a single plugin execution
+#[derive(Debug, Clone)]
+pub(crate) struct CheckResult {
+    pub state: CheckState,
+    pub exit_code: Option<i32>,
+    pub output: PluginOutput,
+    pub duration: Duration,
+}
+
+/// Periodically executes a check command and emits an event whenever its state changes.
+pub struct CommandSensor {
+    key: String,
+    config: CommandSensorConfig,
+    status: StatusHandle,
+}
+
+impl CommandSensor {
+    pub fn new(key: impl Into<String>, config: CommandSensorConfig) -> Result<Self, SensorError> {
+        config.validate()?;
+        Ok(Self {
+            key: key.into(),
+            config,
+            status: StatusHandle::default(),
+        })
+    }
+
+    pub fn config(&self) -> &CommandSensorConfig {
+        &self.config
+    }
+
+    /// Executes the check command once
+    pub(crate) async fn check(&self) -> CheckResult {
+        let started = Instant::now();
+        let child = Command::new(&self.config.command[0])
+            .args(&self.config.command[1..])
+            .envs(
+                self.config
+                    .env
+                    .iter()
+                    .map(|(name, value)| (name, value.expose())),
+            )
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn();
+
+        let child = match child {
+            Ok(child) => child,
+            Err(error) => {
+                tracing::warn!(sensor = %self.key, %error, "failed to spawn check command");
+                self.status.set_health(HealthState::Failed);
+                return CheckResult {
+                    state: CheckState::Unknown,
+                    exit_code: None,
+                    output: PluginOutput {
+                        text: format!("failed to execute {}: {error}", self.config.command[0]),
+                        ..PluginOutput::default()
+                    },
+                    duration: started.elapsed(),
+                };
+            }
+        };
+
+        match tokio::time::timeout(self.config.timeout, child.wait_with_output()).await {
+            Ok(Ok(output)) => {
+                self.status.set_health(HealthState::Healthy);
+                let mut parsed = PluginOutput::parse(&String::from_utf8_lossy(&output.stdout));
+                if parsed.text.is_empty() {
+                    // plugins are supposed to print to stdout, but some only report on stderr
+                    parsed.text = String::from_utf8_lossy(&output.stderr).trim().to_string();
+                }
+                let exit_code = output.status.code();
+                CheckResult {
+                    state: exit_code.map_or(CheckState::Unknown, CheckState::from_exit_code),
+                    exit_code,
+                    output: parsed,
+                    duration: started.elapsed(),
+                }
+            }
+            Ok(Err(error)) => {
+                tracing::warn!(sensor = %self.key, %error, "failed to collect check output");
+                self.status.set_health(HealthState::Failed);
+                CheckResult {
+                    state: CheckState::Unknown,
+                    exit_code: None,
+                    output: PluginOutput {
+                        text: format!("failed to collect output: {error}"),
+                        ..PluginOutput::default()
+                    },
+                    duration: started.elapsed(),
+                }
+            }
+            Err(_) => {
+                // dropping the future kills the child process
+                tracing::warn!(sensor = %self.key, timeout = ?self.config.timeout, "check timed out");
+                self.status.set_health(HealthState::Degraded);
+                CheckResult {
+                    state: self.config.timeout_state,
+                    exit_code: None,
+                    output: PluginOutput {
+                        text: format!(
+                            "check timed out after {}",
+                            humantime::format_duration(self.config.timeout)
+                        ),
+                        ..PluginOutput::default()
+                    },
+                    duration: started.elapsed(),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn build_event(
+        &self,
+        result: &CheckResult,
+        transition: &StateTransition,
+        correlation_id: Option<Uuid>,
+        max_attempts: u32,
+    ) -> Event {
+        let mut builder = EventBuilder::new();
+        builder
+            .with_source(Source {
+                system: "command".to_string(),
+                source_id: Some(self.key.clone()),
+            })
+            .with_impact(transition.current.impact())
+            .with_urgency(transition.current.urgency())
+            .with_text_field("state", &transition.current.to_string())
+            .with_text_field("previous_state", &transition.previous.to_string())
+            .with_text_field("state_type", &transition.state_type.to_string())
+            .with_int_field("attempt", transition.attempt as i64)
+            .with_int_field("max_attempts", max_attempts as i64)
+            .with_text_field("output", &result.output.text)
+            .with_text_field("long_output", &result.output.long_text)
+            .with_field("perfdata", result.output.perfdata_value())
+            .with_int_field("duration_ms", result.duration.as_millis() as i64)
+            .with_field(
+                "exit_code",
+                result.exit_code.map_or(Value::None, |code| code.into()),
+            );
+
+        if let Some(correlation_id) = correlation_id {
+            builder.with_correlation_id(correlation_id);
+        }
+        if transition.is_recovery() {
+            builder.with_resolved_at(Utc::now());
+        }
+        builder.build()
+    }
+}
+
+impl Sensor for CommandSensor {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn status_handle(&self) -> &StatusHandle {
+        &self.status
+    }
+
+    async fn run(&self, sink: EventSink) -> Result<(), SensorError> {
+        self.status.set_lifecycle(LifecycleState::Running);
+
+        let mut tracker = StateTracker::new(self.config.max_check_attempts);
+        // links problem and recovery events of the same incident
+        let mut problem_id: Option<Uuid> = None;
+
+        loop {
+            let result = self.check().await;
+            tracing::debug!(sensor = %self.key, state = %result.state, output = %result.output.text, "check finished");
+
+            if let Some(transition) = tracker.record(result.state) {
+                if transition.previous.is_ok() {
+                    problem_id = Some(Uuid::now_v7());
+                }
+                let correlation_id = problem_id;
+                if transition.current.is_ok() {
+                    problem_id = None;
+                }
+
+                if transition.state_type == StateType::Hard || self.config.emit_soft_states {
+                    let event = self.build_event(
+                        &result,
+                        &transition,
+                        correlation_id,
+                        tracker.max_attempts(),
+                    );
+                    if sink.send(event).await.is_err() {
+                        break;
+                    }
+                }
+            }
+
+            let delay = match tracker.state_type() {
+                StateType::Soft => self.config.retry_interval(),
+                StateType::Hard => self.config.interval,
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = sink.closed() => break,
+            }
+        }
+
+        self.status.set_lifecycle(LifecycleState::Stopped);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(script: &str) -> CommandSensorConfig {
+        CommandSensorConfig {
+            command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            env: HashMap::new(),
+            interval: Duration::from_millis(10),
+            retry_interval: None,
+            timeout: Duration::from_secs(5),
+            timeout_state: CheckState::Unknown,
+            max_check_attempts: 1,
+            emit_soft_states: false,
+        }
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_config() {
+        let mut invalid = config("exit 0");
+        invalid.command.clear();
+        assert!(matches!(
+            CommandSensor::new("check", invalid),
+            Err(SensorError::Config(_))
+        ));
+
+        let mut invalid = config("exit 0");
+        invalid.max_check_attempts = 0;
+        assert!(CommandSensor::new("check", invalid).is_err());
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config: CommandSensorConfig =
+            serde_json::from_str(r#"{"command": ["check_ping"], "retry_interval": "30s"}"#)
+                .unwrap();
+        assert_eq!(config.interval, Duration::from_secs(300));
+        assert_eq!(config.retry_interval(), Duration::from_secs(30));
+        assert_eq!(config.timeout, Duration::from_secs(60));
+        assert_eq!(config.timeout_state, CheckState::Unknown);
+        assert_eq!(config.max_check_attempts, 3);
+        assert!(!config.emit_soft_states);
+    }
+
+    #[tokio::test]
+    async fn test_check_parses_exit_code_and_output() {
+        let sensor = CommandSensor::new(
+            "check",
+            config("echo 'LOAD WARNING | load1=4.2;4;8;0'; echo 'details'; exit 1"),
+        )
+        .unwrap();
+
+        let result = sensor.check().await;
+        assert_eq!(result.state, CheckState::Warning);
+        assert_eq!(result.exit_code, Some(1));
+        assert_eq!(result.output.text, "LOAD WARNING");
+        assert_eq!(result.output.long_text, "details");
+        assert_eq!(result.output.perfdata[0].value, Some(4.2));
+        assert_eq!(sensor.status().health, HealthState::Healthy);
+    }
+
+    #[tokio::test]
+    async fn test_check_timeout() {
+        let mut config = config("sleep 5");
+        config.timeout = Duration::from_millis(50);
+        config.timeout_state = CheckState::Critical;
+        let sensor = CommandSensor::new("check", config).unwrap();
+
+        let result = sensor.check().await;
+        assert_eq!(result.state, CheckState::Critical);
+        assert_eq!(result.exit_code, None);
+        assert!(result.output.text.contains("timed out"));
+        assert_eq!(sensor.status().health, HealthState::Degraded);
+    }
+
+    #[tokio::test]
+    async fn test_check_spawn_failure() {
+        let mut config = config("");
+        config.command = vec!["/nonexistent/check_plugin".to_string()];
+        let sensor = CommandSensor::new("check", config).unwrap();
+
+        let result = sensor.check().await;
+        assert_eq!(result.state, CheckState::Unknown);
+        assert_eq!(sensor.status().health, HealthState::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_run_emits_only_state_changes() {
+        let dir = std::env::temp_dir().join(format!("loid-command-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let counter = dir.join("count");
+        // critical for the 2nd and 3rd execution, OK otherwise
+        let script = format!(
+            "n=$(cat {c} 2>/dev/null || echo 0); n=$((n+1)); echo $n > {c}; \
+             if [ $n -eq 2 ] || [ $n -eq 3 ]; then echo 'CRITICAL | v=9'; exit 2; fi; echo OK; exit 0",
+            c = counter.display()
+        );
+        let sensor = CommandSensor::new("my-check", config(&script)).unwrap();
+        let (sink, mut receiver) = EventSink::channel(8);
+
+        let handle = tokio::spawn(async move { sensor.run(sink).await });
+
+        let problem = receiver.recv().await.unwrap();
+        assert_eq!(problem.source.system, "command");
+        assert_eq!(problem.source.source_id.as_deref(), Some("my-check"));
+        assert_eq!(problem.fields.get("state"), Some(&Value::from("CRITICAL")));
+        assert_eq!(problem.fields.get("state_type"), Some(&Value::from("HARD")));
+        assert_eq!(problem.fields.get("exit_code"), Some(&Value::Int(2)));
+        assert_eq!(problem.impact, loid_events::prelude::Impact::SIGNIFICANT);
+        assert!(problem.correlation_id.is_some());
+        assert!(problem.resolved_at.is_none());
+
+        let recovery = receiver.recv().await.unwrap();
+        assert_eq!(recovery.fields.get("state"), Some(&Value::from("OK")));
+        assert_eq!(
+            recovery.fields.get("previous_state"),
+            Some(&Value::from("CRITICAL"))
+        );
+        assert_eq!(recovery.correlation_id, problem.correlation_id);
+        assert!(recovery.resolved_at.is_some());
+
+        drop(receiver);
+        handle.await.unwrap().unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
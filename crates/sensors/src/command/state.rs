@@ -0,0 +1,279 @@
+use loid_events::prelude::{Impact, Urgency};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Result state of a check, derived from the plugin exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CheckState {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl CheckState {
+    /// Maps a plugin exit code, everything outside of `0..=3` is treated as unknown
+    pub fn from_exit_code(code: i32) -> Self {
+        match code {
+            0 => CheckState::Ok,
+            1 => CheckState::Warning,
+            2 => CheckState::Critical,
+            _ => CheckState::Unknown,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        *self == CheckState::Ok
+    }
+
+    pub fn impact(&self) -> Impact {
+        match self {
+            CheckState::Ok => Impact::NEGLIGIBLE,
+            CheckState::Warning => Impact::MODERATE,
+            CheckState::Critical => Impact::SIGNIFICANT,
+            CheckState::Unknown => Impact::MINOR,
+        }
+    }
+
+    pub fn urgency(&self) -> Urgency {
+        match self {
+            CheckState::Ok => Urgency::LOW,
+            CheckState::Warning => Urgency::MEDIUM,
+            CheckState::Critical => Urgency::HIGH,
+            CheckState::Unknown => Urgency::MEDIUM,
+        }
+    }
+}
+
+impl Display for CheckState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckState::Ok => write!(f, "OK"),
+            CheckState::Warning => write!(f, "WARNING"),
+            CheckState::Critical => write!(f, "CRITICAL"),
+            CheckState::Unknown => write!(f, "UNKNOWN"),
+        }
+    }
+}
+
+/// Whether a state is confirmed (hard) or still being retried (soft)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum StateType {
+    Soft,
+    Hard,
+}
+
+impl Display for StateType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateType::Soft => write!(f, "SOFT"),
+            StateType::Hard => write!(f, "HARD"),
+        }
+    }
+}
+
+/// A change of state or state type reported by the [`StateTracker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateTransition {
+    pub previous: CheckState,
+    pub current: CheckState,
+    pub state_type: StateType,
+    /// Check attempt the transition happened at
+    pub attempt: u32,
+}
+
+impl StateTransition {
+    /// `true` if the transition brings the check back to OK
+    pub fn is_recovery(&self) -> bool {
+        self.current.is_ok() && !self.previous.is_ok()
+    }
+}
+
+/// Tracks soft/hard states with the retry semantics known from Nagios.
+///
+/// A check starts in a hard OK state. A non-OK result puts it into a soft state which is retried
+/// until `max_attempts` consecutive non-OK results turn it into a hard state. Once hard, every
+/// change of the result is a hard state change. Recovering from a soft state is a soft recovery.
+#[derive(Debug, Clone)]
+pub struct StateTracker {
+    state: CheckState,
+    state_type: StateType,
+    attempt: u32,
+    max_attempts: u32,
+}
+
+impl StateTracker {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            state: CheckState::Ok,
+            state_type: StateType::Hard,
+            attempt: 1,
+            max_attempts: max_attempts.max(1),
+        }
+    }
+
+    pub fn state(&self) -> CheckState {
+        self.state
+    }
+
+    pub fn state_type(&self) -> StateType {
+        self.state_type
+    }
+
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Records a check result and returns the resulting transition, if any
+    pub fn record(&mut self, result: CheckState) -> Option<StateTransition> {
+        let previous = self.state;
+
+        match self.state_type {
+            StateType::Hard if result == previous => None,
+            StateType::Hard if previous.is_ok() && self.max_attempts > 1 => {
+                Some(self.transition(previous, result, StateType::Soft, 1))
+            }
+            StateType::Hard => Some(self.transition(previous, result, StateType::Hard, 1)),
+            StateType::Soft if result.is_ok() => {
+                let transition = self.transition(previous, result, StateType::Soft, 1);
+                self.state_type = StateType::Hard;
+                Some(transition)
+            }
+            StateType::Soft => {
+                let attempt = self.attempt + 1;
+                if attempt >= self.max_attempts {
+                    Some(self.transition(previous, result, StateType::Hard, attempt))
+                } else if result != previous {
+                    Some(self.transition(previous, result, StateType::Soft, attempt))
+                } else {
+                    self.attempt = attempt;
+                    None
+                }
+            }
+        }
+    }
+
+    fn transition(
+        &mut self,
+        previous: CheckState,
+        current: CheckState,
+        state_type: StateType,
+        attempt: u32,
+    ) -> StateTransition {
+        self.state = current;
+        self.state_type = state_type;
+        self.attempt = attempt;
+        StateTransition {
+            previous,
+            current,
+            state_type,
+            attempt,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_exit_code() {
+        assert_eq!(CheckState::from_exit_code(0), CheckState::Ok);
+        assert_eq!(CheckState::from_exit_code(1), CheckState::Warning);
+        assert_eq!(CheckState::from_exit_code(2), CheckState::Critical);
+        assert_eq!(CheckState::from_exit_code(3), CheckState::Unknown);
+        assert_eq!(CheckState::from_exit_code(127), CheckState::Unknown);
+        assert_eq!(CheckState::from_exit_code(-1), CheckState::Unknown);
+    }
+
+    #[test]
+    fn test_check_state_display() {
+        assert_eq!(CheckState::Ok.to_string(), "OK");
+        assert_eq!(CheckState::Critical.to_string(), "CRITICAL");
+        assert_eq!(StateType::Soft.to_string(), "SOFT");
+    }
+
+    #[test]
+    fn test_ok_results_do_not_transition() {
+        let mut tracker = StateTracker::new(3);
+        assert_eq!(tracker.record(CheckState::Ok), None);
+        assert_eq!(tracker.record(CheckState::Ok), None);
+        assert_eq!(tracker.state_type(), StateType::Hard);
+    }
+
+    #[test]
+    fn test_problem_becomes_hard_after_max_attempts() {
+        let mut tracker = StateTracker::new(3);
+
+        let soft = tracker.record(CheckState::Critical).unwrap();
+        assert_eq!(soft.state_type, StateType::Soft);
+        assert_eq!(soft.attempt, 1);
+
+        assert_eq!(tracker.record(CheckState::Critical), None);
+        assert_eq!(tracker.attempt(), 2);
+
+        let hard = tracker.record(CheckState::Critical).unwrap();
+        assert_eq!(hard.previous, CheckState::Critical);
+        assert_eq!(hard.current, CheckState::Critical);
+        assert_eq!(hard.state_type, StateType::Hard);
+        assert_eq!(hard.attempt, 3);
+
+        assert_eq!(tracker.record(CheckState::Critical), None);
+    }
+
+    #[test]
+    fn test_single_attempt_is_hard_immediately() {
+        let mut tracker = StateTracker::new(1);
+        let transition = tracker.record(CheckState::Warning).unwrap();
+        assert_eq!(transition.state_type, StateType::Hard);
+    }
+
+    #[test]
+    fn test_soft_state_change() {
+        let mut tracker = StateTracker::new(3);
+        tracker.record(CheckState::Warning);
+
+        let transition = tracker.record(CheckState::Critical).unwrap();
+        assert_eq!(transition.previous, CheckState::Warning);
+        assert_eq!(transition.state_type, StateType::Soft);
+        assert_eq!(transition.attempt, 2);
+    }
+
+    #[test]
+    fn test_soft_recovery() {
+        let mut tracker = StateTracker::new(3);
+        tracker.record(CheckState::Critical);
+
+        let recovery = tracker.record(CheckState::Ok).unwrap();
+        assert!(recovery.is_recovery());
+        assert_eq!(recovery.state_type, StateType::Soft);
+        assert_eq!(tracker.state_type(), StateType::Hard);
+
+        // the next problem starts counting from the beginning
+        let soft = tracker.record(CheckState::Critical).unwrap();
+        assert_eq!(soft.state_type, StateType::Soft);
+        assert_eq!(soft.attempt, 1);
+    }
+
+    #[test]
+    fn test_hard_changes_and_recovery() {
+        let mut tracker = StateTracker::new(2);
+        tracker.record(CheckState::Warning);
+        tracker.record(CheckState::Warning);
+        assert_eq!(tracker.state_type(), StateType::Hard);
+
+        let escalation = tracker.record(CheckState::Critical).unwrap();
+        assert_eq!(escalation.state_type, StateType::Hard);
+        assert_eq!(escalation.previous, CheckState::Warning);
+
+        let recovery = tracker.record(CheckState::Ok).unwrap();
+        assert!(recovery.is_recovery());
+        assert_eq!(recovery.state_type, StateType::Hard);
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt::{Display, Formatter};
+
+/// Errors that can occur while configuring or running a sensor.
+#[derive(Debug)]
+pub enum SensorError {
+    /// The sensor configuration is invalid
+    Config(String),
+    /// An I/O error occurred while collecting data
+    Io(std::io::Error),
+    /// A received payload could not be decoded
+    Payload(String),
+    /// The event sink was closed, no more events can be delivered
+    SinkClosed,
+}
+
+impl Display for SensorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SensorError::Config(message) => write!(f, "invalid sensor configuration: {message}"),
+            SensorError::Io(error) => write!(f, "sensor I/O error: {error}"),
+            SensorError::Payload(message) => write!(f, "invalid payload: {message}"),
+            SensorError::SinkClosed => write!(f, "event sink is closed"),
+        }
+    }
+}
+
+impl std::error::Error for SensorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SensorError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SensorError {
+    fn from(error: std::io::Error) -> Self {
+        SensorError::Io(error)
+    }
+}
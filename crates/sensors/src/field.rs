@@ -0,0 +1,71 @@
+use crate::mapping::convert::text;
+use loid_events::prelude::Value;
+use std::collections::HashMap;
+
+/// Value at a path like `user.name` or `items.0.id`, descending into maps and lists. A field
+/// named like the whole path takes precedence.
+pub(crate) fn lookup<'a>(fields: &'a HashMap<String, Value>, path: &str) -> Option<&'a Value> {
+    if let Some(value) = fields.get(path) {
+        return Some(value);
+    }
+    let mut parts = path.split('.');
+    let mut value = fields.get(parts.next()?)?;
+    for part in parts {
+        value = match value {
+            Value::Map(map) => map.get(part)?,
+            Value::List(list) => list.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Values at the paths identifying the group of an event, `None` if a path is missing or does
+/// not hold a scalar
+pub(crate) fn key(fields: &HashMap<String, Value>, paths: &[String]) -> Option<Vec<Value>> {
+    paths
+        .iter()
+        .map(|path| {
+            let value = lookup(fields, path)?;
+            text(value).map(|_| value.clone())
+        })
+        .collect()
+}
+
+/// Text identifying a group built by [`key`]
+pub(crate) fn key_text(key: &[Value]) -> String {
+    key.iter()
+        .map(|value| text(value).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_paths() {
+        let fields = HashMap::from([
+            (
+                "user".to_string(),
+                Value::Map(HashMap::from([("name".to_string(), Value::from("ada"))])),
+            ),
+            (
+                "items".to_string(),
+                Value::List(vec![Value::Int(1), Value::Int(2)]),
+            ),
+            ("http.status".to_string(), Value::Int(500)),
+        ]);
+        assert_eq!(lookup(&fields, "user.name"), Some(&Value::from("ada")));
+        assert_eq!(lookup(&fields, "items.1"), Some(&Value::Int(2)));
+        assert_eq!(lookup(&fields, "http.status"), Some(&Value::Int(500)));
+        assert_eq!(lookup(&fields, "user.id"), None);
+        assert_eq!(lookup(&fields, "items.name"), None);
+
+        let paths = ["user.name".to_string(), "http.status".to_string()];
+        let key = key(&fields, &paths).unwrap();
+        assert_eq!(key_text(&key), "ada/500");
+        assert_eq!(self::key(&fields, &["user".to_string()]), None);
+    }
+}
@@ -0,0 +1,69 @@
+use loid_events::prelude::{Impact, Priority, Urgency};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// The `flapping` section suppressing the events of sources that keep changing their state
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlappingConfig {
+    /// Field paths identifying a source, the source system and id of the events without
+    #[serde(default)]
+    pub fingerprint: Vec<String>,
+    /// Field path holding the state of a source, events are `open` or `resolved` without
+    #[serde(default)]
+    pub state: Option<String>,
+    /// Time the state changes of a source are counted over
+    #[serde(default = "default_window", with = "humantime_serde")]
+    pub window: Duration,
+    /// A source starts flapping once it changed its state this often within the window
+    #[serde(default = "default_enter")]
+    pub enter: usize,
+    /// A flapping source stabilizes once it changed its state at most this often within the
+    /// window, has to be less than `enter`
+    #[serde(default = "default_leave")]
+    pub leave: usize,
+    #[serde(default = "default_impact")]
+    pub impact: Impact,
+    #[serde(default = "default_urgency")]
+    pub urgency: Urgency,
+    #[serde(default = "default_priority")]
+    pub priority: Priority,
+}
+
+impl Default for FlappingConfig {
+    fn default() -> Self {
+        Self {
+            fingerprint: Vec::new(),
+            state: None,
+            window: default_window(),
+            enter: default_enter(),
+            leave: default_leave(),
+            impact: default_impact(),
+            urgency: default_urgency(),
+            priority: default_priority(),
+        }
+    }
+}
+
+fn default_window() -> Duration {
+    Duration::from_secs(600)
+}
+
+fn default_enter() -> usize {
+    5
+}
+
+fn default_leave() -> usize {
+    2
+}
+
+fn default_impact() -> Impact {
+    Impact::MODERATE
+}
+
+fn default_urgency() -> Urgency {
+    Urgency::MEDIUM
+}
+
+fn default_priority() -> Priority {
+    Priority::MEDIUM
+}
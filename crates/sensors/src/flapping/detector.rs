@@ -0,0 +1,359 @@
+use crate::field::{key, key_text, lookup};
+use crate::flapping::FlappingConfig;
+use crate::mapping::convert::text;
+use crate::{SensorError, Stage};
+use chrono::{DateTime, TimeDelta, Utc};
+use loid_events::prelude::{Event, EventBuilder, Source, Value};
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
+
+/// What is known about one source
+#[derive(Debug)]
+struct Tracked {
+    /// Values at the fingerprint paths, empty for sources identified by their event source
+    key: Vec<Value>,
+    state: String,
+    last_seen: DateTime<Utc>,
+    /// Times the state changed within the window
+    changes: VecDeque<DateTime<Utc>>,
+    /// Time the source started flapping
+    flapping_since: Option<DateTime<Utc>>,
+    /// Correlation id of the latest flapping period, shared with its end
+    incident: Uuid,
+    /// Events suppressed since the source started flapping
+    suppressed: usize,
+}
+
+/// Flapping state of one source, see [`FlapDetector::states`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlapState {
+    pub fingerprint: String,
+    /// Last state the source reported
+    pub state: String,
+    /// State changes within the window
+    pub changes: usize,
+    pub flapping: bool,
+    pub flapping_since: Option<DateTime<Utc>>,
+    pub suppressed: usize,
+}
+
+/// A compiled [`FlappingConfig`] counting the state changes of every source.
+///
+/// State changes follow the creation time of the events. A source starts flapping once it
+/// changed `enter` times within the window and stabilizes once at most `leave` changes are
+/// left in it, which an event or a [`tick`](Self::tick) notices.
+#[derive(Debug)]
+pub struct FlapDetector {
+    config: FlappingConfig,
+    window: TimeDelta,
+    sources: HashMap<String, Tracked>,
+}
+
+impl Tracked {
+    fn forget_before(&mut self, start: DateTime<Utc>) {
+        while self.changes.front().is_some_and(|time| *time <= start) {
+            self.changes.pop_front();
+        }
+    }
+}
+
+impl FlapDetector {
+    pub fn new(config: &FlappingConfig) -> Result<Self, SensorError> {
+        let window = TimeDelta::from_std(config.window)
+            .ok()
+            .filter(|window| *window > TimeDelta::zero())
+            .ok_or_else(|| SensorError::Config("window must be greater than 0".to_string()))?;
+        if config.enter < 2 {
+            return Err(SensorError::Config("enter must be at least 2".to_string()));
+        }
+        if config.leave >= config.enter {
+            return Err(SensorError::Config(format!(
+                "leave must be less than enter ({})",
+                config.enter
+            )));
+        }
+        if config.fingerprint.iter().any(String::is_empty) {
+            return Err(SensorError::Config(
+                "fingerprint contains an empty path".to_string(),
+            ));
+        }
+        if config.state.as_deref() == Some("") {
+            return Err(SensorError::Config("state must not be empty".to_string()));
+        }
+        Ok(Self {
+            config: config.clone(),
+            window,
+            sources: HashMap::new(),
+        })
+    }
+
+    /// Fingerprint and its values identifying the source of the event
+    fn fingerprint(&self, event: &Event) -> Option<(String, Vec<Value>)> {
+        if self.config.fingerprint.is_empty() {
+            let source = &event.source;
+            return Some(match &source.source_id {
+                Some(id) => (format!("{}/{id}", source.system), Vec::new()),
+                None => (source.system.clone(), Vec::new()),
+            });
+        }
+        let key = key(&event.fields, &self.config.fingerprint)?;
+        Some((key_text(&key), key))
+    }
+
+    fn state(&self, event: &Event) -> Option<String> {
+        match &self.config.state {
+            Some(path) => lookup(&event.fields, path).and_then(text),
+            None if event.resolved_at.is_some() => Some("resolved".to_string()),
+            None => Some("open".to_string()),
+        }
+    }
+
+    fn derive(&self, fingerprint: &str, tracked: &Tracked, now: DateTime<Utc>) -> Event {
+        let config = &self.config;
+        let mut builder = EventBuilder::new();
+        builder
+            .with_source(Source {
+                system: "flapping".to_string(),
+                source_id: Some(fingerprint.to_string()),
+            })
+            .with_correlation_id(tracked.incident)
+            .with_impact(config.impact)
+            .with_urgency(config.urgency)
+            .with_priority(config.priority)
+            .with_text_field("fingerprint", fingerprint)
+            .with_text_field("last_state", &tracked.state)
+            .with_int_field("changes", tracked.changes.len() as i64)
+            .with_text_field(
+                "window",
+                &humantime::format_duration(config.window).to_string(),
+            );
+        match tracked.flapping_since {
+            Some(since) => builder
+                .with_text_field("state", "flapping")
+                .with_text_field("flapping_since", &since.to_rfc3339()),
+            None => builder
+                .with_text_field("state", "stabilized")
+                .with_int_field("suppressed", tracked.suppressed as i64)
+                .with_resolved_at(now),
+        };
+        for (path, value) in config.fingerprint.iter().zip(&tracked.key) {
+            builder.with_field(path, value.clone());
+        }
+        builder.build()
+    }
+
+    /// Counts the state change of the event, returns the event unless its source is flapping,
+    /// preceded by the `stabilized` events of the sources that calmed down. The event starting
+    /// to flap is replaced by the `flapping` event.
+    pub fn apply(&mut self, event: Event) -> Vec<Event> {
+        let now = event.created_at;
+        let mut events = self.tick(now);
+
+        let (Some((fingerprint, key)), Some(state)) =
+            (self.fingerprint(&event), self.state(&event))
+        else {
+            events.push(event);
+            return events;
+        };
+        let tracked = self
+            .sources
+            .entry(fingerprint.clone())
+            .or_insert_with(|| Tracked {
+                key,
+                state: state.clone(),
+                last_seen: now,
+                changes: VecDeque::new(),
+                flapping_since: None,
+                incident: Uuid::nil(),
+                suppressed: 0,
+            });
+        if tracked.state != state {
+            tracked.state = state;
+            tracked.changes.push_back(now);
+        }
+        tracked.last_seen = tracked.last_seen.max(now);
+        tracked.forget_before(now - self.window);
+
+        match tracked.flapping_since {
+            Some(_) => tracked.suppressed += 1,
+            None if tracked.changes.len() >= self.config.enter => {
+                tracked.flapping_since = Some(now);
+                tracked.incident = Uuid::now_v7();
+                tracked.suppressed = 1;
+                let tracked = &self.sources[&fingerprint];
+                events.push(self.derive(&fingerprint, tracked, now));
+            }
+            None => events.push(event),
+        }
+        events
+    }
+
+    /// Stabilizes the flapping sources whose state changes left the window before `now` and
+    /// forgets the quiet ones, call it regularly while no events arrive
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        let start = now - self.window;
+        let mut stabilized = Vec::new();
+        for (fingerprint, tracked) in &mut self.sources {
+            tracked.forget_before(start);
+            if tracked.flapping_since.is_some() && tracked.changes.len() <= self.config.leave {
+                tracked.flapping_since = None;
+                stabilized.push(fingerprint.clone());
+            }
+        }
+        stabilized.sort();
+        let events = stabilized
+            .iter()
+            .map(|fingerprint| self.derive(fingerprint, &self.sources[fingerprint], now))
+            .collect();
+        self.sources.retain(|_, tracked| {
+            tracked.flapping_since.is_some()
+                || !tracked.changes.is_empty()
+                || tracked.last_seen > start
+        });
+        events
+    }
+
+    /// Flapping state of every tracked source, ordered by fingerprint
+    pub fn states(&self) -> Vec<FlapState> {
+        let mut states: Vec<_> = self
+            .sources
+            .iter()
+            .map(|(fingerprint, tracked)| FlapState {
+                fingerprint: fingerprint.clone(),
+                state: tracked.state.clone(),
+                changes: tracked.changes.len(),
+                flapping: tracked.flapping_since.is_some(),
+                flapping_since: tracked.flapping_since,
+                suppressed: tracked.suppressed,
+            })
+            .collect();
+        states.sort_by(|a, b| a.fingerprint.cmp(&b.fingerprint));
+        states
+    }
+}
+
+impl Stage for FlapDetector {
+    fn apply(&mut self, event: Event) -> Vec<Event> {
+        FlapDetector::apply(self, event)
+    }
+
+    fn tick(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        FlapDetector::tick(self, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector(json: &str) -> Result<FlapDetector, SensorError> {
+        FlapDetector::new(&serde_json::from_str(json).unwrap())
+    }
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::UNIX_EPOCH + TimeDelta::seconds(seconds)
+    }
+
+    fn check(service: &str, status: &str, seconds: i64) -> Event {
+        let mut event = EventBuilder::new()
+            .with_text_field("service", service)
+            .with_text_field("status", status)
+            .build();
+        event.created_at = at(seconds);
+        event
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        for json in [
+            r#"{"window": "0s"}"#,
+            r#"{"enter": 1, "leave": 0}"#,
+            r#"{"enter": 3, "leave": 3}"#,
+            r#"{"fingerprint": [""]}"#,
+        ] {
+            assert!(
+                matches!(detector(json), Err(SensorError::Config(_))),
+                "{json}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_flapping_suppresses_until_stable() {
+        let mut detector = detector(
+            r#"{"fingerprint": ["service"], "state": "status", "window": "1m", "enter": 4, "leave": 1}"#,
+        )
+        .unwrap();
+
+        // 3 changes pass, the 4th starts flapping
+        for (second, status) in [(0, "up"), (5, "down"), (10, "up"), (15, "down")] {
+            assert_eq!(detector.apply(check("api", status, second)).len(), 1);
+        }
+        assert_eq!(detector.apply(check("db", "down", 18)).len(), 1);
+        let flapping = detector.apply(check("api", "up", 20));
+        assert_eq!(flapping.len(), 1);
+        assert_eq!(flapping[0].fields["state"], Value::from("flapping"));
+        assert_eq!(flapping[0].fields["service"], Value::from("api"));
+        assert_eq!(flapping[0].fields["changes"], Value::Int(4));
+
+        for (second, status) in [(25, "down"), (30, "up"), (40, "down")] {
+            assert!(detector.apply(check("api", status, second)).is_empty());
+        }
+        let states = detector.states();
+        assert_eq!(states.len(), 2);
+        assert!(states[0].flapping);
+        assert_eq!(states[0].suppressed, 4);
+        assert_eq!(states[0].state, "down");
+        assert!(!states[1].flapping);
+
+        // hysteresis: 3 changes are left at 80s, still flapping
+        assert!(detector.tick(at(80)).is_empty());
+        let stabilized = detector.tick(at(95));
+        assert_eq!(stabilized.len(), 1);
+        assert_eq!(stabilized[0].fields["state"], Value::from("stabilized"));
+        assert_eq!(stabilized[0].fields["last_state"], Value::from("down"));
+        assert_eq!(stabilized[0].fields["suppressed"], Value::Int(4));
+        assert_eq!(stabilized[0].correlation_id, flapping[0].correlation_id);
+        assert!(stabilized[0].resolved_at.is_some());
+        assert_eq!(detector.apply(check("api", "up", 100)).len(), 1);
+
+        // flapping again is another incident
+        for (second, status) in [(105, "down"), (110, "up"), (115, "down")] {
+            assert_eq!(detector.apply(check("api", status, second)).len(), 1);
+        }
+        let again = detector.apply(check("api", "up", 120));
+        assert_eq!(again[0].fields["state"], Value::from("flapping"));
+        assert_ne!(again[0].correlation_id, flapping[0].correlation_id);
+    }
+
+    #[test]
+    fn test_source_and_resolution_by_default() {
+        let mut detector = detector(r#"{"window": "1m", "enter": 2, "leave": 0}"#).unwrap();
+        let alert = |resolved: bool, seconds| {
+            let mut builder = EventBuilder::new();
+            builder.with_source(Source {
+                system: "alertmanager".to_string(),
+                source_id: Some("c0ffee".to_string()),
+            });
+            if resolved {
+                builder.with_resolved_at(at(seconds));
+            }
+            let mut event = builder.build();
+            event.created_at = at(seconds);
+            event
+        };
+        assert_eq!(detector.apply(alert(false, 0)).len(), 1);
+        assert_eq!(detector.apply(alert(true, 10)).len(), 1);
+        let events = detector.apply(alert(false, 20));
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].fields["fingerprint"],
+            Value::from("alertmanager/c0ffee")
+        );
+        // once the changes left the window the source stabilizes before the event passes
+        let events = detector.apply(alert(true, 200));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].fields["state"], Value::from("stabilized"));
+        assert!(events[1].resolved_at.is_some());
+    }
+}
@@ -0,0 +1,22 @@
+//! Flapping detection for sources that keep changing their state.
+//!
+//! The `flapping` section sits next to the `sensor` section and works the same for every sensor
+//! type, it sees the events after the [mapping](crate::mapping):
+//!
+//! ```yaml
+//! flapping:
+//!     fingerprint: [service, check]
+//!     state: status
+//!     window: 10m
+//!     enter: 5
+//!     leave: 2
+//! ```
+//!
+//! While a source is flapping its events are suppressed, a single `flapping` event reports the
+//! start and a `stabilized` event the end.
+
+pub(crate) mod config;
+pub(crate) mod detector;
+
+pub use config::FlappingConfig;
+pub use detector::{FlapDetector, FlapState};
@@ -1,5 +1,35 @@
 use chrono::{DateTime, Utc};
+use loid_resources::prelude::{CircuitState, ResourceError, ResourceScope, TypedResource};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use tokio::sync::watch::Receiver;
+use tokio::task::JoinHandle;
+
+pub mod adapter;
+pub mod amqp;
+pub mod buffer;
+pub mod command;
+mod error;
+mod field;
+pub mod flapping;
+pub mod mapping;
+pub mod metrics;
+pub mod nats;
+pub mod otlp;
+pub mod pattern;
+pub mod prelude;
+pub mod probe;
+pub mod redis_stream;
+mod sink;
+pub mod smtp;
+pub mod sql;
+pub mod watch;
+pub mod window;
+
+pub use crate::error::SensorError;
+pub use crate::sink::{EventReceiver, EventSink, SendOutcome, Stage};
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Serialize, Deserialize)]
 pub enum LifecycleState {
@@ -39,9 +69,290 @@ pub struct SensorStatus {
     pub lifecycle: LifecycleState,
     pub health: HealthState,
     pub last_update: DateTime<Utc>,
+    /// Events waiting in the buffer of the sensor, in memory and spilled to disk
+    #[serde(default)]
+    pub buffer_depth: usize,
+    /// Dropped events of broker messages whose other events were handed off, they are not
+    /// delivered again
+    #[serde(default)]
+    pub lost_events: u64,
+}
+
+impl Default for SensorStatus {
+    fn default() -> Self {
+        Self {
+            lifecycle: LifecycleState::Stopped,
+            health: HealthState::Unknown,
+            last_update: Utc::now(),
+            buffer_depth: 0,
+            lost_events: 0,
+        }
+    }
+}
+
+/// Shared handle to the status of a sensor.
+///
+/// The sensor updates the status while it runs, everyone else holding a clone of the handle can
+/// read the latest snapshot at any time. The buffer of the sensor reports its depth and marks the
+/// sensor as [`HealthState::Throttled`] while backpressure is applied, which takes precedence
+/// over the health set by the sensor. While the circuit of any resource the sensor reads from is
+/// not closed the sensor is [`HealthState::ResourceDown`], which takes precedence over both.
+#[derive(Debug, Clone, Default)]
+pub struct StatusHandle(Arc<StatusState>);
+
+#[derive(Debug, Default)]
+struct StatusState {
+    status: RwLock<SensorStatus>,
+    throttled: AtomicBool,
+    /// Resources of the sensor and whether their circuit is not closed
+    resources: Mutex<HashMap<String, bool>>,
+    buffer_depth: AtomicUsize,
+    lost_events: AtomicU64,
+}
+
+impl StatusHandle {
+    /// Returns a snapshot of the current status
+    pub fn get(&self) -> SensorStatus {
+        let mut status = self
+            .0
+            .status
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if self.resources().values().any(|down| *down) {
+            status.health = HealthState::ResourceDown;
+        } else if self.0.throttled.load(Ordering::Relaxed) {
+            status.health = HealthState::Throttled;
+        }
+        status.buffer_depth = self.0.buffer_depth.load(Ordering::Relaxed);
+        status.lost_events = self.0.lost_events.load(Ordering::Relaxed);
+        status
+    }
+
+    /// Updates the lifecycle state
+    pub fn set_lifecycle(&self, lifecycle: LifecycleState) {
+        self.update(|status| status.lifecycle = lifecycle);
+    }
+
+    /// Updates the health state
+    pub fn set_health(&self, health: HealthState) {
+        self.update(|status| status.health = health);
+    }
+
+    /// Marks the sensor as throttled by backpressure or clears the mark
+    pub fn set_throttled(&self, throttled: bool) {
+        if self.0.throttled.swap(throttled, Ordering::Relaxed) != throttled {
+            self.update(|_| ());
+        }
+    }
+
+    /// Marks a resource of the sensor as down or clears the mark, the sensor is resource down
+    /// while any of its resources is
+    pub fn set_resource_down(&self, resource: &str, down: bool) {
+        let before = self.resources().insert(resource.to_string(), down);
+        if before != Some(down) {
+            self.update(|_| ());
+        }
+    }
+
+    /// Keeps the resource marked as down while its circuit is not closed, until the circuit
+    /// breaker is dropped
+    pub fn follow_circuit(
+        &self,
+        resource: impl Into<String>,
+        mut circuit: Receiver<CircuitState>,
+    ) -> JoinHandle<()> {
+        let handle = self.clone();
+        let resource = resource.into();
+        tokio::spawn(async move {
+            loop {
+                let state = *circuit.borrow_and_update();
+                handle.set_resource_down(&resource, state != CircuitState::Closed);
+                if circuit.changed().await.is_err() {
+                    break;
+                }
+            }
+            if handle.resources().remove(&resource) == Some(true) {
+                handle.update(|_| ());
+            }
+        })
+    }
+
+    /// The resource under the key from the scope of the sensor, following its circuit from the
+    /// first call on. Sensors get their resources through here.
+    pub async fn resource<T: TypedResource>(
+        &self,
+        scope: &ResourceScope,
+        key: &str,
+    ) -> Result<Arc<T>, ResourceError> {
+        let followed = self.resources().contains_key(key);
+        if !followed && let Some(circuit) = scope.subscribe(key) {
+            self.set_resource_down(key, *circuit.borrow() != CircuitState::Closed);
+            self.follow_circuit(key, circuit);
+        }
+        scope.get(key).await
+    }
+
+    /// Updates the number of buffered events
+    pub fn set_buffer_depth(&self, depth: usize) {
+        self.0.buffer_depth.store(depth, Ordering::Relaxed);
+    }
+
+    /// Counts events the buffer dropped that are not delivered again
+    pub fn add_lost_events(&self, count: u64) {
+        self.0.lost_events.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn resources(&self) -> MutexGuard<'_, HashMap<String, bool>> {
+        self.0.resources.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut SensorStatus)) {
+        let mut status = self.0.status.write().unwrap_or_else(|e| e.into_inner());
+        f(&mut status);
+        status.last_update = Utc::now();
+    }
 }
 
-trait Sensor {}
+/// A sensor collects data from an external system and turns it into events.
+pub trait Sensor {
+    /// Unique key of the sensor as defined in its configuration
+    fn key(&self) -> &str;
+
+    /// Returns the handle the sensor reports its status to
+    fn status_handle(&self) -> &StatusHandle;
+
+    /// Returns a snapshot of the current sensor status
+    fn status(&self) -> SensorStatus {
+        self.status_handle().get()
+    }
+
+    /// Runs the sensor and sends all collected events to `sink`.
+    ///
+    /// The returned future completes once the sink is closed or the sensor fails permanently.
+    fn run(&self, sink: EventSink) -> impl Future<Output = Result<(), SensorError>> + Send;
+}
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use loid_resources::prelude::{
+        CircuitBreakerConfig, HttpResource, HttpResourceConfig, ResourceConfig, ResourceRegistry,
+        ResourceSpec,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_status_handle_default() {
+        let status = StatusHandle::default().get();
+        assert_eq!(status.lifecycle, LifecycleState::Stopped);
+        assert_eq!(status.health, HealthState::Unknown);
+    }
+
+    #[test]
+    fn test_status_handle_is_shared() {
+        let handle = StatusHandle::default();
+        let clone = handle.clone();
+        let before = handle.get().last_update;
+
+        clone.set_lifecycle(LifecycleState::Running);
+        clone.set_health(HealthState::Degraded);
+
+        let status = handle.get();
+        assert_eq!(status.lifecycle, LifecycleState::Running);
+        assert_eq!(status.health, HealthState::Degraded);
+        assert!(status.last_update >= before);
+    }
+
+    #[tokio::test]
+    async fn test_status_handle_follows_circuit() {
+        let (sender, receiver) = tokio::sync::watch::channel(CircuitState::Closed);
+        let handle = StatusHandle::default();
+        handle.set_health(HealthState::Healthy);
+        handle.set_throttled(true);
+        let task = handle.follow_circuit("orders-db", receiver);
+
+        sender.send(CircuitState::Open).unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(handle.get().health, HealthState::ResourceDown);
+        sender.send(CircuitState::HalfOpen).unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(handle.get().health, HealthState::ResourceDown);
+
+        sender.send(CircuitState::Closed).unwrap();
+        drop(sender);
+        task.await.unwrap();
+        assert_eq!(handle.get().health, HealthState::Throttled);
+    }
+
+    #[test]
+    fn test_status_handle_counts_resources_down() {
+        let handle = StatusHandle::default();
+        handle.set_health(HealthState::Healthy);
+        handle.set_resource_down("orders-db", true);
+        handle.set_resource_down("audit-db", true);
+        handle.set_resource_down("orders-db", false);
+        assert_eq!(handle.get().health, HealthState::ResourceDown);
+        handle.set_resource_down("audit-db", false);
+        assert_eq!(handle.get().health, HealthState::Healthy);
+    }
+
+    #[tokio::test]
+    async fn test_status_handle_follows_the_resources_of_the_sensor() {
+        // a port nobody listens on
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let http: HttpResourceConfig = serde_json::from_value(serde_json::json!({
+            "base_url": format!("http://127.0.0.1:{port}"),
+        }))
+        .unwrap();
+        let spec = ResourceSpec {
+            resource: ResourceConfig::Http(http),
+            circuit_breaker: Some(CircuitBreakerConfig {
+                failures: 1,
+                open_for: Duration::from_secs(3600),
+            }),
+            access: None,
+        };
+        let registry = ResourceRegistry::new([("api".to_string(), spec.clone())]).unwrap();
+        let handle = StatusHandle::default();
+        handle.set_health(HealthState::Healthy);
+
+        let scope = registry.scope("stuck-orders");
+        handle
+            .resource::<HttpResource>(&scope, "api")
+            .await
+            .unwrap();
+        handle
+            .resource::<HttpResource>(&scope, "api")
+            .await
+            .unwrap();
+        assert_eq!(handle.get().health, HealthState::Healthy);
+
+        registry.check_health().await;
+        tokio::task::yield_now().await;
+        assert_eq!(handle.get().health, HealthState::ResourceDown);
+        registry.reload("api", spec).await.unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(handle.get().health, HealthState::Healthy);
+    }
+
+    #[test]
+    fn test_status_handle_throttled_overrides_health() {
+        let handle = StatusHandle::default();
+        handle.set_health(HealthState::Healthy);
+        handle.set_throttled(true);
+        handle.set_buffer_depth(42);
+        handle.set_health(HealthState::Degraded);
+
+        let status = handle.get();
+        assert_eq!(status.health, HealthState::Throttled);
+        assert_eq!(status.buffer_depth, 42);
+
+        handle.set_throttled(false);
+        assert_eq!(handle.get().health, HealthState::Degraded);
+    }
+}
@@ -0,0 +1,106 @@
+use loid_events::prelude::{Impact, Priority, Urgency, Value};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// The `mapping` section shaping the events of a sensor
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MappingConfig {
+    /// Rules setting fields from parts of other fields
+    #[serde(default)]
+    pub extract: Vec<ExtractRule>,
+    /// Fields renamed from the key to the value
+    #[serde(default)]
+    pub rename: HashMap<String, String>,
+    /// Fields converted to the given type
+    #[serde(default)]
+    pub convert: HashMap<String, ValueType>,
+    /// Fields set to a fixed value
+    #[serde(default)]
+    pub constants: HashMap<String, Value>,
+    /// Events matching any of the rules are dropped
+    #[serde(default)]
+    pub drop: Vec<DropRule>,
+    /// Rules setting the classification of matching events, applied in order
+    #[serde(default)]
+    pub rules: Vec<MappingRule>,
+}
+
+/// Sets a field from a part of another field.
+///
+/// Either `pointer` or `regex` selects the part. A regular expression takes the value from the
+/// capture group named `value`, the first capture group or the whole match, in that order.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExtractRule {
+    /// Name of the field to set
+    pub field: String,
+    /// Name of the field the value is extracted from
+    pub from: String,
+    /// JSON pointer into a map, a list or a string containing JSON, e.g. `/response/status`
+    #[serde(default)]
+    pub pointer: Option<String>,
+    /// Regular expression matched against the text of the field
+    #[serde(default)]
+    pub regex: Option<String>,
+    /// Type the extracted value is converted to
+    #[serde(default, rename = "type")]
+    pub value_type: Option<ValueType>,
+}
+
+/// Target type of a conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    String,
+    Int,
+    Float,
+    Bool,
+    /// Integer, float or boolean if the text is one, the text otherwise
+    Auto,
+}
+
+/// Checks a single field, exactly one of the tests has to be configured
+#[derive(Debug, Clone, Deserialize)]
+pub struct Condition {
+    pub field: String,
+    /// The field has this value, numbers and text are compared by their text
+    #[serde(default)]
+    pub equals: Option<Value>,
+    /// The field has one of these values
+    #[serde(default)]
+    pub one_of: Option<Vec<Value>>,
+    /// The text of the field matches this regular expression
+    #[serde(default)]
+    pub matches: Option<String>,
+    /// The field is present or absent
+    #[serde(default)]
+    pub exists: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DropRule {
+    /// All conditions have to match
+    pub when: Vec<Condition>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MappingRule {
+    /// All conditions have to match, a rule without conditions always applies
+    #[serde(default)]
+    pub when: Vec<Condition>,
+    pub set: Classification,
+}
+
+/// Properties of the event set by a rule, unset properties are left unchanged
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Classification {
+    #[serde(default)]
+    pub impact: Option<Impact>,
+    #[serde(default)]
+    pub urgency: Option<Urgency>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    /// Template like `{host}/{service}`, the id is derived from the rendered text unless it is a
+    /// UUID itself
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+}
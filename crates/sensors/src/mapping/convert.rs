@@ -0,0 +1,99 @@
+use crate::mapping::ValueType;
+use loid_events::prelude::Value;
+
+/// Text of a scalar value, `None` for lists, maps and missing values
+pub(crate) fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Int(value) => Some(value.to_string()),
+        Value::Float(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        Value::None | Value::List(_) | Value::Map(_) => None,
+    }
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+impl ValueType {
+    /// Converts a value, `None` if it cannot be represented as this type
+    pub fn convert(&self, value: &Value) -> Option<Value> {
+        match (self, value) {
+            (ValueType::String, value) => text(value).map(Value::String),
+            (ValueType::Int, Value::Int(value)) => Some(Value::Int(*value)),
+            (ValueType::Int, Value::Float(value)) if value.fract() == 0.0 => {
+                Some(Value::Int(*value as i64))
+            }
+            (ValueType::Int, Value::Bool(value)) => Some(Value::Int(*value as i64)),
+            (ValueType::Int, Value::String(value)) => value.trim().parse().ok().map(Value::Int),
+            (ValueType::Float, Value::Int(value)) => Some(Value::Float(*value as f64)),
+            (ValueType::Float, Value::Float(value)) => Some(Value::Float(*value)),
+            (ValueType::Float, Value::String(value)) => value.trim().parse().ok().map(Value::Float),
+            (ValueType::Bool, Value::Bool(value)) => Some(Value::Bool(*value)),
+            (ValueType::Bool, Value::Int(value @ (0 | 1))) => Some(Value::Bool(*value == 1)),
+            (ValueType::Bool, Value::String(value)) => parse_bool(value).map(Value::Bool),
+            (ValueType::Auto, Value::String(value)) => Some(
+                ValueType::Int
+                    .convert(&Value::String(value.clone()))
+                    .or_else(|| ValueType::Float.convert(&Value::String(value.clone())))
+                    .or_else(|| {
+                        let value = value.trim().to_ascii_lowercase();
+                        matches!(value.as_str(), "true" | "false")
+                            .then(|| Value::Bool(value == "true"))
+                    })
+                    .unwrap_or_else(|| Value::String(value.clone())),
+            ),
+            (ValueType::Auto, value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let text = Value::from(" 42 ");
+        assert_eq!(ValueType::Int.convert(&text), Some(Value::Int(42)));
+        assert_eq!(ValueType::Float.convert(&text), Some(Value::Float(42.0)));
+        assert_eq!(ValueType::Bool.convert(&text), None);
+        assert_eq!(
+            ValueType::String.convert(&Value::Float(1.5)),
+            Some(Value::from("1.5"))
+        );
+        assert_eq!(ValueType::Int.convert(&Value::Float(1.5)), None);
+        assert_eq!(
+            ValueType::Bool.convert(&Value::from("Yes")),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(ValueType::String.convert(&Value::List(vec![])), None);
+    }
+
+    #[test]
+    fn test_convert_auto() {
+        assert_eq!(
+            ValueType::Auto.convert(&Value::from("7")),
+            Some(Value::Int(7))
+        );
+        assert_eq!(
+            ValueType::Auto.convert(&Value::from("0.25")),
+            Some(Value::Float(0.25))
+        );
+        assert_eq!(
+            ValueType::Auto.convert(&Value::from("TRUE")),
+            Some(Value::Bool(true))
+        );
+        // only literal booleans, "yes" stays text
+        assert_eq!(
+            ValueType::Auto.convert(&Value::from("yes")),
+            Some(Value::from("yes"))
+        );
+    }
+}
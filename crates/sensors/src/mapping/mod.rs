@@ -0,0 +1,44 @@
+//! Declarative rules shaping the events of a sensor before they reach the engine.
+//!
+//! The `mapping` section sits next to the `sensor` section and works the same for every sensor
+//! type:
+//!
+//! ```yaml
+//! mapping:
+//!     extract:
+//!         - field: status
+//!           from: body
+//!           pointer: /response/status
+//!           type: int
+//!         - field: user
+//!           from: message
+//!           regex: "user=(?P<value>\\w+)"
+//!     rename:
+//!         hostname: host
+//!     convert:
+//!         latency_ms: float
+//!     constants:
+//!         team: platform
+//!     drop:
+//!         - when:
+//!               - field: env
+//!                 equals: staging
+//!     rules:
+//!         - when:
+//!               - field: status
+//!                 one_of: [500, 502, 503]
+//!           set:
+//!               impact: SIGNIFICANT
+//!               urgency: HIGH
+//!               priority: HIGH
+//!               correlation_id: "{host}/http"
+//! ```
+
+pub(crate) mod config;
+pub(crate) mod convert;
+pub(crate) mod rules;
+
+pub use config::{
+    Classification, Condition, DropRule, ExtractRule, MappingConfig, MappingRule, ValueType,
+};
+pub use rules::Mapping;
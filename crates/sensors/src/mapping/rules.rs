@@ -0,0 +1,476 @@
+use crate::SensorError;
+use crate::mapping::convert::text;
+use crate::mapping::{
+    Classification, Condition, ExtractRule, MappingConfig, MappingRule, ValueType,
+};
+use loid_events::prelude::{Event, Value};
+use regex::Regex;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Selects the part of a field an extraction rule takes
+#[derive(Debug)]
+enum Selector {
+    Pointer(String),
+    Regex(Regex),
+}
+
+#[derive(Debug)]
+struct Extraction {
+    field: String,
+    from: String,
+    selector: Selector,
+    value_type: Option<ValueType>,
+}
+
+#[derive(Debug)]
+enum Test {
+    Equals(Value),
+    OneOf(Vec<Value>),
+    Matches(Regex),
+    Exists(bool),
+}
+
+#[derive(Debug)]
+pub(crate) struct Check {
+    field: String,
+    test: Test,
+}
+
+/// A correlation id template, alternating literal text and field names
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+#[derive(Debug)]
+struct Rule {
+    when: Vec<Check>,
+    set: Classification,
+    correlation_id: Option<Vec<Part>>,
+}
+
+/// A compiled [`MappingConfig`] applied to every event of a sensor.
+///
+/// The steps run in a fixed order: extraction, renames, conversions, constants, drop rules and
+/// finally the classification rules, so later steps see the fields produced by earlier ones.
+#[derive(Debug)]
+pub struct Mapping {
+    extract: Vec<Extraction>,
+    rename: HashMap<String, String>,
+    convert: HashMap<String, ValueType>,
+    constants: HashMap<String, Value>,
+    drop: Vec<Vec<Check>>,
+    rules: Vec<Rule>,
+}
+
+fn regex(pattern: &str) -> Result<Regex, SensorError> {
+    Regex::new(pattern).map_err(|e| SensorError::Config(format!("invalid regex '{pattern}': {e}")))
+}
+
+impl Extraction {
+    fn new(rule: &ExtractRule) -> Result<Self, SensorError> {
+        let selector = match (&rule.pointer, &rule.regex) {
+            (Some(pointer), None) if pointer.is_empty() || pointer.starts_with('/') => {
+                Selector::Pointer(pointer.clone())
+            }
+            (Some(pointer), None) => {
+                return Err(SensorError::Config(format!(
+                    "pointer '{pointer}' of field '{}' must start with '/'",
+                    rule.field
+                )));
+            }
+            (None, Some(pattern)) => Selector::Regex(regex(pattern)?),
+            _ => {
+                return Err(SensorError::Config(format!(
+                    "extraction of field '{}' needs either pointer or regex",
+                    rule.field
+                )));
+            }
+        };
+        Ok(Self {
+            field: rule.field.clone(),
+            from: rule.from.clone(),
+            selector,
+            value_type: rule.value_type,
+        })
+    }
+
+    fn extract(&self, fields: &HashMap<String, Value>) -> Option<Value> {
+        let source = fields.get(&self.from)?;
+        let value = match &self.selector {
+            Selector::Pointer(pointer) => {
+                let json = match source {
+                    Value::String(text) => serde_json::from_str(text).ok()?,
+                    value => serde_json::to_value(value).ok()?,
+                };
+                serde_json::from_value(json.pointer(pointer)?.clone()).ok()?
+            }
+            Selector::Regex(regex) => {
+                let text = text(source)?;
+                let captures = regex.captures(&text)?;
+                let value = captures
+                    .name("value")
+                    .or_else(|| captures.get(1))
+                    .or_else(|| captures.get(0))?;
+                Value::from(value.as_str())
+            }
+        };
+        match self.value_type {
+            Some(value_type) => value_type.convert(&value),
+            None => Some(value),
+        }
+    }
+}
+
+impl Check {
+    pub(crate) fn new(condition: &Condition) -> Result<Self, SensorError> {
+        let tests = [
+            condition.equals.clone().map(Test::Equals),
+            condition.one_of.clone().map(Test::OneOf),
+            condition
+                .matches
+                .as_deref()
+                .map(regex)
+                .transpose()?
+                .map(Test::Matches),
+            condition.exists.map(Test::Exists),
+        ];
+        let mut tests = tests.into_iter().flatten();
+        match (tests.next(), tests.next()) {
+            (Some(test), None) => Ok(Self {
+                field: condition.field.clone(),
+                test,
+            }),
+            _ => Err(SensorError::Config(format!(
+                "condition on field '{}' needs exactly one of equals, one_of, matches or exists",
+                condition.field
+            ))),
+        }
+    }
+
+    pub(crate) fn matches(&self, fields: &HashMap<String, Value>) -> bool {
+        let value = fields.get(&self.field);
+        match &self.test {
+            Test::Exists(exists) => value.is_some() == *exists,
+            Test::Equals(expected) => value.is_some_and(|value| equals(value, expected)),
+            Test::OneOf(expected) => {
+                value.is_some_and(|value| expected.iter().any(|expected| equals(value, expected)))
+            }
+            Test::Matches(regex) => value
+                .and_then(text)
+                .is_some_and(|text| regex.is_match(&text)),
+        }
+    }
+}
+
+/// Compares values of the same type directly and scalars of different types by their text
+fn equals(value: &Value, expected: &Value) -> bool {
+    value == expected || matches!((text(value), text(expected)), (Some(a), Some(b)) if a == b)
+}
+
+/// Whether all checks match, `true` without checks
+pub(crate) fn all(checks: &[Check], fields: &HashMap<String, Value>) -> bool {
+    checks.iter().all(|check| check.matches(fields))
+}
+
+fn template(template: &str) -> Result<Vec<Part>, SensorError> {
+    let invalid = || SensorError::Config(format!("invalid correlation_id template '{template}'"));
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        if rest[..start].contains('}') {
+            return Err(invalid());
+        }
+        if start > 0 {
+            parts.push(Part::Text(rest[..start].to_string()));
+        }
+        let end = rest[start..].find('}').ok_or_else(invalid)? + start;
+        let field = rest[start + 1..end].trim();
+        if field.is_empty() || field.contains('{') {
+            return Err(invalid());
+        }
+        parts.push(Part::Field(field.to_string()));
+        rest = &rest[end + 1..];
+    }
+    if rest.contains('}') {
+        return Err(invalid());
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest.to_string()));
+    }
+    Ok(parts)
+}
+
+/// Renders the template, `None` if a referenced field is missing or not a scalar
+fn render(parts: &[Part], fields: &HashMap<String, Value>) -> Option<String> {
+    parts
+        .iter()
+        .map(|part| match part {
+            Part::Text(text) => Some(text.clone()),
+            Part::Field(field) => fields.get(field).and_then(text),
+        })
+        .collect()
+}
+
+impl Rule {
+    fn new(rule: &MappingRule) -> Result<Self, SensorError> {
+        Ok(Self {
+            when: rule.when.iter().map(Check::new).collect::<Result<_, _>>()?,
+            set: rule.set.clone(),
+            correlation_id: rule
+                .set
+                .correlation_id
+                .as_deref()
+                .map(template)
+                .transpose()?,
+        })
+    }
+
+    fn apply(&self, event: &mut Event) {
+        if let Some(impact) = self.set.impact {
+            event.impact = impact;
+        }
+        if let Some(urgency) = self.set.urgency {
+            event.urgency = urgency;
+        }
+        if let Some(priority) = self.set.priority {
+            event.priority = priority;
+        }
+        if let Some(parts) = &self.correlation_id {
+            match render(parts, &event.fields) {
+                Some(id) => {
+                    event.correlation_id = Some(Uuid::parse_str(&id).unwrap_or_else(|_| {
+                        Uuid::new_v5(&Uuid::NAMESPACE_URL, format!("mapping:{id}").as_bytes())
+                    }));
+                }
+                None => {
+                    tracing::debug!(event = %event.id, "correlation_id template references a missing field")
+                }
+            }
+        }
+    }
+}
+
+impl Mapping {
+    pub fn new(config: &MappingConfig) -> Result<Self, SensorError> {
+        Ok(Self {
+            extract: config
+                .extract
+                .iter()
+                .map(Extraction::new)
+                .collect::<Result<_, _>>()?,
+            rename: config.rename.clone(),
+            convert: config.convert.clone(),
+            constants: config.constants.clone(),
+            drop: config
+                .drop
+                .iter()
+                .map(|rule| rule.when.iter().map(Check::new).collect())
+                .collect::<Result<_, _>>()?,
+            rules: config
+                .rules
+                .iter()
+                .map(Rule::new)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Shapes the event, `None` if a drop rule matched
+    pub fn apply(&self, mut event: Event) -> Option<Event> {
+        let extracted: Vec<_> = self
+            .extract
+            .iter()
+            .filter_map(|extraction| {
+                Some((extraction.field.clone(), extraction.extract(&event.fields)?))
+            })
+            .collect();
+        event.fields.extend(extracted);
+
+        for (from, to) in &self.rename {
+            if let Some(value) = event.fields.remove(from) {
+                event.fields.insert(to.clone(), value);
+            }
+        }
+        for (field, value_type) in &self.convert {
+            if let Some(value) = event.fields.get_mut(field)
+                && let Some(converted) = value_type.convert(value)
+            {
+                *value = converted;
+            }
+        }
+        event
+            .fields
+            .extend(self.constants.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        if self.drop.iter().any(|checks| all(checks, &event.fields)) {
+            return None;
+        }
+        for rule in &self.rules {
+            if all(&rule.when, &event.fields) {
+                rule.apply(&mut event);
+            }
+        }
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use loid_events::prelude::{EventBuilder, Impact, Priority, Urgency};
+
+    fn mapping(json: &str) -> Result<Mapping, SensorError> {
+        Mapping::new(&serde_json::from_str(json).unwrap())
+    }
+
+    fn event(fields: &[(&str, Value)]) -> Event {
+        let mut builder = EventBuilder::new();
+        for (key, value) in fields {
+            builder.with_field(key, value.clone());
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_extract_rename_convert_and_constants() {
+        let mapping = mapping(
+            r#"{
+                "extract": [
+                    {"field": "status", "from": "body", "pointer": "/response/status"},
+                    {"field": "user", "from": "message", "regex": "user=(?P<value>\\w+)"},
+                    {"field": "latency", "from": "message", "regex": "took (\\d+)ms", "type": "int"}
+                ],
+                "rename": {"hostname": "host"},
+                "convert": {"status": "string", "retries": "int"},
+                "constants": {"team": "platform"}
+            }"#,
+        )
+        .unwrap();
+        let event = mapping
+            .apply(event(&[
+                ("body", Value::from(r#"{"response": {"status": 503}}"#)),
+                ("message", Value::from("user=alice took 1200ms")),
+                ("hostname", Value::from("web-01")),
+                ("retries", Value::from("three")),
+            ]))
+            .unwrap();
+        assert_eq!(event.fields.get("status"), Some(&Value::from("503")));
+        assert_eq!(event.fields.get("user"), Some(&Value::from("alice")));
+        assert_eq!(event.fields.get("latency"), Some(&Value::Int(1200)));
+        assert_eq!(event.fields.get("host"), Some(&Value::from("web-01")));
+        assert!(!event.fields.contains_key("hostname"));
+        // failed conversions keep the original value
+        assert_eq!(event.fields.get("retries"), Some(&Value::from("three")));
+        assert_eq!(event.fields.get("team"), Some(&Value::from("platform")));
+    }
+
+    #[test]
+    fn test_extract_pointer_into_map() {
+        let mapping =
+            mapping(r#"{"extract": [{"field": "env", "from": "labels", "pointer": "/env"}]}"#)
+                .unwrap();
+        let labels = Value::Map(HashMap::from([("env".to_string(), Value::from("prod"))]));
+        let event = mapping.apply(event(&[("labels", labels)])).unwrap();
+        assert_eq!(event.fields.get("env"), Some(&Value::from("prod")));
+    }
+
+    #[test]
+    fn test_drop_rules() {
+        let mapping = mapping(
+            r#"{"drop": [
+                {"when": [{"field": "env", "equals": "staging"}]},
+                {"when": [{"field": "level", "one_of": ["debug", "trace"]}, {"field": "keep", "exists": false}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(
+            mapping
+                .apply(event(&[("env", Value::from("staging"))]))
+                .is_none()
+        );
+        assert!(
+            mapping
+                .apply(event(&[("level", Value::from("debug"))]))
+                .is_none()
+        );
+        assert!(
+            mapping
+                .apply(event(&[
+                    ("level", Value::from("debug")),
+                    ("keep", Value::Bool(true))
+                ]))
+                .is_some()
+        );
+        assert!(
+            mapping
+                .apply(event(&[("env", Value::from("prod"))]))
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_rules_classify_in_order() {
+        let mapping = mapping(
+            r#"{"rules": [
+                {"set": {"priority": "MEDIUM"}},
+                {
+                    "when": [{"field": "status", "equals": 500}],
+                    "set": {"impact": "SIGNIFICANT", "urgency": "HIGH", "correlation_id": "{host}/http"}
+                },
+                {"when": [{"field": "host", "matches": "^db-"}], "set": {"priority": "CRITICAL"}}
+            ]}"#,
+        )
+        .unwrap();
+        let first = mapping
+            .apply(event(&[
+                ("status", Value::from("500")),
+                ("host", Value::from("web-01")),
+            ]))
+            .unwrap();
+        assert_eq!(first.impact, Impact::SIGNIFICANT);
+        assert_eq!(first.urgency, Urgency::HIGH);
+        assert_eq!(first.priority, Priority::MEDIUM);
+        let second = mapping
+            .apply(event(&[
+                ("status", Value::Int(500)),
+                ("host", Value::from("web-01")),
+            ]))
+            .unwrap();
+        assert!(first.correlation_id.is_some());
+        assert_eq!(first.correlation_id, second.correlation_id);
+
+        let db = mapping
+            .apply(event(&[("host", Value::from("db-01"))]))
+            .unwrap();
+        assert_eq!(db.priority, Priority::CRITICAL);
+        assert_eq!(db.impact, Impact::NEGLIGIBLE);
+        // the template references a field that is not set
+        assert!(db.correlation_id.is_none());
+    }
+
+    #[test]
+    fn test_correlation_id_uuid_is_kept() {
+        let id = "0191f6a0-6f4e-7c2a-9a4e-2f1c3b5d7e9f";
+        let mapping = mapping(r#"{"rules": [{"set": {"correlation_id": "{id}"}}]}"#).unwrap();
+        let event = mapping.apply(event(&[("id", Value::from(id))])).unwrap();
+        assert_eq!(event.correlation_id, Some(Uuid::parse_str(id).unwrap()));
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_config() {
+        assert!(mapping(r#"{"extract": [{"field": "a", "from": "b"}]}"#).is_err());
+        assert!(mapping(r#"{"extract": [{"field": "a", "from": "b", "pointer": "x"}]}"#).is_err());
+        assert!(mapping(r#"{"drop": [{"when": [{"field": "a"}]}]}"#).is_err());
+        assert!(
+            mapping(r#"{"drop": [{"when": [{"field": "a", "equals": 1, "exists": true}]}]}"#)
+                .is_err()
+        );
+        assert!(mapping(r#"{"rules": [{"set": {"correlation_id": "{host"}}]}"#).is_err());
+        assert!(matches!(
+            mapping(r#"{"drop": [{"when": [{"field": "a", "matches": "("}]}]}"#),
+            Err(SensorError::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,122 @@
+use loid_events::prelude::{Impact, Priority, Urgency};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// The `metrics` section evaluating numeric fields of the events of a sensor
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MetricsConfig {
+    /// Fields identifying a series, e.g. `host`, every series is evaluated on its own
+    #[serde(default)]
+    pub series: Vec<String>,
+    /// Rules evaluated against every event having their field
+    pub rules: Vec<MetricRule>,
+    /// Forwards the samples themselves as well, by default only breaches and recoveries are
+    #[serde(default)]
+    pub forward_samples: bool,
+    /// Series without a sample for this long are forgotten
+    #[serde(with = "humantime_serde", default = "default_expire_after")]
+    pub expire_after: Duration,
+}
+
+/// Evaluates one numeric field, exactly one of the detections has to be configured
+#[derive(Debug, Clone, Deserialize)]
+pub struct MetricRule {
+    /// Name of the rule, part of the emitted events
+    pub name: String,
+    /// Numeric field evaluated, text is accepted if it is a number
+    pub field: String,
+    #[serde(default)]
+    pub threshold: Option<ThresholdRule>,
+    #[serde(default)]
+    pub anomaly: Option<AnomalyRule>,
+    #[serde(default)]
+    pub rate_of_change: Option<RateOfChangeRule>,
+    /// Consecutive breaching samples before a breach is emitted
+    #[serde(default = "default_samples")]
+    pub breach_after: u32,
+    /// Consecutive normal samples before a recovery is emitted
+    #[serde(default = "default_samples")]
+    pub recover_after: u32,
+    /// Classification of the breach events
+    #[serde(default = "default_impact")]
+    pub impact: Impact,
+    #[serde(default = "default_urgency")]
+    pub urgency: Urgency,
+    #[serde(default = "default_priority")]
+    pub priority: Priority,
+}
+
+/// Static limits, the value breaches when it is above `above` or below `below`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ThresholdRule {
+    #[serde(default)]
+    pub above: Option<f64>,
+    #[serde(default)]
+    pub below: Option<f64>,
+}
+
+/// Deviation from an exponentially weighted moving average, measured in standard deviations
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnomalyRule {
+    /// Weight of the newest sample, between 0 and 1
+    #[serde(default = "default_alpha")]
+    pub alpha: f64,
+    /// The value breaches when it is this many standard deviations from the average
+    #[serde(default = "default_z_score")]
+    pub z_score: f64,
+    /// Samples learned before the first evaluation
+    #[serde(default = "default_warmup")]
+    pub warmup: u32,
+    /// Smallest standard deviation assumed, 1% of the average if not set, so a series that was
+    /// constant does not breach on the slightest change
+    #[serde(default)]
+    pub min_deviation: Option<f64>,
+}
+
+/// Change of the value over time, the change per `per` breaches when it is above `above` or
+/// below `below`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateOfChangeRule {
+    #[serde(with = "humantime_serde", default = "default_per")]
+    pub per: Duration,
+    #[serde(default)]
+    pub above: Option<f64>,
+    #[serde(default)]
+    pub below: Option<f64>,
+}
+
+fn default_samples() -> u32 {
+    1
+}
+
+fn default_impact() -> Impact {
+    Impact::MODERATE
+}
+
+fn default_urgency() -> Urgency {
+    Urgency::MEDIUM
+}
+
+fn default_priority() -> Priority {
+    Priority::MEDIUM
+}
+
+fn default_alpha() -> f64 {
+    0.1
+}
+
+fn default_z_score() -> f64 {
+    3.0
+}
+
+fn default_warmup() -> u32 {
+    30
+}
+
+fn default_expire_after() -> Duration {
+    Duration::from_secs(3600)
+}
+
+fn default_per() -> Duration {
+    Duration::from_secs(60)
+}
@@ -0,0 +1,624 @@
+use crate::mapping::convert::text;
+use crate::metrics::{MetricRule, MetricsConfig};
+use crate::{SensorError, Stage};
+use chrono::{DateTime, TimeDelta, Utc};
+use loid_events::prelude::{Event, EventBuilder, Value};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+#[derive(Debug)]
+enum Detection {
+    Threshold {
+        above: Option<f64>,
+        below: Option<f64>,
+    },
+    Anomaly {
+        alpha: f64,
+        z_score: f64,
+        warmup: u32,
+        min_deviation: Option<f64>,
+    },
+    RateOfChange {
+        /// Length of `per` in seconds
+        per: f64,
+        above: Option<f64>,
+        below: Option<f64>,
+    },
+}
+
+#[derive(Debug)]
+struct Rule {
+    config: MetricRule,
+    detection: Detection,
+}
+
+/// Outcome of evaluating one sample
+#[derive(Debug, Clone, Copy)]
+struct Evaluation {
+    breach: bool,
+    baseline: f64,
+    deviation: f64,
+    /// Deviation in standard deviations or change per `per`, depending on the detection
+    score: Option<f64>,
+}
+
+/// What a rule remembers about one series
+#[derive(Debug, Default)]
+struct Series {
+    breached: bool,
+    /// Correlation id of the breach, shared with its recovery
+    incident: Option<Uuid>,
+    /// Time of the latest sample
+    seen: DateTime<Utc>,
+    /// Consecutive samples disagreeing with `breached`
+    pending: u32,
+    /// Exponentially weighted mean and variance and the number of samples they saw
+    mean: f64,
+    variance: f64,
+    samples: u32,
+    /// Previous sample and its time
+    last: Option<(f64, DateTime<Utc>)>,
+}
+
+/// A compiled [`MetricsConfig`] evaluating the numeric fields of the events of a sensor.
+///
+/// Samples are consumed, only a transition of a series from normal to breaching or back is
+/// emitted as an event. Events without any of the evaluated fields pass unchanged.
+#[derive(Debug)]
+pub struct MetricDetector {
+    series_fields: Vec<String>,
+    rules: Vec<Rule>,
+    forward_samples: bool,
+    expire_after: TimeDelta,
+    /// State per rule index and series
+    series: HashMap<(usize, String), Series>,
+    /// Time idle series were last looked for
+    expired: DateTime<Utc>,
+}
+
+fn limits(
+    what: &str,
+    name: &str,
+    above: Option<f64>,
+    below: Option<f64>,
+) -> Result<(), SensorError> {
+    match (above, below) {
+        (None, None) => Err(SensorError::Config(format!(
+            "{what} of rule '{name}' needs above, below or both"
+        ))),
+        (Some(above), Some(below)) if below >= above => Err(SensorError::Config(format!(
+            "{what} of rule '{name}' has below {below} not under above {above}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the value is outside the limits
+fn outside(value: f64, above: Option<f64>, below: Option<f64>) -> bool {
+    above.is_some_and(|above| value > above) || below.is_some_and(|below| value < below)
+}
+
+fn number(value: &Value) -> Option<f64> {
+    let number = match value {
+        Value::Int(value) => *value as f64,
+        Value::Float(value) => *value,
+        Value::String(text) => text.trim().parse().ok()?,
+        _ => return None,
+    };
+    number.is_finite().then_some(number)
+}
+
+impl Rule {
+    fn new(config: &MetricRule) -> Result<Self, SensorError> {
+        let name = &config.name;
+        let detections = [
+            config
+                .threshold
+                .as_ref()
+                .map(|threshold| Detection::Threshold {
+                    above: threshold.above,
+                    below: threshold.below,
+                }),
+            config.anomaly.as_ref().map(|anomaly| Detection::Anomaly {
+                alpha: anomaly.alpha,
+                z_score: anomaly.z_score,
+                warmup: anomaly.warmup,
+                min_deviation: anomaly.min_deviation,
+            }),
+            config
+                .rate_of_change
+                .as_ref()
+                .map(|rate| Detection::RateOfChange {
+                    per: rate.per.as_secs_f64(),
+                    above: rate.above,
+                    below: rate.below,
+                }),
+        ];
+        let mut detections = detections.into_iter().flatten();
+        let detection = match (detections.next(), detections.next()) {
+            (Some(detection), None) => detection,
+            _ => {
+                return Err(SensorError::Config(format!(
+                    "rule '{name}' needs exactly one of threshold, anomaly or rate_of_change"
+                )));
+            }
+        };
+        match &detection {
+            Detection::Threshold { above, below } => limits("threshold", name, *above, *below)?,
+            Detection::Anomaly {
+                alpha,
+                z_score,
+                min_deviation,
+                ..
+            } => {
+                if alpha.is_nan() || *alpha <= 0.0 || *alpha > 1.0 {
+                    return Err(SensorError::Config(format!(
+                        "alpha of rule '{name}' must be greater than 0 and at most 1"
+                    )));
+                }
+                if z_score.is_nan() || *z_score <= 0.0 {
+                    return Err(SensorError::Config(format!(
+                        "z_score of rule '{name}' must be greater than 0"
+                    )));
+                }
+                if min_deviation.is_some_and(|min| min.is_nan() || min <= 0.0) {
+                    return Err(SensorError::Config(format!(
+                        "min_deviation of rule '{name}' must be greater than 0"
+                    )));
+                }
+            }
+            Detection::RateOfChange { per, above, below } => {
+                if *per <= 0.0 {
+                    return Err(SensorError::Config(format!(
+                        "per of rule '{name}' must be greater than 0"
+                    )));
+                }
+                limits("rate_of_change", name, *above, *below)?;
+            }
+        }
+        if config.breach_after == 0 || config.recover_after == 0 {
+            return Err(SensorError::Config(format!(
+                "breach_after and recover_after of rule '{name}' must be at least 1"
+            )));
+        }
+        Ok(Self {
+            config: config.clone(),
+            detection,
+        })
+    }
+
+    fn kind(&self) -> &'static str {
+        match self.detection {
+            Detection::Threshold { .. } => "threshold",
+            Detection::Anomaly { .. } => "anomaly",
+            Detection::RateOfChange { .. } => "rate_of_change",
+        }
+    }
+
+    /// Evaluates a sample and learns from it, `None` while the series has too little history
+    fn evaluate(&self, series: &mut Series, value: f64, time: DateTime<Utc>) -> Option<Evaluation> {
+        match self.detection {
+            Detection::Threshold { above, below } => {
+                // the limit closest to the value is the one breached or recovered from
+                let baseline = [above, below]
+                    .into_iter()
+                    .flatten()
+                    .min_by(|a, b| (value - a).abs().total_cmp(&(value - b).abs()))?;
+                Some(Evaluation {
+                    breach: outside(value, above, below),
+                    baseline,
+                    deviation: value - baseline,
+                    score: None,
+                })
+            }
+            Detection::Anomaly {
+                alpha,
+                z_score,
+                warmup,
+                min_deviation,
+            } => {
+                let evaluation = (series.samples >= warmup.max(1)).then(|| {
+                    let deviation = value - series.mean;
+                    // a series that was constant has no deviation to measure against
+                    let min_deviation = min_deviation
+                        .unwrap_or(series.mean.abs() * 0.01)
+                        .max(f64::EPSILON);
+                    let deviations = deviation / series.variance.sqrt().max(min_deviation);
+                    Evaluation {
+                        breach: deviations.abs() >= z_score,
+                        baseline: series.mean,
+                        deviation,
+                        score: Some(deviations),
+                    }
+                });
+                // the baseline keeps adapting, a lasting change becomes the new normal
+                if series.samples == 0 {
+                    series.mean = value;
+                } else {
+                    let difference = value - series.mean;
+                    let increment = alpha * difference;
+                    series.mean += increment;
+                    series.variance = (1.0 - alpha) * (series.variance + difference * increment);
+                }
+                series.samples = series.samples.saturating_add(1);
+                evaluation
+            }
+            Detection::RateOfChange { per, above, below } => {
+                let last = series.last.replace((value, time));
+                let (previous, previous_time) = last?;
+                let seconds = (time - previous_time).as_seconds_f64();
+                if seconds <= 0.0 {
+                    // out of order or duplicate sample, keep the earlier one as reference
+                    series.last = last;
+                    return None;
+                }
+                let rate = (value - previous) / seconds * per;
+                Some(Evaluation {
+                    breach: outside(rate, above, below),
+                    baseline: previous,
+                    deviation: value - previous,
+                    score: Some(rate),
+                })
+            }
+        }
+    }
+}
+
+impl MetricDetector {
+    pub fn new(config: &MetricsConfig) -> Result<Self, SensorError> {
+        let expire_after = TimeDelta::from_std(config.expire_after)
+            .ok()
+            .filter(|expire_after| *expire_after > TimeDelta::zero())
+            .ok_or_else(|| {
+                SensorError::Config("expire_after must be greater than 0".to_string())
+            })?;
+        if config.rules.is_empty() {
+            return Err(SensorError::Config(
+                "at least one rule is required".to_string(),
+            ));
+        }
+        let mut names = HashSet::new();
+        for rule in &config.rules {
+            if rule.name.is_empty() || rule.field.is_empty() {
+                return Err(SensorError::Config(
+                    "rules need a name and a field".to_string(),
+                ));
+            }
+            if !names.insert(rule.name.as_str()) {
+                return Err(SensorError::Config(format!(
+                    "rule name '{}' is used twice",
+                    rule.name
+                )));
+            }
+        }
+        Ok(Self {
+            series_fields: config.series.clone(),
+            rules: config
+                .rules
+                .iter()
+                .map(Rule::new)
+                .collect::<Result<_, _>>()?,
+            forward_samples: config.forward_samples,
+            expire_after,
+            series: HashMap::new(),
+            expired: DateTime::UNIX_EPOCH,
+        })
+    }
+
+    /// Evaluates the event, returns the breaches and recoveries it caused, preceded by the event
+    /// itself if it is no sample or samples are forwarded
+    pub fn apply(&mut self, event: Event) -> Vec<Event> {
+        let mut transitions = self.tick(event.created_at);
+        let ticked = transitions.len();
+        let key = self
+            .series_fields
+            .iter()
+            .map(|field| event.fields.get(field).and_then(text).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut sampled = false;
+        for (index, rule) in self.rules.iter().enumerate() {
+            let Some(value) = event.fields.get(&rule.config.field).and_then(number) else {
+                continue;
+            };
+            sampled = true;
+            let series = self.series.entry((index, key.clone())).or_default();
+            series.seen = series.seen.max(event.created_at);
+            let Some(evaluation) = rule.evaluate(series, value, event.created_at) else {
+                continue;
+            };
+
+            if evaluation.breach == series.breached {
+                series.pending = 0;
+                continue;
+            }
+            series.pending += 1;
+            let needed = match series.breached {
+                true => rule.config.recover_after,
+                false => rule.config.breach_after,
+            };
+            if series.pending >= needed {
+                series.breached = evaluation.breach;
+                series.pending = 0;
+                // every breach is an incident of its own, the recovery closes it
+                let incident = match evaluation.breach {
+                    true => *series.incident.insert(Uuid::now_v7()),
+                    false => series.incident.take().unwrap_or_else(Uuid::now_v7),
+                };
+                transitions.push(self.transition(rule, incident, &event, value, evaluation));
+            }
+        }
+
+        if !sampled || self.forward_samples {
+            transitions.insert(ticked, event);
+        }
+        transitions
+    }
+
+    /// Forgets the series without a sample for `expire_after`, a breaching series is forgotten
+    /// without a recovery
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        // looking for idle series on every sample would cost as much as the samples themselves
+        if now - self.expired < self.expire_after / 4 {
+            return Vec::new();
+        }
+        self.expired = now;
+        let idle_since = now - self.expire_after;
+        self.series.retain(|_, series| series.seen > idle_since);
+        Vec::new()
+    }
+
+    /// Event of a series starting or ending to breach a rule, both share the incident as
+    /// correlation id
+    fn transition(
+        &self,
+        rule: &Rule,
+        incident: Uuid,
+        sample: &Event,
+        value: f64,
+        evaluation: Evaluation,
+    ) -> Event {
+        let config = &rule.config;
+        let mut builder = EventBuilder::new();
+        builder
+            .with_source(sample.source.clone())
+            .with_correlation_id(incident)
+            .with_impact(config.impact)
+            .with_urgency(config.urgency)
+            .with_priority(config.priority)
+            .with_text_field("rule", &config.name)
+            .with_text_field("detection", rule.kind())
+            .with_text_field("metric", &config.field)
+            .with_float_field("value", value)
+            .with_float_field("baseline", evaluation.baseline)
+            .with_float_field("deviation", evaluation.deviation)
+            .with_text_field(
+                "state",
+                if evaluation.breach {
+                    "breach"
+                } else {
+                    "recovery"
+                },
+            );
+        match rule.detection {
+            Detection::Anomaly { .. } => {
+                if let Some(score) = evaluation.score {
+                    builder.with_float_field("z_score", score);
+                }
+            }
+            Detection::RateOfChange { .. } => {
+                if let Some(score) = evaluation.score {
+                    builder.with_float_field("rate", score);
+                }
+            }
+            Detection::Threshold { .. } => {}
+        }
+        for field in &self.series_fields {
+            if let Some(value) = sample.fields.get(field) {
+                builder.with_field(field, value.clone());
+            }
+        }
+        if !evaluation.breach {
+            builder.with_resolved_at(sample.created_at);
+        }
+        builder.build()
+    }
+}
+
+impl Stage for MetricDetector {
+    fn apply(&mut self, event: Event) -> Vec<Event> {
+        MetricDetector::apply(self, event)
+    }
+
+    fn tick(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        MetricDetector::tick(self, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use loid_events::prelude::Impact;
+
+    fn detector(json: &str) -> Result<MetricDetector, SensorError> {
+        MetricDetector::new(&serde_json::from_str(json).unwrap())
+    }
+
+    fn sample(host: &str, field: &str, value: f64, seconds: i64) -> Event {
+        let mut event = EventBuilder::new()
+            .with_text_field("host", host)
+            .with_float_field(field, value)
+            .build();
+        event.created_at = DateTime::UNIX_EPOCH + TimeDelta::seconds(seconds);
+        event
+    }
+
+    /// Feeds the values as samples one second apart, returns the states emitted
+    fn states(detector: &mut MetricDetector, field: &str, values: &[f64]) -> Vec<String> {
+        values
+            .iter()
+            .enumerate()
+            .flat_map(|(second, value)| {
+                detector.apply(sample("db-1", field, *value, second as i64))
+            })
+            .map(|event| match &event.fields["state"] {
+                Value::String(state) => state.clone(),
+                other => panic!("unexpected state {other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for json in [
+            r#"{"rules": []}"#,
+            r#"{"rules": [{"name": "cpu", "field": "cpu"}]}"#,
+            r#"{"rules": [{"name": "cpu", "field": "cpu", "threshold": {}}]}"#,
+            r#"{"rules": [{"name": "cpu", "field": "cpu", "threshold": {"above": 10, "below": 20}}]}"#,
+            r#"{"rules": [{"name": "cpu", "field": "cpu", "threshold": {"above": 90}, "anomaly": {}}]}"#,
+            r#"{"rules": [{"name": "cpu", "field": "cpu", "anomaly": {"alpha": 0}}]}"#,
+            r#"{"rules": [{"name": "cpu", "field": "cpu", "anomaly": {"min_deviation": 0}}]}"#,
+            r#"{"expire_after": "0s", "rules": [{"name": "cpu", "field": "cpu", "threshold": {"above": 90}}]}"#,
+            r#"{"rules": [{"name": "cpu", "field": "cpu", "rate_of_change": {"per": "0s", "above": 1}}]}"#,
+            r#"{"rules": [{"name": "cpu", "field": "cpu", "threshold": {"above": 90}, "breach_after": 0}]}"#,
+            r#"{"rules": [{"name": "cpu", "field": "cpu", "threshold": {"above": 90}}, {"name": "cpu", "field": "load", "threshold": {"above": 4}}]}"#,
+        ] {
+            assert!(
+                matches!(detector(json), Err(SensorError::Config(_))),
+                "{json}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_threshold_emits_breach_and_recovery() {
+        let mut detector = detector(
+            r#"{"series": ["host"], "rules": [{"name": "cpu-high", "field": "cpu", "threshold": {"above": 90}, "breach_after": 2, "impact": "SIGNIFICANT"}]}"#,
+        )
+        .unwrap();
+
+        // events without the field pass, samples are consumed
+        let other = EventBuilder::new()
+            .with_text_field("message", "hello")
+            .build();
+        assert_eq!(detector.apply(other).len(), 1);
+        assert!(detector.apply(sample("db-1", "cpu", 95.0, 0)).is_empty());
+
+        let breach = detector.apply(sample("db-1", "cpu", 97.0, 1));
+        assert_eq!(breach.len(), 1);
+        let breach = &breach[0];
+        assert_eq!(breach.fields["state"], Value::from("breach"));
+        assert_eq!(breach.fields["host"], Value::from("db-1"));
+        assert_eq!(breach.fields["baseline"], Value::Float(90.0));
+        assert_eq!(breach.fields["deviation"], Value::Float(7.0));
+        assert_eq!(breach.impact, Impact::SIGNIFICANT);
+
+        // another host is another series
+        assert!(detector.apply(sample("db-2", "cpu", 99.0, 2)).is_empty());
+        assert!(detector.apply(sample("db-1", "cpu", 99.0, 3)).is_empty());
+
+        let recovery = detector.apply(sample("db-1", "cpu", 40.0, 4));
+        assert_eq!(recovery.len(), 1);
+        assert_eq!(recovery[0].fields["state"], Value::from("recovery"));
+        assert_eq!(recovery[0].correlation_id, breach.correlation_id);
+        assert!(recovery[0].resolved_at.is_some());
+
+        // the next breach is another incident
+        detector.apply(sample("db-1", "cpu", 95.0, 5));
+        let again = detector.apply(sample("db-1", "cpu", 95.0, 6));
+        assert_eq!(again[0].fields["state"], Value::from("breach"));
+        assert_ne!(again[0].correlation_id, breach.correlation_id);
+    }
+
+    #[test]
+    fn test_idle_series_expire() {
+        let mut detector = detector(
+            r#"{"series": ["host"], "expire_after": "1h", "rules": [{"name": "cpu-high", "field": "cpu", "threshold": {"above": 90}}]}"#,
+        )
+        .unwrap();
+        detector.apply(sample("db-1", "cpu", 50.0, 0));
+        detector.apply(sample("db-2", "cpu", 50.0, 1800));
+        assert_eq!(detector.series.len(), 2);
+
+        detector.tick(DateTime::UNIX_EPOCH + TimeDelta::seconds(3000));
+        assert_eq!(detector.series.len(), 2);
+        detector.tick(DateTime::UNIX_EPOCH + TimeDelta::seconds(4000));
+        assert_eq!(detector.series.len(), 1);
+        assert!(detector.series.keys().all(|(_, key)| key == "db-2"));
+    }
+
+    #[test]
+    fn test_anomaly_after_warmup() {
+        let mut detector = detector(
+            r#"{"rules": [{"name": "latency", "field": "latency", "anomaly": {"alpha": 0.2, "z_score": 3, "warmup": 5}}]}"#,
+        )
+        .unwrap();
+        let values = [100.0, 102.0, 98.0, 101.0, 99.0, 100.0, 101.0];
+        assert!(states(&mut detector, "latency", &values).is_empty());
+
+        let events = detector.apply(sample("db-1", "latency", 160.0, 10));
+        assert_eq!(events.len(), 1);
+        let Value::Float(z_score) = events[0].fields["z_score"] else {
+            panic!("z_score is not a float");
+        };
+        assert!(z_score > 3.0);
+        let Value::Float(baseline) = events[0].fields["baseline"] else {
+            panic!("baseline is not a float");
+        };
+        assert!((baseline - 100.0).abs() < 2.0);
+
+        // the baseline adapts, back to normal is a recovery
+        assert_eq!(
+            states(&mut detector, "latency", &[100.0, 100.0]),
+            vec!["recovery"]
+        );
+    }
+
+    #[test]
+    fn test_anomaly_of_constant_series() {
+        let mut detector = detector(
+            r#"{"rules": [{"name": "latency", "field": "latency", "anomaly": {"warmup": 5}}]}"#,
+        )
+        .unwrap();
+        // one percent of the average is the smallest deviation assumed
+        assert!(states(&mut detector, "latency", &[100.0; 6]).is_empty());
+        assert!(states(&mut detector, "latency", &[102.0]).is_empty());
+
+        let events = detector.apply(sample("db-1", "latency", 110.0, 10));
+        let Value::Float(z_score) = events[0].fields["z_score"] else {
+            panic!("z_score is not a float");
+        };
+        assert!(z_score.is_finite() && z_score > 3.0);
+
+        let mut absolute = self::detector(
+            r#"{"rules": [{"name": "errors", "field": "errors", "anomaly": {"warmup": 5, "min_deviation": 5}}]}"#,
+        )
+        .unwrap();
+        assert!(states(&mut absolute, "errors", &[0.0, 0.0, 0.0, 0.0, 0.0, 10.0]).is_empty());
+        assert_eq!(states(&mut absolute, "errors", &[30.0]), vec!["breach"]);
+    }
+
+    #[test]
+    fn test_rate_of_change() {
+        let mut detector = detector(
+            r#"{"rules": [{"name": "queue-growth", "field": "depth", "rate_of_change": {"per": "1m", "above": 600}}]}"#,
+        )
+        .unwrap();
+        // one sample per second, 600 per minute is 10 per sample
+        assert_eq!(
+            states(&mut detector, "depth", &[0.0, 5.0, 25.0, 45.0, 50.0]),
+            vec!["breach", "recovery"]
+        );
+
+        let mut forwarding = self::detector(
+            r#"{"forward_samples": true, "rules": [{"name": "queue-growth", "field": "depth", "rate_of_change": {"above": 10}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(forwarding.apply(sample("db-1", "depth", 1.0, 0)).len(), 1);
+        let events = forwarding.apply(sample("db-1", "depth", 100.0, 30));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].fields["rate"], Value::Float(198.0));
+    }
+}
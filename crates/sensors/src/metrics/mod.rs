@@ -0,0 +1,34 @@
+//! Threshold and anomaly detection on numeric fields of the events of a sensor.
+//!
+//! The `metrics` section sits next to the `sensor` section and works the same for every sensor
+//! type, it sees the events after the [mapping](crate::mapping):
+//!
+//! ```yaml
+//! metrics:
+//!     series: [host]
+//!     rules:
+//!         - name: cpu-high
+//!           field: cpu_percent
+//!           threshold:
+//!               above: 90
+//!           breach_after: 3
+//!         - name: latency-anomaly
+//!           field: latency_ms
+//!           anomaly:
+//!               alpha: 0.1
+//!               z_score: 3
+//!         - name: queue-growth
+//!           field: queue_depth
+//!           rate_of_change:
+//!               per: 1m
+//!               above: 500
+//! ```
+//!
+//! Samples are not forwarded, a series only produces an event when it starts or stops breaching
+//! a rule.
+
+pub(crate) mod config;
+pub(crate) mod detector;
+
+pub use config::{AnomalyRule, MetricRule, MetricsConfig, RateOfChangeRule, ThresholdRule};
+pub use detector::MetricDetector;
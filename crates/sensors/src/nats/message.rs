@@ -0,0 +1,186 @@
+use crate::SensorError;
+use crate::adapter::PayloadAdapter;
+use crate::nats::subject::SubjectPattern;
+use async_nats::HeaderMap;
+use loid_events::prelude::{Event, EventBuilder, Source, Value};
+use std::collections::HashMap;
+
+/// A received message, independent of core NATS or JetStream
+pub(crate) struct Message<'a> {
+    pub subject: &'a str,
+    pub headers: Option<&'a HeaderMap>,
+    pub payload: &'a [u8],
+    /// Stream and stream sequence of a JetStream message
+    pub stream: Option<(&'a str, u64)>,
+}
+
+/// Headers of a message as event values, repeated headers become lists
+pub(crate) fn headers(map: &HeaderMap) -> HashMap<String, Value> {
+    map.iter()
+        .map(|(name, values)| {
+            let value = match values.as_slice() {
+                [value] => Value::from(value.as_str()),
+                values => Value::List(
+                    values
+                        .iter()
+                        .map(|value| Value::from(value.as_str()))
+                        .collect(),
+                ),
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+impl Message<'_> {
+    /// Turns the message into events, with an adapter or from a JSON object, and adds the fields
+    /// captured by the first matching subject pattern
+    pub fn normalize(
+        &self,
+        adapter: Option<PayloadAdapter>,
+        patterns: &[SubjectPattern],
+    ) -> Result<Vec<Event>, SensorError> {
+        let headers = self.headers.map(headers).unwrap_or_default();
+        let mut events = match adapter {
+            Some(adapter) => {
+                // adapters look up header names in lowercase, like HTTP headers
+                let text = headers
+                    .iter()
+                    .filter_map(|(name, value)| match value {
+                        Value::String(text) => Some((name.to_ascii_lowercase(), text.clone())),
+                        _ => None,
+                    })
+                    .collect();
+                adapter.normalize(self.payload, &text)?
+            }
+            None => vec![self.event()?],
+        };
+
+        let tokens = patterns
+            .iter()
+            .find_map(|pattern| pattern.fields(self.subject))
+            .unwrap_or_default();
+        for event in &mut events {
+            event
+                .fields
+                .insert("subject".to_string(), Value::from(self.subject));
+            for (name, token) in &tokens {
+                event
+                    .fields
+                    .insert(name.clone(), Value::from(token.as_str()));
+            }
+            if !headers.is_empty() {
+                event
+                    .fields
+                    .insert("headers".to_string(), Value::Map(headers.clone()));
+            }
+            if let Some((stream, sequence)) = self.stream {
+                event
+                    .fields
+                    .insert("stream".to_string(), Value::from(stream));
+                event.fields.insert(
+                    "stream_sequence".to_string(),
+                    Value::Int(sequence.try_into().unwrap_or(i64::MAX)),
+                );
+            }
+        }
+        Ok(events)
+    }
+
+    /// Event of a message without adapter, the payload has to be a JSON object
+    fn event(&self) -> Result<Event, SensorError> {
+        let fields: HashMap<String, Value> = serde_json::from_slice(self.payload)
+            .map_err(|e| SensorError::Payload(e.to_string()))?;
+
+        let mut builder = EventBuilder::new();
+        builder.with_source(Source {
+            system: "nats".to_string(),
+            source_id: Some(self.subject.to_string()),
+        });
+        for (key, value) in fields {
+            builder.with_field(&key, value);
+        }
+        Ok(builder.build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use loid_events::prelude::Impact;
+
+    #[test]
+    fn test_normalize_json_object() {
+        let mut map = HeaderMap::new();
+        map.insert("Nats-Msg-Id", "m-1");
+        map.append("tag", "prod");
+        map.append("tag", "db");
+        let patterns = vec![
+            SubjectPattern::parse("metrics.>").unwrap(),
+            SubjectPattern::parse("alerts.{site}.{host}.>").unwrap(),
+        ];
+        let message = Message {
+            subject: "alerts.fra1.db-1.cpu",
+            headers: Some(&map),
+            payload: br#"{"message": "load too high", "load": 12.5}"#,
+            stream: Some(("ALERTS", 42)),
+        };
+
+        let events = message.normalize(None, &patterns).unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.source.system, "nats");
+        assert_eq!(
+            event.source.source_id.as_deref(),
+            Some("alerts.fra1.db-1.cpu")
+        );
+        assert_eq!(event.fields["load"], Value::Float(12.5));
+        assert_eq!(event.fields["site"], Value::from("fra1"));
+        assert_eq!(event.fields["host"], Value::from("db-1"));
+        assert_eq!(event.fields["stream"], Value::from("ALERTS"));
+        assert_eq!(event.fields["stream_sequence"], Value::Int(42));
+        let Value::Map(headers) = &event.fields["headers"] else {
+            panic!("headers are not a map: {:?}", event.fields["headers"]);
+        };
+        assert_eq!(headers["Nats-Msg-Id"], Value::from("m-1"));
+        assert_eq!(
+            headers["tag"],
+            Value::List(vec![Value::from("prod"), Value::from("db")])
+        );
+
+        let message = Message {
+            subject: "alerts",
+            headers: None,
+            payload: b"[1, 2]",
+            stream: None,
+        };
+        assert!(matches!(
+            message.normalize(None, &patterns),
+            Err(SensorError::Payload(_))
+        ));
+    }
+
+    #[test]
+    fn test_normalize_with_adapter() {
+        let mut map = HeaderMap::new();
+        map.insert("X-GitHub-Event", "deployment_status");
+        let message = Message {
+            subject: "webhooks.github",
+            headers: Some(&map),
+            payload: br#"{
+                "deployment_status": {"state": "failure"},
+                "deployment": {"environment": "production"},
+                "repository": {"full_name": "loid-labs/loid"}
+            }"#,
+            stream: None,
+        };
+        let events = message
+            .normalize(Some(PayloadAdapter::Github), &[])
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].source.system, "github");
+        assert_eq!(events[0].impact, Impact::SIGNIFICANT);
+        assert_eq!(events[0].fields["subject"], Value::from("webhooks.github"));
+        assert!(!events[0].fields.contains_key("stream"));
+    }
+}
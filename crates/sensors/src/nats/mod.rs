@@ -0,0 +1,28 @@
+//! Subscriber of NATS subjects or consumer of a JetStream stream.
+//!
+//! ```yaml
+//! sensor:
+//!     type: nats
+//!     url: nats://nats:4222
+//!     subjects:
+//!         - "alerts.>"
+//!     tokens:
+//!         - "alerts.{site}.{host}.>"
+//!     jetstream:
+//!         stream: ALERTS
+//!         consumer: loid
+//!         deliver: new
+//! ```
+//!
+//! Without `jetstream` the sensor subscribes to the subjects, optionally in a queue group, and
+//! messages are lost while it is not running. With `jetstream` it consumes from a durable pull
+//! consumer and acknowledges a message once its events were handed off to the sink, messages
+//! that cannot be turned into events are terminated so they are not delivered again.
+
+pub(crate) mod config;
+pub(crate) mod message;
+pub(crate) mod sensor;
+pub(crate) mod subject;
+
+pub use config::{DeliverFrom, JetStreamConfig, NatsSensorConfig};
+pub use sensor::NatsSensor;
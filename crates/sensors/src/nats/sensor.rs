@@ -0,0 +1,585 @@
+use crate::buffer::{Handoff, Redelivery, hand_off};
+use crate::nats::NatsSensorConfig;
+use crate::nats::config::{DeliverFrom, JetStreamConfig};
+use crate::nats::message::Message;
+use crate::nats::subject::SubjectPattern;
+use crate::{EventSink, HealthState, LifecycleState, Sensor, SensorError, StatusHandle};
+use async_nats::connection::State;
+use async_nats::jetstream::AckKind;
+use async_nats::jetstream::consumer::{AckPolicy, DeliverPolicy, pull};
+use async_nats::{Client, ConnectOptions};
+use futures_util::StreamExt;
+use futures_util::stream::select_all;
+
+/// Consumes events from core NATS subjects or a durable JetStream consumer.
+pub struct NatsSensor {
+    key: String,
+    config: NatsSensorConfig,
+    patterns: Vec<SubjectPattern>,
+    status: StatusHandle,
+}
+
+fn nats_error(error: impl std::error::Error + Send + Sync + 'static) -> SensorError {
+    SensorError::Io(std::io::Error::other(error))
+}
+
+/// Health of a JetStream consumer from its pending and unacknowledged messages
+fn consumer_health(
+    num_pending: u64,
+    num_ack_pending: usize,
+    paused: bool,
+    config: &JetStreamConfig,
+) -> HealthState {
+    if paused {
+        HealthState::Waiting
+    } else if num_pending > config.max_lag || num_ack_pending as i64 >= config.max_ack_pending {
+        HealthState::Degraded
+    } else {
+        HealthState::Healthy
+    }
+}
+
+fn deliver_policy(config: &JetStreamConfig) -> Result<DeliverPolicy, SensorError> {
+    if let Some(start_sequence) = config.start_sequence {
+        return Ok(DeliverPolicy::ByStartSequence { start_sequence });
+    }
+    if let Some(start_time) = config.start_time {
+        let nanos = start_time
+            .timestamp_nanos_opt()
+            .ok_or_else(|| SensorError::Config(format!("start_time {start_time} out of range")))?;
+        let start_time = time::OffsetDateTime::from_unix_timestamp_nanos(nanos.into())
+            .map_err(|e| SensorError::Config(format!("invalid start_time: {e}")))?;
+        return Ok(DeliverPolicy::ByStartTime { start_time });
+    }
+    Ok(match config.deliver {
+        DeliverFrom::All => DeliverPolicy::All,
+        DeliverFrom::New => DeliverPolicy::New,
+        DeliverFrom::Last => DeliverPolicy::Last,
+        DeliverFrom::LastPerSubject => DeliverPolicy::LastPerSubject,
+    })
+}
+
+impl NatsSensor {
+    pub fn new(key: impl Into<String>, config: NatsSensorConfig) -> Result<Self, SensorError> {
+        config.validate()?;
+        let patterns = config
+            .tokens
+            .iter()
+            .map(|pattern| SubjectPattern::parse(pattern))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            key: key.into(),
+            config,
+            patterns,
+            status: StatusHandle::default(),
+        })
+    }
+
+    pub fn config(&self) -> &NatsSensorConfig {
+        &self.config
+    }
+
+    /// Hands the events of a message off to the sink, counts the events lost if the buffer only
+    /// dropped some of them
+    async fn deliver(
+        &self,
+        sink: &EventSink,
+        message: Message<'_>,
+    ) -> Result<Handoff, SensorError> {
+        let events = message.normalize(self.config.adapter, &self.patterns)?;
+        let handoff = hand_off(sink, events).await;
+        if let Handoff::Lost(lost) = handoff {
+            tracing::warn!(sensor = %self.key, lost, "buffer is full, events of the message are lost");
+            self.status.add_lost_events(lost as u64);
+        }
+        Ok(handoff)
+    }
+
+    /// Consumes core NATS subjects until the sink is closed, messages are not acknowledged
+    async fn subscribe(&self, client: &Client, sink: &EventSink) -> Result<(), SensorError> {
+        let mut subscribers = Vec::with_capacity(self.config.subjects.len());
+        for subject in &self.config.subjects {
+            let subscriber = match &self.config.queue_group {
+                Some(group) => client.queue_subscribe(subject.clone(), group.clone()).await,
+                None => client.subscribe(subject.clone()).await,
+            };
+            subscribers.push(subscriber.map_err(nats_error)?);
+        }
+        let mut messages = select_all(subscribers);
+        let mut health = tokio::time::interval(self.config.health_interval);
+        tracing::info!(sensor = %self.key, subjects = ?self.config.subjects, "subscribed to NATS subjects");
+
+        loop {
+            let message = tokio::select! {
+                message = messages.next() => message,
+                _ = health.tick() => {
+                    // the client reconnects by itself, subscriptions survive the reconnect
+                    self.status.set_health(match client.connection_state() {
+                        State::Connected => HealthState::Healthy,
+                        _ => HealthState::ResourceDown,
+                    });
+                    continue;
+                }
+                _ = sink.closed() => return Ok(()),
+            };
+            let Some(message) = message else {
+                return Err(SensorError::Io(std::io::Error::other(
+                    "subscriptions were closed",
+                )));
+            };
+            let message = Message {
+                subject: message.subject.as_str(),
+                headers: message.headers.as_ref(),
+                payload: &message.payload,
+                stream: None,
+            };
+            match self.deliver(sink, message).await {
+                Ok(Handoff::Delivered | Handoff::Lost(_)) => {}
+                Ok(Handoff::Dropped) => {
+                    tracing::debug!(sensor = %self.key, "buffer is full, dropping message")
+                }
+                Ok(Handoff::Closed) => return Ok(()),
+                Err(e) => {
+                    tracing::warn!(sensor = %self.key, error = %e, "dropping invalid message")
+                }
+            }
+        }
+    }
+
+    /// Consumes from a durable JetStream consumer until the sink is closed, messages are
+    /// acknowledged once their events were handed off to the sink
+    async fn consume(
+        &self,
+        client: Client,
+        jetstream: &JetStreamConfig,
+        sink: &EventSink,
+    ) -> Result<(), SensorError> {
+        let context = async_nats::jetstream::new(client);
+        let stream = context
+            .get_stream(&jetstream.stream)
+            .await
+            .map_err(nats_error)?;
+        let config = pull::Config {
+            durable_name: Some(jetstream.consumer.clone()),
+            filter_subjects: self.config.subjects.clone(),
+            deliver_policy: deliver_policy(jetstream)?,
+            ack_policy: AckPolicy::Explicit,
+            ack_wait: jetstream.ack_wait,
+            max_ack_pending: jetstream.max_ack_pending,
+            ..Default::default()
+        };
+        let mut consumer = stream
+            .get_or_create_consumer(&jetstream.consumer, config)
+            .await
+            .map_err(nats_error)?;
+        let mut messages = consumer.messages().await.map_err(nats_error)?;
+        let mut health = tokio::time::interval(self.config.health_interval);
+        tracing::info!(
+            sensor = %self.key,
+            stream = %jetstream.stream,
+            consumer = %jetstream.consumer,
+            "consuming from JetStream"
+        );
+
+        let mut redelivery = Redelivery::default();
+        loop {
+            let message = tokio::select! {
+                message = messages.next() => message,
+                _ = health.tick() => {
+                    let info = consumer.info().await.map_err(nats_error)?;
+                    self.status.set_health(consumer_health(
+                        info.num_pending,
+                        info.num_ack_pending,
+                        info.paused,
+                        jetstream,
+                    ));
+                    continue;
+                }
+                _ = sink.closed() => return Ok(()),
+            };
+            let Some(message) = message else {
+                return Err(SensorError::Io(std::io::Error::other(
+                    "consumer was deleted",
+                )));
+            };
+            let message = message.map_err(nats_error)?;
+            let info = message
+                .info()
+                .map_err(|e| SensorError::Io(std::io::Error::other(e)))?;
+            let normalized = Message {
+                subject: message.subject.as_str(),
+                headers: message.headers.as_ref(),
+                payload: &message.payload,
+                stream: Some((info.stream, info.stream_sequence)),
+            };
+            let ack = match self.deliver(sink, normalized).await {
+                Ok(Handoff::Delivered | Handoff::Lost(_)) => {
+                    redelivery.reset();
+                    message.ack().await
+                }
+                Ok(Handoff::Dropped) => {
+                    // the server delivers the message again after the delay, pulling pauses
+                    // meanwhile so the following messages are not dropped right away as well
+                    let delay = redelivery.delay();
+                    tracing::debug!(sensor = %self.key, ?delay, "buffer is full, redelivering message");
+                    let ack = message.ack_with(AckKind::Nak(Some(delay))).await;
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = sink.closed() => {}
+                    }
+                    ack
+                }
+                Ok(Handoff::Closed) => {
+                    // the sensor is stopping, the message is not at fault
+                    let _ = message.ack_with(AckKind::Nak(None)).await;
+                    return Ok(());
+                }
+                Err(e) => {
+                    tracing::warn!(sensor = %self.key, error = %e, "terminating invalid message");
+                    message.ack_with(AckKind::Term).await
+                }
+            };
+            ack.map_err(|e| SensorError::Io(std::io::Error::other(e)))?;
+        }
+    }
+
+    /// Connects and consumes until the sink is closed, returns early if the connection fails
+    async fn connect(&self, sink: &EventSink) -> Result<(), SensorError> {
+        let client = ConnectOptions::new()
+            .name(format!("loid-{}", self.key))
+            .connect(self.config.url.expose())
+            .await
+            .map_err(nats_error)?;
+        self.status.set_health(HealthState::Healthy);
+        let result = match &self.config.jetstream {
+            Some(jetstream) => self.consume(client.clone(), jetstream, sink).await,
+            None => self.subscribe(&client, sink).await,
+        };
+        if let Err(e) = client.drain().await {
+            tracing::debug!(sensor = %self.key, error = %e, "failed to drain NATS connection");
+        }
+        result
+    }
+}
+
+impl Sensor for NatsSensor {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn status_handle(&self) -> &StatusHandle {
+        &self.status
+    }
+
+    async fn run(&self, sink: EventSink) -> Result<(), SensorError> {
+        self.status.set_lifecycle(LifecycleState::Running);
+        while !sink.is_closed() {
+            if let Err(e) = self.connect(&sink).await {
+                self.status.set_health(HealthState::ResourceDown);
+                tracing::warn!(
+                    sensor = %self.key,
+                    error = %e,
+                    delay = ?self.config.reconnect_delay,
+                    "NATS connection failed, reconnecting"
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(self.config.reconnect_delay) => {}
+                    _ = sink.closed() => {}
+                }
+            }
+        }
+        self.status.set_lifecycle(LifecycleState::Stopped);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::PayloadAdapter;
+    use crate::buffer::{BufferConfig, OverflowPolicy};
+    use loid_events::prelude::{Secret, Value};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    /// Server the ignored tests run against, e.g. started with
+    /// `docker run -p 4222:4222 nats:2 -js`
+    fn server_url() -> String {
+        std::env::var("LOID_NATS_URL").unwrap_or_else(|_| "nats://127.0.0.1:4222".to_string())
+    }
+
+    fn config(subjects: &[&str], jetstream: Option<JetStreamConfig>) -> NatsSensorConfig {
+        NatsSensorConfig {
+            url: Secret::new(server_url()),
+            subjects: subjects.iter().map(|s| s.to_string()).collect(),
+            queue_group: None,
+            jetstream,
+            tokens: vec!["alerts.{site}.>".to_string()],
+            adapter: None,
+            health_interval: Duration::from_millis(100),
+            reconnect_delay: Duration::from_millis(100),
+        }
+    }
+
+    fn jetstream(stream: &str) -> JetStreamConfig {
+        serde_json::from_value(serde_json::json!({"stream": stream, "consumer": "loid"})).unwrap()
+    }
+
+    async fn run(sensor: &Arc<NatsSensor>, sink: EventSink) -> tokio::task::JoinHandle<()> {
+        let handle = tokio::spawn({
+            let sensor = sensor.clone();
+            async move { sensor.run(sink).await.unwrap() }
+        });
+        for _ in 0..250 {
+            if sensor.status().health == HealthState::Healthy {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        handle
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let config: NatsSensorConfig = serde_json::from_str(
+            r#"{"subjects": ["alerts.>"], "jetstream": {"stream": "ALERTS", "consumer": "loid", "deliver": "last_per_subject"}}"#,
+        )
+        .unwrap();
+        let jetstream = config.jetstream.clone().unwrap();
+        assert_eq!(jetstream.deliver, DeliverFrom::LastPerSubject);
+        assert_eq!(jetstream.max_ack_pending, 1000);
+        assert!(config.validate().is_ok());
+
+        let mut invalid = config.clone();
+        invalid.queue_group = Some("loid".to_string());
+        assert!(invalid.validate().is_err());
+        let mut invalid = config.clone();
+        invalid.jetstream.as_mut().unwrap().start_sequence = Some(10);
+        invalid.jetstream.as_mut().unwrap().start_time = Some(chrono::Utc::now());
+        assert!(invalid.validate().is_err());
+        let mut invalid = config.clone();
+        invalid.tokens = vec!["alerts.{}".to_string()];
+        assert!(matches!(
+            NatsSensor::new("nats", invalid),
+            Err(SensorError::Config(_))
+        ));
+        let mut invalid = config;
+        invalid.jetstream = None;
+        invalid.subjects.clear();
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_deliver_policy_and_health() {
+        let mut config = jetstream("ALERTS");
+        assert_eq!(deliver_policy(&config).unwrap(), DeliverPolicy::All);
+        config.start_sequence = Some(7);
+        assert_eq!(
+            deliver_policy(&config).unwrap(),
+            DeliverPolicy::ByStartSequence { start_sequence: 7 }
+        );
+        config.start_sequence = None;
+        config.start_time = Some("2025-01-01T00:00:00Z".parse().unwrap());
+        let DeliverPolicy::ByStartTime { start_time } = deliver_policy(&config).unwrap() else {
+            panic!("expected a start time");
+        };
+        assert_eq!(start_time.unix_timestamp(), 1_735_689_600);
+
+        config.max_lag = 100;
+        config.max_ack_pending = 10;
+        assert_eq!(consumer_health(5, 1, false, &config), HealthState::Healthy);
+        assert_eq!(
+            consumer_health(500, 1, false, &config),
+            HealthState::Degraded
+        );
+        assert_eq!(
+            consumer_health(5, 10, false, &config),
+            HealthState::Degraded
+        );
+        assert_eq!(consumer_health(5, 1, true, &config), HealthState::Waiting);
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_unreachable_server() {
+        let mut config = config(&["alerts.>"], None);
+        config.url = Secret::new("nats://127.0.0.1:1");
+        let sensor = Arc::new(NatsSensor::new("nats", config).unwrap());
+        let (sink, receiver) = EventSink::channel(8);
+        let handle = tokio::spawn({
+            let sensor = sensor.clone();
+            async move { sensor.run(sink).await }
+        });
+
+        for _ in 0..100 {
+            if sensor.status().health == HealthState::ResourceDown {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(sensor.status().health, HealthState::ResourceDown);
+
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(sensor.status().lifecycle, LifecycleState::Stopped);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a NATS server, see server_url"]
+    async fn test_run_subscribes_to_subjects() {
+        let prefix = format!("loid{}", Uuid::new_v4().simple());
+        let subject = format!("{prefix}.alerts.fra1.db");
+        let mut config = config(&[&format!("{prefix}.>")], None);
+        config.tokens = vec![format!("{prefix}.alerts.{{site}}.>")];
+        let sensor = Arc::new(NatsSensor::new("nats", config).unwrap());
+        let (sink, mut receiver) = EventSink::channel(8);
+        let handle = run(&sensor, sink).await;
+
+        let client = async_nats::connect(server_url()).await.unwrap();
+        client
+            .publish(subject.clone(), r#"{"message": "disk full"}"#.into())
+            .await
+            .unwrap();
+        client.flush().await.unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.source.source_id.as_deref(), Some(subject.as_str()));
+        assert_eq!(event.fields["site"], Value::from("fra1"));
+
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a NATS server with JetStream enabled, see server_url"]
+    async fn test_run_consumes_durable_consumer() {
+        let name = format!("LOID{}", Uuid::new_v4().simple());
+        let client = async_nats::connect(server_url()).await.unwrap();
+        let context = async_nats::jetstream::new(client);
+        context
+            .create_stream(async_nats::jetstream::stream::Config {
+                name: name.clone(),
+                subjects: vec![format!("{name}.>")],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        for payload in [
+            r#"{"message": "first"}"#,
+            "not json",
+            r#"{"message": "second"}"#,
+        ] {
+            context
+                .publish(format!("{name}.alerts"), payload.into())
+                .await
+                .unwrap()
+                .await
+                .unwrap();
+        }
+
+        let sensor = Arc::new(NatsSensor::new("js", config(&[], Some(jetstream(&name)))).unwrap());
+        let (sink, mut receiver) = EventSink::channel(8);
+        let handle = run(&sensor, sink).await;
+        for (message, sequence) in [("first", 1), ("second", 3)] {
+            let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(event.fields["message"], Value::from(message));
+            assert_eq!(event.fields["stream_sequence"], Value::Int(sequence));
+        }
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // all messages were acknowledged or terminated, the durable resumes after them
+        let mut consumer: async_nats::jetstream::consumer::PullConsumer = context
+            .get_stream(&name)
+            .await
+            .unwrap()
+            .get_consumer("loid")
+            .await
+            .unwrap();
+        let info = consumer.info().await.unwrap();
+        assert_eq!(info.num_pending, 0);
+        assert_eq!(info.num_ack_pending, 0);
+        context.delete_stream(&name).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a NATS server with JetStream enabled, see server_url"]
+    async fn test_run_delays_redelivery_while_the_buffer_is_full() {
+        let name = format!("LOID{}", Uuid::new_v4().simple());
+        let client = async_nats::connect(server_url()).await.unwrap();
+        let context = async_nats::jetstream::new(client);
+        context
+            .create_stream(async_nats::jetstream::stream::Config {
+                name: name.clone(),
+                subjects: vec![format!("{name}.>")],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let alert =
+            |name: &str| format!(r#"{{"status": "firing", "labels": {{"alertname": "{name}"}}}}"#);
+        for alerts in [vec!["first", "lost"], vec!["redelivered"]] {
+            let alerts: Vec<String> = alerts.into_iter().map(alert).collect();
+            let payload = format!(
+                r#"{{"receiver": "loid", "alerts": [{}]}}"#,
+                alerts.join(",")
+            );
+            context
+                .publish(format!("{name}.alerts"), payload.into())
+                .await
+                .unwrap()
+                .await
+                .unwrap();
+        }
+
+        let mut config = config(&[], Some(jetstream(&name)));
+        config.adapter = Some(PayloadAdapter::Alertmanager);
+        let sensor = Arc::new(NatsSensor::new("js", config).unwrap());
+        let buffer = BufferConfig {
+            capacity: 1,
+            overflow: OverflowPolicy::DropNewest,
+            spill: None,
+        };
+        let (sink, mut receiver) =
+            EventSink::buffered(&buffer, sensor.status_handle().clone()).unwrap();
+        let handle = run(&sensor, sink).await;
+
+        // the first message is acknowledged although its second event was dropped, the second
+        // one is negatively acknowledged with a growing delay while the buffer stays full
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(sensor.status().lost_events, 1);
+        let mut received = Vec::new();
+        while let Ok(Some(event)) =
+            tokio::time::timeout(Duration::from_secs(2), receiver.recv()).await
+        {
+            received.push(event.fields["alertname"].clone());
+        }
+        assert_eq!(
+            received,
+            vec![Value::from("first"), Value::from("redelivered")]
+        );
+        assert_eq!(sensor.status().lost_events, 1);
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+        context.delete_stream(&name).await.unwrap();
+    }
+}
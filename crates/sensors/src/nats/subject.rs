@@ -0,0 +1,113 @@
+use crate::SensorError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(String),
+    /// `*`, any single token
+    Any,
+    /// `{name}`, any single token stored in the field `name`
+    Field(String),
+    /// `>`, the remaining tokens
+    Rest,
+}
+
+/// Pattern like `alerts.{site}.*.{host}.>` mapping subject tokens to event fields
+#[derive(Debug, Clone)]
+pub(crate) struct SubjectPattern {
+    tokens: Vec<Token>,
+}
+
+/// Checks a subject to subscribe to, wildcards are allowed
+pub(crate) fn validate_subject(subject: &str) -> Result<(), SensorError> {
+    let tokens: Vec<_> = subject.split('.').collect();
+    for (position, token) in tokens.iter().enumerate() {
+        if token.is_empty() || token.contains(char::is_whitespace) {
+            return Err(SensorError::Config(format!("invalid subject '{subject}'")));
+        }
+        if *token == ">" && position + 1 != tokens.len() {
+            return Err(SensorError::Config(format!(
+                "'>' must be the last token of subject '{subject}'"
+            )));
+        }
+    }
+    Ok(())
+}
+
+impl SubjectPattern {
+    pub fn parse(pattern: &str) -> Result<Self, SensorError> {
+        validate_subject(pattern)?;
+        let tokens = pattern
+            .split('.')
+            .map(|token| match token {
+                "*" => Token::Any,
+                ">" => Token::Rest,
+                _ => match token.strip_prefix('{').and_then(|t| t.strip_suffix('}')) {
+                    Some(name) => Token::Field(name.to_string()),
+                    None => Token::Literal(token.to_string()),
+                },
+            })
+            .collect::<Vec<_>>();
+        if tokens
+            .iter()
+            .any(|token| matches!(token, Token::Field(name) if name.is_empty()))
+        {
+            return Err(SensorError::Config(format!(
+                "empty field name in pattern '{pattern}'"
+            )));
+        }
+        Ok(Self { tokens })
+    }
+
+    /// Fields captured from the subject, `None` if the subject does not match
+    pub fn fields(&self, subject: &str) -> Option<Vec<(String, String)>> {
+        let parts: Vec<_> = subject.split('.').collect();
+        let mut fields = Vec::new();
+        for (position, token) in self.tokens.iter().enumerate() {
+            if *token == Token::Rest {
+                return (parts.len() > position).then_some(fields);
+            }
+            let part = parts.get(position)?;
+            match token {
+                Token::Literal(literal) if literal != part => return None,
+                Token::Field(name) => fields.push((name.clone(), part.to_string())),
+                _ => {}
+            }
+        }
+        (parts.len() == self.tokens.len()).then_some(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pattern: &str, subject: &str) -> Option<Vec<(String, String)>> {
+        SubjectPattern::parse(pattern).unwrap().fields(subject)
+    }
+
+    #[test]
+    fn test_pattern_captures_tokens() {
+        assert_eq!(
+            fields("alerts.{site}.*.{host}", "alerts.fra1.db.db-1"),
+            Some(vec![
+                ("site".to_string(), "fra1".to_string()),
+                ("host".to_string(), "db-1".to_string())
+            ])
+        );
+        assert_eq!(
+            fields("alerts.{site}.>", "alerts.fra1.db.cpu"),
+            Some(vec![("site".to_string(), "fra1".to_string())])
+        );
+        assert_eq!(fields("alerts.{site}.>", "alerts.fra1"), None);
+        assert_eq!(fields("alerts.{site}", "alerts.fra1.db"), None);
+        assert_eq!(fields("alerts.{site}", "metrics.fra1"), None);
+    }
+
+    #[test]
+    fn test_invalid_subjects() {
+        assert!(validate_subject("alerts.>").is_ok());
+        assert!(validate_subject("alerts..db").is_err());
+        assert!(validate_subject("alerts.>.db").is_err());
+        assert!(SubjectPattern::parse("alerts.{}").is_err());
+    }
+}
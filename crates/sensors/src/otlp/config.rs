@@ -0,0 +1,135 @@
+use crate::SensorError;
+use loid_events::prelude::{Impact, Urgency};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Configuration of an [`OtlpSensor`](crate::otlp::OtlpSensor)
+#[derive(Debug, Clone, Deserialize)]
+pub struct OtlpSensorConfig {
+    /// Address of the OTLP/gRPC receiver, usually port 4317
+    #[serde(default)]
+    pub grpc: Option<String>,
+    /// Address of the OTLP/HTTP receiver serving `POST /v1/logs`, usually port 4318
+    #[serde(default)]
+    pub http: Option<String>,
+    /// Only log records matching the filter become events
+    #[serde(default)]
+    pub filter: LogFilter,
+}
+
+/// Conditions a log record has to meet, all configured conditions must match
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LogFilter {
+    /// Records below this severity are dropped, records without severity are kept
+    #[serde(default)]
+    pub min_severity: Option<Severity>,
+    /// Resource attributes that must have the given value
+    #[serde(default)]
+    pub resource: HashMap<String, String>,
+    /// Log record attributes that must have the given value
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+    /// Regular expression the body must match
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+/// Severity ranges of the OpenTelemetry log data model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl Severity {
+    /// Lowest severity number of the range
+    pub fn number(&self) -> i32 {
+        match self {
+            Severity::Trace => 1,
+            Severity::Debug => 5,
+            Severity::Info => 9,
+            Severity::Warn => 13,
+            Severity::Error => 17,
+            Severity::Fatal => 21,
+        }
+    }
+
+    /// Returns the range a severity number belongs to, `None` if unspecified or invalid
+    pub fn from_number(number: i32) -> Option<Severity> {
+        match number {
+            1..=4 => Some(Severity::Trace),
+            5..=8 => Some(Severity::Debug),
+            9..=12 => Some(Severity::Info),
+            13..=16 => Some(Severity::Warn),
+            17..=20 => Some(Severity::Error),
+            21..=24 => Some(Severity::Fatal),
+            _ => None,
+        }
+    }
+
+    pub fn impact(&self) -> Impact {
+        match self {
+            Severity::Trace | Severity::Debug | Severity::Info => Impact::NEGLIGIBLE,
+            Severity::Warn => Impact::MINOR,
+            Severity::Error => Impact::SIGNIFICANT,
+            Severity::Fatal => Impact::SEVERE,
+        }
+    }
+
+    pub fn urgency(&self) -> Urgency {
+        match self {
+            Severity::Trace | Severity::Debug | Severity::Info => Urgency::LOW,
+            Severity::Warn => Urgency::MEDIUM,
+            Severity::Error => Urgency::HIGH,
+            Severity::Fatal => Urgency::CRITICAL,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Trace => write!(f, "trace"),
+            Severity::Debug => write!(f, "debug"),
+            Severity::Info => write!(f, "info"),
+            Severity::Warn => write!(f, "warn"),
+            Severity::Error => write!(f, "error"),
+            Severity::Fatal => write!(f, "fatal"),
+        }
+    }
+}
+
+impl OtlpSensorConfig {
+    /// Checks the configuration for values the sensor cannot work with
+    pub fn validate(&self) -> Result<(), SensorError> {
+        if self.grpc.is_none() && self.http.is_none() {
+            return Err(SensorError::Config(
+                "at least one of grpc or http is required".to_string(),
+            ));
+        }
+        if self.grpc.is_some() && self.grpc == self.http {
+            return Err(SensorError::Config(
+                "grpc and http cannot listen on the same address".to_string(),
+            ));
+        }
+        self.filter.compile_body()?;
+        Ok(())
+    }
+}
+
+impl LogFilter {
+    pub(crate) fn compile_body(&self) -> Result<Option<Regex>, SensorError> {
+        self.body
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| SensorError::Config(format!("invalid body filter: {e}")))
+    }
+}
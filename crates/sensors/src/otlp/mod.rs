@@ -0,0 +1,23 @@
+//! OpenTelemetry logs receiver implementing OTLP/gRPC and OTLP/HTTP.
+//!
+//! ```yaml
+//! sensor:
+//!     type: otlp
+//!     grpc: 0.0.0.0:4317
+//!     http: 0.0.0.0:4318
+//!     filter:
+//!         min_severity: warn
+//!         resource:
+//!             deployment.environment: production
+//!         attributes:
+//!             http.route: /checkout
+//!         body: "(?i)timeout|failed"
+//! ```
+
+pub(crate) mod config;
+pub(crate) mod record;
+pub(crate) mod sensor;
+
+pub use config::{LogFilter, OtlpSensorConfig, Severity};
+pub use record::LogConverter;
+pub use sensor::OtlpSensor;
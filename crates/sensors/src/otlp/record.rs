@@ -0,0 +1,328 @@
+use crate::SensorError;
+use crate::otlp::{LogFilter, Severity};
+use chrono::DateTime;
+use loid_events::prelude::{Event, EventBuilder, Source, Value};
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceRequest;
+use opentelemetry_proto::tonic::common::v1::{AnyValue, KeyValue, any_value};
+use opentelemetry_proto::tonic::logs::v1::LogRecord;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Turns OTLP log records into events.
+#[derive(Debug, Clone)]
+pub struct LogConverter {
+    key: String,
+    filter: LogFilter,
+    body: Option<Regex>,
+}
+
+impl LogConverter {
+    pub fn new(key: impl Into<String>, filter: LogFilter) -> Result<Self, SensorError> {
+        let body = filter.compile_body()?;
+        Ok(Self {
+            key: key.into(),
+            filter,
+            body,
+        })
+    }
+
+    /// Converts all records of an export request that pass the filter
+    pub fn convert(&self, request: ExportLogsServiceRequest) -> Vec<Event> {
+        let mut events = Vec::new();
+        for resource_logs in request.resource_logs {
+            let resource = resource_logs
+                .resource
+                .map(|resource| attributes(&resource.attributes))
+                .unwrap_or_default();
+            if !matches_all(&self.filter.resource, &resource) {
+                continue;
+            }
+            for scope_logs in resource_logs.scope_logs {
+                let scope = scope_logs.scope.map(|scope| scope.name);
+                for record in scope_logs.log_records {
+                    if let Some(event) = self.convert_record(&resource, scope.as_deref(), record) {
+                        events.push(event);
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    fn convert_record(
+        &self,
+        resource: &HashMap<String, Value>,
+        scope: Option<&str>,
+        record: LogRecord,
+    ) -> Option<Event> {
+        let severity = Severity::from_number(record.severity_number);
+        if let (Some(min_severity), Some(severity)) = (self.filter.min_severity, severity)
+            && severity < min_severity
+        {
+            return None;
+        }
+        let record_attributes = attributes(&record.attributes);
+        if !matches_all(&self.filter.attributes, &record_attributes) {
+            return None;
+        }
+        let body = record.body.map(any_value);
+        if let Some(pattern) = &self.body {
+            let text = body.as_ref().map(value_text).unwrap_or_default();
+            if !pattern.is_match(&text) {
+                return None;
+            }
+        }
+
+        let system = resource
+            .get("service.name")
+            .map(value_text)
+            .unwrap_or_else(|| "otlp".to_string());
+        let source_id = ["service.instance.id", "host.name"]
+            .iter()
+            .find_map(|key| resource.get(*key))
+            .map(value_text);
+
+        let mut builder = EventBuilder::new();
+        builder.with_source(Source { system, source_id });
+        for (key, value) in record_attributes {
+            builder.with_field(&key, value);
+        }
+        builder
+            .with_text_field("sensor", &self.key)
+            .with_map_field("resource", resource.clone())
+            .with_int_field("severity_number", record.severity_number as i64);
+        if let Some(severity) = severity {
+            builder
+                .with_impact(severity.impact())
+                .with_urgency(severity.urgency())
+                .with_text_field("severity", &severity.to_string());
+        }
+        if !record.severity_text.is_empty() {
+            builder.with_text_field("severity_text", &record.severity_text);
+        }
+        if let Some(body) = body {
+            builder.with_field("body", body);
+        }
+        if let Some(scope) = scope.filter(|scope| !scope.is_empty()) {
+            builder.with_text_field("scope", scope);
+        }
+        if !record.event_name.is_empty() {
+            builder.with_text_field("event_name", &record.event_name);
+        }
+        // the observed time is used by collectors if the source did not set a time
+        let time = match record.time_unix_nano {
+            0 => record.observed_time_unix_nano,
+            time => time,
+        };
+        if time != 0 {
+            let time = DateTime::from_timestamp_nanos(time as i64);
+            builder.with_text_field("timestamp", &time.to_rfc3339());
+        }
+        if !record.trace_id.is_empty() {
+            builder.with_text_field("trace_id", &hex(&record.trace_id));
+        }
+        if !record.span_id.is_empty() {
+            builder.with_text_field("span_id", &hex(&record.span_id));
+        }
+        Some(builder.build())
+    }
+}
+
+fn matches_all(expected: &HashMap<String, String>, actual: &HashMap<String, Value>) -> bool {
+    expected.iter().all(|(key, expected)| {
+        actual
+            .get(key)
+            .is_some_and(|value| value_text(value) == *expected)
+    })
+}
+
+fn attributes(attributes: &[KeyValue]) -> HashMap<String, Value> {
+    attributes
+        .iter()
+        .map(|attribute| {
+            let value = attribute.value.clone().map(any_value).unwrap_or_default();
+            (attribute.key.clone(), value)
+        })
+        .collect()
+}
+
+fn any_value(value: AnyValue) -> Value {
+    match value.value {
+        None => Value::None,
+        Some(any_value::Value::StringValue(value)) => Value::String(value),
+        Some(any_value::Value::BoolValue(value)) => Value::Bool(value),
+        Some(any_value::Value::IntValue(value)) => Value::Int(value),
+        Some(any_value::Value::DoubleValue(value)) => Value::Float(value),
+        Some(any_value::Value::ArrayValue(array)) => {
+            Value::List(array.values.into_iter().map(any_value).collect())
+        }
+        Some(any_value::Value::KvlistValue(list)) => Value::Map(attributes(&list.values)),
+        Some(any_value::Value::BytesValue(bytes)) => Value::String(hex(&bytes)),
+    }
+}
+
+/// String form of a value used for filtering and the source
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::None => String::new(),
+        Value::String(value) => value.clone(),
+        Value::Int(value) => value.to_string(),
+        Value::Float(value) => value.to_string(),
+        Value::Bool(value) => value.to_string(),
+        Value::List(_) | Value::Map(_) => format!("{value:?}"),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use loid_events::prelude::{Impact, Urgency};
+    use opentelemetry_proto::tonic::logs::v1::{ResourceLogs, ScopeLogs};
+    use opentelemetry_proto::tonic::resource::v1::Resource;
+
+    fn string_attribute(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(value.to_string())),
+            }),
+        }
+    }
+
+    fn request(service: &str, records: Vec<LogRecord>) -> ExportLogsServiceRequest {
+        ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(Resource {
+                    attributes: vec![
+                        string_attribute("service.name", service),
+                        string_attribute("host.name", "web-01"),
+                    ],
+                    ..Default::default()
+                }),
+                scope_logs: vec![ScopeLogs {
+                    scope: None,
+                    log_records: records,
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        }
+    }
+
+    fn record(severity_number: i32, body: &str) -> LogRecord {
+        LogRecord {
+            time_unix_nano: 1_760_860_800_000_000_000,
+            severity_number,
+            severity_text: "ERROR".to_string(),
+            body: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(body.to_string())),
+            }),
+            attributes: vec![string_attribute("http.route", "/checkout")],
+            trace_id: vec![0xab; 16],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_convert_maps_resource_attributes_and_severity() {
+        let converter = LogConverter::new("otlp", LogFilter::default()).unwrap();
+        let events = converter.convert(request("checkout", vec![record(17, "payment failed")]));
+        assert_eq!(events.len(), 1);
+
+        let event = &events[0];
+        assert_eq!(event.source.system, "checkout");
+        assert_eq!(event.source.source_id.as_deref(), Some("web-01"));
+        assert_eq!(event.impact, Impact::SIGNIFICANT);
+        assert_eq!(event.urgency, Urgency::HIGH);
+        assert_eq!(
+            event.fields.get("body"),
+            Some(&Value::from("payment failed"))
+        );
+        assert_eq!(event.fields.get("severity"), Some(&Value::from("error")));
+        assert_eq!(
+            event.fields.get("http.route"),
+            Some(&Value::from("/checkout"))
+        );
+        assert_eq!(
+            event.fields.get("timestamp"),
+            Some(&Value::from("2025-10-19T08:00:00+00:00"))
+        );
+        assert_eq!(
+            event.fields.get("trace_id"),
+            Some(&Value::from("ab".repeat(16).as_str()))
+        );
+        let Some(Value::Map(resource)) = event.fields.get("resource") else {
+            panic!("resource missing");
+        };
+        assert_eq!(resource.get("service.name"), Some(&Value::from("checkout")));
+    }
+
+    #[test]
+    fn test_convert_applies_filter() {
+        let filter = LogFilter {
+            min_severity: Some(Severity::Warn),
+            resource: HashMap::from([("service.name".to_string(), "checkout".to_string())]),
+            attributes: HashMap::from([("http.route".to_string(), "/checkout".to_string())]),
+            body: Some("failed".to_string()),
+        };
+        let converter = LogConverter::new("otlp", filter).unwrap();
+
+        let records = vec![
+            record(9, "payment failed"),
+            record(13, "payment failed"),
+            record(17, "payment succeeded"),
+            record(0, "payment failed without severity"),
+        ];
+        let events = converter.convert(request("checkout", records.clone()));
+        let bodies: Vec<_> = events
+            .iter()
+            .map(|event| event.fields.get("body").cloned())
+            .collect();
+        assert_eq!(
+            bodies,
+            vec![
+                Some(Value::from("payment failed")),
+                Some(Value::from("payment failed without severity")),
+            ]
+        );
+
+        assert!(converter.convert(request("cart", records)).is_empty());
+    }
+
+    #[test]
+    fn test_any_value_conversion() {
+        let value = AnyValue {
+            value: Some(any_value::Value::KvlistValue(
+                opentelemetry_proto::tonic::common::v1::KeyValueList {
+                    values: vec![KeyValue {
+                        key: "codes".to_string(),
+                        value: Some(AnyValue {
+                            value: Some(any_value::Value::ArrayValue(
+                                opentelemetry_proto::tonic::common::v1::ArrayValue {
+                                    values: vec![AnyValue {
+                                        value: Some(any_value::Value::IntValue(500)),
+                                    }],
+                                },
+                            )),
+                        }),
+                    }],
+                },
+            )),
+        };
+        assert_eq!(
+            any_value(value),
+            Value::Map(HashMap::from([(
+                "codes".to_string(),
+                Value::List(vec![Value::Int(500)])
+            )]))
+        );
+    }
+}
@@ -0,0 +1,430 @@
+use crate::otlp::{LogConverter, OtlpSensorConfig};
+use crate::{
+    EventSink, HealthState, LifecycleState, SendOutcome, Sensor, SensorError, StatusHandle,
+};
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use opentelemetry_proto::tonic::collector::logs::v1::logs_service_server::{
+    LogsService, LogsServiceServer,
+};
+use opentelemetry_proto::tonic::collector::logs::v1::{
+    ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use prost::Message;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tonic::transport::Server;
+use tonic::transport::server::TcpIncoming;
+
+const PROTOBUF: &str = "application/x-protobuf";
+const JSON: &str = "application/json";
+
+/// Receives OpenTelemetry logs over OTLP/gRPC and OTLP/HTTP and emits one event per log record.
+pub struct OtlpSensor {
+    key: String,
+    config: OtlpSensorConfig,
+    converter: Arc<LogConverter>,
+    status: StatusHandle,
+}
+
+impl OtlpSensor {
+    pub fn new(key: impl Into<String>, config: OtlpSensorConfig) -> Result<Self, SensorError> {
+        let key = key.into();
+        config.validate()?;
+        let converter = LogConverter::new(key.clone(), config.filter.clone())?;
+        Ok(Self {
+            key,
+            config,
+            converter: Arc::new(converter),
+            status: StatusHandle::default(),
+        })
+    }
+
+    pub fn config(&self) -> &OtlpSensorConfig {
+        &self.config
+    }
+
+    async fn bind(address: Option<&str>) -> std::io::Result<Option<TcpListener>> {
+        match address {
+            Some(address) => TcpListener::bind(address).await.map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Shared by the gRPC and the HTTP receiver
+#[derive(Clone)]
+struct LogsReceiver {
+    converter: Arc<LogConverter>,
+    sink: EventSink,
+}
+
+impl LogsReceiver {
+    /// Hands the events of the request off, stops at the first event the buffer dropped so the
+    /// client retries the export
+    async fn export(&self, request: ExportLogsServiceRequest) -> Result<SendOutcome, SensorError> {
+        let mut outcome = SendOutcome::Filtered;
+        for event in self.converter.convert(request) {
+            outcome = outcome.max(self.sink.send(event).await?);
+            if outcome == SendOutcome::Dropped {
+                break;
+            }
+        }
+        Ok(outcome)
+    }
+}
+
+#[tonic::async_trait]
+impl LogsService for LogsReceiver {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportLogsServiceRequest>,
+    ) -> Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
+        let outcome = LogsReceiver::export(self, request.into_inner())
+            .await
+            .map_err(|e| tonic::Status::unavailable(e.to_string()))?;
+        if outcome == SendOutcome::Dropped {
+            return Err(tonic::Status::resource_exhausted(
+                "buffer is full, retry later",
+            ));
+        }
+        Ok(tonic::Response::new(ExportLogsServiceResponse::default()))
+    }
+}
+
+/// `POST /v1/logs` accepting binary protobuf and JSON encoded requests
+async fn export_http(
+    State(receiver): State<LogsReceiver>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(str::trim)
+        .unwrap_or_default();
+
+    let request = match content_type {
+        PROTOBUF => ExportLogsServiceRequest::decode(body).map_err(|e| e.to_string()),
+        JSON => serde_json::from_slice(&body).map_err(|e| e.to_string()),
+        _ => {
+            return (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("content type must be {PROTOBUF} or {JSON}"),
+            )
+                .into_response();
+        }
+    };
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match receiver.export(request).await {
+        Ok(SendOutcome::Dropped) => {
+            return (StatusCode::TOO_MANY_REQUESTS, "buffer is full, retry later").into_response();
+        }
+        Ok(_) => {}
+        Err(e) => return (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response(),
+    }
+
+    let response = ExportLogsServiceResponse::default();
+    let body = match content_type {
+        PROTOBUF => response.encode_to_vec(),
+        _ => serde_json::to_vec(&response).unwrap_or_default(),
+    };
+    ([(header::CONTENT_TYPE, content_type.to_string())], body).into_response()
+}
+
+impl Sensor for OtlpSensor {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn status_handle(&self) -> &StatusHandle {
+        &self.status
+    }
+
+    async fn run(&self, sink: EventSink) -> Result<(), SensorError> {
+        let listeners = async {
+            let grpc = Self::bind(self.config.grpc.as_deref()).await?;
+            let http = Self::bind(self.config.http.as_deref()).await?;
+            Ok::<_, std::io::Error>((grpc, http))
+        };
+        let (grpc, http) = match listeners.await {
+            Ok(listeners) => listeners,
+            Err(e) => {
+                self.status.set_health(HealthState::Misconfigured);
+                return Err(e.into());
+            }
+        };
+        self.status.set_lifecycle(LifecycleState::Running);
+        self.status.set_health(HealthState::Healthy);
+
+        let receiver = LogsReceiver {
+            converter: self.converter.clone(),
+            sink: sink.clone(),
+        };
+
+        let grpc = async {
+            let Some(listener) = grpc else {
+                return Ok(());
+            };
+            tracing::info!(sensor = %self.key, address = ?listener.local_addr(), "OTLP/gRPC receiver started");
+            Server::builder()
+                .add_service(LogsServiceServer::new(receiver.clone()))
+                .serve_with_incoming_shutdown(TcpIncoming::from(listener), sink.closed())
+                .await
+                .map_err(std::io::Error::other)
+        };
+        let shutdown = sink.clone();
+        let http = async {
+            let Some(listener) = http else {
+                return Ok(());
+            };
+            tracing::info!(sensor = %self.key, address = ?listener.local_addr(), "OTLP/HTTP receiver started");
+            let router = Router::new()
+                .route("/v1/logs", post(export_http))
+                .with_state(receiver.clone());
+            axum::serve(listener, router)
+                .with_graceful_shutdown(async move { shutdown.closed().await })
+                .await
+        };
+
+        let result = tokio::try_join!(grpc, http);
+        self.status.set_lifecycle(LifecycleState::Stopped);
+        if let Err(e) = result {
+            self.status.set_health(HealthState::Failed);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::{BufferConfig, OverflowPolicy};
+    use crate::otlp::{LogFilter, Severity};
+    use loid_events::prelude::{Impact, Value};
+    use opentelemetry_proto::tonic::collector::logs::v1::logs_service_client::LogsServiceClient;
+    use opentelemetry_proto::tonic::common::v1::{AnyValue, KeyValue, any_value};
+    use opentelemetry_proto::tonic::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+    use opentelemetry_proto::tonic::resource::v1::Resource;
+    use std::time::Duration;
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    async fn wait_for_listener(port: u16) {
+        for _ in 0..100 {
+            if tokio::net::TcpStream::connect(("127.0.0.1", port))
+                .await
+                .is_ok()
+            {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("OTLP receiver did not start");
+    }
+
+    fn request(body: &str, severity_number: i32) -> ExportLogsServiceRequest {
+        ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(Resource {
+                    attributes: vec![KeyValue {
+                        key: "service.name".to_string(),
+                        value: Some(AnyValue {
+                            value: Some(any_value::Value::StringValue("checkout".to_string())),
+                        }),
+                    }],
+                    ..Default::default()
+                }),
+                scope_logs: vec![ScopeLogs {
+                    log_records: vec![LogRecord {
+                        severity_number,
+                        body: Some(AnyValue {
+                            value: Some(any_value::Value::StringValue(body.to_string())),
+                        }),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_config_from_yaml_like_json() {
+        let config: OtlpSensorConfig = serde_json::from_str(
+            r#"{
+                "grpc": "0.0.0.0:4317",
+                "filter": {"min_severity": "warn", "resource": {"service.name": "checkout"}}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.filter.min_severity, Some(Severity::Warn));
+        assert!(config.http.is_none());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_config() {
+        let config = OtlpSensorConfig {
+            grpc: None,
+            http: None,
+            filter: LogFilter::default(),
+        };
+        assert!(matches!(
+            OtlpSensor::new("otlp", config),
+            Err(SensorError::Config(_))
+        ));
+
+        let config = OtlpSensorConfig {
+            grpc: Some("127.0.0.1:4317".to_string()),
+            http: None,
+            filter: LogFilter {
+                body: Some("(".to_string()),
+                ..Default::default()
+            },
+        };
+        assert!(OtlpSensor::new("otlp", config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_receives_grpc_and_http_exports() {
+        let grpc_port = free_port();
+        let http_port = free_port();
+        let config = OtlpSensorConfig {
+            grpc: Some(format!("127.0.0.1:{grpc_port}")),
+            http: Some(format!("127.0.0.1:{http_port}")),
+            filter: LogFilter {
+                min_severity: Some(Severity::Warn),
+                ..Default::default()
+            },
+        };
+        let sensor = OtlpSensor::new("otlp", config).unwrap();
+        let (sink, mut receiver) = EventSink::channel(8);
+        let handle = tokio::spawn(async move { sensor.run(sink).await });
+        wait_for_listener(grpc_port).await;
+        wait_for_listener(http_port).await;
+
+        let mut client = LogsServiceClient::connect(format!("http://127.0.0.1:{grpc_port}"))
+            .await
+            .unwrap();
+        client.export(request("debug noise", 5)).await.unwrap();
+        client.export(request("via grpc", 17)).await.unwrap();
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.source.system, "checkout");
+        assert_eq!(event.fields.get("body"), Some(&Value::from("via grpc")));
+        assert_eq!(event.impact, Impact::SIGNIFICANT);
+
+        let http = reqwest::Client::new();
+        let url = format!("http://127.0.0.1:{http_port}/v1/logs");
+        let response = http
+            .post(&url)
+            .header(header::CONTENT_TYPE, PROTOBUF)
+            .body(request("via protobuf", 13).encode_to_vec())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.fields.get("body"), Some(&Value::from("via protobuf")));
+
+        let json = r#"{"resourceLogs": [{"scopeLogs": [{"logRecords": [
+            {"severityNumber": 21, "body": {"stringValue": "via json"}}
+        ]}]}]}"#;
+        let response = http
+            .post(&url)
+            .header(header::CONTENT_TYPE, JSON)
+            .body(json)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.source.system, "otlp");
+        assert_eq!(event.fields.get("body"), Some(&Value::from("via json")));
+        assert_eq!(event.impact, Impact::SEVERE);
+
+        let response = http
+            .post(&url)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body("hello")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        drop(client);
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_export_is_retryable_while_the_buffer_is_full() {
+        let grpc_port = free_port();
+        let http_port = free_port();
+        let config = OtlpSensorConfig {
+            grpc: Some(format!("127.0.0.1:{grpc_port}")),
+            http: Some(format!("127.0.0.1:{http_port}")),
+            filter: LogFilter::default(),
+        };
+        let sensor = OtlpSensor::new("otlp", config).unwrap();
+        let buffer = BufferConfig {
+            capacity: 1,
+            overflow: OverflowPolicy::DropNewest,
+            spill: None,
+        };
+        let (sink, mut receiver) = EventSink::buffered(&buffer, StatusHandle::default()).unwrap();
+        let handle = tokio::spawn(async move { sensor.run(sink).await });
+        wait_for_listener(grpc_port).await;
+        wait_for_listener(http_port).await;
+
+        let mut client = LogsServiceClient::connect(format!("http://127.0.0.1:{grpc_port}"))
+            .await
+            .unwrap();
+        client.export(request("first", 17)).await.unwrap();
+        let status = client.export(request("second", 17)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+
+        let response = reqwest::Client::new()
+            .post(format!("http://127.0.0.1:{http_port}/v1/logs"))
+            .header(header::CONTENT_TYPE, PROTOBUF)
+            .body(request("third", 17).encode_to_vec())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.fields.get("body"), Some(&Value::from("first")));
+        client.export(request("retried", 17)).await.unwrap();
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.fields.get("body"), Some(&Value::from("retried")));
+
+        drop(client);
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+use crate::mapping::Condition;
+use loid_events::prelude::{Impact, Priority, Urgency};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// The `patterns` section detecting sequences and absences of events
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PatternsConfig {
+    pub rules: Vec<PatternRule>,
+}
+
+/// Steps that have to happen in order for the same key
+#[derive(Debug, Clone, Deserialize)]
+pub struct PatternRule {
+    /// Name of the rule, part of the derived events
+    pub name: String,
+    /// Field paths like `service` correlating the events of a sequence, every key is tracked
+    /// on its own
+    #[serde(default)]
+    pub key: Vec<String>,
+    /// The first step starts a sequence, every following step has to happen after the previous
+    pub steps: Vec<PatternStep>,
+    /// Time the whole sequence has to complete in, counted from the first step
+    #[serde(default, with = "humantime_serde")]
+    pub within: Option<Duration>,
+    /// Emits an event when a sequence runs out of time before it completed
+    #[serde(default)]
+    pub report_timeouts: bool,
+    #[serde(default = "default_impact")]
+    pub impact: Impact,
+    #[serde(default = "default_urgency")]
+    pub urgency: Urgency,
+    #[serde(default = "default_priority")]
+    pub priority: Priority,
+}
+
+/// One step of a pattern
+#[derive(Debug, Clone, Deserialize)]
+pub struct PatternStep {
+    /// Name of the step, listed in the derived events
+    pub name: String,
+    /// All conditions have to match for an event to be the step
+    #[serde(default)]
+    pub when: Vec<Condition>,
+    /// Time the step has to happen in, counted from the previous step
+    #[serde(default, with = "humantime_serde")]
+    pub within: Option<Duration>,
+    /// The step must not happen: the pattern completes once the time of the step ran out and
+    /// is abandoned if a matching event arrives before. Only the last step can be absent.
+    #[serde(default)]
+    pub absent: bool,
+}
+
+fn default_impact() -> Impact {
+    Impact::MODERATE
+}
+
+fn default_urgency() -> Urgency {
+    Urgency::MEDIUM
+}
+
+fn default_priority() -> Priority {
+    Priority::MEDIUM
+}
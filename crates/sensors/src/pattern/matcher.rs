@@ -0,0 +1,460 @@
+use crate::field::{key, key_text};
+use crate::mapping::rules::{Check, all};
+use crate::pattern::{PatternRule, PatternsConfig};
+use crate::{SensorError, Stage};
+use chrono::{DateTime, TimeDelta, Utc};
+use loid_events::prelude::{Event, EventBuilder, Source, Value};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+#[derive(Debug)]
+struct Step {
+    when: Vec<Check>,
+    within: Option<TimeDelta>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    config: PatternRule,
+    steps: Vec<Step>,
+    within: Option<TimeDelta>,
+}
+
+/// How a sequence ended
+#[derive(Debug, Clone, Copy)]
+enum Outcome {
+    /// All steps happened in time
+    Matched,
+    /// The absent last step did not happen in time
+    Absent,
+    /// A step did not happen in time
+    Timeout,
+}
+
+/// A started sequence of one key
+#[derive(Debug)]
+struct Sequence {
+    key: Vec<Value>,
+    first: DateTime<Utc>,
+    /// Ids of the events of the steps that happened, in order
+    inputs: Vec<Uuid>,
+    /// Index of the step waited for
+    next: usize,
+    /// Time the next step has to happen by
+    deadline: Option<DateTime<Utc>>,
+    /// Correlation id of the derived event, created when the sequence started
+    correlation_id: Uuid,
+}
+
+/// A compiled [`PatternsConfig`] following sequences of events per rule and key.
+///
+/// Sequences follow the creation time of the events. Steps that did not happen in time are
+/// noticed once an event or a [`tick`](Self::tick) is past their deadline.
+#[derive(Debug)]
+pub struct PatternMatcher {
+    rules: Vec<Rule>,
+    /// Started sequence per rule index and key
+    sequences: HashMap<(usize, String), Sequence>,
+}
+
+fn duration(
+    duration: Option<std::time::Duration>,
+    what: &str,
+) -> Result<Option<TimeDelta>, SensorError> {
+    duration
+        .map(|duration| {
+            TimeDelta::from_std(duration)
+                .ok()
+                .filter(|span| *span > TimeDelta::zero())
+                .ok_or_else(|| {
+                    SensorError::Config(format!("within of {what} must be greater than 0"))
+                })
+        })
+        .transpose()
+}
+
+impl Outcome {
+    fn name(&self) -> &'static str {
+        match self {
+            Outcome::Matched => "matched",
+            Outcome::Absent => "absent",
+            Outcome::Timeout => "timeout",
+        }
+    }
+}
+
+impl Rule {
+    fn new(config: &PatternRule) -> Result<Self, SensorError> {
+        let name = &config.name;
+        if config.steps.len() < 2 {
+            return Err(SensorError::Config(format!(
+                "rule '{name}' needs at least two steps"
+            )));
+        }
+        if config.key.iter().any(String::is_empty) {
+            return Err(SensorError::Config(format!(
+                "key of rule '{name}' contains an empty path"
+            )));
+        }
+        let within = duration(config.within, &format!("rule '{name}'"))?;
+        let last = config.steps.len() - 1;
+        let mut steps = Vec::with_capacity(config.steps.len());
+        for (index, step) in config.steps.iter().enumerate() {
+            if step.name.is_empty() {
+                return Err(SensorError::Config(format!(
+                    "steps of rule '{name}' need a name"
+                )));
+            }
+            let what = format!("step '{}' of rule '{name}'", step.name);
+            if index == 0 && step.within.is_some() {
+                return Err(SensorError::Config(format!(
+                    "{what} starts the sequence and can not have within"
+                )));
+            }
+            if step.absent && index != last {
+                return Err(SensorError::Config(format!(
+                    "{what} is absent but only the last step can be"
+                )));
+            }
+            if step.absent && step.within.is_none() && within.is_none() {
+                return Err(SensorError::Config(format!(
+                    "{what} is absent and needs within on the step or the rule"
+                )));
+            }
+            steps.push(Step {
+                when: step.when.iter().map(Check::new).collect::<Result<_, _>>()?,
+                within: duration(step.within, &what)?,
+            });
+        }
+        Ok(Self {
+            config: config.clone(),
+            steps,
+            within,
+        })
+    }
+
+    /// Deadline of the step at `next` after the previous step happened at `now`
+    fn deadline(&self, sequence: &Sequence, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let step = self.steps[sequence.next].within.map(|within| now + within);
+        let rule = self.within.map(|within| sequence.first + within);
+        match (step, rule) {
+            (Some(step), Some(rule)) => Some(step.min(rule)),
+            (step, rule) => step.or(rule),
+        }
+    }
+
+    fn derive(&self, sequence: &Sequence, outcome: Outcome, end: DateTime<Utc>) -> Event {
+        let config = &self.config;
+        let mut builder = EventBuilder::new();
+        builder
+            .with_source(Source {
+                system: "pattern".to_string(),
+                source_id: Some(config.name.clone()),
+            })
+            .with_correlation_id(sequence.correlation_id)
+            .with_impact(config.impact)
+            .with_urgency(config.urgency)
+            .with_priority(config.priority)
+            .with_text_field("rule", &config.name)
+            .with_text_field("outcome", outcome.name())
+            .with_text_field("started_at", &sequence.first.to_rfc3339())
+            .with_text_field("ended_at", &end.to_rfc3339())
+            .with_list_field(
+                "steps",
+                config.steps[..sequence.inputs.len()]
+                    .iter()
+                    .map(|step| Value::String(step.name.clone()))
+                    .collect(),
+            )
+            .with_list_field(
+                "inputs",
+                sequence
+                    .inputs
+                    .iter()
+                    .map(|id| Value::String(id.to_string()))
+                    .collect(),
+            );
+        if !matches!(outcome, Outcome::Matched) {
+            builder.with_text_field("missing", &config.steps[sequence.next].name);
+        }
+        for (path, value) in config.key.iter().zip(&sequence.key) {
+            builder.with_field(path, value.clone());
+        }
+        builder.build()
+    }
+}
+
+impl PatternMatcher {
+    pub fn new(config: &PatternsConfig) -> Result<Self, SensorError> {
+        if config.rules.is_empty() {
+            return Err(SensorError::Config(
+                "at least one rule is required".to_string(),
+            ));
+        }
+        let mut names = HashSet::new();
+        for rule in &config.rules {
+            if rule.name.is_empty() {
+                return Err(SensorError::Config("rules need a name".to_string()));
+            }
+            if !names.insert(rule.name.as_str()) {
+                return Err(SensorError::Config(format!(
+                    "rule name '{}' is used twice",
+                    rule.name
+                )));
+            }
+        }
+        Ok(Self {
+            rules: config
+                .rules
+                .iter()
+                .map(Rule::new)
+                .collect::<Result<_, _>>()?,
+            sequences: HashMap::new(),
+        })
+    }
+
+    /// Advances the sequences with the event, returns the event followed by the derived events
+    /// of the sequences it or the time passed since the last event ended
+    pub fn apply(&mut self, event: Event) -> Vec<Event> {
+        let now = event.created_at;
+        let mut derived = self.tick(now);
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            let Some(key) = key(&event.fields, &rule.config.key) else {
+                continue;
+            };
+            let group = key_text(&key);
+            let entry = (index, group);
+            match self.sequences.get_mut(&entry) {
+                Some(sequence) => {
+                    let step = &rule.steps[sequence.next];
+                    if !all(&step.when, &event.fields) {
+                        continue;
+                    }
+                    if rule.config.steps[sequence.next].absent {
+                        // the step that must not happen did, the sequence is abandoned
+                        self.sequences.remove(&entry);
+                        continue;
+                    }
+                    sequence.inputs.push(event.id);
+                    sequence.next += 1;
+                    if sequence.next == rule.steps.len() {
+                        if let Some(sequence) = self.sequences.remove(&entry) {
+                            derived.push(rule.derive(&sequence, Outcome::Matched, now));
+                        }
+                    } else {
+                        sequence.deadline = rule.deadline(sequence, now);
+                    }
+                }
+                None if all(&rule.steps[0].when, &event.fields) => {
+                    let mut sequence = Sequence {
+                        key,
+                        first: now,
+                        inputs: vec![event.id],
+                        next: 1,
+                        deadline: None,
+                        correlation_id: Uuid::now_v7(),
+                    };
+                    sequence.deadline = rule.deadline(&sequence, now);
+                    self.sequences.insert(entry, sequence);
+                }
+                None => {}
+            }
+        }
+
+        let mut events = vec![event];
+        events.append(&mut derived);
+        events
+    }
+
+    /// Ends the sequences whose next step did not happen before `now`, call it regularly while
+    /// no events arrive
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        let mut ended = Vec::new();
+        let rules = &self.rules;
+        self.sequences.retain(|(index, group), sequence| {
+            let Some(deadline) = sequence.deadline.filter(|deadline| *deadline <= now) else {
+                return true;
+            };
+            let rule = &rules[*index];
+            let outcome = if rule.config.steps[sequence.next].absent {
+                Outcome::Absent
+            } else if rule.config.report_timeouts {
+                Outcome::Timeout
+            } else {
+                return false;
+            };
+            ended.push((
+                deadline,
+                *index,
+                group.clone(),
+                rule.derive(sequence, outcome, deadline),
+            ));
+            false
+        });
+        ended.sort_by(|a, b| (a.0, a.1, &a.2).cmp(&(b.0, b.1, &b.2)));
+        ended.into_iter().map(|(.., event)| event).collect()
+    }
+}
+
+impl Stage for PatternMatcher {
+    fn apply(&mut self, event: Event) -> Vec<Event> {
+        PatternMatcher::apply(self, event)
+    }
+
+    fn tick(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        PatternMatcher::tick(self, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(json: &str) -> Result<PatternMatcher, SensorError> {
+        PatternMatcher::new(&serde_json::from_str(json).unwrap())
+    }
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::UNIX_EPOCH + TimeDelta::seconds(seconds)
+    }
+
+    fn event(fields: &[(&str, Value)], seconds: i64) -> Event {
+        let mut builder = EventBuilder::new();
+        for (key, value) in fields {
+            builder.with_field(key, value.clone());
+        }
+        let mut event = builder.build();
+        event.created_at = at(seconds);
+        event
+    }
+
+    fn deploy_then_errors() -> PatternMatcher {
+        matcher(
+            r#"{"rules": [{
+                "name": "deploy-then-errors",
+                "key": ["service"],
+                "report_timeouts": true,
+                "steps": [
+                    {"name": "deploy", "when": [{"field": "action", "equals": "deploy"}]},
+                    {"name": "error", "when": [{"field": "status", "one_of": [500, 503]}], "within": "10m"}
+                ]
+            }]}"#,
+        )
+        .unwrap()
+    }
+
+    fn deploy(service: &str, seconds: i64) -> Event {
+        event(
+            &[
+                ("action", Value::from("deploy")),
+                ("service", Value::from(service)),
+            ],
+            seconds,
+        )
+    }
+
+    fn error(service: &str, seconds: i64) -> Event {
+        event(
+            &[
+                ("status", Value::Int(503)),
+                ("service", Value::from(service)),
+            ],
+            seconds,
+        )
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for json in [
+            r#"{"rules": []}"#,
+            r#"{"rules": [{"name": "a", "steps": [{"name": "one"}]}]}"#,
+            r#"{"rules": [{"name": "a", "steps": [{"name": "one", "absent": true, "within": "1m"}, {"name": "two"}]}]}"#,
+            r#"{"rules": [{"name": "a", "steps": [{"name": "one"}, {"name": "two", "absent": true}]}]}"#,
+            r#"{"rules": [{"name": "a", "steps": [{"name": "one"}, {"name": "two", "within": "0s"}]}]}"#,
+            r#"{"rules": [{"name": "a", "steps": [{"name": "one", "within": "1m"}, {"name": "two"}]}]}"#,
+            r#"{"rules": [{"name": "a", "steps": [{"name": "one"}, {"name": "two"}]}, {"name": "a", "steps": [{"name": "one"}, {"name": "two"}]}]}"#,
+        ] {
+            assert!(
+                matches!(matcher(json), Err(SensorError::Config(_))),
+                "{json}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_followed_by_within() {
+        let mut matcher = deploy_then_errors();
+
+        let first = deploy("checkout", 0);
+        let id = first.id;
+        assert_eq!(matcher.apply(first).len(), 1);
+        // errors of another service do not advance the sequence
+        assert_eq!(matcher.apply(error("search", 60)).len(), 1);
+        let events = matcher.apply(error("checkout", 120));
+        assert_eq!(events.len(), 2);
+        let derived = &events[1];
+        assert_eq!(derived.fields["outcome"], Value::from("matched"));
+        assert_eq!(derived.fields["service"], Value::from("checkout"));
+        assert_eq!(
+            derived.fields["steps"],
+            Value::List(vec![Value::from("deploy"), Value::from("error")])
+        );
+        assert_eq!(
+            derived.fields["inputs"],
+            Value::List(vec![
+                Value::String(id.to_string()),
+                Value::String(events[0].id.to_string())
+            ])
+        );
+
+        // a deploy without errors in time reports a timeout once the time passed
+        assert_eq!(matcher.apply(deploy("checkout", 1000)).len(), 1);
+        assert!(matcher.tick(at(1599)).is_empty());
+        let timeouts = matcher.tick(at(1600));
+        assert_eq!(timeouts.len(), 1);
+        assert_eq!(timeouts[0].fields["outcome"], Value::from("timeout"));
+        assert_eq!(timeouts[0].fields["missing"], Value::from("error"));
+        // another sequence of the same service is another incident
+        assert_ne!(timeouts[0].correlation_id, derived.correlation_id);
+        // the late error no longer belongs to a sequence
+        assert_eq!(matcher.apply(error("checkout", 1700)).len(), 1);
+    }
+
+    #[test]
+    fn test_not_followed_by() {
+        let mut matcher = matcher(
+            r#"{"rules": [{
+                "name": "backup-unfinished",
+                "key": ["job"],
+                "within": "2h",
+                "steps": [
+                    {"name": "started", "when": [{"field": "state", "equals": "started"}]},
+                    {"name": "finished", "when": [{"field": "state", "equals": "finished"}], "absent": true}
+                ]
+            }]}"#,
+        )
+        .unwrap();
+        let backup = |job: &str, state: &str, seconds| {
+            event(
+                &[("job", Value::from(job)), ("state", Value::from(state))],
+                seconds,
+            )
+        };
+
+        assert_eq!(matcher.apply(backup("db", "started", 0)).len(), 1);
+        assert_eq!(matcher.apply(backup("files", "started", 60)).len(), 1);
+        assert_eq!(matcher.apply(backup("db", "finished", 3600)).len(), 1);
+
+        // the next event past the deadline reports the missing step along with itself
+        let events = matcher.apply(backup("db", "started", 7300));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].fields["outcome"], Value::from("absent"));
+        assert_eq!(events[1].fields["job"], Value::from("files"));
+        assert_eq!(events[1].fields["missing"], Value::from("finished"));
+        assert_eq!(
+            events[1].fields["ended_at"],
+            Value::String(at(7260).to_rfc3339())
+        );
+    }
+}
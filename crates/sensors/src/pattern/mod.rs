@@ -0,0 +1,43 @@
+//! Sequence and absence patterns over the events of a sensor.
+//!
+//! The `patterns` section sits next to the `sensor` section and works the same for every sensor
+//! type, it sees the events after the [mapping](crate::mapping):
+//!
+//! ```yaml
+//! patterns:
+//!     rules:
+//!         - name: deploy-then-errors
+//!           key: [service]
+//!           steps:
+//!               - name: deploy
+//!                 when:
+//!                     - field: action
+//!                       equals: deploy
+//!               - name: error
+//!                 when:
+//!                     - field: status
+//!                       one_of: [500, 502, 503]
+//!                 within: 10m
+//!         - name: backup-unfinished
+//!           key: [job]
+//!           steps:
+//!               - name: started
+//!                 when:
+//!                     - field: state
+//!                       equals: started
+//!               - name: finished
+//!                 when:
+//!                     - field: state
+//!                       equals: finished
+//!                 within: 2h
+//!                 absent: true
+//! ```
+//!
+//! Events pass unchanged, a completed or timed out sequence adds a derived event sharing a
+//! correlation id per rule and key.
+
+pub(crate) mod config;
+pub(crate) mod matcher;
+
+pub use config::{PatternRule, PatternStep, PatternsConfig};
+pub use matcher::PatternMatcher;
@@ -0,0 +1,20 @@
+pub use crate::adapter::PayloadAdapter;
+pub use crate::amqp::{AmqpSensor, AmqpSensorConfig, QueueBinding, QueueDeclaration};
+pub use crate::buffer::{BufferConfig, OverflowPolicy, SpillConfig};
+pub use crate::command::{CheckState, CommandSensor, CommandSensorConfig, PerfData, StateType};
+pub use crate::flapping::{FlapDetector, FlapState, FlappingConfig};
+pub use crate::mapping::{Mapping, MappingConfig};
+pub use crate::metrics::{MetricDetector, MetricsConfig};
+pub use crate::nats::{DeliverFrom, JetStreamConfig, NatsSensor, NatsSensorConfig};
+pub use crate::otlp::{LogFilter, OtlpSensor, OtlpSensorConfig, Severity};
+pub use crate::pattern::{PatternMatcher, PatternsConfig};
+pub use crate::probe::{ProbeCheck, ProbeSensor, ProbeSensorConfig, ProbeTarget, ProbeTransition};
+pub use crate::redis_stream::{RedisStreamSensor, RedisStreamSensorConfig};
+pub use crate::smtp::{ExtractFrom, ExtractionRule, SmtpSensor, SmtpSensorConfig};
+pub use crate::sql::SqlSensorConfig;
+pub use crate::watch::{FileChangeKind, WatchSensor, WatchSensorConfig};
+pub use crate::window::{WindowAggregator, WindowsConfig};
+pub use crate::{
+    EventReceiver, EventSink, HealthState, LifecycleState, SendOutcome, Sensor, SensorError,
+    SensorStatus, Stage, StatusHandle,
+};
@@ -0,0 +1,363 @@
+use crate::probe::ProbeCheck;
+use chrono::{DateTime, Utc};
+use loid_events::prelude::{Secret, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+/// Outcome of a single probe
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    pub success: bool,
+    pub latency: Duration,
+    /// Human readable description of the outcome
+    pub message: String,
+    /// Check specific details, e.g. the HTTP status code
+    pub fields: HashMap<String, Value>,
+}
+
+impl ProbeResult {
+    fn up(latency: Duration, message: impl Into<String>) -> Self {
+        Self {
+            success: true,
+            latency,
+            message: message.into(),
+            fields: HashMap::new(),
+        }
+    }
+
+    fn down(latency: Duration, message: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            ..Self::up(latency, message)
+        }
+    }
+
+    fn with_field(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.fields.insert(key.to_string(), value.into());
+        self
+    }
+}
+
+/// Runs the check once, failing it if it takes longer than `timeout`
+pub(crate) async fn probe(
+    check: &ProbeCheck,
+    client: &reqwest::Client,
+    timeout: Duration,
+) -> ProbeResult {
+    let started = Instant::now();
+    let result = tokio::time::timeout(timeout, async {
+        match check {
+            ProbeCheck::Tcp { address } => probe_tcp(address, started).await,
+            ProbeCheck::Http {
+                url,
+                method,
+                headers,
+                expected_status,
+                body_contains,
+                max_latency,
+            } => {
+                probe_http(
+                    client,
+                    HttpProbe {
+                        url,
+                        method,
+                        headers,
+                        expected_status,
+                        body_contains: body_contains.as_deref(),
+                        max_latency: *max_latency,
+                    },
+                    started,
+                )
+                .await
+            }
+            ProbeCheck::Tls {
+                address,
+                server_name,
+                min_days_valid,
+            } => {
+                let server_name = server_name.as_deref().unwrap_or_else(|| host(address));
+                probe_tls(address, server_name, *min_days_valid, started).await
+            }
+        }
+    })
+    .await;
+
+    result.unwrap_or_else(|_| {
+        ProbeResult::down(
+            started.elapsed(),
+            format!("timed out after {}", humantime::format_duration(timeout)),
+        )
+    })
+}
+
+async fn probe_tcp(address: &str, started: Instant) -> ProbeResult {
+    match TcpStream::connect(address).await {
+        Ok(_) => ProbeResult::up(started.elapsed(), format!("connected to {address}")),
+        Err(error) => ProbeResult::down(
+            started.elapsed(),
+            format!("failed to connect to {address}: {error}"),
+        ),
+    }
+}
+
+struct HttpProbe<'a> {
+    url: &'a str,
+    method: &'a str,
+    headers: &'a HashMap<String, Secret>,
+    expected_status: &'a [u16],
+    body_contains: Option<&'a str>,
+    max_latency: Option<Duration>,
+}
+
+async fn probe_http(
+    client: &reqwest::Client,
+    probe: HttpProbe<'_>,
+    started: Instant,
+) -> ProbeResult {
+    let method = reqwest::Method::from_bytes(probe.method.as_bytes()).unwrap_or_default();
+    let mut request = client.request(method, probe.url);
+    for (name, value) in probe.headers {
+        request = request.header(name, value.expose());
+    }
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(error) => {
+            return ProbeResult::down(
+                started.elapsed(),
+                format!("request to {} failed: {error}", probe.url),
+            );
+        }
+    };
+
+    let status = response.status();
+    let body = match probe.body_contains {
+        Some(_) => response.text().await.ok(),
+        None => None,
+    };
+    let latency = started.elapsed();
+
+    let status_ok = if probe.expected_status.is_empty() {
+        status.is_success()
+    } else {
+        probe.expected_status.contains(&status.as_u16())
+    };
+
+    let body_ok = match (probe.body_contains, &body) {
+        (Some(expected), Some(body)) => body.contains(expected),
+        (Some(_), None) => false,
+        (None, _) => true,
+    };
+    let too_slow = probe
+        .max_latency
+        .filter(|max_latency| latency > *max_latency);
+
+    let result = if !status_ok {
+        ProbeResult::down(latency, format!("unexpected status {status}"))
+    } else if !body_ok {
+        ProbeResult::down(
+            latency,
+            format!(
+                "response body does not contain '{}'",
+                probe.body_contains.unwrap_or_default()
+            ),
+        )
+    } else if let Some(max_latency) = too_slow {
+        ProbeResult::down(
+            latency,
+            format!(
+                "response took {}ms, allowed are {}ms",
+                latency.as_millis(),
+                max_latency.as_millis()
+            ),
+        )
+    } else {
+        ProbeResult::up(latency, format!("status {status}"))
+    };
+    result.with_field("status_code", status.as_u16())
+}
+
+async fn probe_tls(
+    address: &str,
+    server_name: &str,
+    min_days_valid: u32,
+    started: Instant,
+) -> ProbeResult {
+    let stream = match TcpStream::connect(address).await {
+        Ok(stream) => stream,
+        Err(error) => {
+            return ProbeResult::down(
+                started.elapsed(),
+                format!("failed to connect to {address}: {error}"),
+            );
+        }
+    };
+
+    let connector = match native_tls::TlsConnector::new() {
+        Ok(connector) => tokio_native_tls::TlsConnector::from(connector),
+        Err(error) => {
+            return ProbeResult::down(started.elapsed(), format!("TLS setup failed: {error}"));
+        }
+    };
+    let tls = match connector.connect(server_name, stream).await {
+        Ok(tls) => tls,
+        Err(error) => {
+            return ProbeResult::down(
+                started.elapsed(),
+                format!("TLS handshake with {server_name} failed: {error}"),
+            );
+        }
+    };
+    let latency = started.elapsed();
+
+    let not_after = tls
+        .get_ref()
+        .peer_certificate()
+        .ok()
+        .flatten()
+        .and_then(|certificate| certificate.to_der().ok())
+        .and_then(|der| certificate_not_after(&der));
+    let Some(not_after) = not_after else {
+        return ProbeResult::down(latency, "peer did not present a readable certificate");
+    };
+
+    let days_remaining = (not_after - Utc::now()).num_days();
+    let result = if days_remaining < min_days_valid as i64 {
+        ProbeResult::down(
+            latency,
+            format!("certificate expires in {days_remaining} days ({not_after})"),
+        )
+    } else {
+        ProbeResult::up(
+            latency,
+            format!("certificate valid for {days_remaining} days"),
+        )
+    };
+    result
+        .with_field("days_remaining", days_remaining)
+        .with_field("not_after", not_after.to_rfc3339())
+}
+
+/// Reads the end of the validity period of a DER encoded certificate
+fn certificate_not_after(der: &[u8]) -> Option<DateTime<Utc>> {
+    let (_, certificate) = x509_parser::parse_x509_certificate(der).ok()?;
+    DateTime::from_timestamp(certificate.validity().not_after.timestamp(), 0)
+}
+
+/// Host part of a `host:port` address
+fn host(address: &str) -> &str {
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves a single canned HTTP response per connection
+    async fn http_server(status: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buffer = [0u8; 1024];
+                let _ = stream.read(&mut buffer).await;
+                let response = format!(
+                    "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{address}/health")
+    }
+
+    fn http_check(url: String) -> ProbeCheck {
+        ProbeCheck::Http {
+            url,
+            method: "GET".to_string(),
+            headers: HashMap::new(),
+            expected_status: vec![],
+            body_contains: None,
+            max_latency: None,
+        }
+    }
+
+    #[test]
+    fn test_host() {
+        assert_eq!(host("example.com:443"), "example.com");
+        assert_eq!(host("[::1]:443"), "::1");
+        assert_eq!(host("example.com"), "example.com");
+    }
+
+    #[tokio::test]
+    async fn test_probe_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let client = reqwest::Client::new();
+
+        let up = probe(
+            &ProbeCheck::Tcp {
+                address: address.clone(),
+            },
+            &client,
+            Duration::from_secs(1),
+        )
+        .await;
+        assert!(up.success);
+
+        drop(listener);
+        let down = probe(
+            &ProbeCheck::Tcp { address },
+            &client,
+            Duration::from_secs(1),
+        )
+        .await;
+        assert!(!down.success);
+    }
+
+    #[tokio::test]
+    async fn test_probe_http_status_and_body() {
+        let client = reqwest::Client::new();
+        let url = http_server("200 OK", "{\"status\": \"ok\"}").await;
+
+        let up = probe(&http_check(url.clone()), &client, Duration::from_secs(1)).await;
+        assert!(up.success);
+        assert_eq!(up.fields.get("status_code"), Some(&Value::Int(200)));
+
+        let mut check = http_check(url.clone());
+        if let ProbeCheck::Http { body_contains, .. } = &mut check {
+            *body_contains = Some("\"status\": \"ok\"".to_string());
+        }
+        assert!(probe(&check, &client, Duration::from_secs(1)).await.success);
+
+        if let ProbeCheck::Http { body_contains, .. } = &mut check {
+            *body_contains = Some("healthy".to_string());
+        }
+        let down = probe(&check, &client, Duration::from_secs(1)).await;
+        assert!(!down.success);
+        assert!(down.message.contains("healthy"));
+    }
+
+    #[tokio::test]
+    async fn test_probe_http_unexpected_status() {
+        let client = reqwest::Client::new();
+        let url = http_server("503 Service Unavailable", "").await;
+
+        let down = probe(&http_check(url.clone()), &client, Duration::from_secs(1)).await;
+        assert!(!down.success);
+        assert_eq!(down.fields.get("status_code"), Some(&Value::Int(503)));
+
+        let mut check = http_check(url);
+        if let ProbeCheck::Http {
+            expected_status, ..
+        } = &mut check
+        {
+            *expected_status = vec![503];
+        }
+        assert!(probe(&check, &client, Duration::from_secs(1)).await.success);
+    }
+}
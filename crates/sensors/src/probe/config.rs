@@ -0,0 +1,141 @@
+use crate::SensorError;
+use loid_events::prelude::Secret;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Configuration of a [`ProbeSensor`](crate::probe::ProbeSensor)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProbeSensorConfig {
+    /// Time between two probes of the same target
+    #[serde(with = "humantime_serde", default = "default_interval")]
+    pub interval: Duration,
+    /// Maximum time a single probe may take before it counts as failed
+    #[serde(with = "humantime_serde", default = "default_timeout")]
+    pub timeout: Duration,
+    /// Consecutive failed probes required before a target is reported as down
+    #[serde(default = "default_threshold")]
+    pub failure_threshold: u32,
+    /// Consecutive successful probes required before a target is reported as up again
+    #[serde(default = "default_success_threshold")]
+    pub success_threshold: u32,
+    pub targets: Vec<ProbeTarget>,
+}
+
+/// A named target together with the check to run against it
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProbeTarget {
+    pub name: String,
+    #[serde(flatten)]
+    pub check: ProbeCheck,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ProbeCheck {
+    /// Opens a TCP connection to `address`
+    Tcp { address: String },
+    /// Sends an HTTP request and validates status, body and latency
+    Http {
+        url: String,
+        #[serde(default = "default_method")]
+        method: String,
+        /// Request headers, e.g. credentials
+        #[serde(default)]
+        headers: HashMap<String, Secret>,
+        /// Accepted status codes, any 2xx status if empty
+        #[serde(default)]
+        expected_status: Vec<u16>,
+        /// Text the response body has to contain
+        #[serde(default)]
+        body_contains: Option<String>,
+        /// Responses slower than this count as failed
+        #[serde(with = "humantime_serde", default)]
+        max_latency: Option<Duration>,
+    },
+    /// Performs a TLS handshake and validates the expiry of the peer certificate
+    Tls {
+        address: String,
+        /// Name used for SNI and verification, defaults to the host of `address`
+        #[serde(default)]
+        server_name: Option<String>,
+        /// Certificates expiring in less days count as failed
+        #[serde(default = "default_min_days_valid")]
+        min_days_valid: u32,
+    },
+}
+
+impl ProbeCheck {
+    /// Short name of the check type
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProbeCheck::Tcp { .. } => "tcp",
+            ProbeCheck::Http { .. } => "http",
+            ProbeCheck::Tls { .. } => "tls",
+        }
+    }
+}
+
+fn default_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_threshold() -> u32 {
+    3
+}
+
+fn default_success_threshold() -> u32 {
+    1
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_min_days_valid() -> u32 {
+    14
+}
+
+impl ProbeSensorConfig {
+    /// Checks the configuration for values the sensor cannot work with
+    pub fn validate(&self) -> Result<(), SensorError> {
+        if self.targets.is_empty() {
+            return Err(SensorError::Config(
+                "at least one target is required".to_string(),
+            ));
+        }
+        if self.failure_threshold == 0 || self.success_threshold == 0 {
+            return Err(SensorError::Config(
+                "failure_threshold and success_threshold must be at least 1".to_string(),
+            ));
+        }
+        if self.interval.is_zero() || self.timeout.is_zero() {
+            return Err(SensorError::Config(
+                "interval and timeout must be greater than zero".to_string(),
+            ));
+        }
+
+        let mut names = std::collections::HashSet::new();
+        for target in &self.targets {
+            if !names.insert(target.name.as_str()) {
+                return Err(SensorError::Config(format!(
+                    "duplicate target name '{}'",
+                    target.name
+                )));
+            }
+            if let ProbeCheck::Http { method, .. } = &target.check
+                && reqwest::Method::from_bytes(method.as_bytes()).is_err()
+            {
+                return Err(SensorError::Config(format!(
+                    "invalid HTTP method '{method}' for target '{}'",
+                    target.name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
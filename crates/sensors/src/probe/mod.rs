@@ -0,0 +1,32 @@
+//! Synthetic probe sensor checking the reachability of TCP, HTTP and TLS endpoints.
+//!
+//! ```yaml
+//! sensor:
+//!     type: probe
+//!     interval: 30s
+//!     timeout: 5s
+//!     failure_threshold: 3
+//!     targets:
+//!         - name: database
+//!           type: tcp
+//!           address: db.internal:5432
+//!         - name: api
+//!           type: http
+//!           url: https://api.internal/health
+//!           body_contains: "ok"
+//!           max_latency: 500ms
+//!         - name: api-certificate
+//!           type: tls
+//!           address: api.internal:443
+//!           min_days_valid: 14
+//! ```
+
+pub(crate) mod check;
+pub(crate) mod config;
+pub(crate) mod sensor;
+pub(crate) mod state;
+
+pub use check::ProbeResult;
+pub use config::{ProbeCheck, ProbeSensorConfig, ProbeTarget};
+pub use sensor::ProbeSensor;
+pub use state::{ProbeTransition, TargetStatus, TargetTracker};
@@ -0,0 +1,276 @@
+use crate::probe::check::probe;
+use crate::probe::{ProbeResult, ProbeSensorConfig, ProbeTarget, ProbeTransition, TargetTracker};
+use crate::{EventSink, HealthState, LifecycleState, Sensor, SensorError, StatusHandle};
+use chrono::Utc;
+use loid_events::prelude::{Event, EventBuilder, Impact, Source, Urgency};
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+use uuid::Uuid;
+
+/// Probes a list of targets and emits up/down/recovered events on reachability changes.
+pub struct ProbeSensor {
+    key: String,
+    config: ProbeSensorConfig,
+    client: reqwest::Client,
+    status: StatusHandle,
+}
+
+impl ProbeSensor {
+    pub fn new(key: impl Into<String>, config: ProbeSensorConfig) -> Result<Self, SensorError> {
+        config.validate()?;
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| SensorError::Config(format!("failed to create HTTP client: {e}")))?;
+        Ok(Self {
+            key: key.into(),
+            config,
+            client,
+            status: StatusHandle::default(),
+        })
+    }
+
+    pub fn config(&self) -> &ProbeSensorConfig {
+        &self.config
+    }
+
+    /// Probes all targets concurrently, results are returned in target order
+    async fn probe_all(&self) -> Vec<ProbeResult> {
+        let mut tasks = JoinSet::new();
+        for (index, target) in self.config.targets.iter().enumerate() {
+            let check = target.check.clone();
+            let client = self.client.clone();
+            let timeout = self.config.timeout;
+            tasks.spawn(async move { (index, probe(&check, &client, timeout).await) });
+        }
+
+        let mut results: Vec<Option<ProbeResult>> = vec![None; self.config.targets.len()];
+        while let Some(joined) = tasks.join_next().await {
+            if let Ok((index, result)) = joined {
+                results[index] = Some(result);
+            }
+        }
+        results
+            .into_iter()
+            .map(|result| {
+                result.unwrap_or_else(|| ProbeResult {
+                    success: false,
+                    latency: Default::default(),
+                    message: "probe task failed".to_string(),
+                    fields: Default::default(),
+                })
+            })
+            .collect()
+    }
+
+    fn build_event(
+        &self,
+        target: &ProbeTarget,
+        transition: ProbeTransition,
+        result: &ProbeResult,
+        tracker: &TargetTracker,
+        correlation_id: Option<Uuid>,
+    ) -> Event {
+        let (impact, urgency) = match transition {
+            ProbeTransition::Down => (Impact::SIGNIFICANT, Urgency::HIGH),
+            ProbeTransition::Up | ProbeTransition::Recovered => (Impact::NEGLIGIBLE, Urgency::LOW),
+        };
+
+        let mut builder = EventBuilder::new();
+        builder
+            .with_source(Source {
+                system: "probe".to_string(),
+                source_id: Some(target.name.clone()),
+            })
+            .with_impact(impact)
+            .with_urgency(urgency)
+            .with_text_field("sensor", &self.key)
+            .with_text_field("target", &target.name)
+            .with_text_field("probe", target.check.kind())
+            .with_text_field("status", &transition.to_string())
+            .with_text_field("message", &result.message)
+            .with_float_field("latency_ms", result.latency.as_secs_f64() * 1000.0)
+            .with_int_field(
+                "consecutive_failures",
+                tracker.consecutive_failures() as i64,
+            );
+        for (key, value) in &result.fields {
+            builder.with_field(key, value.clone());
+        }
+        if let Some(correlation_id) = correlation_id {
+            builder.with_correlation_id(correlation_id);
+        }
+        if transition == ProbeTransition::Recovered {
+            builder.with_resolved_at(Utc::now());
+        }
+        builder.build()
+    }
+}
+
+impl Sensor for ProbeSensor {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn status_handle(&self) -> &StatusHandle {
+        &self.status
+    }
+
+    async fn run(&self, sink: EventSink) -> Result<(), SensorError> {
+        self.status.set_lifecycle(LifecycleState::Running);
+        self.status.set_health(HealthState::Healthy);
+
+        let mut trackers: Vec<_> = self
+            .config
+            .targets
+            .iter()
+            .map(|_| {
+                TargetTracker::new(self.config.failure_threshold, self.config.success_threshold)
+            })
+            .collect();
+        // links down and recovered events of the same outage per target
+        let mut outages: Vec<Option<Uuid>> = vec![None; self.config.targets.len()];
+
+        let mut interval = tokio::time::interval(self.config.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        'outer: loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = sink.closed() => break,
+            }
+
+            let results = self.probe_all().await;
+            for (index, result) in results.iter().enumerate() {
+                let target = &self.config.targets[index];
+                let tracker = &mut trackers[index];
+                tracing::debug!(sensor = %self.key, target = %target.name, success = result.success, message = %result.message, "probe finished");
+
+                let Some(transition) = tracker.record(result.success) else {
+                    continue;
+                };
+                if transition == ProbeTransition::Down {
+                    outages[index] = Some(Uuid::now_v7());
+                }
+                let correlation_id = outages[index];
+                if transition == ProbeTransition::Recovered {
+                    outages[index] = None;
+                }
+
+                let event = self.build_event(target, transition, result, tracker, correlation_id);
+                if sink.send(event).await.is_err() {
+                    break 'outer;
+                }
+            }
+        }
+
+        self.status.set_lifecycle(LifecycleState::Stopped);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::ProbeCheck;
+    use loid_events::prelude::Value;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    fn config(targets: Vec<ProbeTarget>) -> ProbeSensorConfig {
+        ProbeSensorConfig {
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_secs(1),
+            failure_threshold: 2,
+            success_threshold: 1,
+            targets,
+        }
+    }
+
+    fn tcp_target(name: &str, address: String) -> ProbeTarget {
+        ProbeTarget {
+            name: name.to_string(),
+            check: ProbeCheck::Tcp { address },
+        }
+    }
+
+    #[test]
+    fn test_config_from_yaml_like_json() {
+        let config: ProbeSensorConfig = serde_json::from_str(
+            r#"{
+                "targets": [
+                    {"name": "db", "type": "tcp", "address": "db:5432"},
+                    {"name": "api", "type": "http", "url": "http://api/health", "max_latency": "500ms"},
+                    {"name": "cert", "type": "tls", "address": "api:443"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.failure_threshold, 3);
+        assert_eq!(config.targets[0].check.kind(), "tcp");
+        assert!(matches!(
+            &config.targets[1].check,
+            ProbeCheck::Http { method, max_latency: Some(latency), .. }
+                if method == "GET" && *latency == Duration::from_millis(500)
+        ));
+        assert!(matches!(
+            &config.targets[2].check,
+            ProbeCheck::Tls {
+                min_days_valid: 14,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_config() {
+        assert!(ProbeSensor::new("probe", config(vec![])).is_err());
+
+        let duplicate = config(vec![
+            tcp_target("db", "localhost:1".to_string()),
+            tcp_target("db", "localhost:2".to_string()),
+        ]);
+        assert!(matches!(
+            ProbeSensor::new("probe", duplicate),
+            Err(SensorError::Config(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_emits_up_down_and_recovered() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let sensor =
+            ProbeSensor::new("probe", config(vec![tcp_target("db", address.to_string())])).unwrap();
+        let (sink, mut receiver) = EventSink::channel(8);
+        let handle = tokio::spawn(async move { sensor.run(sink).await });
+
+        let up = receiver.recv().await.unwrap();
+        assert_eq!(up.source.system, "probe");
+        assert_eq!(up.source.source_id.as_deref(), Some("db"));
+        assert_eq!(up.fields.get("status"), Some(&Value::from("up")));
+        assert!(matches!(up.fields.get("latency_ms"), Some(Value::Float(_))));
+
+        drop(listener);
+        let down = receiver.recv().await.unwrap();
+        assert_eq!(down.fields.get("status"), Some(&Value::from("down")));
+        assert_eq!(
+            down.fields.get("consecutive_failures"),
+            Some(&Value::Int(2))
+        );
+        assert_eq!(down.impact, Impact::SIGNIFICANT);
+        assert!(down.correlation_id.is_some());
+
+        let _listener = TcpListener::bind(address).await.unwrap();
+        let recovered = receiver.recv().await.unwrap();
+        assert_eq!(
+            recovered.fields.get("status"),
+            Some(&Value::from("recovered"))
+        );
+        assert_eq!(recovered.correlation_id, down.correlation_id);
+        assert!(recovered.resolved_at.is_some());
+
+        drop(receiver);
+        handle.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt::{Display, Formatter};
+
+/// Reachability of a target as reported in events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetStatus {
+    /// No decision was made yet
+    Unknown,
+    Up,
+    Down,
+}
+
+/// Kind of event emitted for a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeTransition {
+    /// The target was reachable when it was first decided
+    Up,
+    /// The target became unreachable
+    Down,
+    /// The target is reachable again after being down
+    Recovered,
+}
+
+impl Display for ProbeTransition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeTransition::Up => write!(f, "up"),
+            ProbeTransition::Down => write!(f, "down"),
+            ProbeTransition::Recovered => write!(f, "recovered"),
+        }
+    }
+}
+
+/// Debounces probe results of a single target.
+///
+/// The status only changes after `failure_threshold` consecutive failures or
+/// `success_threshold` consecutive successes, so a single lost probe does not cause an event.
+#[derive(Debug, Clone)]
+pub struct TargetTracker {
+    status: TargetStatus,
+    consecutive_failures: u32,
+    consecutive_successes: u32,
+    failure_threshold: u32,
+    success_threshold: u32,
+}
+
+impl TargetTracker {
+    pub fn new(failure_threshold: u32, success_threshold: u32) -> Self {
+        Self {
+            status: TargetStatus::Unknown,
+            consecutive_failures: 0,
+            consecutive_successes: 0,
+            failure_threshold: failure_threshold.max(1),
+            success_threshold: success_threshold.max(1),
+        }
+    }
+
+    pub fn status(&self) -> TargetStatus {
+        self.status
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Records a probe result and returns the transition it caused, if any
+    pub fn record(&mut self, success: bool) -> Option<ProbeTransition> {
+        if success {
+            self.consecutive_failures = 0;
+            self.consecutive_successes += 1;
+            if self.consecutive_successes < self.success_threshold {
+                return None;
+            }
+            let transition = match self.status {
+                TargetStatus::Up => None,
+                TargetStatus::Unknown => Some(ProbeTransition::Up),
+                TargetStatus::Down => Some(ProbeTransition::Recovered),
+            };
+            self.status = TargetStatus::Up;
+            transition
+        } else {
+            self.consecutive_successes = 0;
+            self.consecutive_failures += 1;
+            if self.consecutive_failures < self.failure_threshold
+                || self.status == TargetStatus::Down
+            {
+                return None;
+            }
+            self.status = TargetStatus::Down;
+            Some(ProbeTransition::Down)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_success_reports_up() {
+        let mut tracker = TargetTracker::new(3, 1);
+        assert_eq!(tracker.record(true), Some(ProbeTransition::Up));
+        assert_eq!(tracker.record(true), None);
+        assert_eq!(tracker.status(), TargetStatus::Up);
+    }
+
+    #[test]
+    fn test_down_requires_consecutive_failures() {
+        let mut tracker = TargetTracker::new(3, 1);
+        tracker.record(true);
+
+        assert_eq!(tracker.record(false), None);
+        assert_eq!(tracker.record(false), None);
+        // a single success resets the counter
+        assert_eq!(tracker.record(true), None);
+        assert_eq!(tracker.record(false), None);
+        assert_eq!(tracker.record(false), None);
+        assert_eq!(tracker.record(false), Some(ProbeTransition::Down));
+        assert_eq!(tracker.consecutive_failures(), 3);
+
+        // further failures do not repeat the event
+        assert_eq!(tracker.record(false), None);
+    }
+
+    #[test]
+    fn test_recovery_requires_consecutive_successes() {
+        let mut tracker = TargetTracker::new(1, 2);
+        assert_eq!(tracker.record(false), Some(ProbeTransition::Down));
+        assert_eq!(tracker.record(true), None);
+        assert_eq!(tracker.record(false), None);
+        assert_eq!(tracker.record(true), None);
+        assert_eq!(tracker.record(true), Some(ProbeTransition::Recovered));
+        assert_eq!(tracker.status(), TargetStatus::Up);
+    }
+}
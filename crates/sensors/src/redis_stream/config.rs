@@ -0,0 +1,129 @@
+use crate::SensorError;
+use crate::adapter::PayloadAdapter;
+use loid_events::prelude::Secret;
+use redis::IntoConnectionInfo;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Configuration of a [`RedisStreamSensor`](crate::redis_stream::RedisStreamSensor)
+#[derive(Debug, Clone, Deserialize)]
+pub struct RedisStreamSensorConfig {
+    /// Server URL, e.g. `redis://:password@localhost:6379/0`
+    #[serde(default = "default_url")]
+    pub url: Secret,
+    /// Keys of the streams read from
+    pub streams: Vec<String>,
+    /// Consumer group sharing the entries between sensors
+    pub group: String,
+    /// Name of the consumer within the group, the key of the sensor if not set
+    #[serde(default)]
+    pub consumer: Option<String>,
+    /// Creates the group and the streams if they do not exist
+    #[serde(default = "default_create_group")]
+    pub create_group: bool,
+    /// Entry id a created group starts after, `$` for new entries and `0` for the whole stream
+    #[serde(default = "default_start_id")]
+    pub start_id: String,
+    /// Field of an entry holding the payload, the fields of an entry become the event fields if
+    /// not set
+    #[serde(default)]
+    pub payload_field: Option<String>,
+    /// Format of the payloads, plain JSON objects if not set
+    #[serde(default)]
+    pub adapter: Option<PayloadAdapter>,
+    /// Maximum number of entries read at once
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Time a read waits for new entries
+    #[serde(with = "humantime_serde", default = "default_block")]
+    pub block: Duration,
+    /// Entries pending this long with another consumer are claimed, the consumer is considered
+    /// dead
+    #[serde(with = "humantime_serde", default = "default_claim_idle")]
+    pub claim_idle: Duration,
+    /// Time between two attempts to claim stale entries
+    #[serde(with = "humantime_serde", default = "default_claim_interval")]
+    pub claim_interval: Duration,
+    /// Stream receiving the entries that cannot be turned into events, they are only
+    /// acknowledged if not set
+    #[serde(default)]
+    pub dead_letter_stream: Option<String>,
+    /// Time to wait before connecting again after the connection failed
+    #[serde(with = "humantime_serde", default = "default_reconnect_delay")]
+    pub reconnect_delay: Duration,
+}
+
+fn default_url() -> Secret {
+    Secret::new("redis://127.0.0.1:6379")
+}
+
+fn default_create_group() -> bool {
+    true
+}
+
+fn default_start_id() -> String {
+    "$".to_string()
+}
+
+fn default_batch_size() -> usize {
+    100
+}
+
+fn default_block() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_claim_idle() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_claim_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_reconnect_delay() -> Duration {
+    Duration::from_secs(5)
+}
+
+impl RedisStreamSensorConfig {
+    /// Checks the configuration for values the sensor cannot work with
+    pub fn validate(&self) -> Result<(), SensorError> {
+        self.url
+            .expose()
+            .into_connection_info()
+            .map_err(|e| SensorError::Config(format!("invalid url: {e}")))?;
+        if self.streams.is_empty() || self.streams.iter().any(String::is_empty) {
+            return Err(SensorError::Config(
+                "at least one stream is required, stream keys cannot be empty".to_string(),
+            ));
+        }
+        if self.group.is_empty() || self.consumer.as_deref() == Some("") {
+            return Err(SensorError::Config(
+                "group and consumer cannot be empty".to_string(),
+            ));
+        }
+        if self.adapter.is_some() && self.payload_field.is_none() {
+            return Err(SensorError::Config(
+                "an adapter requires a payload_field".to_string(),
+            ));
+        }
+        if self.batch_size == 0 {
+            return Err(SensorError::Config(
+                "batch_size must be greater than 0".to_string(),
+            ));
+        }
+        if self.claim_idle.is_zero() || self.claim_interval.is_zero() {
+            return Err(SensorError::Config(
+                "claim_idle and claim_interval must be greater than 0".to_string(),
+            ));
+        }
+        if let Some(stream) = &self.dead_letter_stream
+            && self.streams.contains(stream)
+        {
+            return Err(SensorError::Config(format!(
+                "dead_letter_stream '{stream}' cannot be one of the streams read from"
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use crate::SensorError;
+use crate::adapter::PayloadAdapter;
+use loid_events::prelude::{Event, EventBuilder, Source, Value};
+use std::collections::HashMap;
+
+/// An entry read from a stream
+pub(crate) struct Entry<'a> {
+    pub stream: &'a str,
+    pub id: &'a str,
+    pub fields: &'a HashMap<String, redis::Value>,
+}
+
+/// Text of a field value, entries only hold strings
+pub(crate) fn text(value: &redis::Value) -> Option<String> {
+    match value {
+        redis::Value::BulkString(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+        redis::Value::SimpleString(text) | redis::Value::VerbatimString { text, .. } => {
+            Some(text.clone())
+        }
+        redis::Value::Int(value) => Some(value.to_string()),
+        redis::Value::Double(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+impl Entry<'_> {
+    /// Turns the entry into events. Without payload field every field of the entry becomes a
+    /// field of one event, otherwise the payload is normalized by the adapter or has to be a
+    /// JSON object and the other fields become the `headers` field.
+    pub fn normalize(
+        &self,
+        adapter: Option<PayloadAdapter>,
+        payload_field: Option<&str>,
+    ) -> Result<Vec<Event>, SensorError> {
+        let fields: HashMap<String, String> = self
+            .fields
+            .iter()
+            .filter_map(|(name, value)| Some((name.clone(), text(value)?)))
+            .collect();
+
+        let mut events = match payload_field {
+            None => {
+                let mut builder = self.builder();
+                for (name, value) in &fields {
+                    builder.with_text_field(name, value);
+                }
+                vec![builder.build()]
+            }
+            Some(payload_field) => {
+                let Some(payload) = fields.get(payload_field) else {
+                    return Err(SensorError::Payload(format!(
+                        "entry has no field '{payload_field}'"
+                    )));
+                };
+                let headers: HashMap<String, String> = fields
+                    .iter()
+                    .filter(|(name, _)| name.as_str() != payload_field)
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect();
+                let mut events = match adapter {
+                    Some(adapter) => {
+                        // adapters look up header names in lowercase, like HTTP headers
+                        let lowercase = headers
+                            .iter()
+                            .map(|(name, value)| (name.to_ascii_lowercase(), value.clone()))
+                            .collect();
+                        adapter.normalize(payload.as_bytes(), &lowercase)?
+                    }
+                    None => {
+                        let object: HashMap<String, Value> = serde_json::from_str(payload)
+                            .map_err(|e| SensorError::Payload(e.to_string()))?;
+                        let mut builder = self.builder();
+                        for (key, value) in object {
+                            builder.with_field(&key, value);
+                        }
+                        vec![builder.build()]
+                    }
+                };
+                if !headers.is_empty() {
+                    let headers: HashMap<String, Value> = headers
+                        .into_iter()
+                        .map(|(name, value)| (name, Value::String(value)))
+                        .collect();
+                    for event in &mut events {
+                        event
+                            .fields
+                            .insert("headers".to_string(), Value::Map(headers.clone()));
+                    }
+                }
+                events
+            }
+        };
+
+        // the entry id identifies replayed entries, whatever the adapter chose
+        for event in &mut events {
+            event.source.source_id = Some(self.id.to_string());
+            event
+                .fields
+                .insert("stream".to_string(), Value::from(self.stream));
+        }
+        Ok(events)
+    }
+
+    fn builder(&self) -> EventBuilder {
+        let mut builder = EventBuilder::new();
+        builder.with_source(Source {
+            system: "redis".to_string(),
+            source_id: Some(self.id.to_string()),
+        });
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(entries: &[(&str, &str)]) -> HashMap<String, redis::Value> {
+        entries
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    redis::Value::BulkString(value.as_bytes().to_vec()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_normalize_entry_fields() {
+        let fields = fields(&[("message", "queue full"), ("host", "worker-1")]);
+        let entry = Entry {
+            stream: "app-events",
+            id: "1718000000000-0",
+            fields: &fields,
+        };
+        let events = entry.normalize(None, None).unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.source.system, "redis");
+        assert_eq!(event.source.source_id.as_deref(), Some("1718000000000-0"));
+        assert_eq!(event.fields["host"], Value::from("worker-1"));
+        assert_eq!(event.fields["stream"], Value::from("app-events"));
+    }
+
+    #[test]
+    fn test_normalize_payload_field() {
+        let fields = fields(&[
+            ("payload", r#"{"message": "queue full", "depth": 1200}"#),
+            ("app", "billing"),
+        ]);
+        let entry = Entry {
+            stream: "app-events",
+            id: "1718000000000-1",
+            fields: &fields,
+        };
+        let events = entry.normalize(None, Some("payload")).unwrap();
+        assert_eq!(events[0].fields["depth"], Value::Int(1200));
+        let Value::Map(headers) = &events[0].fields["headers"] else {
+            panic!("headers are not a map: {:?}", events[0].fields["headers"]);
+        };
+        assert_eq!(headers["app"], Value::from("billing"));
+
+        assert!(matches!(
+            entry.normalize(None, Some("body")),
+            Err(SensorError::Payload(_))
+        ));
+        let fields = self::fields(&[("payload", "not json")]);
+        let entry = Entry {
+            stream: "app-events",
+            id: "1718000000000-2",
+            fields: &fields,
+        };
+        assert!(matches!(
+            entry.normalize(None, Some("payload")),
+            Err(SensorError::Payload(_))
+        ));
+    }
+
+    #[test]
+    fn test_normalize_with_adapter_keeps_entry_id() {
+        let fields = fields(&[
+            (
+                "payload",
+                r#"{"deployment_status": {"state": "failure"}, "deployment": {"environment": "production"}, "repository": {"full_name": "loid-labs/loid"}}"#,
+            ),
+            ("X-GitHub-Event", "deployment_status"),
+        ]);
+        let entry = Entry {
+            stream: "webhooks",
+            id: "1718000000000-3",
+            fields: &fields,
+        };
+        let events = entry
+            .normalize(Some(PayloadAdapter::Github), Some("payload"))
+            .unwrap();
+        assert_eq!(events[0].source.system, "github");
+        assert_eq!(
+            events[0].source.source_id.as_deref(),
+            Some("1718000000000-3")
+        );
+    }
+}
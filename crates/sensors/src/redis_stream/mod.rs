@@ -0,0 +1,24 @@
+//! Consumer of Redis Streams within a consumer group.
+//!
+//! ```yaml
+//! sensor:
+//!     type: redis_stream
+//!     url: redis://redis:6379
+//!     streams:
+//!         - app-events
+//!     group: loid
+//!     payload_field: payload
+//!     dead_letter_stream: app-events-dead
+//! ```
+//!
+//! An entry is acknowledged with `XACK` once its events were handed off to the sink. Entries
+//! another consumer of the group read but did not acknowledge within `claim_idle` are claimed with
+//! `XAUTOCLAIM`, so the entries of a dead consumer are not lost. The entry id becomes the source id
+//! of the events, which lets replayed entries be recognized.
+
+pub(crate) mod config;
+pub(crate) mod entry;
+pub(crate) mod sensor;
+
+pub use config::RedisStreamSensorConfig;
+pub use sensor::RedisStreamSensor;
@@ -0,0 +1,548 @@
+use crate::redis_stream::RedisStreamSensorConfig;
+use crate::redis_stream::entry::{Entry, text};
+use crate::{
+    EventSink, HealthState, LifecycleState, SendOutcome, Sensor, SensorError, StatusHandle,
+};
+use redis::aio::MultiplexedConnection;
+use redis::streams::{
+    StreamAutoClaimOptions, StreamAutoClaimReply, StreamId, StreamReadOptions, StreamReadReply,
+};
+use redis::{AsyncCommands, AsyncConnectionConfig, Client, RedisError};
+use std::time::{Duration, Instant};
+
+/// Reads events from Redis Streams as a consumer of a consumer group.
+pub struct RedisStreamSensor {
+    key: String,
+    config: RedisStreamSensorConfig,
+    status: StatusHandle,
+}
+
+fn redis_error(error: RedisError) -> SensorError {
+    SensorError::Io(std::io::Error::other(error))
+}
+
+impl RedisStreamSensor {
+    pub fn new(
+        key: impl Into<String>,
+        config: RedisStreamSensorConfig,
+    ) -> Result<Self, SensorError> {
+        config.validate()?;
+        Ok(Self {
+            key: key.into(),
+            config,
+            status: StatusHandle::default(),
+        })
+    }
+
+    pub fn config(&self) -> &RedisStreamSensorConfig {
+        &self.config
+    }
+
+    fn consumer(&self) -> &str {
+        self.config.consumer.as_deref().unwrap_or(&self.key)
+    }
+
+    /// Creates the group of every stream, groups that already exist are kept as they are
+    async fn create_groups(
+        &self,
+        connection: &mut MultiplexedConnection,
+    ) -> Result<(), RedisError> {
+        for stream in &self.config.streams {
+            let created: Result<(), RedisError> = connection
+                .xgroup_create_mkstream(stream, &self.config.group, &self.config.start_id)
+                .await;
+            match created {
+                Err(e) if e.code() != Some("BUSYGROUP") => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads entries of all streams, `0` reads the entries pending with this consumer and `>`
+    /// the entries never delivered to the group
+    async fn read(
+        &self,
+        connection: &mut MultiplexedConnection,
+        id: &str,
+        block: Option<Duration>,
+    ) -> Result<Vec<(String, StreamId)>, RedisError> {
+        let mut options = StreamReadOptions::default()
+            .group(&self.config.group, self.consumer())
+            .count(self.config.batch_size);
+        if let Some(block) = block {
+            options = options.block(block.as_millis().try_into().unwrap_or(usize::MAX));
+        }
+        let ids = vec![id; self.config.streams.len()];
+        let reply: Option<StreamReadReply> = connection
+            .xread_options(&self.config.streams, &ids, &options)
+            .await?;
+        Ok(reply
+            .map(|reply| {
+                reply
+                    .keys
+                    .into_iter()
+                    .flat_map(|key| {
+                        let stream = key.key;
+                        key.ids.into_iter().map(move |id| (stream.clone(), id))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Claims the entries another consumer did not acknowledge within `claim_idle`, continuing
+    /// at the cursor of every stream
+    async fn claim(
+        &self,
+        connection: &mut MultiplexedConnection,
+        cursors: &mut [String],
+    ) -> Result<Vec<(String, StreamId)>, RedisError> {
+        let min_idle = u64::try_from(self.config.claim_idle.as_millis()).unwrap_or(u64::MAX);
+        let mut claimed = Vec::new();
+        for (stream, cursor) in self.config.streams.iter().zip(cursors.iter_mut()) {
+            let reply: StreamAutoClaimReply = connection
+                .xautoclaim_options(
+                    stream,
+                    &self.config.group,
+                    self.consumer(),
+                    min_idle,
+                    cursor.as_str(),
+                    StreamAutoClaimOptions::default().count(self.config.batch_size),
+                )
+                .await?;
+            if !reply.claimed.is_empty() {
+                tracing::info!(
+                    sensor = %self.key,
+                    stream = %stream,
+                    entries = reply.claimed.len(),
+                    "claimed stale entries"
+                );
+            }
+            *cursor = reply.next_stream_id;
+            claimed.extend(reply.claimed.into_iter().map(|id| (stream.clone(), id)));
+        }
+        Ok(claimed)
+    }
+
+    /// Hands the events of the entries off to the sink and acknowledges every entry afterwards,
+    /// except those with an event the buffer dropped. `false` once the sink is closed
+    async fn deliver(
+        &self,
+        connection: &mut MultiplexedConnection,
+        sink: &EventSink,
+        entries: Vec<(String, StreamId)>,
+    ) -> Result<bool, SensorError> {
+        for (stream, entry) in entries {
+            // entries deleted from the stream while pending have no fields
+            if !entry.map.is_empty() {
+                let normalized = Entry {
+                    stream: &stream,
+                    id: &entry.id,
+                    fields: &entry.map,
+                }
+                .normalize(self.config.adapter, self.config.payload_field.as_deref());
+                match normalized {
+                    Ok(events) => {
+                        let mut dropped = false;
+                        for event in events {
+                            match sink.send(event).await {
+                                Ok(SendOutcome::Dropped) => {
+                                    dropped = true;
+                                    break;
+                                }
+                                Ok(_) => {}
+                                // the entry stays pending and is read again after a restart
+                                Err(_) => return Ok(false),
+                            }
+                        }
+                        if dropped {
+                            // the entry stays pending and is claimed again after `claim_idle`
+                            tracing::debug!(
+                                sensor = %self.key,
+                                stream = %stream,
+                                id = %entry.id,
+                                "buffer is full, leaving entry pending"
+                            );
+                            continue;
+                        }
+                    }
+                    Err(e) => self.dead_letter(connection, &stream, &entry, &e).await?,
+                }
+            }
+            let _: usize = connection
+                .xack(&stream, &self.config.group, &[&entry.id])
+                .await
+                .map_err(redis_error)?;
+        }
+        Ok(true)
+    }
+
+    /// Copies an entry that cannot be turned into events to the dead letter stream
+    async fn dead_letter(
+        &self,
+        connection: &mut MultiplexedConnection,
+        stream: &str,
+        entry: &StreamId,
+        error: &SensorError,
+    ) -> Result<(), SensorError> {
+        let Some(dead_letter_stream) = &self.config.dead_letter_stream else {
+            tracing::warn!(
+                sensor = %self.key,
+                stream,
+                id = %entry.id,
+                error = %error,
+                "dropping invalid entry"
+            );
+            return Ok(());
+        };
+        tracing::warn!(
+            sensor = %self.key,
+            stream,
+            id = %entry.id,
+            error = %error,
+            "dead-lettering invalid entry"
+        );
+        let mut fields: Vec<(String, String)> = entry
+            .map
+            .iter()
+            .filter_map(|(name, value)| Some((name.clone(), text(value)?)))
+            .collect();
+        fields.extend([
+            ("dead_letter_stream".to_string(), stream.to_string()),
+            ("dead_letter_id".to_string(), entry.id.clone()),
+            ("dead_letter_error".to_string(), error.to_string()),
+        ]);
+        let _: Option<String> = connection
+            .xadd(dead_letter_stream, "*", &fields)
+            .await
+            .map_err(redis_error)?;
+        Ok(())
+    }
+
+    /// Reads until the sink is closed, returns early if the connection fails
+    async fn consume(&self, sink: &EventSink) -> Result<(), SensorError> {
+        let client = Client::open(self.config.url.expose()).map_err(redis_error)?;
+        let config = AsyncConnectionConfig::new()
+            .set_connection_timeout(Duration::from_secs(10))
+            .set_response_timeout(self.config.block + Duration::from_secs(10));
+        let mut connection = client
+            .get_multiplexed_async_connection_with_config(&config)
+            .await
+            .map_err(redis_error)?;
+        if self.config.create_group {
+            self.create_groups(&mut connection)
+                .await
+                .map_err(redis_error)?;
+        }
+        self.status.set_health(HealthState::Healthy);
+        tracing::info!(
+            sensor = %self.key,
+            streams = ?self.config.streams,
+            group = %self.config.group,
+            consumer = %self.consumer(),
+            "reading from Redis streams"
+        );
+
+        // entries delivered to this consumer before but never acknowledged
+        loop {
+            let entries = self
+                .read(&mut connection, "0", None)
+                .await
+                .map_err(redis_error)?;
+            if entries.is_empty() {
+                break;
+            }
+            if !self.deliver(&mut connection, sink, entries).await? {
+                return Ok(());
+            }
+        }
+
+        let mut cursors = vec!["0-0".to_string(); self.config.streams.len()];
+        let mut last_claim: Option<Instant> = None;
+        loop {
+            // claiming between reads, a cancelled read would leave entries pending
+            if last_claim.is_none_or(|last| last.elapsed() >= self.config.claim_interval) {
+                last_claim = Some(Instant::now());
+                let claimed = self
+                    .claim(&mut connection, &mut cursors)
+                    .await
+                    .map_err(redis_error)?;
+                if !self.deliver(&mut connection, sink, claimed).await? {
+                    return Ok(());
+                }
+            }
+
+            let entries = tokio::select! {
+                entries = self.read(&mut connection, ">", Some(self.config.block)) => {
+                    entries.map_err(redis_error)?
+                }
+                _ = sink.closed() => return Ok(()),
+            };
+            if !self.deliver(&mut connection, sink, entries).await? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Sensor for RedisStreamSensor {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn status_handle(&self) -> &StatusHandle {
+        &self.status
+    }
+
+    async fn run(&self, sink: EventSink) -> Result<(), SensorError> {
+        self.status.set_lifecycle(LifecycleState::Running);
+        while !sink.is_closed() {
+            if let Err(e) = self.consume(&sink).await {
+                self.status.set_health(HealthState::ResourceDown);
+                tracing::warn!(
+                    sensor = %self.key,
+                    error = %e,
+                    delay = ?self.config.reconnect_delay,
+                    "Redis connection failed, reconnecting"
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(self.config.reconnect_delay) => {}
+                    _ = sink.closed() => {}
+                }
+            }
+        }
+        self.status.set_lifecycle(LifecycleState::Stopped);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use loid_events::prelude::{Secret, Value};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    /// Server the ignored tests run against, e.g. started with
+    /// `docker run -p 6379:6379 redis:7`
+    fn server_url() -> String {
+        std::env::var("LOID_REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string())
+    }
+
+    fn config(stream: &str) -> RedisStreamSensorConfig {
+        let mut config: RedisStreamSensorConfig = serde_json::from_value(serde_json::json!({
+            "streams": [stream],
+            "group": "loid",
+            "start_id": "0",
+            "block": "100ms",
+            "claim_idle": "200ms",
+            "claim_interval": "100ms",
+            "dead_letter_stream": format!("{stream}-dead"),
+            "reconnect_delay": "100ms",
+        }))
+        .unwrap();
+        config.url = Secret::new(server_url());
+        config
+    }
+
+    async fn connection() -> MultiplexedConnection {
+        Client::open(server_url())
+            .unwrap()
+            .get_multiplexed_async_connection()
+            .await
+            .unwrap()
+    }
+
+    async fn wait_healthy(sensor: &RedisStreamSensor) {
+        for _ in 0..250 {
+            if sensor.status().health == HealthState::Healthy {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("sensor did not become healthy");
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let config: RedisStreamSensorConfig =
+            serde_json::from_str(r#"{"streams": ["app-events"], "group": "loid"}"#).unwrap();
+        assert_eq!(config.start_id, "$");
+        assert_eq!(config.claim_idle, Duration::from_secs(60));
+        assert!(config.validate().is_ok());
+        let sensor = RedisStreamSensor::new("app-events", config.clone()).unwrap();
+        assert_eq!(sensor.consumer(), "app-events");
+
+        let mut invalid = config.clone();
+        invalid.url = Secret::new("http://localhost");
+        assert!(matches!(
+            RedisStreamSensor::new("redis", invalid),
+            Err(SensorError::Config(_))
+        ));
+        let mut invalid = config.clone();
+        invalid.adapter = Some(crate::adapter::PayloadAdapter::Github);
+        assert!(invalid.validate().is_err());
+        let mut invalid = config.clone();
+        invalid.dead_letter_stream = Some("app-events".to_string());
+        assert!(invalid.validate().is_err());
+        let mut invalid = config;
+        invalid.streams.clear();
+        assert!(invalid.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_unreachable_server() {
+        let mut config = config("app-events");
+        config.url = Secret::new("redis://127.0.0.1:1");
+        let sensor = Arc::new(RedisStreamSensor::new("redis", config).unwrap());
+        let (sink, receiver) = EventSink::channel(8);
+        let handle = tokio::spawn({
+            let sensor = sensor.clone();
+            async move { sensor.run(sink).await }
+        });
+
+        for _ in 0..100 {
+            if sensor.status().health == HealthState::ResourceDown {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(sensor.status().health, HealthState::ResourceDown);
+
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(sensor.status().lifecycle, LifecycleState::Stopped);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis server, see server_url"]
+    async fn test_run_acknowledges_and_dead_letters() {
+        let stream = format!("loid-test-{}", Uuid::new_v4());
+        let mut connection = connection().await;
+        let mut ids = Vec::new();
+        for fields in [
+            vec![("message", "queue full")],
+            vec![("payload", "ignored"), ("message", "second")],
+        ] {
+            let id: String = connection.xadd(&stream, "*", &fields).await.unwrap();
+            ids.push(id);
+        }
+        let mut config = config(&stream);
+        config.payload_field = None;
+        let sensor = Arc::new(RedisStreamSensor::new("redis", config).unwrap());
+        let (sink, mut receiver) = EventSink::channel(8);
+        let handle = tokio::spawn({
+            let sensor = sensor.clone();
+            async move { sensor.run(sink).await }
+        });
+        wait_healthy(&sensor).await;
+
+        for id in &ids {
+            let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(event.source.source_id.as_ref(), Some(id));
+            assert_eq!(event.fields["stream"], Value::from(stream.as_str()));
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        let pending: redis::streams::StreamPendingReply =
+            connection.xpending(&stream, "loid").await.unwrap();
+        assert_eq!(pending.count(), 0);
+
+        // invalid payloads end up in the dead letter stream
+        let mut config = self::config(&stream);
+        config.payload_field = Some("payload".to_string());
+        let sensor = Arc::new(RedisStreamSensor::new("redis", config).unwrap());
+        let (sink, receiver) = EventSink::channel(8);
+        let handle = tokio::spawn({
+            let sensor = sensor.clone();
+            async move { sensor.run(sink).await }
+        });
+        wait_healthy(&sensor).await;
+        let _: String = connection
+            .xadd(&stream, "*", &[("payload", "not json")])
+            .await
+            .unwrap();
+        let mut dead = 0;
+        for _ in 0..100 {
+            dead = connection.xlen(format!("{stream}-dead")).await.unwrap();
+            if dead > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(dead, 1);
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let _: () = connection
+            .del(&[stream.clone(), format!("{stream}-dead")])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis server, see server_url"]
+    async fn test_run_claims_entries_of_dead_consumer() {
+        let stream = format!("loid-test-{}", Uuid::new_v4());
+        let mut connection = connection().await;
+        let _: () = connection
+            .xgroup_create_mkstream(&stream, "loid", "0")
+            .await
+            .unwrap();
+        let id: String = connection
+            .xadd(&stream, "*", &[("message", "stuck")])
+            .await
+            .unwrap();
+        // a consumer reads the entry and dies before acknowledging it
+        let _: Option<StreamReadReply> = connection
+            .xread_options(
+                &[&stream],
+                &[">"],
+                &StreamReadOptions::default().group("loid", "dead-consumer"),
+            )
+            .await
+            .unwrap();
+
+        let sensor = Arc::new(RedisStreamSensor::new("redis", config(&stream)).unwrap());
+        let (sink, mut receiver) = EventSink::channel(8);
+        let handle = tokio::spawn({
+            let sensor = sensor.clone();
+            async move { sensor.run(sink).await }
+        });
+        let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.source.source_id, Some(id));
+        assert_eq!(event.fields["message"], Value::from("stuck"));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(receiver);
+        tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let pending: redis::streams::StreamPendingReply =
+            connection.xpending(&stream, "loid").await.unwrap();
+        assert_eq!(pending.count(), 0);
+        let _: () = connection.del(&stream).await.unwrap();
+    }
+}
@@ -0,0 +1,707 @@
+use crate::buffer::{BufferConfig, OverflowPolicy, SegmentLog};
+use crate::mapping::Mapping;
+use crate::{SensorError, StatusHandle};
+use chrono::{DateTime, Utc};
+use loid_events::prelude::Event;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::MissedTickBehavior;
+
+/// How often the stages of a sink are ticked
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The bounded buffer shared by the sinks and the receiver of a sensor
+#[derive(Debug)]
+struct Buffer {
+    state: Mutex<BufferState>,
+    capacity: usize,
+    overflow: OverflowPolicy,
+    status: StatusHandle,
+    /// Signalled when an event was added or the last sink was dropped
+    readable: Notify,
+    /// Signalled when an event was removed or the receiver was dropped
+    writable: Notify,
+    /// Signalled when the receiver was dropped
+    closed: Notify,
+}
+
+#[derive(Debug)]
+struct BufferState {
+    queue: VecDeque<Event>,
+    spill: Option<SegmentLog>,
+    senders: usize,
+    closed: bool,
+}
+
+impl BufferState {
+    fn depth(&self) -> usize {
+        self.queue.len() + self.spill.as_ref().map_or(0, SegmentLog::len)
+    }
+
+    fn spilled(&self) -> bool {
+        self.spill.as_ref().is_some_and(|spill| !spill.is_empty())
+    }
+}
+
+impl Buffer {
+    fn lock(&self) -> MutexGuard<'_, BufferState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn report(&self, state: &BufferState) {
+        self.status.set_buffer_depth(state.depth());
+    }
+
+    /// Applies backpressure, the sensor stays throttled until the buffer drained to half capacity
+    fn throttle(&self, state: &BufferState) {
+        self.status.set_throttled(true);
+        self.report(state);
+    }
+
+    fn push(&self, state: &mut BufferState, event: Event) {
+        state.queue.push_back(event);
+        self.report(state);
+        self.readable.notify_one();
+    }
+
+    /// Writes the event to disk, `false` if the segment log is full or failed
+    fn spill(&self, state: &mut BufferState, event: &Event) -> bool {
+        let Some(spill) = state.spill.as_mut() else {
+            return false;
+        };
+        match spill.push(event) {
+            Ok(written) => written,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to spill event to disk");
+                false
+            }
+        }
+    }
+
+    fn pop(&self, state: &mut BufferState) -> Option<Event> {
+        // spilled events are newer than everything in memory, they move up in order
+        while state.queue.len() < self.capacity
+            && let Some(spill) = state.spill.as_mut()
+            && !spill.is_empty()
+        {
+            match spill.pop() {
+                Ok(Some(event)) => state.queue.push_back(event),
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to read spilled event");
+                    break;
+                }
+            }
+        }
+        let event = state.queue.pop_front()?;
+        self.report(state);
+        if state.queue.len() <= self.capacity / 2 && !state.spilled() {
+            self.status.set_throttled(false);
+        }
+        self.writable.notify_one();
+        Some(event)
+    }
+
+    /// Adds the event, what happens while the buffer is full depends on the overflow policy
+    async fn send(&self, event: Event) -> Result<SendOutcome, SensorError> {
+        loop {
+            let writable = self.writable.notified();
+            tokio::pin!(writable);
+            writable.as_mut().enable();
+            {
+                let mut state = self.lock();
+                if state.closed {
+                    return Err(SensorError::SinkClosed);
+                }
+                // once spilling started new events queue up behind the spilled ones
+                if state.spilled() {
+                    let outcome = if self.spill(&mut state, &event) {
+                        SendOutcome::Accepted
+                    } else {
+                        tracing::warn!(event = %event.id, "spill is full, dropping event");
+                        SendOutcome::Dropped
+                    };
+                    self.throttle(&state);
+                    return Ok(outcome);
+                }
+                if state.queue.len() < self.capacity {
+                    self.push(&mut state, event);
+                    return Ok(SendOutcome::Accepted);
+                }
+
+                self.throttle(&state);
+                match self.overflow {
+                    OverflowPolicy::Block => {}
+                    OverflowPolicy::DropOldest => {
+                        if let Some(oldest) = state.queue.pop_front() {
+                            tracing::debug!(event = %oldest.id, "buffer is full, dropping event");
+                        }
+                        self.push(&mut state, event);
+                        return Ok(SendOutcome::Displaced);
+                    }
+                    OverflowPolicy::DropNewest => {
+                        tracing::debug!(event = %event.id, "buffer is full, dropping event");
+                        return Ok(SendOutcome::Dropped);
+                    }
+                    OverflowPolicy::Spill => {
+                        if self.spill(&mut state, &event) {
+                            self.report(&state);
+                            return Ok(SendOutcome::Accepted);
+                        }
+                        tracing::warn!(event = %event.id, "spill is full, dropping event");
+                        return Ok(SendOutcome::Dropped);
+                    }
+                }
+            }
+            writable.await;
+        }
+    }
+}
+
+/// What became of an event handed to [`EventSink::send`], ordered by what was lost
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SendOutcome {
+    /// The mapping or a stage consumed the event on purpose
+    Filtered,
+    /// The event is buffered, in memory or spilled to disk
+    Accepted,
+    /// The event is buffered, the oldest buffered event was discarded to make room for it
+    Displaced,
+    /// The buffer is full and the overflow policy discarded the event, a sensor consuming from
+    /// a broker should hand the message back for redelivery
+    Dropped,
+}
+
+/// A stateful step between the mapping and the buffer of a sensor, e.g. a
+/// [`MetricDetector`](crate::metrics::MetricDetector)
+pub trait Stage: Debug + Send + 'static {
+    /// Processes an event, returns the events passed on in its place
+    fn apply(&mut self, event: Event) -> Vec<Event>;
+
+    /// Lets time pass without an event, returns the events due by `now`
+    fn tick(&mut self, now: DateTime<Utc>) -> Vec<Event>;
+}
+
+/// The stages of a sink in order, the events of one stage pass through the following ones
+#[derive(Debug, Default)]
+struct Pipeline {
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl Pipeline {
+    fn apply(&mut self, event: Event) -> Vec<Event> {
+        self.pass(0, vec![event])
+    }
+
+    fn tick(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        let mut events = Vec::new();
+        for index in 0..self.stages.len() {
+            let due = self.stages[index].tick(now);
+            events.extend(self.pass(index + 1, due));
+        }
+        events
+    }
+
+    /// Passes the events through the stages starting at `from`
+    fn pass(&mut self, from: usize, mut events: Vec<Event>) -> Vec<Event> {
+        for stage in &mut self.stages[from..] {
+            events = events
+                .into_iter()
+                .flat_map(|event| stage.apply(event))
+                .collect();
+        }
+        events
+    }
+}
+
+fn lock(pipeline: &Mutex<Pipeline>) -> MutexGuard<'_, Pipeline> {
+    pipeline.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Ticks the stages until the sinks or the receiver are gone
+async fn tick(buffer: Arc<Buffer>, pipeline: Weak<Mutex<Pipeline>>) {
+    let mut interval = tokio::time::interval(TICK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if buffer.lock().closed {
+            return;
+        }
+        let Some(pipeline) = pipeline.upgrade() else {
+            return;
+        };
+        let events = lock(&pipeline).tick(Utc::now());
+        drop(pipeline);
+        for event in events {
+            if buffer.send(event).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// The channel end a sensor sends its events into.
+///
+/// Events pass through the mapping, the stages and a bounded buffer, the [`OverflowPolicy`]
+/// decides what happens once it is full. Clones share the stages and the buffer.
+#[derive(Debug)]
+pub struct EventSink {
+    buffer: Arc<Buffer>,
+    mapping: Option<Arc<Mapping>>,
+    stages: Option<Arc<Mutex<Pipeline>>>,
+}
+
+/// The receiving end of a sensor buffer, dropping it closes all sinks
+#[derive(Debug)]
+pub struct EventReceiver {
+    buffer: Arc<Buffer>,
+}
+
+impl EventSink {
+    /// Creates a blocking sink together with the receiver the events are delivered to
+    pub fn channel(capacity: usize) -> (Self, EventReceiver) {
+        let config = BufferConfig {
+            capacity,
+            ..Default::default()
+        };
+        Self::buffered(&config, StatusHandle::default())
+            .expect("a blocking buffer without spill cannot fail")
+    }
+
+    /// Creates a sink with the configured buffer reporting its state to the status of a sensor
+    pub fn buffered(
+        config: &BufferConfig,
+        status: StatusHandle,
+    ) -> Result<(Self, EventReceiver), SensorError> {
+        config.validate()?;
+        let spill = match (&config.overflow, &config.spill) {
+            (OverflowPolicy::Spill, Some(spill)) => Some(SegmentLog::open(spill.clone())?),
+            _ => None,
+        };
+        let state = BufferState {
+            queue: VecDeque::with_capacity(config.capacity.min(1024)),
+            spill,
+            senders: 1,
+            closed: false,
+        };
+        let buffer = Arc::new(Buffer {
+            capacity: config.capacity,
+            overflow: config.overflow,
+            status,
+            readable: Notify::new(),
+            writable: Notify::new(),
+            closed: Notify::new(),
+            state: Mutex::new(state),
+        });
+        // include segments left over by a previous run
+        buffer.report(&buffer.lock());
+        let sink = Self {
+            buffer: buffer.clone(),
+            mapping: None,
+            stages: None,
+        };
+        Ok((sink, EventReceiver { buffer }))
+    }
+
+    /// Applies the mapping to every event sent through this sink
+    pub fn with_mapping(mut self, mapping: Mapping) -> Self {
+        self.mapping = Some(Arc::new(mapping));
+        self
+    }
+
+    /// Passes the mapped events through the stage, after the stages added before.
+    ///
+    /// The stages are ticked every second by a task of the current Tokio runtime, so windows
+    /// close and sources stabilize while no events arrive.
+    pub fn with_stage(mut self, stage: impl Stage) -> Self {
+        match &self.stages {
+            Some(stages) => lock(stages).stages.push(Box::new(stage)),
+            None => {
+                let stages = Arc::new(Mutex::new(Pipeline {
+                    stages: vec![Box::new(stage)],
+                }));
+                tokio::spawn(tick(self.buffer.clone(), Arc::downgrade(&stages)));
+                self.stages = Some(stages);
+            }
+        }
+        self
+    }
+
+    /// Sends an event, what happens while the buffer is full depends on the overflow policy.
+    ///
+    /// Fails only once the receiver was dropped, the outcome tells whether the event was
+    /// buffered or discarded.
+    pub async fn send(&self, event: Event) -> Result<SendOutcome, SensorError> {
+        let event = match &self.mapping {
+            Some(mapping) => match mapping.apply(event) {
+                Some(event) => event,
+                None => return Ok(SendOutcome::Filtered),
+            },
+            None => event,
+        };
+
+        let events = match &self.stages {
+            Some(stages) => lock(stages).apply(event),
+            None => vec![event],
+        };
+        // the outcome of the event losing the most
+        let mut outcome = SendOutcome::Filtered;
+        for event in events {
+            outcome = outcome.max(self.buffer.send(event).await?);
+        }
+        Ok(outcome)
+    }
+
+    /// Number of events waiting in the buffer, in memory and spilled to disk
+    pub fn depth(&self) -> usize {
+        self.buffer.lock().depth()
+    }
+
+    /// Returns `true` if the receiving side has been dropped
+    pub fn is_closed(&self) -> bool {
+        self.buffer.lock().closed
+    }
+
+    /// Completes once the receiving side has been dropped
+    pub async fn closed(&self) {
+        loop {
+            let closed = self.buffer.closed.notified();
+            tokio::pin!(closed);
+            closed.as_mut().enable();
+            if self.is_closed() {
+                return;
+            }
+            closed.await;
+        }
+    }
+}
+
+impl Clone for EventSink {
+    fn clone(&self) -> Self {
+        self.buffer.lock().senders += 1;
+        Self {
+            buffer: self.buffer.clone(),
+            mapping: self.mapping.clone(),
+            stages: self.stages.clone(),
+        }
+    }
+}
+
+impl Drop for EventSink {
+    fn drop(&mut self) {
+        let mut state = self.buffer.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.buffer.readable.notify_one();
+        }
+    }
+}
+
+impl EventReceiver {
+    /// Receives the next event, `None` once all sinks were dropped and the buffer is empty
+    pub async fn recv(&mut self) -> Option<Event> {
+        let buffer = &self.buffer;
+        loop {
+            let readable = buffer.readable.notified();
+            tokio::pin!(readable);
+            readable.as_mut().enable();
+            {
+                let mut state = buffer.lock();
+                if let Some(event) = buffer.pop(&mut state) {
+                    return Some(event);
+                }
+                if state.senders == 0 {
+                    return None;
+                }
+            }
+            readable.await;
+        }
+    }
+
+    /// Number of events waiting in the buffer, in memory and spilled to disk
+    pub fn depth(&self) -> usize {
+        self.buffer.lock().depth()
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.buffer.lock().closed = true;
+        self.buffer.writable.notify_waiters();
+        self.buffer.closed.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HealthState;
+    use crate::buffer::SpillConfig;
+    use crate::mapping::MappingConfig;
+    use loid_events::prelude::{EventBuilder, Value};
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    fn event(number: i64) -> Event {
+        EventBuilder::new().with_int_field("number", number).build()
+    }
+
+    fn config(capacity: usize, overflow: OverflowPolicy) -> BufferConfig {
+        BufferConfig {
+            capacity,
+            overflow,
+            spill: None,
+        }
+    }
+
+    async fn numbers(receiver: &mut EventReceiver, count: usize) -> Vec<i64> {
+        let mut numbers = Vec::new();
+        for _ in 0..count {
+            match receiver.recv().await.unwrap().fields.get("number") {
+                Some(Value::Int(number)) => numbers.push(*number),
+                other => panic!("unexpected field {other:?}"),
+            }
+        }
+        numbers
+    }
+
+    #[tokio::test]
+    async fn test_send_applies_mapping() {
+        let config: MappingConfig = serde_json::from_str(
+            r#"{
+                "constants": {"team": "platform"},
+                "drop": [{"when": [{"field": "level", "equals": "debug"}]}]
+            }"#,
+        )
+        .unwrap();
+        let (sink, mut receiver) = EventSink::channel(4);
+        let sink = sink.with_mapping(Mapping::new(&config).unwrap());
+
+        let debug = EventBuilder::new()
+            .with_text_field("level", "debug")
+            .build();
+        assert_eq!(sink.send(debug).await.unwrap(), SendOutcome::Filtered);
+        let error = EventBuilder::new()
+            .with_text_field("level", "error")
+            .build();
+        assert_eq!(sink.send(error).await.unwrap(), SendOutcome::Accepted);
+        drop(sink);
+
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.fields.get("level"), Some(&Value::from("error")));
+        assert_eq!(event.fields.get("team"), Some(&Value::from("platform")));
+        assert!(receiver.recv().await.is_none());
+    }
+
+    /// Passes every other event, releases the held ones on tick
+    #[derive(Debug, Default)]
+    struct Alternate {
+        held: Vec<Event>,
+    }
+
+    impl Stage for Alternate {
+        fn apply(&mut self, event: Event) -> Vec<Event> {
+            if self.held.is_empty() {
+                self.held.push(event);
+                Vec::new()
+            } else {
+                vec![event]
+            }
+        }
+
+        fn tick(&mut self, _now: DateTime<Utc>) -> Vec<Event> {
+            std::mem::take(&mut self.held)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stages_apply_in_order_and_tick() {
+        let (sink, mut receiver) = EventSink::channel(8);
+        let sink = sink
+            .with_stage(Alternate::default())
+            .with_stage(Alternate::default());
+
+        // the first stage holds 0 and passes 1, which the second stage holds
+        assert_eq!(sink.send(event(0)).await.unwrap(), SendOutcome::Filtered);
+        assert_eq!(sink.send(event(1)).await.unwrap(), SendOutcome::Filtered);
+        assert_eq!(sink.send(event(2)).await.unwrap(), SendOutcome::Accepted);
+        assert_eq!(numbers(&mut receiver, 1).await, vec![2]);
+
+        // ticking releases 0 from the first stage, which the second one passes, then 1 from
+        // the second stage
+        let ticked = tokio::time::timeout(Duration::from_secs(5), numbers(&mut receiver, 2))
+            .await
+            .unwrap();
+        assert_eq!(ticked, vec![0, 1]);
+        drop(sink);
+        assert!(receiver.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_room_and_throttles() {
+        let status = StatusHandle::default();
+        status.set_health(HealthState::Healthy);
+        let (sink, mut receiver) =
+            EventSink::buffered(&config(2, OverflowPolicy::Block), status.clone()).unwrap();
+        sink.send(event(0)).await.unwrap();
+        sink.send(event(1)).await.unwrap();
+        assert_eq!(sink.depth(), 2);
+
+        let blocked = tokio::spawn({
+            let sink = sink.clone();
+            async move { sink.send(event(2)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!blocked.is_finished());
+        assert_eq!(status.get().health, HealthState::Throttled);
+        assert_eq!(status.get().buffer_depth, 2);
+
+        assert_eq!(numbers(&mut receiver, 3).await, vec![0, 1, 2]);
+        blocked.await.unwrap().unwrap();
+        assert_eq!(status.get().health, HealthState::Healthy);
+        assert_eq!(receiver.depth(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_drop_policies() {
+        let (sink, mut receiver) = EventSink::buffered(
+            &config(2, OverflowPolicy::DropOldest),
+            StatusHandle::default(),
+        )
+        .unwrap();
+        let mut outcomes = Vec::new();
+        for number in 0..4 {
+            outcomes.push(sink.send(event(number)).await.unwrap());
+        }
+        assert_eq!(
+            outcomes,
+            [
+                SendOutcome::Accepted,
+                SendOutcome::Accepted,
+                SendOutcome::Displaced,
+                SendOutcome::Displaced
+            ]
+        );
+        assert_eq!(numbers(&mut receiver, 2).await, vec![2, 3]);
+
+        let (sink, mut receiver) = EventSink::buffered(
+            &config(2, OverflowPolicy::DropNewest),
+            StatusHandle::default(),
+        )
+        .unwrap();
+        let mut outcomes = Vec::new();
+        for number in 0..4 {
+            outcomes.push(sink.send(event(number)).await.unwrap());
+        }
+        assert_eq!(
+            outcomes,
+            [
+                SendOutcome::Accepted,
+                SendOutcome::Accepted,
+                SendOutcome::Dropped,
+                SendOutcome::Dropped
+            ]
+        );
+        assert_eq!(numbers(&mut receiver, 2).await, vec![0, 1]);
+        drop(sink);
+        assert!(receiver.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_spill_keeps_order() {
+        let directory = std::env::temp_dir().join(format!("loid-sink-{}", Uuid::new_v4()));
+        let config = BufferConfig {
+            capacity: 4,
+            overflow: OverflowPolicy::Spill,
+            spill: Some(SpillConfig {
+                directory: directory.clone(),
+                segment_size: 1024,
+                max_size: 1024 * 1024,
+            }),
+        };
+        let status = StatusHandle::default();
+        let (sink, mut receiver) = EventSink::buffered(&config, status.clone()).unwrap();
+        for number in 0..20 {
+            sink.send(event(number)).await.unwrap();
+        }
+        assert_eq!(sink.depth(), 20);
+        assert_eq!(status.get().health, HealthState::Throttled);
+
+        assert_eq!(
+            numbers(&mut receiver, 10).await,
+            (0..10).collect::<Vec<_>>()
+        );
+        sink.send(event(20)).await.unwrap();
+        assert_eq!(
+            numbers(&mut receiver, 11).await,
+            (10..=20).collect::<Vec<_>>()
+        );
+        assert_ne!(status.get().health, HealthState::Throttled);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_full_spill_reports_dropped_events() {
+        let directory = std::env::temp_dir().join(format!("loid-sink-{}", Uuid::new_v4()));
+        let config = BufferConfig {
+            capacity: 1,
+            overflow: OverflowPolicy::Spill,
+            spill: Some(SpillConfig {
+                directory: directory.clone(),
+                segment_size: 1024,
+                max_size: 1024,
+            }),
+        };
+        let (sink, mut receiver) = EventSink::buffered(&config, StatusHandle::default()).unwrap();
+        let mut outcomes = Vec::new();
+        for number in 0..10 {
+            outcomes.push(sink.send(event(number)).await.unwrap());
+        }
+        let accepted = outcomes
+            .iter()
+            .take_while(|outcome| **outcome == SendOutcome::Accepted)
+            .count();
+        assert!(accepted > 1 && accepted < 10);
+        assert!(
+            outcomes[accepted..]
+                .iter()
+                .all(|outcome| *outcome == SendOutcome::Dropped)
+        );
+        assert_eq!(
+            numbers(&mut receiver, accepted).await,
+            (0..accepted as i64).collect::<Vec<_>>()
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_fails_once_receiver_is_dropped() {
+        let (sink, receiver) = EventSink::channel(1);
+        sink.send(event(0)).await.unwrap();
+        let blocked = tokio::spawn({
+            let sink = sink.clone();
+            async move { sink.send(event(1)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(receiver);
+        assert!(matches!(
+            blocked.await.unwrap(),
+            Err(SensorError::SinkClosed)
+        ));
+        assert!(sink.is_closed());
+        tokio::time::timeout(Duration::from_secs(1), sink.closed())
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_buffered_rejects_spill_without_directory() {
+        assert!(matches!(
+            EventSink::buffered(&config(4, OverflowPolicy::Spill), StatusHandle::default()),
+            Err(SensorError::Config(_))
+        ));
+    }
+}
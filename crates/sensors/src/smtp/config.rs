@@ -0,0 +1,116 @@
+use crate::SensorError;
+use regex::Regex;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Configuration of a [`SmtpSensor`](crate::smtp::SmtpSensor)
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmtpSensorConfig {
+    /// Address the SMTP listener binds to
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// Host name announced in the greeting
+    #[serde(default = "default_hostname")]
+    pub hostname: String,
+    /// Accepted recipient addresses, `@example.com` accepts a whole domain
+    pub recipients: Vec<String>,
+    /// Messages larger than this many bytes are rejected
+    #[serde(default = "default_max_message_size")]
+    pub max_message_size: usize,
+    /// Connections idle for this long are closed
+    #[serde(with = "humantime_serde", default = "default_timeout")]
+    pub timeout: Duration,
+    /// Rules extracting additional fields from the subject or body
+    #[serde(default)]
+    pub extract: Vec<ExtractionRule>,
+}
+
+/// Sets a field from the first match of a regular expression.
+///
+/// The value is taken from the capture group named `value`, the first capture group
+/// or the whole match, in that order.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExtractionRule {
+    /// Name of the field to set
+    pub field: String,
+    /// Part of the message the pattern is matched against
+    #[serde(default)]
+    pub from: ExtractFrom,
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractFrom {
+    #[default]
+    Subject,
+    /// The plain text body, HTML bodies are converted to text
+    Body,
+}
+
+fn default_listen() -> String {
+    "0.0.0.0:2525".to_string()
+}
+
+fn default_hostname() -> String {
+    "loid".to_string()
+}
+
+fn default_max_message_size() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+impl SmtpSensorConfig {
+    /// Checks the configuration for values the sensor cannot work with
+    pub fn validate(&self) -> Result<(), SensorError> {
+        if self.recipients.is_empty() {
+            return Err(SensorError::Config(
+                "at least one recipient is required".to_string(),
+            ));
+        }
+        if let Some(recipient) = self.recipients.iter().find(|r| !r.contains('@')) {
+            return Err(SensorError::Config(format!(
+                "recipient '{recipient}' is not an email address or @domain"
+            )));
+        }
+        if self.max_message_size == 0 {
+            return Err(SensorError::Config(
+                "max_message_size must be greater than zero".to_string(),
+            ));
+        }
+        if self.timeout.is_zero() {
+            return Err(SensorError::Config(
+                "timeout must be greater than zero".to_string(),
+            ));
+        }
+        for rule in &self.extract {
+            rule.compile()?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if messages for the given address are accepted
+    pub fn accepts(&self, recipient: &str) -> bool {
+        let recipient = recipient.to_ascii_lowercase();
+        self.recipients.iter().any(|accepted| {
+            let accepted = accepted.to_ascii_lowercase();
+            if accepted.starts_with('@') {
+                recipient.ends_with(&accepted)
+            } else {
+                recipient == accepted
+            }
+        })
+    }
+}
+
+impl ExtractionRule {
+    pub(crate) fn compile(&self) -> Result<Regex, SensorError> {
+        Regex::new(&self.pattern).map_err(|e| {
+            SensorError::Config(format!("invalid pattern for field '{}': {e}", self.field))
+        })
+    }
+}
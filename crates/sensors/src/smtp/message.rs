@@ -0,0 +1,295 @@
+use crate::SensorError;
+use crate::smtp::{ExtractFrom, ExtractionRule};
+use loid_events::prelude::Value;
+use mail_parser::{Address, MessageParser, MimeHeaders, PartType};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// A message as received by the SMTP listener
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Envelope {
+    /// Reverse path of `MAIL FROM`, empty for bounces
+    pub mail_from: String,
+    /// Accepted forward paths of `RCPT TO`
+    pub rcpt_to: Vec<String>,
+    /// The raw message with dot-stuffing removed
+    pub data: Vec<u8>,
+}
+
+/// Metadata of an attached file, the content itself is not kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub name: Option<String>,
+    pub content_type: String,
+    pub size: usize,
+}
+
+/// The parts of a MIME message turned into event fields
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MailMessage {
+    pub from: Option<String>,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub subject: Option<String>,
+    pub message_id: Option<String>,
+    pub date: Option<String>,
+    pub text_body: Option<String>,
+    pub html_body: Option<String>,
+    pub attachments: Vec<Attachment>,
+    /// Header names in lowercase, the last occurrence wins
+    pub headers: HashMap<String, String>,
+}
+
+impl MailMessage {
+    /// Parses a raw RFC 5322 message, `None` if it has no headers at all
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let message = MessageParser::default().parse(data)?;
+
+        let text_body = message
+            .text_part(0)
+            .filter(|part| matches!(part.body, PartType::Text(_)))
+            .and_then(|_| message.body_text(0))
+            .map(|body| body.into_owned());
+        let html_body = message
+            .html_part(0)
+            .filter(|part| matches!(part.body, PartType::Html(_)))
+            .and_then(|_| message.body_html(0))
+            .map(|body| body.into_owned());
+
+        let attachments = message
+            .attachments()
+            .map(|part| Attachment {
+                name: part.attachment_name().map(str::to_string),
+                content_type: part
+                    .content_type()
+                    .map(|content_type| match content_type.subtype() {
+                        Some(subtype) => format!("{}/{}", content_type.ctype(), subtype),
+                        None => content_type.ctype().to_string(),
+                    })
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+                size: part.len(),
+            })
+            .collect();
+
+        let headers = message
+            .headers()
+            .iter()
+            .filter_map(|header| {
+                let name = header.name().to_string();
+                let raw = message.header_raw(name.clone())?;
+                Some((name.to_ascii_lowercase(), unfold(raw)))
+            })
+            .collect();
+
+        Some(Self {
+            from: message.from().and_then(first_address),
+            to: message.to().map(all_addresses).unwrap_or_default(),
+            cc: message.cc().map(all_addresses).unwrap_or_default(),
+            subject: message.subject().map(str::to_string),
+            message_id: message.message_id().map(str::to_string),
+            date: message.date().map(|date| date.to_rfc3339()),
+            text_body,
+            html_body,
+            attachments,
+            headers,
+        })
+    }
+
+    /// Text the extraction rules of the body are matched against
+    fn body(&self) -> Option<String> {
+        self.text_body.clone().or_else(|| {
+            self.html_body
+                .as_deref()
+                .map(mail_parser::decoders::html::html_to_text)
+        })
+    }
+}
+
+/// Compiled extraction rules of a sensor
+#[derive(Debug, Clone, Default)]
+pub struct Extractor {
+    rules: Vec<(ExtractionRule, Regex)>,
+}
+
+impl Extractor {
+    pub fn new(rules: &[ExtractionRule]) -> Result<Self, SensorError> {
+        let rules = rules
+            .iter()
+            .map(|rule| Ok((rule.clone(), rule.compile()?)))
+            .collect::<Result<_, SensorError>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Applies all rules, rules without a match are skipped
+    pub fn extract(&self, message: &MailMessage) -> Vec<(String, String)> {
+        let body = message.body();
+        self.rules
+            .iter()
+            .filter_map(|(rule, regex)| {
+                let haystack = match rule.from {
+                    ExtractFrom::Subject => message.subject.as_deref()?,
+                    ExtractFrom::Body => body.as_deref()?,
+                };
+                let captures = regex.captures(haystack)?;
+                let value = captures
+                    .name("value")
+                    .or_else(|| captures.get(1))
+                    .or_else(|| captures.get(0))?;
+                Some((rule.field.clone(), value.as_str().trim().to_string()))
+            })
+            .collect()
+    }
+}
+
+impl Attachment {
+    pub fn to_value(&self) -> Value {
+        let mut map = HashMap::new();
+        if let Some(name) = &self.name {
+            map.insert("name".to_string(), Value::from(name.as_str()));
+        }
+        map.insert(
+            "content_type".to_string(),
+            Value::from(self.content_type.as_str()),
+        );
+        map.insert("size".to_string(), Value::Int(self.size as i64));
+        Value::Map(map)
+    }
+}
+
+fn first_address(address: &Address) -> Option<String> {
+    address
+        .first()
+        .and_then(|addr| addr.address())
+        .map(str::to_string)
+}
+
+fn all_addresses(address: &Address) -> Vec<String> {
+    address
+        .iter()
+        .filter_map(|addr| addr.address())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Joins folded header lines into a single line
+fn unfold(raw: &str) -> String {
+    raw.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MULTIPART: &str = "From: Vendor Alerts <alerts@vendor.example>\r\n\
+        To: ops@loid.local, oncall@loid.local\r\n\
+        Subject: [CRITICAL] disk full on db-01\r\n\
+        Message-ID: <1234@vendor.example>\r\n\
+        Date: Mon, 19 Oct 2026 08:00:00 +0000\r\n\
+        X-Priority: 1\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+        \r\n\
+        --outer\r\n\
+        Content-Type: multipart/alternative; boundary=\"inner\"\r\n\
+        \r\n\
+        --inner\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        \r\n\
+        Host: db-01\r\n\
+        Usage: 99%\r\n\
+        --inner\r\n\
+        Content-Type: text/html; charset=utf-8\r\n\
+        \r\n\
+        <p>Host: <b>db-01</b></p>\r\n\
+        --inner--\r\n\
+        --outer\r\n\
+        Content-Type: text/csv; name=\"usage.csv\"\r\n\
+        Content-Disposition: attachment; filename=\"usage.csv\"\r\n\
+        \r\n\
+        mount,used\r\n\
+        --outer--\r\n";
+
+    fn rule(field: &str, from: ExtractFrom, pattern: &str) -> ExtractionRule {
+        ExtractionRule {
+            field: field.to_string(),
+            from,
+            pattern: pattern.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_multipart_message() {
+        let message = MailMessage::parse(MULTIPART.as_bytes()).unwrap();
+        assert_eq!(message.from.as_deref(), Some("alerts@vendor.example"));
+        assert_eq!(message.to, vec!["ops@loid.local", "oncall@loid.local"]);
+        assert_eq!(
+            message.subject.as_deref(),
+            Some("[CRITICAL] disk full on db-01")
+        );
+        assert_eq!(message.message_id.as_deref(), Some("1234@vendor.example"));
+        assert_eq!(message.date.as_deref(), Some("2026-10-19T08:00:00Z"));
+        assert!(message.text_body.unwrap().contains("Usage: 99%"));
+        assert!(message.html_body.unwrap().contains("<b>db-01</b>"));
+        assert_eq!(
+            message.attachments,
+            vec![Attachment {
+                name: Some("usage.csv".to_string()),
+                content_type: "text/csv".to_string(),
+                size: 10,
+            }]
+        );
+        assert_eq!(
+            message.headers.get("x-priority").map(String::as_str),
+            Some("1")
+        );
+    }
+
+    #[test]
+    fn test_parse_plain_message_has_no_html_body() {
+        let message = MailMessage::parse(b"Subject: hello\r\n\r\njust text\r\n").unwrap();
+        assert_eq!(message.text_body.as_deref(), Some("just text\r\n"));
+        assert!(message.html_body.is_none());
+        assert!(message.attachments.is_empty());
+    }
+
+    #[test]
+    fn test_extractor() {
+        let extractor = Extractor::new(&[
+            rule("severity", ExtractFrom::Subject, r"^\[(\w+)\]"),
+            rule("host", ExtractFrom::Body, r"Host:\s*(?P<value>[\w-]+)"),
+            rule("ticket", ExtractFrom::Subject, r"#(\d+)"),
+            rule("disk", ExtractFrom::Subject, r"disk \w+"),
+        ])
+        .unwrap();
+        let message = MailMessage::parse(MULTIPART.as_bytes()).unwrap();
+        assert_eq!(
+            extractor.extract(&message),
+            vec![
+                ("severity".to_string(), "CRITICAL".to_string()),
+                ("host".to_string(), "db-01".to_string()),
+                ("disk".to_string(), "disk full".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extractor_matches_html_only_body_as_text() {
+        let extractor = Extractor::new(&[rule("host", ExtractFrom::Body, r"Host: (\S+)")]).unwrap();
+        let message = MailMessage::parse(
+            b"Subject: x\r\nContent-Type: text/html\r\n\r\n<p>Host: <b>web-02</b></p>\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            extractor.extract(&message),
+            vec![("host".to_string(), "web-02".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_extractor_rejects_invalid_pattern() {
+        assert!(matches!(
+            Extractor::new(&[rule("host", ExtractFrom::Body, "(")]),
+            Err(SensorError::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,26 @@
+//! SMTP receiver sensor turning inbound emails into events.
+//!
+//! ```yaml
+//! sensor:
+//!     type: smtp
+//!     listen: 0.0.0.0:2525
+//!     hostname: alerts.loid.local
+//!     recipients: [ alerts@loid.local, "@vendor-alerts.loid.local" ]
+//!     max_message_size: 10485760
+//!     extract:
+//!         - field: severity
+//!           from: subject
+//!           pattern: '^\[(\w+)\]'
+//!         - field: host
+//!           from: body
+//!           pattern: 'Host:\s*(?P<value>\S+)'
+//! ```
+
+pub(crate) mod config;
+pub(crate) mod message;
+pub(crate) mod sensor;
+pub(crate) mod session;
+
+pub use config::{ExtractFrom, ExtractionRule, SmtpSensorConfig};
+pub use message::{Attachment, Envelope, Extractor, MailMessage};
+pub use sensor::SmtpSensor;
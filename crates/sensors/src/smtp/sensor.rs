@@ -0,0 +1,382 @@
+use crate::smtp::session::SmtpSession;
+use crate::smtp::{Envelope, Extractor, MailMessage, SmtpSensorConfig};
+use crate::{
+    EventSink, HealthState, LifecycleState, SendOutcome, Sensor, SensorError, StatusHandle,
+};
+use loid_events::prelude::{Event, EventBuilder, Source, Value};
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+
+/// Receives emails over SMTP and emits one event per accepted message.
+pub struct SmtpSensor {
+    key: String,
+    config: Arc<SmtpSensorConfig>,
+    extractor: Arc<Extractor>,
+    status: StatusHandle,
+}
+
+impl SmtpSensor {
+    pub fn new(key: impl Into<String>, config: SmtpSensorConfig) -> Result<Self, SensorError> {
+        config.validate()?;
+        let extractor = Extractor::new(&config.extract)?;
+        Ok(Self {
+            key: key.into(),
+            config: Arc::new(config),
+            extractor: Arc::new(extractor),
+            status: StatusHandle::default(),
+        })
+    }
+
+    pub fn config(&self) -> &SmtpSensorConfig {
+        &self.config
+    }
+
+    async fn handle_connection(
+        key: String,
+        config: Arc<SmtpSensorConfig>,
+        extractor: Arc<Extractor>,
+        stream: TcpStream,
+        sink: EventSink,
+    ) -> std::io::Result<()> {
+        let mut session = SmtpSession::new(stream, config);
+        session.greet().await?;
+        while let Some(envelope) = session.next_message().await? {
+            let Some(event) = build_event(&key, &extractor, &envelope) else {
+                session
+                    .reply(554, "5.6.0 message could not be parsed")
+                    .await?;
+                continue;
+            };
+            // the message is only acknowledged once the event was handed off
+            match sink.send(event).await {
+                Ok(SendOutcome::Dropped) => {
+                    session
+                        .reply(451, "4.3.1 buffer is full, try again later")
+                        .await?;
+                }
+                Ok(_) => session.reply(250, "2.0.0 message accepted").await?,
+                Err(_) => {
+                    session
+                        .reply(451, "4.3.0 not accepting messages right now")
+                        .await?;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn build_event(key: &str, extractor: &Extractor, envelope: &Envelope) -> Option<Event> {
+    let message = MailMessage::parse(&envelope.data)?;
+    let sender = message
+        .from
+        .clone()
+        .unwrap_or_else(|| envelope.mail_from.clone());
+
+    let mut builder = EventBuilder::new();
+    builder
+        .with_source(Source {
+            system: "smtp".to_string(),
+            source_id: Some(sender),
+        })
+        .with_text_field("sensor", key)
+        .with_text_field("mail_from", &envelope.mail_from)
+        .with_list_field(
+            "rcpt_to",
+            envelope
+                .rcpt_to
+                .iter()
+                .map(|rcpt| Value::from(rcpt.as_str()))
+                .collect(),
+        )
+        .with_list_field(
+            "to",
+            message
+                .to
+                .iter()
+                .map(|to| Value::from(to.as_str()))
+                .collect(),
+        )
+        .with_int_field("size", envelope.data.len() as i64)
+        .with_list_field(
+            "attachments",
+            message
+                .attachments
+                .iter()
+                .map(|attachment| attachment.to_value())
+                .collect(),
+        )
+        .with_map_field(
+            "headers",
+            message
+                .headers
+                .iter()
+                .map(|(name, value)| (name.clone(), Value::from(value.as_str())))
+                .collect(),
+        );
+    if !message.cc.is_empty() {
+        builder.with_list_field(
+            "cc",
+            message
+                .cc
+                .iter()
+                .map(|cc| Value::from(cc.as_str()))
+                .collect(),
+        );
+    }
+    let optional = [
+        ("from", &message.from),
+        ("subject", &message.subject),
+        ("message_id", &message.message_id),
+        ("date", &message.date),
+        ("text_body", &message.text_body),
+        ("html_body", &message.html_body),
+    ];
+    for (field, value) in optional {
+        if let Some(value) = value {
+            builder.with_text_field(field, value);
+        }
+    }
+    for (field, value) in extractor.extract(&message) {
+        builder.with_text_field(&field, &value);
+    }
+    Some(builder.build())
+}
+
+impl Sensor for SmtpSensor {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn status_handle(&self) -> &StatusHandle {
+        &self.status
+    }
+
+    async fn run(&self, sink: EventSink) -> Result<(), SensorError> {
+        let listener = match TcpListener::bind(&self.config.listen).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                self.status.set_health(HealthState::Misconfigured);
+                return Err(e.into());
+            }
+        };
+        self.status.set_lifecycle(LifecycleState::Running);
+        self.status.set_health(HealthState::Healthy);
+        tracing::info!(sensor = %self.key, listen = %self.config.listen, "SMTP listener started");
+
+        let mut connections = JoinSet::new();
+        loop {
+            let (stream, peer) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        tracing::warn!(sensor = %self.key, error = %e, "failed to accept SMTP connection");
+                        continue;
+                    }
+                },
+                Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+                _ = sink.closed() => break,
+            };
+
+            let key = self.key.clone();
+            let config = self.config.clone();
+            let extractor = self.extractor.clone();
+            let sink = sink.clone();
+            connections.spawn(async move {
+                if let Err(e) =
+                    Self::handle_connection(key.clone(), config, extractor, stream, sink).await
+                {
+                    tracing::debug!(sensor = %key, peer = %peer, error = %e, "SMTP connection failed");
+                }
+            });
+        }
+
+        connections.shutdown().await;
+        self.status.set_lifecycle(LifecycleState::Stopped);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::{BufferConfig, OverflowPolicy};
+    use crate::smtp::{ExtractFrom, ExtractionRule};
+    use lettre::message::{Attachment, MultiPart, SinglePart, header::ContentType};
+    use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+    use std::time::Duration;
+
+    fn config(listen: String) -> SmtpSensorConfig {
+        SmtpSensorConfig {
+            listen,
+            hostname: "mx.test".to_string(),
+            recipients: vec!["@loid.local".to_string()],
+            max_message_size: 1024 * 1024,
+            timeout: Duration::from_secs(5),
+            extract: vec![
+                ExtractionRule {
+                    field: "severity".to_string(),
+                    from: ExtractFrom::Subject,
+                    pattern: r"^\[(\w+)\]".to_string(),
+                },
+                ExtractionRule {
+                    field: "host".to_string(),
+                    from: ExtractFrom::Body,
+                    pattern: r"host=(\S+)".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    async fn wait_for_listener(port: u16) {
+        for _ in 0..100 {
+            if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("SMTP listener did not start");
+    }
+
+    #[test]
+    fn test_config_from_yaml_like_json() {
+        let config: SmtpSensorConfig = serde_json::from_str(
+            r#"{
+                "recipients": ["alerts@loid.local"],
+                "extract": [{"field": "host", "from": "body", "pattern": "host=(\\S+)"}]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.listen, "0.0.0.0:2525");
+        assert_eq!(config.timeout, Duration::from_secs(300));
+        assert_eq!(config.extract[0].from, ExtractFrom::Body);
+        assert!(config.accepts("ALERTS@loid.local"));
+        assert!(!config.accepts("other@loid.local"));
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_config() {
+        let mut invalid = config("127.0.0.1:0".to_string());
+        invalid.recipients = vec![];
+        assert!(matches!(
+            SmtpSensor::new("smtp", invalid),
+            Err(SensorError::Config(_))
+        ));
+
+        let mut invalid = config("127.0.0.1:0".to_string());
+        invalid.extract[0].pattern = "[".to_string();
+        assert!(SmtpSensor::new("smtp", invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_emits_event_for_received_mail() {
+        let port = free_port();
+        let sensor = SmtpSensor::new("smtp", config(format!("127.0.0.1:{port}"))).unwrap();
+        let (sink, mut receiver) = EventSink::channel(8);
+        let handle = tokio::spawn(async move { sensor.run(sink).await });
+        wait_for_listener(port).await;
+
+        let email = Message::builder()
+            .from("Vendor <alerts@vendor.example>".parse().unwrap())
+            .to("ops@loid.local".parse().unwrap())
+            .subject("[CRITICAL] backup failed")
+            .multipart(
+                MultiPart::mixed()
+                    .singlepart(SinglePart::plain(
+                        "Backup job failed on host=db-01".to_string(),
+                    ))
+                    .singlepart(
+                        Attachment::new("job.log".to_string())
+                            .body(b"exit 1".to_vec(), ContentType::TEXT_PLAIN),
+                    ),
+            )
+            .unwrap();
+        let mailer = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous("127.0.0.1")
+            .port(port)
+            .build();
+        mailer.send(email).await.unwrap();
+
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.source.system, "smtp");
+        assert_eq!(
+            event.source.source_id.as_deref(),
+            Some("alerts@vendor.example")
+        );
+        assert_eq!(
+            event.fields.get("subject"),
+            Some(&Value::from("[CRITICAL] backup failed"))
+        );
+        assert_eq!(
+            event.fields.get("rcpt_to"),
+            Some(&Value::List(vec![Value::from("ops@loid.local")]))
+        );
+        assert_eq!(event.fields.get("severity"), Some(&Value::from("CRITICAL")));
+        assert_eq!(event.fields.get("host"), Some(&Value::from("db-01")));
+        let Some(Value::List(attachments)) = event.fields.get("attachments") else {
+            panic!("attachments missing");
+        };
+        let Value::Map(attachment) = &attachments[0] else {
+            panic!("attachment is not a map");
+        };
+        assert_eq!(attachment.get("name"), Some(&Value::from("job.log")));
+        assert_eq!(attachment.get("size"), Some(&Value::Int(6)));
+
+        let rejected = Message::builder()
+            .from("alerts@vendor.example".parse().unwrap())
+            .to("ops@elsewhere.example".parse().unwrap())
+            .body("nope".to_string())
+            .unwrap();
+        assert!(mailer.send(rejected).await.is_err());
+
+        drop(receiver);
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_defers_mail_while_the_buffer_is_full() {
+        let port = free_port();
+        let sensor = SmtpSensor::new("smtp", config(format!("127.0.0.1:{port}"))).unwrap();
+        let buffer = BufferConfig {
+            capacity: 1,
+            overflow: OverflowPolicy::DropNewest,
+            spill: None,
+        };
+        let (sink, mut receiver) = EventSink::buffered(&buffer, StatusHandle::default()).unwrap();
+        let handle = tokio::spawn(async move { sensor.run(sink).await });
+        wait_for_listener(port).await;
+
+        let email = |subject: &str| {
+            Message::builder()
+                .from("alerts@vendor.example".parse().unwrap())
+                .to("ops@loid.local".parse().unwrap())
+                .subject(subject)
+                .body("Backup job failed".to_string())
+                .unwrap()
+        };
+        let mailer = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous("127.0.0.1")
+            .port(port)
+            .build();
+        mailer.send(email("first")).await.unwrap();
+        let error = mailer.send(email("second")).await.unwrap_err();
+        assert!(error.is_transient());
+
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.fields.get("subject"), Some(&Value::from("first")));
+        mailer.send(email("retried")).await.unwrap();
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.fields.get("subject"), Some(&Value::from("retried")));
+
+        drop(receiver);
+        handle.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,382 @@
+use crate::smtp::{Envelope, SmtpSensorConfig};
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+/// RFC 5321 allows 512 octets per command line, some clients send a little more
+const MAX_COMMAND_LINE: u64 = 4096;
+const MAX_RECIPIENTS: usize = 100;
+
+/// Server side of a single SMTP connection.
+///
+/// Only the subset needed to receive mail is implemented: no relaying, authentication or STARTTLS.
+/// [`next_message`](SmtpSession::next_message) drives the conversation until a message was
+/// transferred, the caller then answers it with [`reply`](SmtpSession::reply) so a message is
+/// only acknowledged after it was handed off.
+pub(crate) struct SmtpSession<S> {
+    stream: BufReader<S>,
+    config: Arc<SmtpSensorConfig>,
+    greeted: bool,
+    transaction: Option<Envelope>,
+}
+
+/// Outcome of reading a line from the client
+enum Line {
+    Complete(Vec<u8>),
+    /// The client disconnected or was idle for too long
+    Closed,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> SmtpSession<S> {
+    pub fn new(stream: S, config: Arc<SmtpSensorConfig>) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            config,
+            greeted: false,
+            transaction: None,
+        }
+    }
+
+    /// Sends the service ready greeting
+    pub async fn greet(&mut self) -> io::Result<()> {
+        let text = format!("{} ESMTP loid ready", self.config.hostname);
+        self.reply(220, &text).await
+    }
+
+    pub async fn reply(&mut self, code: u16, text: &str) -> io::Result<()> {
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(format!("{code} {text}\r\n").as_bytes())
+            .await?;
+        stream.flush().await
+    }
+
+    async fn reply_multiline(&mut self, code: u16, lines: &[String]) -> io::Result<()> {
+        let mut reply = String::new();
+        for (index, line) in lines.iter().enumerate() {
+            let separator = if index + 1 == lines.len() { ' ' } else { '-' };
+            reply.push_str(&format!("{code}{separator}{line}\r\n"));
+        }
+        let stream = self.stream.get_mut();
+        stream.write_all(reply.as_bytes()).await?;
+        stream.flush().await
+    }
+
+    /// Handles commands until a complete message was received.
+    ///
+    /// Returns `None` once the client quit or the connection was closed.
+    pub async fn next_message(&mut self) -> io::Result<Option<Envelope>> {
+        loop {
+            let line = match self.read_line(MAX_COMMAND_LINE).await? {
+                Line::Complete(line) => line,
+                Line::Closed => return Ok(None),
+            };
+            if !line.ends_with(b"\n") {
+                self.reply(500, "5.5.2 line too long").await?;
+                return Ok(None);
+            }
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            let (verb, argument) = match line.split_once(' ') {
+                Some((verb, argument)) => (verb.to_ascii_uppercase(), argument.trim()),
+                None => (line.to_ascii_uppercase(), ""),
+            };
+
+            match verb.as_str() {
+                "EHLO" => {
+                    self.greeted = true;
+                    self.transaction = None;
+                    let lines = [
+                        format!("{} greets {}", self.config.hostname, argument),
+                        format!("SIZE {}", self.config.max_message_size),
+                        "8BITMIME".to_string(),
+                        "PIPELINING".to_string(),
+                    ];
+                    self.reply_multiline(250, &lines).await?;
+                }
+                "HELO" => {
+                    self.greeted = true;
+                    self.transaction = None;
+                    let text = self.config.hostname.clone();
+                    self.reply(250, &text).await?;
+                }
+                "MAIL" => self.mail(argument).await?,
+                "RCPT" => self.rcpt(argument).await?,
+                "DATA" => {
+                    if let Some(envelope) = self.data().await? {
+                        return Ok(Some(envelope));
+                    }
+                }
+                "RSET" => {
+                    self.transaction = None;
+                    self.reply(250, "2.0.0 OK").await?;
+                }
+                "NOOP" => self.reply(250, "2.0.0 OK").await?,
+                "VRFY" => {
+                    self.reply(252, "2.5.0 cannot verify user, will accept message")
+                        .await?
+                }
+                "HELP" => self.reply(214, "2.0.0 see RFC 5321").await?,
+                "QUIT" => {
+                    self.reply(221, "2.0.0 bye").await?;
+                    return Ok(None);
+                }
+                _ => self.reply(500, "5.5.1 command not recognized").await?,
+            }
+        }
+    }
+
+    async fn mail(&mut self, argument: &str) -> io::Result<()> {
+        if !self.greeted {
+            return self.reply(503, "5.5.1 send EHLO or HELO first").await;
+        }
+        if self.transaction.is_some() {
+            return self.reply(503, "5.5.1 nested MAIL command").await;
+        }
+        let Some((mail_from, parameters)) = parse_path(argument, "FROM:") else {
+            return self.reply(501, "5.5.4 syntax: MAIL FROM:<address>").await;
+        };
+        let size = parameters
+            .split_whitespace()
+            .filter_map(|parameter| parameter.split_once('='))
+            .find(|(name, _)| name.eq_ignore_ascii_case("SIZE"))
+            .and_then(|(_, value)| value.parse::<usize>().ok());
+        if size.is_some_and(|size| size > self.config.max_message_size) {
+            return self.reply(552, "5.3.4 message size exceeds limit").await;
+        }
+
+        self.transaction = Some(Envelope {
+            mail_from,
+            ..Default::default()
+        });
+        self.reply(250, "2.1.0 OK").await
+    }
+
+    async fn rcpt(&mut self, argument: &str) -> io::Result<()> {
+        let Some(transaction) = &self.transaction else {
+            return self.reply(503, "5.5.1 send MAIL first").await;
+        };
+        let Some((recipient, _)) = parse_path(argument, "TO:") else {
+            return self.reply(501, "5.5.4 syntax: RCPT TO:<address>").await;
+        };
+        if transaction.rcpt_to.len() >= MAX_RECIPIENTS {
+            return self.reply(452, "4.5.3 too many recipients").await;
+        }
+        if !self.config.accepts(&recipient) {
+            return self.reply(550, "5.1.1 mailbox unavailable").await;
+        }
+
+        if let Some(transaction) = &mut self.transaction {
+            transaction.rcpt_to.push(recipient);
+        }
+        self.reply(250, "2.1.5 OK").await
+    }
+
+    /// Receives the message content, `None` if it was rejected
+    async fn data(&mut self) -> io::Result<Option<Envelope>> {
+        match &self.transaction {
+            None => {
+                self.reply(503, "5.5.1 send MAIL first").await?;
+                return Ok(None);
+            }
+            Some(transaction) if transaction.rcpt_to.is_empty() => {
+                self.reply(554, "5.5.1 no valid recipients").await?;
+                return Ok(None);
+            }
+            Some(_) => {}
+        }
+        self.reply(354, "end data with <CR><LF>.<CR><LF>").await?;
+
+        let limit = self.config.max_message_size as u64 + 2;
+        let mut data = Vec::new();
+        let mut too_large = false;
+        let mut at_line_start = true;
+        loop {
+            let line = match self.read_line(limit).await? {
+                Line::Complete(line) => line,
+                Line::Closed => return Ok(None),
+            };
+            let ends_line = line.ends_with(b"\n");
+            let mut content = line.as_slice();
+            if at_line_start {
+                if content == b".\r\n" || content == b".\n" {
+                    break;
+                }
+                // dot-stuffing, RFC 5321 4.5.2
+                if content.starts_with(b".") {
+                    content = &content[1..];
+                }
+            }
+            at_line_start = ends_line;
+
+            if data.len() + content.len() > self.config.max_message_size {
+                too_large = true;
+            }
+            if !too_large {
+                data.extend_from_slice(content);
+            }
+        }
+
+        let envelope = self.transaction.take().unwrap_or_default();
+        if too_large {
+            self.reply(552, "5.3.4 message size exceeds limit").await?;
+            return Ok(None);
+        }
+        Ok(Some(Envelope { data, ..envelope }))
+    }
+
+    async fn read_line(&mut self, limit: u64) -> io::Result<Line> {
+        let mut line = Vec::new();
+        let read = tokio::time::timeout(
+            self.config.timeout,
+            (&mut self.stream).take(limit).read_until(b'\n', &mut line),
+        )
+        .await;
+        match read {
+            Ok(Ok(0)) => Ok(Line::Closed),
+            Ok(Ok(_)) => Ok(Line::Complete(line)),
+            Ok(Err(e)) => Err(e),
+            Err(_) => {
+                self.reply(421, "4.4.2 idle timeout, closing connection")
+                    .await?;
+                Ok(Line::Closed)
+            }
+        }
+    }
+}
+
+/// Parses `FROM:<address> PARAMS` into the address and the parameters
+fn parse_path<'a>(argument: &'a str, prefix: &str) -> Option<(String, &'a str)> {
+    let head = argument.get(..prefix.len())?;
+    if !head.eq_ignore_ascii_case(prefix) {
+        return None;
+    }
+    let rest = argument[prefix.len()..].trim_start();
+    let (path, parameters) = match rest.strip_prefix('<') {
+        Some(rest) => rest.split_once('>')?,
+        None => rest.split_once(' ').unwrap_or((rest, "")),
+    };
+    // source routes like <@relay:user@example.com> are ignored, RFC 5321 C
+    let address = path.rsplit(':').next().unwrap_or(path);
+    Some((address.to_string(), parameters.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{DuplexStream, duplex};
+
+    fn config() -> Arc<SmtpSensorConfig> {
+        Arc::new(SmtpSensorConfig {
+            listen: "127.0.0.1:0".to_string(),
+            hostname: "mx.test".to_string(),
+            recipients: vec!["alerts@loid.local".to_string()],
+            max_message_size: 64,
+            timeout: Duration::from_secs(5),
+            extract: vec![],
+        })
+    }
+
+    async fn expect(client: &mut BufReader<DuplexStream>, code: &str) -> String {
+        loop {
+            let mut line = String::new();
+            client.read_line(&mut line).await.unwrap();
+            assert!(line.starts_with(code), "expected {code}, got {line:?}");
+            // multiline replies continue with `<code>-`
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return line;
+            }
+        }
+    }
+
+    async fn send(client: &mut BufReader<DuplexStream>, line: &str) {
+        client.get_mut().write_all(line.as_bytes()).await.unwrap();
+    }
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            parse_path("FROM:<a@example.com> SIZE=10", "FROM:"),
+            Some(("a@example.com".to_string(), "SIZE=10"))
+        );
+        assert_eq!(
+            parse_path("to: <@relay:b@example.com>", "TO:"),
+            Some(("b@example.com".to_string(), ""))
+        );
+        assert_eq!(parse_path("FROM:<>", "FROM:"), Some((String::new(), "")));
+        assert_eq!(parse_path("TO:<a@example.com>", "FROM:"), None);
+    }
+
+    #[tokio::test]
+    async fn test_session_receives_message() {
+        let (server, client) = duplex(4096);
+        let mut session = SmtpSession::new(server, config());
+        let handle = tokio::spawn(async move {
+            session.greet().await.unwrap();
+            let envelope = session.next_message().await.unwrap();
+            session.reply(250, "2.0.0 accepted").await.unwrap();
+            let next = session.next_message().await.unwrap();
+            (envelope, next)
+        });
+
+        let mut client = BufReader::new(client);
+        expect(&mut client, "220").await;
+        send(&mut client, "MAIL FROM:<vendor@example.com>\r\n").await;
+        expect(&mut client, "503").await;
+        send(&mut client, "EHLO client.test\r\n").await;
+        assert!(expect(&mut client, "250").await.contains("PIPELINING"));
+        send(&mut client, "DATA\r\n").await;
+        expect(&mut client, "503").await;
+        send(&mut client, "MAIL FROM:<vendor@example.com>\r\n").await;
+        expect(&mut client, "250").await;
+        send(&mut client, "RCPT TO:<nobody@loid.local>\r\n").await;
+        expect(&mut client, "550").await;
+        send(&mut client, "DATA\r\n").await;
+        expect(&mut client, "554").await;
+        send(&mut client, "RCPT TO:<Alerts@loid.local>\r\n").await;
+        expect(&mut client, "250").await;
+        send(&mut client, "DATA\r\n").await;
+        expect(&mut client, "354").await;
+        send(&mut client, "Subject: hi\r\n\r\n..leading dot\r\n.\r\n").await;
+        expect(&mut client, "250").await;
+        send(&mut client, "QUIT\r\n").await;
+        expect(&mut client, "221").await;
+
+        let (envelope, next) = handle.await.unwrap();
+        assert_eq!(
+            envelope,
+            Some(Envelope {
+                mail_from: "vendor@example.com".to_string(),
+                rcpt_to: vec!["Alerts@loid.local".to_string()],
+                data: b"Subject: hi\r\n\r\n.leading dot\r\n".to_vec(),
+            })
+        );
+        assert_eq!(next, None);
+    }
+
+    #[tokio::test]
+    async fn test_session_rejects_oversized_message() {
+        let (server, client) = duplex(4096);
+        let mut session = SmtpSession::new(server, config());
+        let handle = tokio::spawn(async move { session.next_message().await.unwrap() });
+
+        let mut client = BufReader::new(client);
+        send(&mut client, "HELO client.test\r\n").await;
+        expect(&mut client, "250").await;
+        send(&mut client, "MAIL FROM:<a@example.com> SIZE=1000\r\n").await;
+        expect(&mut client, "552").await;
+        send(&mut client, "MAIL FROM:<a@example.com>\r\n").await;
+        expect(&mut client, "250").await;
+        send(&mut client, "RCPT TO:<alerts@loid.local>\r\n").await;
+        expect(&mut client, "250").await;
+        send(&mut client, "DATA\r\n").await;
+        expect(&mut client, "354").await;
+        send(&mut client, &format!("{}\r\n.\r\n", "x".repeat(100))).await;
+        expect(&mut client, "552").await;
+        send(&mut client, "QUIT\r\n").await;
+        expect(&mut client, "221").await;
+
+        assert_eq!(handle.await.unwrap(), None);
+    }
+}
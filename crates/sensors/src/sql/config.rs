@@ -0,0 +1,36 @@
+use crate::SensorError;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Configuration of the SQL sensor
+#[derive(Debug, Clone, Deserialize)]
+pub struct SqlSensorConfig {
+    /// Statement executed on every poll, every returned row becomes an event
+    pub statement: String,
+    /// Key of the database resource the statement runs against
+    pub resource: String,
+    /// Time between two polls
+    #[serde(with = "humantime_serde", default = "default_interval")]
+    pub interval: Duration,
+}
+
+fn default_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+impl SqlSensorConfig {
+    /// Checks the configuration for values the sensor cannot work with
+    pub fn validate(&self) -> Result<(), SensorError> {
+        if self.statement.trim().is_empty() {
+            return Err(SensorError::Config(
+                "statement must not be empty".to_string(),
+            ));
+        }
+        if self.interval.is_zero() {
+            return Err(SensorError::Config(
+                "interval must be greater than 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+//! Sensor polling a database resource with a SQL statement.
+//!
+//! ```yaml
+//! sensor:
+//!     type: sql
+//!     statement: "SELECT * FROM alerts WHERE status = 'open'"
+//!     resource: "my-mariadb-server"
+//!     interval: 15m
+//! ```
+//!
+//! The resource is referenced by its key, the configuration loader checks that it exists.
+
+pub(crate) mod config;
+
+pub use config::SqlSensorConfig;
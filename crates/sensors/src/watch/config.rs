@@ -0,0 +1,117 @@
+use crate::SensorError;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Configuration of a [`WatchSensor`](crate::watch::WatchSensor)
+#[derive(Debug, Clone, Deserialize)]
+pub struct WatchSensorConfig {
+    /// Directories (or single files) to watch
+    pub paths: Vec<PathBuf>,
+    /// Watch sub directories as well
+    #[serde(default = "default_true")]
+    pub recursive: bool,
+    /// Only paths matching one of these globs are reported, all paths if empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Paths matching one of these globs are never reported
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Kinds of changes to report, all if empty
+    #[serde(default)]
+    pub events: Vec<FileChangeKind>,
+    /// Changes of the same path within this window are merged into one event
+    #[serde(with = "humantime_serde", default = "default_debounce")]
+    pub debounce: Duration,
+    /// Add the SHA-256 hash of the file content to the events
+    #[serde(default)]
+    pub hash: bool,
+    /// Files larger than this are not hashed
+    #[serde(default = "default_max_hash_size")]
+    pub max_hash_size: u64,
+    /// Report files that exist without being changed for this long
+    #[serde(with = "humantime_serde", default)]
+    pub stall_after: Option<Duration>,
+}
+
+/// Kind of change reported by the watch sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileChangeKind {
+    Created,
+    Modified,
+    Deleted,
+    Moved,
+    /// The file was not changed for `stall_after`
+    Stalled,
+}
+
+impl Display for FileChangeKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileChangeKind::Created => write!(f, "created"),
+            FileChangeKind::Modified => write!(f, "modified"),
+            FileChangeKind::Deleted => write!(f, "deleted"),
+            FileChangeKind::Moved => write!(f, "moved"),
+            FileChangeKind::Stalled => write!(f, "stalled"),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_debounce() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_max_hash_size() -> u64 {
+    64 * 1024 * 1024
+}
+
+impl WatchSensorConfig {
+    /// Checks the configuration for values the sensor cannot work with
+    pub fn validate(&self) -> Result<(), SensorError> {
+        if self.paths.is_empty() {
+            return Err(SensorError::Config(
+                "at least one path is required".to_string(),
+            ));
+        }
+        if self.debounce.is_zero() {
+            return Err(SensorError::Config(
+                "debounce must be greater than zero".to_string(),
+            ));
+        }
+        if self
+            .stall_after
+            .is_some_and(|stall_after| stall_after.is_zero())
+        {
+            return Err(SensorError::Config(
+                "stall_after must be greater than zero".to_string(),
+            ));
+        }
+        build_glob_set(&self.include)?;
+        build_glob_set(&self.exclude)?;
+        Ok(())
+    }
+
+    /// Returns `true` if changes of the given kind should be reported
+    pub fn reports(&self, kind: FileChangeKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+pub(crate) fn build_glob_set(patterns: &[String]) -> Result<GlobSet, SensorError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|e| SensorError::Config(format!("invalid glob '{pattern}': {e}")))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| SensorError::Config(format!("invalid globs: {e}")))
+}
@@ -0,0 +1,21 @@
+//! Filesystem watch sensor reporting changes in directories.
+//!
+//! ```yaml
+//! sensor:
+//!     type: watch
+//!     paths: [ /data/landing ]
+//!     recursive: true
+//!     include: [ "**/*.csv" ]
+//!     exclude: [ "**/*.tmp" ]
+//!     debounce: 500ms
+//!     hash: true
+//!     stall_after: 15m
+//! ```
+
+pub(crate) mod config;
+pub(crate) mod sensor;
+pub(crate) mod tracker;
+
+pub use config::{FileChangeKind, WatchSensorConfig};
+pub use sensor::WatchSensor;
+pub use tracker::{Debouncer, FileChange, StallTracker};
@@ -0,0 +1,474 @@
+use crate::watch::config::build_glob_set;
+use crate::watch::{Debouncer, FileChange, FileChangeKind, StallTracker, WatchSensorConfig};
+use crate::{EventSink, HealthState, LifecycleState, Sensor, SensorError, StatusHandle};
+use globset::GlobSet;
+use loid_events::prelude::{Event, EventBuilder, Impact, Source, Urgency, Value};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{EventKind, RecursiveMode, Watcher};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Watches directories for changes and emits an event per debounced file change.
+pub struct WatchSensor {
+    key: String,
+    config: WatchSensorConfig,
+    include: GlobSet,
+    exclude: GlobSet,
+    status: StatusHandle,
+}
+
+impl WatchSensor {
+    pub fn new(key: impl Into<String>, config: WatchSensorConfig) -> Result<Self, SensorError> {
+        config.validate()?;
+        Ok(Self {
+            key: key.into(),
+            include: build_glob_set(&config.include)?,
+            exclude: build_glob_set(&config.exclude)?,
+            config,
+            status: StatusHandle::default(),
+        })
+    }
+
+    pub fn config(&self) -> &WatchSensorConfig {
+        &self.config
+    }
+
+    /// Checks the path against the include and exclude globs.
+    ///
+    /// Globs are matched against the path relative to the watched directory it belongs to.
+    pub(crate) fn matches(&self, path: &Path) -> bool {
+        let relative = self
+            .config
+            .paths
+            .iter()
+            .find_map(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+
+        if self.exclude.is_match(relative) {
+            return false;
+        }
+        self.config.include.is_empty() || self.include.is_match(relative)
+    }
+
+    /// Translates a raw notification into changes of the affected paths
+    fn changes(&self, event: notify::Event) -> Vec<(PathBuf, FileChange)> {
+        let paths = event.paths;
+        let changes = match event.kind {
+            EventKind::Create(_) => paths
+                .into_iter()
+                .map(|p| (p, FileChange::Created))
+                .collect(),
+            EventKind::Remove(_) => paths
+                .into_iter()
+                .map(|p| (p, FileChange::Deleted))
+                .collect(),
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if paths.len() == 2 => {
+                let mut paths = paths.into_iter();
+                let from = paths.next().unwrap_or_default();
+                let to = paths.next().unwrap_or_default();
+                if !self.matches(&to) {
+                    // moved out of the watched set of files
+                    vec![(from, FileChange::Deleted)]
+                } else if !self.matches(&from) {
+                    vec![(to, FileChange::Created)]
+                } else {
+                    vec![(to, FileChange::Moved { from })]
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => paths
+                .into_iter()
+                .map(|p| (p, FileChange::Deleted))
+                .collect(),
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => paths
+                .into_iter()
+                .map(|p| (p, FileChange::Created))
+                .collect(),
+            EventKind::Modify(_) => paths
+                .into_iter()
+                .map(|p| (p, FileChange::Modified))
+                .collect(),
+            _ => Vec::new(),
+        };
+        changes
+            .into_iter()
+            .filter(|(path, _)| self.matches(path))
+            .collect()
+    }
+
+    /// Files that already exist when the sensor starts, used to seed stall detection
+    fn existing_files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let mut directories: Vec<(PathBuf, bool)> = self
+            .config
+            .paths
+            .iter()
+            .map(|p| (p.clone(), true))
+            .collect();
+
+        while let Some((directory, root)) = directories.pop() {
+            if directory.is_file() {
+                files.push(directory);
+                continue;
+            }
+            if !root && !self.config.recursive {
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(&directory) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    directories.push((path, false));
+                } else if self.matches(&path) {
+                    files.push(path);
+                }
+            }
+        }
+        files
+    }
+
+    async fn build_event(&self, path: &Path, kind: FileChangeKind, from: Option<&Path>) -> Event {
+        let mut builder = EventBuilder::new();
+        builder
+            .with_source(Source {
+                system: "watch".to_string(),
+                source_id: Some(path.display().to_string()),
+            })
+            .with_text_field("sensor", &self.key)
+            .with_text_field("kind", &kind.to_string())
+            .with_text_field("path", &path.display().to_string());
+
+        if let Some(name) = path.file_name() {
+            builder.with_text_field("file_name", &name.to_string_lossy());
+        }
+        if let Some(from) = from {
+            builder.with_text_field("from_path", &from.display().to_string());
+        }
+        if kind == FileChangeKind::Stalled {
+            builder
+                .with_impact(Impact::MINOR)
+                .with_urgency(Urgency::MEDIUM);
+        }
+
+        if kind != FileChangeKind::Deleted
+            && let Ok(metadata) = tokio::fs::metadata(path).await
+        {
+            builder
+                .with_int_field("size", metadata.len() as i64)
+                .with_bool_field("is_dir", metadata.is_dir());
+            if let Ok(modified) = metadata.modified() {
+                let modified: chrono::DateTime<chrono::Utc> = modified.into();
+                builder.with_text_field("modified_at", &modified.to_rfc3339());
+            }
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                builder
+                    .with_int_field("owner", metadata.uid() as i64)
+                    .with_int_field("group", metadata.gid() as i64);
+            }
+
+            if self.config.hash && metadata.is_file() && metadata.len() <= self.config.max_hash_size
+            {
+                let file = path.to_path_buf();
+                match tokio::task::spawn_blocking(move || sha256(&file)).await {
+                    Ok(Ok(hash)) => {
+                        builder.with_field("sha256", Value::String(hash));
+                    }
+                    Ok(Err(error)) => {
+                        tracing::debug!(sensor = %self.key, path = %path.display(), %error, "failed to hash file");
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+        builder.build()
+    }
+}
+
+/// Hex encoded SHA-256 hash of the file content
+fn sha256(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+impl Sensor for WatchSensor {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn status_handle(&self) -> &StatusHandle {
+        &self.status
+    }
+
+    async fn run(&self, sink: EventSink) -> Result<(), SensorError> {
+        self.status.set_lifecycle(LifecycleState::Starting);
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |result| {
+            let _ = sender.send(result);
+        })
+        .map_err(|e| SensorError::Config(format!("failed to create watcher: {e}")))?;
+
+        let mode = if self.config.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        for path in &self.config.paths {
+            if let Err(error) = watcher.watch(path, mode) {
+                self.status.set_health(HealthState::Misconfigured);
+                self.status.set_lifecycle(LifecycleState::Stopped);
+                return Err(SensorError::Config(format!(
+                    "cannot watch {}: {error}",
+                    path.display()
+                )));
+            }
+        }
+
+        let mut debouncer = Debouncer::new(self.config.debounce);
+        let mut stalls = self.config.stall_after.map(StallTracker::new);
+        if let Some(stalls) = stalls.as_mut() {
+            let now = Instant::now();
+            for file in self.existing_files() {
+                stalls.touch(file, now);
+            }
+        }
+
+        let resolution = self
+            .config
+            .stall_after
+            .map_or(self.config.debounce, |stall| {
+                stall.min(self.config.debounce)
+            });
+        let mut tick = tokio::time::interval((resolution / 2).max(Duration::from_millis(10)));
+
+        self.status.set_lifecycle(LifecycleState::Running);
+        self.status.set_health(HealthState::Healthy);
+
+        'outer: loop {
+            tokio::select! {
+                notification = receiver.recv() => match notification {
+                    Some(Ok(event)) => {
+                        let now = Instant::now();
+                        for (path, change) in self.changes(event) {
+                            debouncer.push(path, change, now);
+                        }
+                    }
+                    Some(Err(error)) => {
+                        tracing::warn!(sensor = %self.key, %error, "watch error");
+                        self.status.set_health(HealthState::Degraded);
+                    }
+                    None => break,
+                },
+                _ = tick.tick() => {
+                    let now = Instant::now();
+                    for (path, change) in debouncer.drain_ready(now) {
+                        if let Some(stalls) = stalls.as_mut() {
+                            match &change {
+                                FileChange::Deleted => stalls.remove(&path),
+                                FileChange::Moved { from } => {
+                                    stalls.remove(from);
+                                    stalls.touch(path.clone(), now);
+                                }
+                                _ => stalls.touch(path.clone(), now),
+                            }
+                        }
+                        if !self.config.reports(change.kind()) {
+                            continue;
+                        }
+                        let from = match &change {
+                            FileChange::Moved { from } => Some(from.as_path()),
+                            _ => None,
+                        };
+                        let event = self.build_event(&path, change.kind(), from).await;
+                        if sink.send(event).await.is_err() {
+                            break 'outer;
+                        }
+                    }
+
+                    let stalled = stalls.as_mut().map(|s| s.drain_stalled(now)).unwrap_or_default();
+                    if self.config.reports(FileChangeKind::Stalled) {
+                        for path in stalled {
+                            let event = self.build_event(&path, FileChangeKind::Stalled, None).await;
+                            if sink.send(event).await.is_err() {
+                                break 'outer;
+                            }
+                        }
+                    }
+                }
+                _ = sink.closed() => break,
+            }
+        }
+
+        drop(watcher);
+        self.status.set_lifecycle(LifecycleState::Stopped);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventReceiver;
+    use uuid::Uuid;
+
+    fn config(root: &Path) -> WatchSensorConfig {
+        WatchSensorConfig {
+            paths: vec![root.to_path_buf()],
+            recursive: true,
+            include: vec![],
+            exclude: vec![],
+            events: vec![],
+            debounce: Duration::from_millis(50),
+            hash: false,
+            max_hash_size: 1024,
+            stall_after: None,
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("loid-watch-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    async fn next_event(receiver: &mut EventReceiver) -> Event {
+        tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("no event received")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_globs_match_relative_paths() {
+        let mut config = config(Path::new("/landing"));
+        config.include = vec!["**/*.csv".to_string()];
+        config.exclude = vec!["tmp/**".to_string()];
+        let sensor = WatchSensor::new("watch", config).unwrap();
+
+        assert!(sensor.matches(Path::new("/landing/orders.csv")));
+        assert!(sensor.matches(Path::new("/landing/eu/orders.csv")));
+        assert!(!sensor.matches(Path::new("/landing/orders.json")));
+        assert!(!sensor.matches(Path::new("/landing/tmp/orders.csv")));
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_config() {
+        let mut invalid = config(Path::new("/landing"));
+        invalid.include = vec!["[".to_string()];
+        assert!(matches!(
+            WatchSensor::new("watch", invalid),
+            Err(SensorError::Config(_))
+        ));
+
+        let mut invalid = config(Path::new("/landing"));
+        invalid.paths.clear();
+        assert!(WatchSensor::new("watch", invalid).is_err());
+    }
+
+    #[test]
+    fn test_sha256() {
+        let dir = temp_dir();
+        let file = dir.join("hello.txt");
+        std::fs::write(&file, "hello").unwrap();
+        assert_eq!(
+            sha256(&file).unwrap(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_created_and_deleted_files() {
+        let dir = temp_dir();
+        let mut config = config(&dir);
+        config.exclude = vec!["*.tmp".to_string()];
+        config.hash = true;
+        let sensor = WatchSensor::new("landing", config).unwrap();
+        let (sink, mut receiver) = EventSink::channel(8);
+        let handle = tokio::spawn(async move { sensor.run(sink).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        std::fs::write(dir.join("ignored.tmp"), "tmp").unwrap();
+        std::fs::write(dir.join("orders.csv"), "hello").unwrap();
+
+        let created = next_event(&mut receiver).await;
+        assert_eq!(created.source.system, "watch");
+        assert_eq!(created.fields.get("kind"), Some(&Value::from("created")));
+        assert_eq!(
+            created.fields.get("file_name"),
+            Some(&Value::from("orders.csv"))
+        );
+        assert_eq!(created.fields.get("size"), Some(&Value::Int(5)));
+        assert_eq!(
+            created.fields.get("sha256"),
+            Some(&Value::from(
+                "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+            ))
+        );
+
+        std::fs::remove_file(dir.join("orders.csv")).unwrap();
+        let deleted = next_event(&mut receiver).await;
+        assert_eq!(deleted.fields.get("kind"), Some(&Value::from("deleted")));
+        assert!(!deleted.fields.contains_key("size"));
+
+        drop(receiver);
+        handle.await.unwrap().unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_stalled_files() {
+        let dir = temp_dir();
+        std::fs::write(dir.join("waiting.csv"), "data").unwrap();
+        let mut config = config(&dir);
+        config.stall_after = Some(Duration::from_millis(100));
+        config.events = vec![FileChangeKind::Stalled];
+        let sensor = WatchSensor::new("landing", config).unwrap();
+        let (sink, mut receiver) = EventSink::channel(8);
+        let handle = tokio::spawn(async move { sensor.run(sink).await });
+
+        let stalled = next_event(&mut receiver).await;
+        assert_eq!(stalled.fields.get("kind"), Some(&Value::from("stalled")));
+        assert_eq!(
+            stalled.fields.get("file_name"),
+            Some(&Value::from("waiting.csv"))
+        );
+        assert_eq!(stalled.impact, Impact::MINOR);
+
+        drop(receiver);
+        handle.await.unwrap().unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_fails_for_missing_path() {
+        let sensor =
+            WatchSensor::new("landing", config(Path::new("/nonexistent/landing"))).unwrap();
+        let (sink, _receiver) = EventSink::channel(1);
+
+        assert!(matches!(
+            sensor.run(sink).await,
+            Err(SensorError::Config(_))
+        ));
+        assert_eq!(sensor.status().health, HealthState::Misconfigured);
+    }
+}
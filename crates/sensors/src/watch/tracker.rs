@@ -0,0 +1,216 @@
+use crate::watch::FileChangeKind;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// A debounced change of a single path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileChange {
+    Created,
+    Modified,
+    Deleted,
+    Moved { from: PathBuf },
+}
+
+impl FileChange {
+    pub fn kind(&self) -> FileChangeKind {
+        match self {
+            FileChange::Created => FileChangeKind::Created,
+            FileChange::Modified => FileChangeKind::Modified,
+            FileChange::Deleted => FileChangeKind::Deleted,
+            FileChange::Moved { .. } => FileChangeKind::Moved,
+        }
+    }
+
+    /// Merges a newer change of the same path into this one, `None` if they cancel out
+    fn merge(self, newer: FileChange) -> Option<FileChange> {
+        match (self, newer) {
+            // a file that only existed briefly is not worth reporting
+            (FileChange::Created, FileChange::Deleted) => None,
+            (FileChange::Created, FileChange::Modified) => Some(FileChange::Created),
+            (FileChange::Moved { from }, FileChange::Modified) => Some(FileChange::Moved { from }),
+            (FileChange::Deleted, FileChange::Created) => Some(FileChange::Modified),
+            (_, newer) => Some(newer),
+        }
+    }
+}
+
+/// Merges bursts of changes of the same path into a single change.
+///
+/// A change is released once the path was quiet for the debounce window.
+#[derive(Debug)]
+pub struct Debouncer {
+    window: Duration,
+    pending: HashMap<PathBuf, (FileChange, Instant)>,
+}
+
+impl Debouncer {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, path: PathBuf, change: FileChange, now: Instant) {
+        let merged = match self.pending.remove(&path) {
+            Some((existing, _)) => existing.merge(change),
+            None => Some(change),
+        };
+        if let Some(change) = merged {
+            self.pending.insert(path, (change, now));
+        }
+    }
+
+    /// Removes and returns all changes whose path was quiet for the debounce window
+    pub fn drain_ready(&mut self, now: Instant) -> Vec<(PathBuf, FileChange)> {
+        let ready: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, (_, seen))| now.duration_since(*seen) >= self.window)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let mut changes: Vec<_> = ready
+            .into_iter()
+            .filter_map(|path| {
+                self.pending
+                    .remove(&path)
+                    .map(|(change, seen)| (path, change, seen))
+            })
+            .collect();
+        changes.sort_by_key(|(_, _, seen)| *seen);
+        changes
+            .into_iter()
+            .map(|(path, change, _)| (path, change))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Tracks how long files exist without being changed.
+#[derive(Debug)]
+pub struct StallTracker {
+    after: Duration,
+    /// Last change of every known file and whether it was reported as stalled already
+    files: HashMap<PathBuf, (Instant, bool)>,
+}
+
+impl StallTracker {
+    pub fn new(after: Duration) -> Self {
+        Self {
+            after,
+            files: HashMap::new(),
+        }
+    }
+
+    /// Records a change of the file, restarting its stall timer
+    pub fn touch(&mut self, path: PathBuf, now: Instant) {
+        self.files.insert(path, (now, false));
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.files.remove(path);
+    }
+
+    /// Returns all files that became stalled since the last call
+    pub fn drain_stalled(&mut self, now: Instant) -> Vec<PathBuf> {
+        let mut stalled = Vec::new();
+        for (path, (changed, reported)) in self.files.iter_mut() {
+            if !*reported && now.duration_since(*changed) >= self.after {
+                *reported = true;
+                stalled.push(path.clone());
+            }
+        }
+        stalled.sort();
+        stalled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn test_debouncer_waits_for_quiet_period() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(WINDOW);
+        debouncer.push(PathBuf::from("a.csv"), FileChange::Modified, start);
+
+        assert!(debouncer.drain_ready(start + WINDOW / 2).is_empty());
+        debouncer.push(
+            PathBuf::from("a.csv"),
+            FileChange::Modified,
+            start + WINDOW / 2,
+        );
+        assert!(debouncer.drain_ready(start + WINDOW).is_empty());
+
+        let ready = debouncer.drain_ready(start + WINDOW * 2);
+        assert_eq!(ready, vec![(PathBuf::from("a.csv"), FileChange::Modified)]);
+        assert!(debouncer.is_empty());
+    }
+
+    #[test]
+    fn test_debouncer_merges_changes() {
+        let now = Instant::now();
+        let mut debouncer = Debouncer::new(WINDOW);
+
+        debouncer.push(PathBuf::from("new"), FileChange::Created, now);
+        debouncer.push(PathBuf::from("new"), FileChange::Modified, now);
+
+        debouncer.push(PathBuf::from("tmp"), FileChange::Created, now);
+        debouncer.push(PathBuf::from("tmp"), FileChange::Deleted, now);
+
+        debouncer.push(PathBuf::from("replaced"), FileChange::Deleted, now);
+        debouncer.push(PathBuf::from("replaced"), FileChange::Created, now);
+
+        let moved = FileChange::Moved {
+            from: PathBuf::from("old"),
+        };
+        debouncer.push(PathBuf::from("moved"), moved.clone(), now);
+        debouncer.push(PathBuf::from("moved"), FileChange::Modified, now);
+
+        let mut ready = debouncer.drain_ready(now + WINDOW);
+        ready.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            ready,
+            vec![
+                (PathBuf::from("moved"), moved),
+                (PathBuf::from("new"), FileChange::Created),
+                (PathBuf::from("replaced"), FileChange::Modified),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stall_tracker() {
+        let start = Instant::now();
+        let mut tracker = StallTracker::new(WINDOW);
+        tracker.touch(PathBuf::from("a"), start);
+        tracker.touch(PathBuf::from("b"), start);
+        tracker.touch(PathBuf::from("c"), start);
+
+        tracker.touch(PathBuf::from("b"), start + WINDOW / 2);
+        tracker.remove(Path::new("c"));
+
+        assert_eq!(
+            tracker.drain_stalled(start + WINDOW),
+            vec![PathBuf::from("a")]
+        );
+        // a stalled file is only reported once
+        assert_eq!(
+            tracker.drain_stalled(start + WINDOW * 2),
+            vec![PathBuf::from("b")]
+        );
+        assert!(tracker.drain_stalled(start + WINDOW * 3).is_empty());
+    }
+}
@@ -0,0 +1,586 @@
+use crate::field::{key, key_text, lookup};
+use crate::mapping::convert::text;
+use crate::mapping::rules::{Check, all};
+use crate::window::{Aggregate, Trigger, Window, WindowRule, WindowsConfig};
+use crate::{SensorError, Stage};
+use chrono::{DateTime, TimeDelta, Utc};
+use loid_events::prelude::{Event, EventBuilder, Source, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use uuid::Uuid;
+
+/// Derived events list at most this many input event ids
+const MAX_INPUTS: usize = 100;
+
+#[derive(Debug, Clone, Copy)]
+enum Span {
+    Tumbling(TimeDelta),
+    Sliding(TimeDelta),
+    Session(TimeDelta),
+}
+
+#[derive(Debug)]
+struct Rule {
+    config: WindowRule,
+    when: Vec<Check>,
+    span: Span,
+}
+
+/// An event counted by a rule
+#[derive(Debug)]
+struct Input {
+    id: Uuid,
+    time: DateTime<Utc>,
+    /// Value at the `field` of the rule
+    value: Option<Value>,
+}
+
+/// The open window of one group
+#[derive(Debug)]
+struct Bucket {
+    key: Vec<Value>,
+    start: DateTime<Utc>,
+    last: DateTime<Utc>,
+    inputs: VecDeque<Input>,
+    /// A sliding window triggered and its limits still hold
+    fired: bool,
+    /// Shared by the derived event and the forwarded inputs of the window, a sliding window
+    /// gets a new one once its limits no longer hold after it triggered
+    correlation_id: Uuid,
+}
+
+/// A compiled [`WindowsConfig`] aggregating the events of a sensor in windows.
+///
+/// Windows follow the creation time of the events. A tumbling or session window is evaluated
+/// once an event or a [`tick`](Self::tick) is past its end, a sliding window on every event.
+#[derive(Debug)]
+pub struct WindowAggregator {
+    rules: Vec<Rule>,
+    forward_inputs: bool,
+    /// Open window per rule index and group
+    buckets: HashMap<(usize, String), Bucket>,
+}
+
+fn span(duration: std::time::Duration, what: &str, name: &str) -> Result<TimeDelta, SensorError> {
+    TimeDelta::from_std(duration)
+        .ok()
+        .filter(|span| *span > TimeDelta::zero())
+        .ok_or_else(|| {
+            SensorError::Config(format!("{what} of rule '{name}' must be greater than 0"))
+        })
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(value) => Some(*value as f64),
+        Value::Float(value) => Some(*value),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+impl Trigger {
+    fn holds(&self, value: f64) -> bool {
+        self.above.is_none_or(|limit| value > limit)
+            && self.at_least.is_none_or(|limit| value >= limit)
+            && self.below.is_none_or(|limit| value < limit)
+            && self.at_most.is_none_or(|limit| value <= limit)
+    }
+}
+
+impl Aggregate {
+    fn name(&self) -> &'static str {
+        match self {
+            Aggregate::Count => "count",
+            Aggregate::Sum => "sum",
+            Aggregate::Avg => "avg",
+            Aggregate::Distinct => "distinct",
+        }
+    }
+
+    /// Aggregate of the inputs, `None` for the average of no numbers
+    fn compute(&self, inputs: &VecDeque<Input>) -> Option<f64> {
+        let numbers = || {
+            inputs
+                .iter()
+                .filter_map(|input| input.value.as_ref().and_then(number))
+        };
+        match self {
+            Aggregate::Count => Some(inputs.len() as f64),
+            Aggregate::Sum => Some(numbers().sum()),
+            Aggregate::Avg => {
+                let (sum, count) = numbers().fold((0.0, 0), |(sum, count), n| (sum + n, count + 1));
+                (count > 0).then(|| sum / count as f64)
+            }
+            Aggregate::Distinct => Some(
+                inputs
+                    .iter()
+                    .filter_map(|input| input.value.as_ref().and_then(text))
+                    .collect::<HashSet<_>>()
+                    .len() as f64,
+            ),
+        }
+    }
+}
+
+impl Rule {
+    fn new(config: &WindowRule) -> Result<Self, SensorError> {
+        let name = &config.name;
+        let span = match &config.window {
+            Window::Tumbling { size } => Span::Tumbling(span(*size, "size", name)?),
+            Window::Sliding { size } => Span::Sliding(span(*size, "size", name)?),
+            Window::Session { gap } => Span::Session(span(*gap, "gap", name)?),
+        };
+        if config.aggregate != Aggregate::Count && config.field.is_none() {
+            return Err(SensorError::Config(format!(
+                "aggregate {} of rule '{name}' needs a field",
+                config.aggregate.name()
+            )));
+        }
+        let trigger = &config.trigger;
+        if [
+            trigger.above,
+            trigger.at_least,
+            trigger.below,
+            trigger.at_most,
+        ]
+        .iter()
+        .all(Option::is_none)
+        {
+            return Err(SensorError::Config(format!(
+                "trigger of rule '{name}' needs above, at_least, below or at_most"
+            )));
+        }
+        if config.group_by.iter().any(String::is_empty) {
+            return Err(SensorError::Config(format!(
+                "group_by of rule '{name}' contains an empty path"
+            )));
+        }
+        Ok(Self {
+            config: config.clone(),
+            when: config
+                .when
+                .iter()
+                .map(Check::new)
+                .collect::<Result<_, _>>()?,
+            span,
+        })
+    }
+
+    /// Derived event of a window whose aggregate satisfies the trigger
+    fn derive(&self, bucket: &Bucket, end: DateTime<Utc>) -> Option<Event> {
+        let value = self.config.aggregate.compute(&bucket.inputs)?;
+        if !self.config.trigger.holds(value) {
+            return None;
+        }
+        let config = &self.config;
+        let window = match self.span {
+            Span::Tumbling(_) => "tumbling",
+            Span::Sliding(_) => "sliding",
+            Span::Session(_) => "session",
+        };
+        let mut builder = EventBuilder::new();
+        builder
+            .with_source(Source {
+                system: "window".to_string(),
+                source_id: Some(config.name.clone()),
+            })
+            .with_correlation_id(bucket.correlation_id)
+            .with_impact(config.impact)
+            .with_urgency(config.urgency)
+            .with_priority(config.priority)
+            .with_text_field("rule", &config.name)
+            .with_text_field("window", window)
+            .with_text_field("aggregate", config.aggregate.name())
+            .with_int_field("count", bucket.inputs.len() as i64)
+            .with_text_field("window_start", &bucket.start.to_rfc3339())
+            .with_text_field("window_end", &end.to_rfc3339())
+            .with_list_field(
+                "inputs",
+                bucket
+                    .inputs
+                    .iter()
+                    .rev()
+                    .take(MAX_INPUTS)
+                    .map(|input| Value::String(input.id.to_string()))
+                    .collect(),
+            );
+        match config.aggregate {
+            Aggregate::Count | Aggregate::Distinct => builder.with_int_field("value", value as i64),
+            Aggregate::Sum | Aggregate::Avg => builder.with_float_field("value", value),
+        };
+        if let Some(field) = &config.field {
+            builder.with_text_field("field", field);
+        }
+        for (path, value) in config.group_by.iter().zip(&bucket.key) {
+            builder.with_field(path, value.clone());
+        }
+        Some(builder.build())
+    }
+}
+
+impl WindowAggregator {
+    pub fn new(config: &WindowsConfig) -> Result<Self, SensorError> {
+        if config.rules.is_empty() {
+            return Err(SensorError::Config(
+                "at least one rule is required".to_string(),
+            ));
+        }
+        let mut names = HashSet::new();
+        for rule in &config.rules {
+            if rule.name.is_empty() {
+                return Err(SensorError::Config("rules need a name".to_string()));
+            }
+            if !names.insert(rule.name.as_str()) {
+                return Err(SensorError::Config(format!(
+                    "rule name '{}' is used twice",
+                    rule.name
+                )));
+            }
+        }
+        Ok(Self {
+            rules: config
+                .rules
+                .iter()
+                .map(Rule::new)
+                .collect::<Result<_, _>>()?,
+            forward_inputs: config.forward_inputs,
+            buckets: HashMap::new(),
+        })
+    }
+
+    /// Counts the event, returns the derived events of the windows it closed or triggered,
+    /// preceded by the event itself if no rule counted it or inputs are forwarded
+    pub fn apply(&mut self, mut event: Event) -> Vec<Event> {
+        let now = event.created_at;
+        let mut events = self.tick(now);
+
+        let mut counted = false;
+        for (index, rule) in self.rules.iter().enumerate() {
+            if !all(&rule.when, &event.fields) {
+                continue;
+            }
+            let Some(key) = key(&event.fields, &rule.config.group_by) else {
+                continue;
+            };
+            let group = key_text(&key);
+            let input = Input {
+                id: event.id,
+                time: now,
+                value: rule
+                    .config
+                    .field
+                    .as_deref()
+                    .and_then(|field| lookup(&event.fields, field))
+                    .cloned(),
+            };
+            let start = match rule.span {
+                Span::Tumbling(size) => {
+                    // windows are aligned to the clock, e.g. full minutes
+                    let size = size.num_milliseconds().max(1);
+                    let start = now.timestamp_millis().div_euclid(size) * size;
+                    DateTime::from_timestamp_millis(start).unwrap_or(now)
+                }
+                Span::Sliding(_) | Span::Session(_) => now,
+            };
+            let bucket = self
+                .buckets
+                .entry((index, group.clone()))
+                .or_insert_with(|| Bucket {
+                    key,
+                    start,
+                    last: now,
+                    inputs: VecDeque::new(),
+                    fired: false,
+                    correlation_id: Uuid::now_v7(),
+                });
+            if matches!(rule.span, Span::Tumbling(_)) && start < bucket.start {
+                // late event of a window that was already evaluated
+                continue;
+            }
+            counted = true;
+            if self.forward_inputs && event.correlation_id.is_none() {
+                event.correlation_id = Some(bucket.correlation_id);
+            }
+            bucket.last = bucket.last.max(now);
+            bucket.inputs.push_back(input);
+
+            if let Span::Sliding(size) = rule.span {
+                while bucket
+                    .inputs
+                    .front()
+                    .is_some_and(|input| input.time <= now - size)
+                {
+                    bucket.inputs.pop_front();
+                }
+                bucket.start = bucket.inputs.front().map_or(now, |input| input.time);
+                match rule.derive(bucket, now) {
+                    Some(derived) if !bucket.fired => {
+                        bucket.fired = true;
+                        events.push(derived);
+                    }
+                    Some(_) => {}
+                    None if bucket.fired => {
+                        bucket.fired = false;
+                        bucket.correlation_id = Uuid::now_v7();
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        if !counted || self.forward_inputs {
+            events.insert(0, event);
+        }
+        events
+    }
+
+    /// Evaluates the tumbling and session windows that ended before `now` and forgets the
+    /// inputs sliding windows no longer cover, call it regularly while no events arrive
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        let mut closed = Vec::new();
+        let rules = &self.rules;
+        self.buckets.retain(|(index, group), bucket| {
+            let rule = &rules[*index];
+            match rule.span {
+                Span::Tumbling(size) if bucket.start + size <= now => {
+                    closed.push((
+                        bucket.start + size,
+                        *index,
+                        group.clone(),
+                        rule.derive(bucket, bucket.start + size),
+                    ));
+                    false
+                }
+                Span::Session(gap) if bucket.last + gap <= now => {
+                    closed.push((
+                        bucket.last,
+                        *index,
+                        group.clone(),
+                        rule.derive(bucket, bucket.last),
+                    ));
+                    false
+                }
+                Span::Sliding(size) => {
+                    while bucket
+                        .inputs
+                        .front()
+                        .is_some_and(|input| input.time <= now - size)
+                    {
+                        bucket.inputs.pop_front();
+                    }
+                    if bucket.fired && rule.derive(bucket, now).is_none() {
+                        bucket.fired = false;
+                        bucket.correlation_id = Uuid::now_v7();
+                    }
+                    !bucket.inputs.is_empty()
+                }
+                _ => true,
+            }
+        });
+        closed.sort_by(|a, b| (a.0, a.1, &a.2).cmp(&(b.0, b.1, &b.2)));
+        closed.into_iter().filter_map(|(.., event)| event).collect()
+    }
+}
+
+impl Stage for WindowAggregator {
+    fn apply(&mut self, event: Event) -> Vec<Event> {
+        WindowAggregator::apply(self, event)
+    }
+
+    fn tick(&mut self, now: DateTime<Utc>) -> Vec<Event> {
+        WindowAggregator::tick(self, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aggregator(json: &str) -> Result<WindowAggregator, SensorError> {
+        WindowAggregator::new(&serde_json::from_str(json).unwrap())
+    }
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::UNIX_EPOCH + TimeDelta::seconds(seconds)
+    }
+
+    fn event(fields: &[(&str, Value)], seconds: i64) -> Event {
+        let mut builder = EventBuilder::new();
+        for (key, value) in fields {
+            builder.with_field(key, value.clone());
+        }
+        let mut event = builder.build();
+        event.created_at = at(seconds);
+        event
+    }
+
+    fn login_failure(user: &str, seconds: i64) -> Event {
+        event(
+            &[
+                ("action", Value::from("login")),
+                ("outcome", Value::from("failure")),
+                (
+                    "user",
+                    Value::Map(HashMap::from([("name".to_string(), Value::from(user))])),
+                ),
+            ],
+            seconds,
+        )
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for json in [
+            r#"{"rules": []}"#,
+            r#"{"rules": [{"name": "a", "window": {"type": "tumbling", "size": "0s"}, "trigger": {"above": 1}}]}"#,
+            r#"{"rules": [{"name": "a", "window": {"type": "sliding", "size": "1m"}, "trigger": {}}]}"#,
+            r#"{"rules": [{"name": "a", "window": {"type": "session", "gap": "1m"}, "aggregate": "sum", "trigger": {"above": 1}}]}"#,
+            r#"{"rules": [{"name": "a", "window": {"type": "session", "gap": "1m"}, "trigger": {"above": 1}}, {"name": "a", "window": {"type": "session", "gap": "1m"}, "trigger": {"above": 1}}]}"#,
+        ] {
+            assert!(
+                matches!(aggregator(json), Err(SensorError::Config(_))),
+                "{json}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_sliding_window_triggers_once() {
+        let mut aggregator = aggregator(
+            r#"{"rules": [{
+                "name": "brute-force",
+                "when": [{"field": "action", "equals": "login"}, {"field": "outcome", "equals": "failure"}],
+                "group_by": ["user.name"],
+                "window": {"type": "sliding", "size": "1m"},
+                "trigger": {"at_least": 5}
+            }]}"#,
+        )
+        .unwrap();
+
+        let other = event(&[("action", Value::from("logout"))], 0);
+        assert_eq!(aggregator.apply(other).len(), 1);
+
+        let mut inputs = Vec::new();
+        let mut derived = Vec::new();
+        for second in 0..4 {
+            let event = login_failure("ada", second * 10);
+            inputs.push(event.id);
+            assert!(aggregator.apply(event).is_empty());
+        }
+        // another user is another group
+        assert!(aggregator.apply(login_failure("bob", 41)).is_empty());
+        for second in [45, 50] {
+            let event = login_failure("ada", second);
+            inputs.push(event.id);
+            derived.extend(aggregator.apply(event));
+        }
+        assert_eq!(derived.len(), 1);
+        let event = derived[0].clone();
+        assert_eq!(event.fields["user.name"], Value::from("ada"));
+        assert_eq!(event.fields["value"], Value::Int(5));
+        assert_eq!(event.fields["window"], Value::from("sliding"));
+        let Value::List(ids) = &event.fields["inputs"] else {
+            panic!("inputs are not a list");
+        };
+        assert_eq!(ids.len(), 5);
+        assert!(ids.contains(&Value::String(inputs[0].to_string())));
+
+        // the failures slide out of the window and re-arm the rule
+        assert!(aggregator.tick(at(200)).is_empty());
+        for second in 200..205 {
+            derived = aggregator.apply(login_failure("ada", second));
+        }
+        assert_eq!(derived.len(), 1);
+        assert_ne!(derived[0].correlation_id, event.correlation_id);
+    }
+
+    #[test]
+    fn test_tumbling_window_evaluated_at_end() {
+        let mut aggregator = aggregator(
+            r#"{"forward_inputs": true, "rules": [{
+                "name": "errors",
+                "when": [{"field": "status", "one_of": [500, 503]}],
+                "group_by": ["service"],
+                "window": {"type": "tumbling", "size": "5m"},
+                "trigger": {"above": 2}
+            }]}"#,
+        )
+        .unwrap();
+        let error = |service: &str, seconds| {
+            event(
+                &[
+                    ("status", Value::Int(500)),
+                    ("service", Value::from(service)),
+                ],
+                seconds,
+            )
+        };
+        let mut forwarded = Vec::new();
+        for seconds in [10, 20, 30] {
+            let events = aggregator.apply(error("checkout", seconds));
+            assert_eq!(events.len(), 1);
+            forwarded.push(events[0].correlation_id);
+        }
+        assert_eq!(aggregator.apply(error("search", 40)).len(), 1);
+
+        assert!(aggregator.tick(at(299)).is_empty());
+        let derived = aggregator.tick(at(300));
+        assert_eq!(derived.len(), 1);
+        assert_eq!(derived[0].fields["service"], Value::from("checkout"));
+        assert_eq!(derived[0].fields["value"], Value::Int(3));
+        assert_eq!(
+            derived[0].fields["window_end"],
+            Value::String(at(300).to_rfc3339())
+        );
+        assert!(derived[0].correlation_id.is_some());
+        assert!(forwarded.iter().all(|id| *id == derived[0].correlation_id));
+
+        // the next window of the group is another incident
+        for seconds in [310, 320, 330] {
+            aggregator.apply(error("checkout", seconds));
+        }
+        let next = aggregator.tick(at(600));
+        assert_eq!(next.len(), 1);
+        assert_ne!(next[0].correlation_id, derived[0].correlation_id);
+    }
+
+    #[test]
+    fn test_session_window_aggregates() {
+        let mut aggregator = aggregator(
+            r#"{"rules": [
+                {"name": "volume", "group_by": ["host"], "window": {"type": "session", "gap": "30s"}, "aggregate": "sum", "field": "bytes", "trigger": {"above": 1000}},
+                {"name": "ips", "group_by": ["host"], "window": {"type": "session", "gap": "30s"}, "aggregate": "distinct", "field": "ip", "trigger": {"at_least": 2}},
+                {"name": "latency", "window": {"type": "session", "gap": "30s"}, "aggregate": "avg", "field": "latency", "trigger": {"above": 100}}
+            ]}"#,
+        )
+        .unwrap();
+        for (seconds, bytes, ip, latency) in [
+            (0, 600, "10.0.0.1", 90),
+            (20, 500, "10.0.0.2", 130),
+            (45, 1, "10.0.0.1", 110),
+        ] {
+            let event = event(
+                &[
+                    ("host", Value::from("web-1")),
+                    ("bytes", Value::Int(bytes)),
+                    ("ip", Value::from(ip)),
+                    ("latency", Value::Int(latency)),
+                ],
+                seconds,
+            );
+            assert!(aggregator.apply(event).is_empty());
+        }
+        let derived = aggregator.tick(at(75));
+        let values: HashMap<_, _> = derived
+            .iter()
+            .map(|event| match &event.fields["rule"] {
+                Value::String(rule) => (rule.clone(), event.fields["value"].clone()),
+                other => panic!("unexpected rule {other:?}"),
+            })
+            .collect();
+        assert_eq!(values.len(), 3);
+        assert_eq!(values["volume"], Value::Float(1101.0));
+        assert_eq!(values["ips"], Value::Int(2));
+        assert_eq!(values["latency"], Value::Float(110.0));
+    }
+}
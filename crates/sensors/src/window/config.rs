@@ -0,0 +1,100 @@
+use crate::mapping::Condition;
+use loid_events::prelude::{Impact, Priority, Urgency};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// The `windows` section aggregating the events of a sensor over time
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WindowsConfig {
+    pub rules: Vec<WindowRule>,
+    /// Forwards the events counted by a rule as well, by default only the derived events are
+    #[serde(default)]
+    pub forward_inputs: bool,
+}
+
+/// Aggregates the matching events of every group in a window and triggers on the result
+#[derive(Debug, Clone, Deserialize)]
+pub struct WindowRule {
+    /// Name of the rule, part of the derived events
+    pub name: String,
+    /// All conditions have to match for an event to be counted, every event counts without
+    #[serde(default)]
+    pub when: Vec<Condition>,
+    /// Field paths like `user.name` grouping the events, every group has its own windows
+    #[serde(default)]
+    pub group_by: Vec<String>,
+    pub window: Window,
+    #[serde(default)]
+    pub aggregate: Aggregate,
+    /// Field path the aggregate is computed from, required by all but `count`
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Limits the aggregate has to satisfy to trigger, all configured limits have to hold
+    pub trigger: Trigger,
+    #[serde(default = "default_impact")]
+    pub impact: Impact,
+    #[serde(default = "default_urgency")]
+    pub urgency: Urgency,
+    #[serde(default = "default_priority")]
+    pub priority: Priority,
+}
+
+/// How events are assigned to windows
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Window {
+    /// Fixed, non-overlapping windows of `size` aligned to the clock, evaluated when they end
+    Tumbling {
+        #[serde(with = "humantime_serde")]
+        size: Duration,
+    },
+    /// The last `size` before every event, triggers as soon as the limits hold and again only
+    /// after they stopped holding
+    Sliding {
+        #[serde(with = "humantime_serde")]
+        size: Duration,
+    },
+    /// Events less than `gap` apart, evaluated once no event arrived for `gap`
+    Session {
+        #[serde(with = "humantime_serde")]
+        gap: Duration,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregate {
+    /// Number of events
+    #[default]
+    Count,
+    /// Sum of a numeric field
+    Sum,
+    /// Average of a numeric field
+    Avg,
+    /// Number of different values of a field
+    Distinct,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Trigger {
+    #[serde(default)]
+    pub above: Option<f64>,
+    #[serde(default)]
+    pub at_least: Option<f64>,
+    #[serde(default)]
+    pub below: Option<f64>,
+    #[serde(default)]
+    pub at_most: Option<f64>,
+}
+
+fn default_impact() -> Impact {
+    Impact::MODERATE
+}
+
+fn default_urgency() -> Urgency {
+    Urgency::MEDIUM
+}
+
+fn default_priority() -> Priority {
+    Priority::MEDIUM
+}
@@ -0,0 +1,38 @@
+//! Windowed aggregation turning many events into one derived event.
+//!
+//! The `windows` section sits next to the `sensor` section and works the same for every sensor
+//! type, it sees the events after the [mapping](crate::mapping):
+//!
+//! ```yaml
+//! windows:
+//!     rules:
+//!         - name: brute-force
+//!           when:
+//!               - field: outcome
+//!                 equals: failure
+//!           group_by: [user.name]
+//!           window:
+//!               type: sliding
+//!               size: 1m
+//!           trigger:
+//!               at_least: 5
+//!         - name: server-errors
+//!           when:
+//!               - field: status
+//!                 one_of: [500, 502, 503]
+//!           group_by: [service]
+//!           window:
+//!               type: tumbling
+//!               size: 5m
+//!           trigger:
+//!               above: 100
+//! ```
+//!
+//! Events counted by a rule are replaced by the derived events, which list the ids of their
+//! inputs and share a correlation id per rule and group.
+
+pub(crate) mod aggregator;
+pub(crate) mod config;
+
+pub use aggregator::WindowAggregator;
+pub use config::{Aggregate, Trigger, Window, WindowRule, WindowsConfig};
@@ -1,6 +1,6 @@
 use std::error::Error;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    tonic_build::compile_protos("proto/calculator.proto")?;
+    tonic_prost_build::compile_protos("proto/calculator.proto")?;
     Ok(())
 }